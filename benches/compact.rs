@@ -0,0 +1,86 @@
+//! Compares the memory and decoding time of `Value` and `CompactValue`.
+//!
+//! Run with `cargo bench --features compact`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use bende::value::CompactValue;
+use bende::Value;
+use serde::de::DeserializeOwned;
+
+/// Counts the bytes that are currently allocated.
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const ROUNDS: u32 = 20;
+
+/// Builds a tracker scrape response for the given number of torrents.
+fn scrape(torrents: usize) -> Vec<u8> {
+    let mut src = b"d5:filesd".to_vec();
+    for i in 0..torrents {
+        let hash = format!("{:020}", i);
+        src.extend_from_slice(format!("20:{}", hash).as_bytes());
+        src.extend_from_slice(
+            format!(
+                "d8:completei{}e10:downloadedi{}e10:incompletei{}ee",
+                i % 7,
+                i,
+                i % 3
+            )
+            .as_bytes(),
+        );
+    }
+    src.extend_from_slice(b"ee");
+    src
+}
+
+/// Decodes the source, reporting the bytes the decoded value keeps allocated and the average decoding time.
+fn measure<T>(src: &[u8]) -> (usize, Duration)
+where
+    T: DeserializeOwned,
+{
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let val: T = bende::decode(src).unwrap();
+    let retained = ALLOCATED.load(Ordering::Relaxed) - before;
+    drop(val);
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        black_box(bende::decode::<T>(black_box(src)).unwrap());
+    }
+    (retained, start.elapsed() / ROUNDS)
+}
+
+fn main() {
+    for torrents in [100, 10_000, 100_000] {
+        let src = scrape(torrents);
+        let (value_mem, value_time) = measure::<Value>(&src);
+        let (compact_mem, compact_time) = measure::<CompactValue>(&src);
+
+        println!("{} torrents, {} bytes encoded", torrents, src.len());
+        println!("  Value:        {:>11} bytes, {:?}", value_mem, value_time);
+        println!(
+            "  CompactValue: {:>11} bytes, {:?}",
+            compact_mem, compact_time
+        );
+    }
+}
@@ -0,0 +1,70 @@
+//! Cooperative cancellation of encodes and decodes.
+//!
+//! * [`CancelToken`] - A shared flag that the encoder and decoder check between values.
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+/// A shared flag used to cancel an encode or decode from another thread.
+///
+/// The token is checked between values, so a cancelled decode fails with `Error::Cancelled` after at most one more value, and the thread that runs it is never interrupted. Clones of a token share the same flag.
+///
+/// # Examples
+///
+/// ```
+/// use serde::Deserialize;
+/// use bende::cancel::CancelToken;
+/// use bende::de::{Decoder, Error, ErrorKind};
+///
+/// let token = CancelToken::new();
+/// let mut de = Decoder::new(b"li1ei2ee").with_cancel_token(token.clone());
+///
+/// token.cancel();
+/// let err = Vec::<i64>::deserialize(&mut de).unwrap_err();
+///
+/// assert_eq!(err, Error::Cancelled { at: 1 });
+/// assert_eq!(err.kind(), ErrorKind::Cancelled);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Constructs a new token that isn't cancelled.
+    #[inline]
+    pub fn new() -> CancelToken {
+        Self::default()
+    }
+
+    /// Cancels every encode and decode that checks this token, or a clone of it.
+    #[inline]
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Checks if the token has been cancelled.
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use super::CancelToken;
+    use crate::en::{Encoder, Error};
+
+    #[test]
+    fn cancel_from_another_thread() {
+        let token = CancelToken::new();
+        let clone = token.clone();
+        thread::spawn(move || clone.cancel()).join().unwrap();
+
+        assert!(token.is_cancelled());
+        let mut en = Encoder::new(vec![]).with_cancel_token(token);
+        let res = serde::Serialize::serialize(&vec![1, 2, 3], &mut en);
+        assert!(matches!(res, Err(Error::Cancelled)));
+    }
+}
@@ -0,0 +1,350 @@
+//! Content-addressed splitting of encoded values into fixed-size chunks.
+//!
+//! Protocols that send a value in pieces, eg: the metadata exchange of BEP 9, which sends the `info` dictionary of a torrent in 16 KiB pieces, need to check each piece as it arrives, rather than only the whole value once it's been reassembled. The items included in this module are:
+//!
+//! * [`split`] - Encodes a value and splits the bytes into chunks, returning them alongside their [`Manifest`].
+//! * [`Manifest`] - The length of the encoded value, the length of its chunks and the hash of each chunk.
+//! * [`reassemble`] - Checks chunks against their manifest and joins them back into the encoded bytes.
+//!
+//! The chunks are hashed with any hasher from the [`digest`](https://docs.rs/digest) crates, eg: `sha1::Sha1` for the pieces of BEP 9, or `sha2::Sha256`.
+
+use std::fmt;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::marker::PhantomData;
+
+use digest::Digest;
+use digest::Output;
+use serde::Serialize;
+
+use super::en;
+
+/// The length of the metadata pieces of BEP 9, which every chunk but the last one has.
+pub const METADATA_PIECE_LEN: usize = 16 * 1024;
+
+/// Describes the chunks an encoded value was split into, so each chunk can be verified on its own.
+///
+/// The hashes are computed with `D`, which has to match between the side that splits the value and the side that verifies the chunks.
+pub struct Manifest<D: Digest> {
+    len: usize,
+    chunk_len: usize,
+    hashes: Vec<Output<D>>,
+    hasher: PhantomData<fn() -> D>,
+}
+
+impl<D: Digest> Manifest<D> {
+    /// Constructs a manifest from hashes computed elsewhere, eg: received alongside the chunks.
+    ///
+    /// # Errors
+    ///
+    /// * The number of hashes doesn't match the number of chunks `len` bytes split into, at which point `ChunkError::Count` is returned.
+    ///
+    /// # Panics
+    ///
+    /// * `chunk_len` is `0`.
+    pub fn new(
+        len: usize,
+        chunk_len: usize,
+        hashes: Vec<Output<D>>,
+    ) -> Result<Manifest<D>, ChunkError> {
+        assert!(chunk_len > 0, "chunks can't be empty");
+
+        let expected = len.div_ceil(chunk_len);
+        if hashes.len() != expected {
+            return Err(ChunkError::Count { expected, found: hashes.len() });
+        }
+        Ok(Manifest { len, chunk_len, hashes, hasher: PhantomData })
+    }
+
+    /// Gets the length of the encoded value, in bytes.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Checks if the encoded value is empty, in which case there are no chunks.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Gets the length of every chunk but the last one, which may be shorter.
+    #[inline]
+    pub fn chunk_len(&self) -> usize {
+        self.chunk_len
+    }
+
+    /// Gets the hash of every chunk, in order.
+    #[inline]
+    pub fn hashes(&self) -> &[Output<D>] {
+        &self.hashes
+    }
+
+    /// Gets the number of chunks.
+    #[inline]
+    pub fn chunk_count(&self) -> usize {
+        self.hashes.len()
+    }
+
+    /// Gets the length the chunk at the given index should have, or `None` if there's no such chunk.
+    pub fn expected_len(&self, index: usize) -> Option<usize> {
+        match index.checked_add(1)?.cmp(&self.hashes.len()) {
+            std::cmp::Ordering::Less => Some(self.chunk_len),
+            std::cmp::Ordering::Equal => {
+                Some(self.len - self.chunk_len * index)
+            }
+            std::cmp::Ordering::Greater => None,
+        }
+    }
+
+    /// Checks a single chunk, eg: as soon as it arrives from a peer.
+    ///
+    /// # Errors
+    ///
+    /// * There's no chunk at the index, at which point `ChunkError::Count` is returned.
+    /// * The chunk has the wrong length, or its hash doesn't match the manifest.
+    pub fn verify(&self, index: usize, chunk: &[u8]) -> Result<(), ChunkError> {
+        let expected = self.expected_len(index).ok_or(ChunkError::Count {
+            expected: self.hashes.len(),
+            found: index.saturating_add(1),
+        })?;
+        if chunk.len() != expected {
+            return Err(ChunkError::Length {
+                index,
+                expected,
+                found: chunk.len(),
+            });
+        }
+        match D::digest(chunk) == self.hashes[index] {
+            true => Ok(()),
+            false => Err(ChunkError::Hash { index }),
+        }
+    }
+}
+
+// These are implemented by hand, as deriving them would require the hasher to implement them too.
+
+impl<D: Digest> fmt::Debug for Manifest<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Manifest")
+            .field("len", &self.len)
+            .field("chunk_len", &self.chunk_len)
+            .field("hashes", &self.hashes)
+            .finish()
+    }
+}
+
+impl<D: Digest> Clone for Manifest<D> {
+    fn clone(&self) -> Self {
+        Manifest {
+            len: self.len,
+            chunk_len: self.chunk_len,
+            hashes: self.hashes.clone(),
+            hasher: PhantomData,
+        }
+    }
+}
+
+impl<D: Digest> PartialEq for Manifest<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len
+            && self.chunk_len == other.chunk_len
+            && self.hashes == other.hashes
+    }
+}
+
+impl<D: Digest> Eq for Manifest<D> {}
+
+impl<D: Digest> Hash for Manifest<D> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len.hash(state);
+        self.chunk_len.hash(state);
+        self.hashes.hash(state);
+    }
+}
+
+/// An error returned when chunks don't match their [`Manifest`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkError {
+    /// There are more or fewer chunks than the manifest has.
+    Count {
+        /// The number of chunks in the manifest.
+        expected: usize,
+        /// The number of chunks found.
+        found: usize,
+    },
+    /// A chunk doesn't have the length the manifest expects.
+    Length {
+        /// The index of the chunk.
+        index: usize,
+        /// The length the manifest expects.
+        expected: usize,
+        /// The length of the chunk.
+        found: usize,
+    },
+    /// The hash of a chunk doesn't match the manifest.
+    Hash {
+        /// The index of the chunk.
+        index: usize,
+    },
+}
+
+impl fmt::Display for ChunkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ChunkError::Count { expected, found } => {
+                write!(f, "expected {} chunks, found {}", expected, found)
+            }
+            ChunkError::Length { index, expected, found } => write!(
+                f,
+                "chunk {} is {} bytes long, expected {}",
+                index, found, expected
+            ),
+            ChunkError::Hash { index } => {
+                write!(f, "chunk {} doesn't match its hash", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ChunkError {}
+
+/// Encodes the given value, and splits the bytes into chunks of `chunk_len` bytes, the last of which may be shorter.
+///
+/// Use [`METADATA_PIECE_LEN`] and `sha1::Sha1` for the pieces of BEP 9.
+///
+/// # Errors
+///
+/// * The value can't be encoded.
+///
+/// # Panics
+///
+/// * `chunk_len` is `0`.
+///
+/// # Examples
+///
+/// ```
+/// use bende::chunk;
+/// use sha1::Sha1;
+///
+/// let (chunks, manifest) = chunk::split::<Sha1, _>(&("spam", "eggs"), 4).unwrap();
+/// assert_eq!(chunks, [&b"l4:s"[..], b"pam4", b":egg", b"se"]);
+/// assert_eq!(manifest.len(), 14);
+///
+/// manifest.verify(3, b"se").unwrap();
+/// assert!(manifest.verify(3, b"sx").is_err());
+///
+/// let bytes = chunk::reassemble(&chunks, &manifest).unwrap();
+/// assert_eq!(bende::decode::<(String, String)>(&bytes).unwrap(), ("spam".into(), "eggs".into()));
+/// ```
+pub fn split<D, T>(
+    val: &T,
+    chunk_len: usize,
+) -> Result<(Vec<Vec<u8>>, Manifest<D>), en::Error>
+where
+    D: Digest,
+    T: Serialize,
+{
+    assert!(chunk_len > 0, "chunks can't be empty");
+
+    let bytes = super::encode(val)?;
+    let chunks: Vec<Vec<u8>> =
+        bytes.chunks(chunk_len).map(<[u8]>::to_vec).collect();
+    let manifest = Manifest {
+        len: bytes.len(),
+        chunk_len,
+        hashes: chunks.iter().map(D::digest).collect(),
+        hasher: PhantomData,
+    };
+    Ok((chunks, manifest))
+}
+
+/// Verifies every chunk against the manifest, and joins them back into the encoded value.
+///
+/// # Errors
+///
+/// * The number of chunks doesn't match the manifest, at which point `ChunkError::Count` is returned.
+/// * A chunk doesn't match the manifest, in which case the first one that doesn't is reported. See [`Manifest::verify`].
+pub fn reassemble<D, C>(
+    chunks: &[C],
+    manifest: &Manifest<D>,
+) -> Result<Vec<u8>, ChunkError>
+where
+    D: Digest,
+    C: AsRef<[u8]>,
+{
+    if chunks.len() != manifest.chunk_count() {
+        return Err(ChunkError::Count {
+            expected: manifest.chunk_count(),
+            found: chunks.len(),
+        });
+    }
+
+    let mut bytes = Vec::with_capacity(manifest.len);
+    for (i, chunk) in chunks.iter().enumerate() {
+        manifest.verify(i, chunk.as_ref())?;
+        bytes.extend_from_slice(chunk.as_ref());
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use sha1::{Digest, Sha1};
+
+    use super::{reassemble, split, ChunkError, Manifest, METADATA_PIECE_LEN};
+    use crate::Value;
+
+    #[test]
+    fn split_and_reassemble() {
+        let info = Value::from(("pieces", vec![7u8; 40_000]));
+        let (mut chunks, manifest) =
+            split::<Sha1, _>(&info, METADATA_PIECE_LEN).unwrap();
+
+        assert_eq!(manifest.chunk_count(), 3);
+        assert_eq!(manifest.expected_len(2), Some(manifest.len() - 32768));
+        assert_eq!(manifest.expected_len(3), None);
+        assert_eq!(
+            reassemble(&chunks, &manifest).unwrap(),
+            crate::encode(&info).unwrap()
+        );
+
+        chunks[1][0] ^= 1;
+        assert_eq!(
+            reassemble(&chunks, &manifest),
+            Err(ChunkError::Hash { index: 1 })
+        );
+        chunks[1].pop();
+        assert_eq!(
+            reassemble(&chunks, &manifest),
+            Err(ChunkError::Length {
+                index: 1,
+                expected: METADATA_PIECE_LEN,
+                found: METADATA_PIECE_LEN - 1
+            })
+        );
+        assert_eq!(
+            reassemble(&chunks[..2], &manifest),
+            Err(ChunkError::Count { expected: 3, found: 2 })
+        );
+        assert_eq!(
+            manifest.verify(usize::MAX, b""),
+            Err(ChunkError::Count { expected: 3, found: usize::MAX })
+        );
+    }
+
+    #[test]
+    fn manifest_from_sha1_hashes() {
+        let bytes = crate::encode(&("spam", "eggs")).unwrap();
+        let chunks: Vec<&[u8]> = bytes.chunks(4).collect();
+        let hashes = chunks.iter().map(Sha1::digest).collect();
+        let manifest = Manifest::<Sha1>::new(bytes.len(), 4, hashes).unwrap();
+
+        assert_eq!(manifest.hashes()[0], Sha1::digest(b"l4:s"));
+        assert_eq!(reassemble(&chunks, &manifest).unwrap(), bytes);
+        assert_eq!(
+            Manifest::<Sha1>::new(bytes.len(), 4, vec![]),
+            Err(ChunkError::Count { expected: 4, found: 0 })
+        );
+    }
+}
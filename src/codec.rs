@@ -0,0 +1,500 @@
+//! Typed message channels over async readers and writers, for `tokio`.
+//!
+//! The types included in this module are:
+//!
+//! * [`BencodeCodec`] - A [`tokio_util::codec`] codec that frames a stream of concatenated values.
+//! * [`BencodeStream`] - A [`Stream`] of values decoded from an [`AsyncRead`].
+//! * [`BencodeSink`] - A [`Sink`] of values encoded into an [`AsyncWrite`].
+//!
+//! Both ends limit the length of a single value to [`DEFAULT_MAX_LEN`] unless told otherwise, so a peer can't make them buffer an unbounded amount of data.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+
+use bytes::BytesMut;
+use futures_core::Stream;
+use futures_sink::Sink;
+use pin_project_lite::pin_project;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio_util::codec::Decoder;
+use tokio_util::codec::Encoder;
+use tokio_util::codec::FramedRead;
+use tokio_util::codec::FramedWrite;
+
+use super::de;
+use super::de::FrameScanner;
+use super::en;
+use super::stream::check_len;
+pub use super::stream::DEFAULT_MAX_LEN;
+
+/// A codec that decodes values of type `T` from a stream of concatenated values, and encodes any serializable value.
+///
+/// Like the [`NonBlockingDecoder`](super::stream::NonBlockingDecoder), the bytes of a value are only scanned once, however many reads it takes to arrive. A stream can't be resynchronized after bytes that aren't valid bencode, so the buffered bytes are discarded at the first such error.
+///
+/// # Examples
+///
+/// ```
+/// use bytes::BytesMut;
+/// use tokio_util::codec::{Decoder, Encoder};
+/// use bende::codec::BencodeCodec;
+///
+/// let mut codec = BencodeCodec::<String>::new();
+/// let mut buf = BytesMut::new();
+/// codec.encode("foo", &mut buf).unwrap();
+///
+/// assert_eq!(&buf[..], b"3:foo");
+/// assert_eq!(codec.decode(&mut buf).unwrap(), Some("foo".to_string()));
+/// ```
+pub struct BencodeCodec<T> {
+    scanner: FrameScanner,
+    max_len: Option<usize>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> BencodeCodec<T> {
+    /// Constructs a new codec, which limits the length of a value to [`DEFAULT_MAX_LEN`].
+    #[inline]
+    pub fn new() -> BencodeCodec<T> {
+        Self {
+            scanner: FrameScanner::default(),
+            max_len: Some(DEFAULT_MAX_LEN),
+            marker: PhantomData,
+        }
+    }
+
+    /// Limits the length of a single value, in bytes, both when decoding and encoding. Defaults to [`DEFAULT_MAX_LEN`].
+    ///
+    /// When decoding, a value is rejected as soon as the buffered bytes show that it's longer than the limit, without waiting for the rest of it.
+    #[inline]
+    pub fn with_max_len(mut self, max: usize) -> BencodeCodec<T> {
+        self.max_len = Some(max);
+        self
+    }
+
+    /// Lifts the limit on the length of a single value, for peers that are trusted not to send an unbounded amount of data.
+    #[inline]
+    pub fn without_max_len(mut self) -> BencodeCodec<T> {
+        self.max_len = None;
+        self
+    }
+
+    /// Gets the limit on the length of a single value, if any.
+    #[inline]
+    pub fn max_len(&self) -> Option<usize> {
+        self.max_len
+    }
+}
+
+// These are implemented by hand, as deriving them would require `T` to implement them too.
+
+impl<T> fmt::Debug for BencodeCodec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BencodeCodec")
+            .field("scanner", &self.scanner)
+            .field("max_len", &self.max_len)
+            .finish()
+    }
+}
+
+impl<T> Clone for BencodeCodec<T> {
+    fn clone(&self) -> Self {
+        Self {
+            scanner: self.scanner,
+            max_len: self.max_len,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for BencodeCodec<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: DeserializeOwned> Decoder for BencodeCodec<T> {
+    type Item = T;
+    type Error = de::Error;
+
+    /// Decodes the next value, once all of its bytes have been buffered.
+    ///
+    /// # Errors
+    ///
+    /// * The value is longer than the limit set with [`with_max_len`](BencodeCodec::with_max_len), at which point `Error::Length` is returned.
+    /// * The bytes are not valid bencode, or can't be decoded into `T`.
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<T>, de::Error> {
+        let frame = self
+            .scanner
+            .scan(src)
+            .and_then(|frame| {
+                let len = frame.unwrap_or(src.len());
+                check_len(len, self.max_len)
+                    .map_err(|expected| de::Error::Length { len, expected })?;
+                Ok(frame)
+            })
+            .inspect_err(|_| {
+                src.clear();
+                self.scanner.reset();
+            })?;
+
+        let Some(len) = frame else {
+            return Ok(None);
+        };
+        let bytes = src.split_to(len);
+        self.scanner.reset();
+        super::decode(&bytes).map(Some)
+    }
+
+    /// Decodes the last value once the reader has reached its end, failing with `Error::EOF` if it's incomplete.
+    fn decode_eof(
+        &mut self,
+        src: &mut BytesMut,
+    ) -> Result<Option<T>, de::Error> {
+        match self.decode(src)? {
+            Some(val) => Ok(Some(val)),
+            None if src.is_empty() => Ok(None),
+            None => Err(de::Error::EOF),
+        }
+    }
+}
+
+impl<T, I: Serialize> Encoder<I> for BencodeCodec<T> {
+    type Error = en::Error;
+
+    /// Encodes the value after the buffered ones.
+    ///
+    /// # Errors
+    ///
+    /// * The value can't be encoded.
+    /// * The value is longer than the limit set with [`with_max_len`](BencodeCodec::with_max_len), at which point `Error::Length` is returned, and nothing is written.
+    fn encode(&mut self, item: I, dst: &mut BytesMut) -> Result<(), en::Error> {
+        let bytes = super::encode(&item)?;
+        let len = bytes.len();
+        check_len(len, self.max_len)
+            .map_err(|expected| en::Error::Length { len, expected })?;
+        dst.extend_from_slice(&bytes);
+        Ok(())
+    }
+}
+
+pin_project! {
+    /// A stream of values of type `T`, decoded from an async reader.
+    ///
+    /// The stream ends after the first error, as the bytes that follow a value that can't be decoded can't be trusted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use futures::StreamExt;
+    /// use bende::codec::BencodeStream;
+    ///
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let mut stream = BencodeStream::<_, i64>::new(&b"i1ei2e"[..]);
+    ///
+    /// assert_eq!(stream.next().await.unwrap().unwrap(), 1);
+    /// assert_eq!(stream.next().await.unwrap().unwrap(), 2);
+    /// assert!(stream.next().await.is_none());
+    /// # });
+    /// ```
+    pub struct BencodeStream<R, T> {
+        #[pin]
+        inner: FramedRead<R, BencodeCodec<T>>,
+    }
+}
+
+impl<R: AsyncRead, T> BencodeStream<R, T> {
+    /// Constructs a new stream with the given reader, which limits the length of a value to [`DEFAULT_MAX_LEN`].
+    #[inline]
+    pub fn new(reader: R) -> BencodeStream<R, T> {
+        Self { inner: FramedRead::new(reader, BencodeCodec::new()) }
+    }
+
+    /// Limits the length of a single value, in bytes, so a peer can't make the stream buffer an unbounded amount of data. Defaults to [`DEFAULT_MAX_LEN`].
+    ///
+    /// See [`BencodeCodec::with_max_len`].
+    #[inline]
+    pub fn with_max_len(mut self, max: usize) -> BencodeStream<R, T> {
+        self.inner.decoder_mut().max_len = Some(max);
+        self
+    }
+
+    /// Lifts the limit on the length of a single value.
+    ///
+    /// See [`BencodeCodec::without_max_len`].
+    #[inline]
+    pub fn without_max_len(mut self) -> BencodeStream<R, T> {
+        self.inner.decoder_mut().max_len = None;
+        self
+    }
+
+    /// Gets a reference to the underlying reader.
+    #[inline]
+    pub fn get_ref(&self) -> &R {
+        self.inner.get_ref()
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut R {
+        self.inner.get_mut()
+    }
+
+    /// Consumes the stream, returning the underlying reader.
+    ///
+    /// Note that any buffered bytes are lost.
+    #[inline]
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+}
+
+impl<R, T> Stream for BencodeStream<R, T>
+where
+    R: AsyncRead,
+    T: DeserializeOwned,
+{
+    type Item = Result<T, de::Error>;
+
+    #[inline]
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.project().inner.poll_next(cx)
+    }
+}
+
+impl<R: fmt::Debug, T> fmt::Debug for BencodeStream<R, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BencodeStream").field("inner", &self.inner).finish()
+    }
+}
+
+pin_project! {
+    /// A sink of values of type `T`, encoded into an async writer.
+    ///
+    /// Encoded values are buffered, and the buffer is written out once it grows past the backpressure boundary, at which point the sink stops accepting values until the writer catches up.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use futures::SinkExt;
+    /// use bende::codec::BencodeSink;
+    ///
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let mut sink = BencodeSink::new(vec![]);
+    /// sink.send(("foo", 1995)).await.unwrap();
+    ///
+    /// assert_eq!(sink.into_inner(), b"l3:fooi1995ee");
+    /// # });
+    /// ```
+    pub struct BencodeSink<W, T> {
+        #[pin]
+        inner: FramedWrite<W, BencodeCodec<T>>,
+    }
+}
+
+impl<W: AsyncWrite, T> BencodeSink<W, T> {
+    /// Constructs a new sink with the given writer, which limits the length of a value to [`DEFAULT_MAX_LEN`].
+    #[inline]
+    pub fn new(writer: W) -> BencodeSink<W, T> {
+        Self { inner: FramedWrite::new(writer, BencodeCodec::new()) }
+    }
+
+    /// Limits the length of a single value, in bytes, so a value the peer would reject is never sent. Defaults to [`DEFAULT_MAX_LEN`].
+    ///
+    /// See [`BencodeCodec::with_max_len`].
+    #[inline]
+    pub fn with_max_len(mut self, max: usize) -> BencodeSink<W, T> {
+        self.inner.encoder_mut().max_len = Some(max);
+        self
+    }
+
+    /// Lifts the limit on the length of a single value.
+    ///
+    /// See [`BencodeCodec::without_max_len`].
+    #[inline]
+    pub fn without_max_len(mut self) -> BencodeSink<W, T> {
+        self.inner.encoder_mut().max_len = None;
+        self
+    }
+
+    /// Sets the number of buffered bytes past which the sink writes them out before accepting another value. Defaults to 8 KiB.
+    #[inline]
+    pub fn with_backpressure_boundary(
+        mut self,
+        boundary: usize,
+    ) -> BencodeSink<W, T> {
+        self.inner.set_backpressure_boundary(boundary);
+        self
+    }
+
+    /// Gets a reference to the underlying writer.
+    #[inline]
+    pub fn get_ref(&self) -> &W {
+        self.inner.get_ref()
+    }
+
+    /// Gets a mutable reference to the underlying writer.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.get_mut()
+    }
+
+    /// Consumes the sink, returning the underlying writer.
+    ///
+    /// Note that any buffered bytes are lost, so the sink should be flushed first.
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.inner.into_inner()
+    }
+}
+
+impl<W, T> Sink<T> for BencodeSink<W, T>
+where
+    W: AsyncWrite,
+    T: Serialize,
+{
+    type Error = en::Error;
+
+    #[inline]
+    fn poll_ready(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), en::Error>> {
+        Sink::<T>::poll_ready(self.project().inner, cx)
+    }
+
+    #[inline]
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), en::Error> {
+        Sink::<T>::start_send(self.project().inner, item)
+    }
+
+    #[inline]
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), en::Error>> {
+        Sink::<T>::poll_flush(self.project().inner, cx)
+    }
+
+    #[inline]
+    fn poll_close(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), en::Error>> {
+        Sink::<T>::poll_close(self.project().inner, cx)
+    }
+}
+
+impl<W: fmt::Debug, T> fmt::Debug for BencodeSink<W, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BencodeSink").field("inner", &self.inner).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use bytes::BytesMut;
+    use futures::{SinkExt, StreamExt};
+    use serde::{Deserialize, Serialize};
+    use tokio_util::codec::Decoder;
+
+    use super::{BencodeCodec, BencodeSink, BencodeStream, DEFAULT_MAX_LEN};
+    use crate::{de, en};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Message {
+        id: u32,
+        args: BTreeMap<String, String>,
+    }
+
+    fn message(id: u32) -> Message {
+        let args = BTreeMap::from([("q".into(), "ping".repeat(id as usize))]);
+        Message { id, args }
+    }
+
+    #[tokio::test]
+    async fn stream_and_sink_round_trip() {
+        let (client, server) = tokio::io::duplex(64);
+        let mut sink = BencodeSink::new(client).with_backpressure_boundary(16);
+        let mut stream = BencodeStream::<_, Message>::new(server);
+
+        let send = async {
+            for id in 0..100 {
+                sink.send(message(id)).await.unwrap();
+            }
+            sink.close().await.unwrap();
+        };
+        let recv = async {
+            let mut received = vec![];
+            while let Some(msg) = stream.next().await {
+                received.push(msg.unwrap());
+            }
+            received
+        };
+
+        let ((), received) = tokio::join!(send, recv);
+        assert_eq!(received, (0..100).map(message).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn stream_rejects_long_values() {
+        let src = &b"3:foo20:aaaaaaaaaaaaaaaaaaaa3:bar"[..];
+        let mut stream = BencodeStream::<_, String>::new(src).with_max_len(8);
+
+        assert_eq!(stream.next().await.unwrap().unwrap(), "foo");
+        let err = stream.next().await.unwrap().unwrap_err();
+        assert!(matches!(err, de::Error::Length { len: 23, .. }));
+        assert!(stream.next().await.is_none());
+    }
+
+    #[test]
+    fn codec_limits_values_by_default() {
+        let mut codec = BencodeCodec::<String>::new();
+        assert_eq!(codec.max_len(), Some(DEFAULT_MAX_LEN));
+
+        let text = "a".repeat(DEFAULT_MAX_LEN);
+        let src = format!("{}:{}", text.len(), text);
+
+        let mut buf = BytesMut::from(src.as_str());
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert!(matches!(err, de::Error::Length { .. }));
+
+        let mut codec = codec.without_max_len();
+        assert_eq!(codec.max_len(), None);
+        let mut buf = BytesMut::from(src.as_str());
+        assert_eq!(codec.decode(&mut buf), Ok(Some(text)));
+    }
+
+    #[tokio::test]
+    async fn stream_fails_on_truncated_value() {
+        let mut stream = BencodeStream::<_, Vec<i64>>::new(&b"li1ei2e"[..]);
+
+        assert_eq!(stream.next().await.unwrap().unwrap_err(), de::Error::EOF);
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn sink_rejects_long_values() {
+        let mut sink = BencodeSink::new(vec![]).with_max_len(8);
+
+        sink.send("foo").await.unwrap();
+        let err = sink.send("a long string").await.unwrap_err();
+        assert!(matches!(err, en::Error::Length { len: 16, .. }));
+        assert_eq!(
+            err.to_string(),
+            "invalid length 16, expected a value of at most 8 bytes"
+        );
+        assert_eq!(sink.into_inner(), b"3:foo");
+    }
+}
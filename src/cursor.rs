@@ -0,0 +1,217 @@
+//! A checked reader over a slice of bytes.
+//!
+//! * [`ByteCursor`] - Advances through a slice without any offset arithmetic, for protocols that embed bencode in their own framing.
+
+use super::de::scan_frame;
+use super::de::Error;
+
+/// A reader that advances through a slice of bytes, borrowing everything it returns from the slice.
+///
+/// Every operation is checked: reading past the end of the slice returns `Error::EOF` and leaves the cursor where it was, so the cursor never panics and its position never leaves the slice. The cursor contains no `unsafe` code.
+///
+/// # Examples
+///
+/// ```
+/// use bende::cursor::ByteCursor;
+///
+/// // A message framed as a 1-byte tag, followed by a bencoded payload.
+/// let mut cur = ByteCursor::new(b"\x14d1:mi1ee\x00");
+///
+/// assert_eq!(cur.take_u8(), Ok(0x14));
+/// assert_eq!(cur.take_value(), Ok(&b"d1:mi1ee"[..]));
+/// assert_eq!(cur.remaining(), b"\x00");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteCursor<'a> {
+    src: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteCursor<'a> {
+    /// Constructs a cursor at the start of the given slice.
+    #[inline]
+    pub fn new(src: &'a [u8]) -> ByteCursor<'a> {
+        Self { src, pos: 0 }
+    }
+
+    /// Gets the position of the cursor, as an offset into the slice.
+    #[inline]
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// Gets the bytes that haven't been read yet.
+    #[inline]
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.src[self.pos..]
+    }
+
+    /// Checks if all the bytes have been read.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.remaining().is_empty()
+    }
+
+    /// Peeks the next byte **without consuming it**.
+    #[inline]
+    pub fn peek(&self) -> Option<u8> {
+        self.remaining().first().copied()
+    }
+
+    /// Peeks the next `n` bytes **without consuming them**.
+    #[inline]
+    pub fn peek_n(&self, n: usize) -> Option<&'a [u8]> {
+        self.remaining().get(..n)
+    }
+
+    /// Consumes the next byte.
+    ///
+    /// # Errors
+    ///
+    /// * The cursor has reached the end of the slice, at which point `Error::EOF` is returned.
+    #[inline]
+    pub fn take_u8(&mut self) -> Result<u8, Error> {
+        let next = self.peek().ok_or(Error::EOF)?;
+        self.pos += 1;
+        Ok(next)
+    }
+
+    /// Consumes the next `n` bytes.
+    ///
+    /// # Errors
+    ///
+    /// * There are fewer than `n` bytes left, at which point `Error::EOF` is returned.
+    #[inline]
+    pub fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        let bytes = self.peek_n(n).ok_or(Error::EOF)?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    /// Consumes the next `N` bytes as an array, eg: a 20-byte info hash.
+    ///
+    /// # Errors
+    ///
+    /// * There are fewer than `N` bytes left, at which point `Error::EOF` is returned.
+    #[inline]
+    pub fn take_array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut arr = [0; N];
+        arr.copy_from_slice(self.take(N)?);
+        Ok(arr)
+    }
+
+    /// Consumes the bytes up until the given delimiter, and the delimiter itself, returning the bytes before it.
+    ///
+    /// # Errors
+    ///
+    /// * The delimiter isn't found, at which point `Error::EOF` is returned.
+    pub fn take_until(&mut self, delim: u8) -> Result<&'a [u8], Error> {
+        let rest = self.remaining();
+        let len = rest.iter().position(|&b| b == delim).ok_or(Error::EOF)?;
+        self.pos += len + 1;
+        Ok(&rest[..len])
+    }
+
+    /// Consumes the raw bytes of the next complete bencoded value, without decoding it.
+    ///
+    /// # Errors
+    ///
+    /// * The value is incomplete, at which point `Error::EOF` is returned.
+    /// * The value is malformed.
+    pub fn take_value(&mut self) -> Result<&'a [u8], Error> {
+        let len = scan_frame(self.remaining())?.ok_or(Error::EOF)?;
+        self.take(len)
+    }
+
+    /// Skips the next `n` bytes.
+    ///
+    /// # Errors
+    ///
+    /// * There are fewer than `n` bytes left, at which point `Error::EOF` is returned.
+    #[inline]
+    pub fn skip(&mut self, n: usize) -> Result<(), Error> {
+        self.take(n).map(|_| ())
+    }
+
+    /// Splits off a cursor over the next `n` bytes, and advances this cursor past them.
+    ///
+    /// The new cursor starts at position `0`, and can't read past the `n` bytes it was given.
+    ///
+    /// # Errors
+    ///
+    /// * There are fewer than `n` bytes left, at which point `Error::EOF` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::cursor::ByteCursor;
+    ///
+    /// let mut cur = ByteCursor::new(b"4:spam3:egg");
+    /// let mut head = cur.split(6).unwrap();
+    ///
+    /// assert_eq!(head.take_value(), Ok(&b"4:spam"[..]));
+    /// assert!(head.is_empty());
+    /// assert_eq!(cur.remaining(), b"3:egg");
+    /// ```
+    #[inline]
+    pub fn split(&mut self, n: usize) -> Result<ByteCursor<'a>, Error> {
+        self.take(n).map(ByteCursor::new)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ByteCursor;
+    use crate::de::Error;
+
+    #[test]
+    fn cursor_errors_leave_position() {
+        let mut cur = ByteCursor::new(b"ab:c");
+
+        assert_eq!(cur.take(5), Err(Error::EOF));
+        assert_eq!(cur.take_until(b'x'), Err(Error::EOF));
+        assert_eq!(cur.take_value(), Err(Error::Malformed));
+        assert_eq!(cur.take(usize::MAX), Err(Error::EOF));
+        assert_eq!(cur.pos(), 0);
+
+        assert_eq!(cur.take_until(b':'), Ok(&b"ab"[..]));
+        assert_eq!(cur.take_array::<2>(), Err(Error::EOF));
+        assert_eq!(cur.take_array::<1>(), Ok(*b"c"));
+        assert_eq!(cur.take_u8(), Err(Error::EOF));
+        assert_eq!(cur.pos(), 4);
+    }
+
+    #[test]
+    fn cursor_never_leaves_slice() {
+        // Exercise every operation at every position of every prefix of a message.
+        let src = b"d3:fooli1ei-2ee1:x0:e";
+        for end in 0..=src.len() {
+            for start in 0..=end {
+                for n in 0..=end + 1 {
+                    let mut cur = ByteCursor::new(&src[..end]);
+                    let _ = cur.skip(start);
+                    let at = cur.pos();
+
+                    let mut taken = cur;
+                    let res = taken.take(n);
+                    assert_eq!(res.is_ok(), n <= end - at);
+                    let mut split = cur;
+                    if let Ok(head) = split.split(n) {
+                        assert_eq!(head.remaining().len(), n);
+                        assert_eq!(split.pos(), at + n);
+                    }
+                    let mut c = cur;
+                    match c.take_value() {
+                        Ok(_) => assert!(c.pos() <= end),
+                        Err(_) => assert_eq!(c.pos(), at),
+                    }
+                    let mut c = cur;
+                    match c.take_until(b'e') {
+                        Ok(_) => assert!(c.pos() <= end),
+                        Err(_) => assert_eq!(c.pos(), at),
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,21 +1,45 @@
 //! Bencode decoding and deserialization.
 
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::Error as IoError;
+use std::num::IntErrorKind;
+use std::num::ParseIntError;
 use std::str;
 use std::str::Utf8Error;
 use std::string::FromUtf8Error;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
 
+use serde::de::value::SeqDeserializer;
 use serde::de::EnumAccess;
+use serde::de::Expected;
 use serde::de::IntoDeserializer;
 use serde::de::MapAccess;
 use serde::de::SeqAccess;
 use serde::de::VariantAccess;
-use serde::Deserializer;
-
-use super::DICT_START;
-use super::INT_START;
-use super::LIST_START;
-use super::TEXT_DELIM;
-use super::TYPE_END;
+use serde::Deserializer as _;
+
+use super::cancel::CancelToken;
+use super::path::Path;
+use super::path::Pattern;
+use super::path::Segment;
+use super::profile::Profile;
+use super::raw::RAW_TOKEN;
+use super::spec::cmp_keys;
+use super::spec::DICT_START;
+use super::spec::INT_START;
+use super::spec::LIST_START;
+use super::spec::MAX_INT_DIGITS;
+use super::spec::TEXT_DELIM;
+use super::spec::TYPE_END;
+use super::token::Token;
+use super::token::Tokens;
+use super::value::ArcDict;
+use super::value::ArcValue;
+use super::value::Key;
+use super::Value;
 
 /// An error that can occur when decoding types from bencode.
 ///
@@ -25,9 +49,18 @@ use super::TYPE_END;
 /// * `Malformed` - You've given the decoder invalid or malformed input.
 /// * `Wanted` - The decoder expected a certain type, but found something else.
 /// * `Unsupported` - Tried decoding a type that is not supported by the library.
+/// * `IntegerTooLong` - Tried decoding an integer with more digits than allowed.
+/// * `IntegerOverflow` - Tried decoding an integer that doesn't fit in an `i64`.
 /// * `Deserialize` - A custom serde deserialization error.
 /// * `Utf8` - A UTF-8 error straight from the standard library.
-#[derive(Debug, PartialEq)]
+/// * `Io` - An I/O error from the standard library, when decoding from a reader.
+/// * `Cancelled` - The caller cancelled the decode, from a progress observer or a cancel token.
+/// * `TimedOut` - The decode took longer than its deadline allowed.
+/// * `Rejected` - An interceptor rejected a value.
+/// * `Length` - A value was longer than a fixed-capacity type or a configured limit allows.
+/// * `InvalidLength` - A type didn't accept the length of a value, eg: a tuple that was given too few elements.
+#[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// The decoder unexpectedly reached the end of the source.
     EOF,
@@ -44,10 +77,155 @@ pub enum Error {
     },
     /// Tried decoding a type not currently supported by the library.
     Unsupported(&'static str),
+    /// Tried decoding an integer with more digits than the configured maximum.
+    IntegerTooLong {
+        /// The starting position of the integer's digits.
+        at: usize,
+        /// The maximum number of digits allowed, excluding the sign.
+        max: usize,
+    },
+    /// Tried decoding an integer that is out of the range of an `i64`.
+    IntegerOverflow {
+        /// The starting position of the integer's digits.
+        at: usize,
+    },
     /// A serde deserialization error.
     Deserialize(String),
     /// Tried decoding a string that is not valid UTF-8.
     Utf8(Utf8Error),
+    /// A standard I/O error.
+    Io(IoError),
+    /// The caller cancelled the decode before it finished.
+    Cancelled {
+        /// The number of bytes consumed when the decode was cancelled.
+        at: usize,
+    },
+    /// The decode didn't finish before its deadline.
+    TimedOut {
+        /// The number of bytes consumed when the deadline passed.
+        at: usize,
+    },
+    /// An interceptor rejected the value at a path.
+    Rejected {
+        /// The path of the rejected value.
+        path: Path,
+        /// Why the value was rejected.
+        reason: String,
+    },
+    /// A value was longer than a fixed-capacity type, eg: a `heapless::Vec`, or a configured limit allows.
+    ///
+    /// Other lengths a type can't hold, eg: fewer elements than an array or a tuple needs, fail with `Error::InvalidLength`.
+    Length {
+        /// The length of the value, as far as it was decoded.
+        len: usize,
+        /// A description of the lengths the type can hold.
+        expected: String,
+    },
+    /// A type didn't accept the length of a value, as reported by [`invalid_length`](serde::de::Error::invalid_length), eg: an array or a tuple that was given too few elements.
+    ///
+    /// A fixed-capacity type that runs out of room for an element it was given fails with `Error::Length` instead.
+    InvalidLength {
+        /// The length of the value, as far as it was decoded.
+        len: usize,
+        /// A description of the lengths the type can hold.
+        expected: String,
+    },
+}
+
+/// A broad category of decoding errors, used to tell I/O failures apart from bad input.
+///
+/// For example, retry logic would typically only retry errors of kind `Io`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The underlying reader failed.
+    Io,
+    /// The source ended before a complete value was decoded.
+    Eof,
+    /// The source is not valid bencode.
+    Syntax,
+    /// The source is valid bencode, but does not match the type being decoded.
+    Data,
+    /// The source exceeded one of the decoder's configured limits.
+    Limit,
+    /// The caller cancelled the decode.
+    Cancelled,
+    /// The decode didn't finish before its deadline.
+    TimedOut,
+}
+
+impl Error {
+    /// Gets the category of the error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::de::ErrorKind;
+    ///
+    /// let err = bende::decode::<i32>(b"i19").unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::Syntax);
+    /// ```
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            Error::Io(_) => ErrorKind::Io,
+            Error::EOF => ErrorKind::Eof,
+            Error::Malformed | Error::Utf8(_) => ErrorKind::Syntax,
+            Error::IntegerTooLong { .. } | Error::Length { .. } => {
+                ErrorKind::Limit
+            }
+            Error::Cancelled { .. } => ErrorKind::Cancelled,
+            Error::TimedOut { .. } => ErrorKind::TimedOut,
+            Error::Wanted { .. }
+            | Error::Unsupported(_)
+            | Error::IntegerOverflow { .. }
+            | Error::Deserialize(_)
+            | Error::InvalidLength { .. }
+            | Error::Rejected { .. } => ErrorKind::Data,
+        }
+    }
+}
+
+impl PartialEq for Error {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Error::EOF, Error::EOF) => true,
+            (Error::Malformed, Error::Malformed) => true,
+            (
+                Error::Wanted { at, expected, found },
+                Error::Wanted { at: at2, expected: expected2, found: found2 },
+            ) => at == at2 && expected == expected2 && found == found2,
+            (Error::Unsupported(a), Error::Unsupported(b)) => a == b,
+            (
+                Error::IntegerTooLong { at, max },
+                Error::IntegerTooLong { at: at2, max: max2 },
+            ) => at == at2 && max == max2,
+            (
+                Error::IntegerOverflow { at },
+                Error::IntegerOverflow { at: at2 },
+            ) => at == at2,
+            (Error::Deserialize(a), Error::Deserialize(b)) => a == b,
+            (
+                Error::InvalidLength { len, expected },
+                Error::InvalidLength { len: len2, expected: expected2 },
+            ) => len == len2 && expected == expected2,
+            (Error::Utf8(a), Error::Utf8(b)) => a == b,
+            (Error::Cancelled { at }, Error::Cancelled { at: at2 }) => {
+                at == at2
+            }
+            (Error::TimedOut { at }, Error::TimedOut { at: at2 }) => at == at2,
+            (
+                Error::Rejected { path, reason },
+                Error::Rejected { path: path2, reason: reason2 },
+            ) => path == path2 && reason == reason2,
+            (
+                Error::Length { len, expected },
+                Error::Length { len: len2, expected: expected2 },
+            ) => len == len2 && expected == expected2,
+            // I/O errors can't be compared directly, so we settle for comparing their kinds.
+            (Error::Io(a), Error::Io(b)) => a.kind() == b.kind(),
+            _ => false,
+        }
+    }
 }
 
 impl std::fmt::Display for Error {
@@ -72,13 +250,45 @@ impl std::fmt::Display for Error {
                     ty
                 )
             }
+            Error::IntegerTooLong { at, max } => write!(
+                f,
+                "integer at column {} has more than {} digits",
+                at, max
+            ),
+            Error::IntegerOverflow { at } => write!(
+                f,
+                "integer at column {} is out of the range of an i64",
+                at
+            ),
             Error::Deserialize(ref e) => e.fmt(f),
             Error::Utf8(ref e) => e.fmt(f),
+            Error::Io(ref e) => e.fmt(f),
+            Error::Cancelled { at } => {
+                write!(f, "decoding was cancelled at column {}", at)
+            }
+            Error::TimedOut { at } => {
+                write!(f, "decoding timed out at column {}", at)
+            }
+            Error::Rejected { ref path, ref reason } => {
+                write!(f, "value at '{}' was rejected: {}", path, reason)
+            }
+            Error::Length { len, ref expected }
+            | Error::InvalidLength { len, ref expected } => {
+                write!(f, "invalid length {}, expected {}", len, expected)
+            }
         }
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            Error::Utf8(ref e) => Some(e),
+            Error::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 impl serde::de::Error for Error {
     fn custom<T>(e: T) -> Self
@@ -87,6 +297,22 @@ impl serde::de::Error for Error {
     {
         Error::Deserialize(e.to_string())
     }
+
+    fn invalid_length(len: usize, exp: &dyn Expected) -> Self {
+        Error::InvalidLength { len, expected: exp.to_string() }
+    }
+}
+
+impl Error {
+    /// Turns an error made by [`invalid_length`](serde::de::Error::invalid_length) into `Error::Length`, for a value that didn't fit its type, which is how fixed-capacity types, eg: those of the `heapless` crate, report running out of room.
+    pub(crate) fn into_overflow(self) -> Error {
+        match self {
+            Error::InvalidLength { len, expected } => {
+                Error::Length { len, expected }
+            }
+            e => e,
+        }
+    }
 }
 
 impl From<Utf8Error> for Error {
@@ -95,12 +321,378 @@ impl From<Utf8Error> for Error {
     }
 }
 
+impl From<IoError> for Error {
+    fn from(e: IoError) -> Self {
+        Error::Io(e)
+    }
+}
+
 impl From<FromUtf8Error> for Error {
     fn from(e: FromUtf8Error) -> Self {
         Error::Utf8(e.utf8_error())
     }
 }
 
+/// Visits an integer as a `u64` if it's not negative, or as an `i64` otherwise.
+///
+/// This keeps the signedness of integers intact when they're buffered by serde, eg: for untagged enums, so visitors that only accept unsigned integers still work.
+pub(crate) fn visit_int<'de, V>(visitor: V, v: i64) -> Result<V::Value, Error>
+where
+    V: serde::de::Visitor<'de>,
+{
+    match u64::try_from(v) {
+        Ok(v) => visitor.visit_u64(v),
+        Err(_) => visitor.visit_i64(v),
+    }
+}
+
+/// The kind of construct that makes bencoded bytes non-canonical.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NonCanonicalKind {
+    /// A dictionary key sorts before the key that precedes it.
+    UnsortedKeys,
+    /// A dictionary key is the same as the key that precedes it.
+    DuplicateKey,
+    /// An integer or a byte array's length has a leading zero, eg: `i01e`.
+    LeadingZero,
+    /// An integer is a negative zero, eg: `i-0e`.
+    NegativeZero,
+    /// An integer doesn't fit in an `i64`, so the crate can neither encode nor decode it, eg: `i9223372036854775808e`.
+    OutOfRange,
+    /// The bytes aren't exactly one well-formed value, eg: they're truncated or have trailing bytes.
+    Malformed,
+}
+
+/// The first construct that makes bencoded bytes non-canonical, as found by [`check_canonical`](super::check_canonical).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NonCanonical {
+    /// The position the construct starts at.
+    pub offset: usize,
+    /// What's wrong with the construct.
+    pub kind: NonCanonicalKind,
+    /// The bytes of the construct, eg: a whole integer or an encoded key. Empty if the source ended unexpectedly.
+    pub bytes: Vec<u8>,
+}
+
+impl NonCanonical {
+    fn new(
+        src: &[u8],
+        offset: usize,
+        end: usize,
+        kind: NonCanonicalKind,
+    ) -> Self {
+        let end = end.min(src.len());
+        let bytes = src.get(offset..end).unwrap_or_default().to_vec();
+        Self { offset, kind, bytes }
+    }
+
+    /// Constructs a malformed construct of the single byte at the position.
+    fn malformed(src: &[u8], offset: usize) -> Self {
+        Self::new(src, offset, offset + 1, NonCanonicalKind::Malformed)
+    }
+}
+
+impl std::fmt::Display for NonCanonical {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            NonCanonicalKind::UnsortedKeys => "unsorted dictionary key",
+            NonCanonicalKind::DuplicateKey => "duplicate dictionary key",
+            NonCanonicalKind::LeadingZero => "leading zero",
+            NonCanonicalKind::NegativeZero => "negative zero",
+            NonCanonicalKind::OutOfRange => "integer out of range",
+            NonCanonicalKind::Malformed => "malformed bencode",
+        };
+        write!(f, "{} at position {}", kind, self.offset)
+    }
+}
+
+impl std::error::Error for NonCanonical {}
+
+/// Checks that the digits of an integer or a length have no leading zeros, and aren't a negative zero.
+fn check_digits(digits: &[u8], signed: bool) -> Option<NonCanonicalKind> {
+    let digits = match digits.split_first() {
+        Some((b'-', rest)) if signed => match rest.first() {
+            Some(b'0') => return Some(NonCanonicalKind::NegativeZero),
+            None => return Some(NonCanonicalKind::Malformed),
+            _ => rest,
+        },
+        _ => digits,
+    };
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        Some(NonCanonicalKind::Malformed)
+    } else if digits[0] == b'0' && digits.len() > 1 {
+        Some(NonCanonicalKind::LeadingZero)
+    } else {
+        None
+    }
+}
+
+/// Checks that the source is exactly one value in canonical form, returning the first problem otherwise.
+///
+/// Canonical bencode has no leading zeros or negative zero in integers and lengths, and no unsorted or repeated dictionary keys. Integers must also fit in an `i64`, which is what the decoder accepts by default.
+pub(crate) fn check_canonical(src: &[u8]) -> Result<(), NonCanonical> {
+    /// An open list, or an open dictionary with its previous key and whether a key is expected next.
+    enum Frame<'a> {
+        List,
+        Dict { prev: Option<&'a [u8]>, key: bool },
+    }
+
+    let malformed = |at| NonCanonical::malformed(src, at);
+    let mut stack = vec![];
+    let mut pos = 0;
+    loop {
+        let start = pos;
+        let next = *src.get(pos).ok_or_else(|| malformed(pos))?;
+        let want_key =
+            matches!(stack.last(), Some(Frame::Dict { key: true, .. }));
+        match next {
+            TYPE_END if !stack.is_empty() => {
+                if matches!(stack.last(), Some(Frame::Dict { key: false, .. }))
+                {
+                    return Err(malformed(pos));
+                }
+                stack.pop();
+                pos += 1;
+            }
+            LIST_START if !want_key => {
+                stack.push(Frame::List);
+                pos += 1;
+                continue;
+            }
+            DICT_START if !want_key => {
+                stack.push(Frame::Dict { prev: None, key: true });
+                pos += 1;
+                continue;
+            }
+            INT_START if !want_key => {
+                let len = src[pos..].iter().position(|&b| b == TYPE_END);
+                let end =
+                    len.map(|len| pos + len).ok_or_else(|| malformed(pos))?;
+                let digits = &src[pos + 1..end];
+                let kind = check_digits(digits, true).or_else(|| {
+                    // `check_digits` found nothing wrong, so the digits are all ASCII.
+                    let int = ascii_str(digits).parse::<i64>();
+                    int.is_err().then_some(NonCanonicalKind::OutOfRange)
+                });
+                if let Some(kind) = kind {
+                    return Err(NonCanonical::new(src, start, end + 1, kind));
+                }
+                pos = end + 1;
+            }
+            b'0'..=b'9' => {
+                let len = src[pos..].iter().position(|&b| b == TEXT_DELIM);
+                let delim =
+                    len.map(|len| pos + len).ok_or_else(|| malformed(pos))?;
+                let digits = &src[pos..delim];
+                if let Some(kind) = check_digits(digits, false) {
+                    return Err(NonCanonical::new(src, start, delim + 1, kind));
+                }
+                // `check_digits` found nothing wrong, so the digits are all ASCII.
+                let len: usize =
+                    ascii_str(digits).parse().map_err(|_| malformed(start))?;
+                let end = match (delim + 1).checked_add(len) {
+                    Some(end) if end <= src.len() => end,
+                    _ => return Err(malformed(start)),
+                };
+                if let Some(Frame::Dict { prev, key: true }) = stack.last_mut()
+                {
+                    let key = &src[delim + 1..end];
+                    let kind = match prev.map(|prev| cmp_keys(prev, key)) {
+                        Some(Ordering::Greater) => {
+                            Some(NonCanonicalKind::UnsortedKeys)
+                        }
+                        Some(Ordering::Equal) => {
+                            Some(NonCanonicalKind::DuplicateKey)
+                        }
+                        _ => None,
+                    };
+                    if let Some(kind) = kind {
+                        return Err(NonCanonical::new(src, start, end, kind));
+                    }
+                    *prev = Some(key);
+                }
+                pos = end;
+            }
+            _ => return Err(malformed(pos)),
+        }
+
+        // A key or value was completed, so a dictionary expects the other one next.
+        match stack.last_mut() {
+            Some(Frame::Dict { key, .. }) => *key = !*key,
+            Some(Frame::List) => {}
+            None if pos == src.len() => return Ok(()),
+            None => return Err(malformed(pos)),
+        }
+    }
+}
+
+/// Parses a byte array of an optional `-` followed by ASCII digits, eg: a dictionary key, into an integer.
+///
+/// Returns `None` if the bytes are anything else, or if the integer is out of the type's range.
+fn parse_decimal<T>(bytes: &[u8]) -> Option<T>
+where
+    T: str::FromStr,
+{
+    let digits = bytes.strip_prefix(b"-").unwrap_or(bytes);
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    ascii_str(bytes).parse().ok()
+}
+
+/// Converts bytes that have already been checked to be ASCII, eg: the digits of an integer key, into a string.
+///
+/// With the `fast-unsafe` feature, the UTF-8 validation that the standard library would repeat is skipped. Without it, bytes that aren't ASCII are converted into an empty string, which no caller parses successfully.
+#[inline]
+fn ascii_str(bytes: &[u8]) -> &str {
+    debug_assert!(bytes.is_ascii());
+    // SAFETY: Both callers only pass bytes they've just checked to be an optional `-` followed by ASCII digits: `parse_decimal` checks them itself, and `check_canonical` only converts an integer or a length once `check_digits` has found nothing wrong with it. ASCII bytes are always valid UTF-8.
+    #[cfg(feature = "fast-unsafe")]
+    let text = unsafe { str::from_utf8_unchecked(bytes) };
+    #[cfg(not(feature = "fast-unsafe"))]
+    let text = str::from_utf8(bytes).unwrap_or_default();
+    text
+}
+
+/// Scans the source for the first complete value, returning its length in bytes.
+///
+/// Returns `None` if the source ends before the value is complete. The scan doesn't allocate, and only validates the structure of the source - not the types of dictionary keys.
+pub(crate) fn scan_frame(src: &[u8]) -> Result<Option<usize>, Error> {
+    FrameScanner::default().scan(src)
+}
+
+/// A resumable [`scan_frame`], for a source that grows between scans.
+///
+/// The scanner remembers the offset of the first incomplete token and how deeply it's nested, so each scan only looks at the bytes that weren't complete the last time. The source must only be appended to between scans, and the scanner must be [`reset`](FrameScanner::reset) once the frame is removed from it.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct FrameScanner {
+    pos: usize,
+    depth: usize,
+}
+
+impl FrameScanner {
+    /// Scans the source for the end of the first complete value, resuming where the last scan stopped.
+    pub(crate) fn scan(&mut self, src: &[u8]) -> Result<Option<usize>, Error> {
+        let start = self.pos;
+        let mut tokens = Tokens::new(&src[start..]);
+
+        while let Some(token) = tokens.next() {
+            let token = match token {
+                Ok((_, token)) => token,
+                Err(Error::EOF) => return Ok(None),
+                Err(e) => return Err(e),
+            };
+            // Only complete tokens are skipped by the next scan.
+            let end = start + tokens.offset();
+            match token {
+                Token::ListStart | Token::DictStart => {
+                    self.depth += 1;
+                    self.pos = end;
+                    continue;
+                }
+                Token::End if self.depth > 0 => self.depth -= 1,
+                Token::End => return Err(Error::Malformed),
+                Token::Int(_) | Token::Bytes(_) => {}
+            }
+
+            if self.depth == 0 {
+                return Ok(Some(end));
+            }
+            self.pos = end;
+        }
+        Ok(None)
+    }
+
+    /// Forgets the progress of the last scan, for scanning a new frame.
+    #[inline]
+    pub(crate) fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Statistics about a single decode, returned by [`decode_with_report`](super::decode_with_report).
+///
+/// Useful for capacity planning and spotting pathological payloads, without an external profiler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeReport {
+    /// The number of bytes consumed from the source.
+    pub consumed: usize,
+    /// The number of values decoded, including every list, dictionary, key and element.
+    pub nodes: usize,
+    /// The deepest level of nested lists and dictionaries reached, where a top-level list has a depth of `1`.
+    pub max_depth: usize,
+    /// The time spent decoding.
+    pub elapsed: Duration,
+}
+
+/// How byte arrays are handed to visitors by `deserialize_any`, which is used by self-describing types like untagged enums.
+///
+/// Bencode doesn't tell strings and byte arrays apart, so the decoder has to pick one. [`Value`] and the other value types decode the same way with either choice.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum AnyBytes {
+    /// Byte arrays are always visited as bytes. This is lossless, and most visitors that expect strings accept bytes that are valid UTF-8 anyway.
+    #[default]
+    Bytes,
+    /// Byte arrays that are valid UTF-8 are visited as strings, and any others as bytes.
+    ///
+    /// This suits visitors that only accept strings, eg: `char`, but byte arrays that happen to be valid UTF-8 are then seen as strings by visitors that tell them apart.
+    Str,
+}
+
+/// Which integers and byte arrays the decoder accepts as booleans.
+///
+/// Bencode has no booleans, so they're conventionally encoded as `i0e` and `i1e`. Other implementations are more forgiving when reading them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum BoolRepr {
+    /// Only `i0e` and `i1e` are accepted.
+    #[default]
+    Strict,
+    /// Any integer is accepted, where every integer but `0` is `true`, like libtorrent reads flags such as `private`.
+    NonZero,
+    /// `i0e` and `i1e` are accepted, as are the byte arrays `true` and `false`, like Transmission reads its settings.
+    IntOrText,
+}
+
+/// How [`decode_utf8_map`](super::decode_utf8_map) turns keys and values that aren't UTF-8 text into strings.
+///
+/// Integers are always written in decimal, and byte arrays that are valid UTF-8 are always kept as is. The policy applies to the rest: byte arrays that aren't valid UTF-8, and nested lists and dictionaries, which are converted from their encoded bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TextPolicy {
+    /// Fails on the first byte array that isn't valid UTF-8, or nested value.
+    #[default]
+    Error,
+    /// Replaces invalid UTF-8 sequences with `U+FFFD`, so nested values are kept as their encoded text, eg: `l1:ae`.
+    Lossy,
+    /// Writes the bytes as lowercase hexadecimal, eg: a 20-byte hash as 40 digits.
+    Hex,
+}
+
+impl TextPolicy {
+    /// Converts bytes that aren't valid UTF-8 text, or the encoded bytes of a nested value, into a string.
+    pub(crate) fn convert(self, bytes: &[u8]) -> Result<String, Error> {
+        match self {
+            TextPolicy::Error => Ok(std::str::from_utf8(bytes)?.to_owned()),
+            TextPolicy::Lossy => {
+                Ok(String::from_utf8_lossy(bytes).into_owned())
+            }
+            TextPolicy::Hex => Ok(bytes.iter().fold(
+                String::with_capacity(bytes.len() * 2),
+                |mut s, b| {
+                    s.push(char::from_digit((b >> 4).into(), 16).unwrap());
+                    s.push(char::from_digit((b & 0xf).into(), 16).unwrap());
+                    s
+                },
+            )),
+        }
+    }
+}
+
+/// The number of values decoded between checks of the deadline, as reading the clock is much slower than decoding a small value.
+const DEADLINE_CHECK_INTERVAL: usize = 256;
+
+/// The default maximum number of digits an integer may have, which is enough for any `i64`.
+const DEFAULT_MAX_INT_DIGITS: usize = MAX_INT_DIGITS;
+
 /// A decoder used to decode types from bencode representation.
 ///
 /// When decoding types, you'd want to use the [`decode`](super::decode) function, but you **can** use the decoder directly - though it's important to note that there is currently no real benefit to doing so.
@@ -115,10 +707,324 @@ impl From<FromUtf8Error> for Error {
 /// assert_eq!(i32::deserialize(&mut de).unwrap(), 1995);
 ///
 /// ```
-#[derive(Debug)]
 pub struct Decoder<'de> {
     src: &'de [u8],
     pos: usize,
+    observer: Option<Box<dyn AllocObserver + Send + 'de>>,
+    /// Whether values and nesting are counted for a [`DecodeReport`], which is otherwise only done when something else needs the counts.
+    stats: bool,
+    nodes: usize,
+    depth: usize,
+    max_depth: usize,
+    max_int_digits: usize,
+    any_bytes: AnyBytes,
+    lenient_ints: bool,
+    bools: BoolRepr,
+    digest: Option<Digest<'de>>,
+    progress: Option<Progress<'de>>,
+    cancel: Option<CancelToken>,
+    deadline: Option<Instant>,
+    cache: Option<StringCache<'de>>,
+    interceptors: Vec<(Pattern, Box<dyn Interceptor + Send + 'de>)>,
+    /// The path of the value being decoded, which is only kept while there are interceptors.
+    path: Path,
+    /// The state of the [`Value`] being decoded for interceptors, if there is one.
+    view: Option<View>,
+}
+
+/// What happened while decoding the [`Value`] that interceptors are given.
+#[derive(Debug, Default)]
+struct View {
+    /// Whether an interceptor changed a value.
+    modified: bool,
+}
+
+/// The [`Decoder`], under the name serde gives to the types that implement [`serde::Deserializer`].
+///
+/// This alias is part of the stable API, so frameworks can name the deserializer they embed the same way for every serde format.
+pub type Deserializer<'de> = Decoder<'de>;
+
+impl<'de> std::fmt::Debug for Decoder<'de> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Decoder")
+            .field("src", &self.src)
+            .field("pos", &self.pos)
+            .field("observer", &self.observer.is_some())
+            .field("progress", &self.progress.is_some())
+            .field("digest", &self.digest.as_ref().map(|d| &d.path))
+            .finish()
+    }
+}
+
+/// A hook that is notified of every buffer the decoder allocates while decoding, eg: when decoding an owned `String`.
+///
+/// This gives services observability into memory usage, so they can enforce per-connection budgets and emit metrics. Allocations made by the types being decoded are **not** reported - only the decoder's own.
+///
+/// The trait is implemented for any `FnMut(usize)` closure. Observers must be `Send`, so the decoder that holds them can be moved across threads.
+///
+/// # Examples
+///
+/// ```
+/// use serde::Deserialize;
+/// use bende::de::Decoder;
+///
+/// let mut total = 0;
+///
+/// let mut de = Decoder::new(b"l3:foo6:foobare")
+///     .with_alloc_observer(|len| total += len);
+/// let list = Vec::<String>::deserialize(&mut de).unwrap();
+/// drop(de);
+///
+/// assert_eq!(list, ["foo", "foobar"]);
+/// assert_eq!(total, 9);
+/// ```
+pub trait AllocObserver {
+    /// Called with the size, in bytes, of a buffer that the decoder is about to allocate.
+    fn on_alloc(&mut self, len: usize);
+}
+
+impl<F> AllocObserver for F
+where
+    F: FnMut(usize),
+{
+    fn on_alloc(&mut self, len: usize) {
+        self(len)
+    }
+}
+
+/// A hook that is notified as the decoder makes its way through the source, eg: to show a progress bar when decoding large files.
+///
+/// The observer is called with the number of bytes consumed so far, and decoding continues for as long as it returns `true`. Returning `false` cancels the decode, which then fails with `Error::Cancelled`.
+///
+/// # Examples
+///
+/// ```
+/// use bende::Value;
+/// use serde::Deserialize;
+/// use bende::de::{Decoder, Error};
+///
+/// let src = b"l3:foo3:bar3:baze";
+///
+/// let mut seen = Vec::new();
+/// let mut de = Decoder::new(src).with_progress(5, |consumed| {
+///     seen.push(consumed);
+///     true
+/// });
+/// assert!(Value::deserialize(&mut de).is_ok());
+/// drop(de);
+/// assert_eq!(seen, [8, 13]);
+///
+/// let mut de = Decoder::new(src).with_progress(5, |consumed| consumed < 10);
+/// assert_eq!(Value::deserialize(&mut de), Err(Error::Cancelled { at: 13 }));
+/// ```
+pub trait ProgressObserver {
+    /// Called with the number of bytes consumed so far. Returns whether to continue decoding.
+    fn on_progress(&mut self, consumed: usize) -> bool;
+}
+
+impl<F> ProgressObserver for F
+where
+    F: FnMut(usize) -> bool,
+{
+    fn on_progress(&mut self, consumed: usize) -> bool {
+        self(consumed)
+    }
+}
+
+/// A progress observer and how often it's notified.
+pub(crate) struct Progress<'a> {
+    observer: Box<dyn ProgressObserver + Send + 'a>,
+    every: usize,
+    /// The number of consumed bytes at which the observer is notified next.
+    next: usize,
+}
+
+impl<'a> Progress<'a> {
+    /// Constructs a progress observer that is notified every time another `every` bytes have been consumed.
+    ///
+    /// # Panics
+    ///
+    /// * `every` is `0`.
+    pub(crate) fn new<O>(every: usize, observer: O) -> Progress<'a>
+    where
+        O: ProgressObserver + Send + 'a,
+    {
+        assert!(every > 0, "progress must be reported every 1 byte or more");
+        Self { observer: Box::new(observer), every, next: every }
+    }
+
+    /// Notifies the observer if another `every` bytes have been consumed since it was last notified.
+    ///
+    /// # Errors
+    ///
+    /// * The observer cancelled the decode, at which point `Error::Cancelled` is returned.
+    #[inline]
+    pub(crate) fn update(&mut self, consumed: usize) -> Result<(), Error> {
+        if consumed < self.next {
+            return Ok(());
+        }
+        self.next = (consumed / self.every)
+            .saturating_add(1)
+            .saturating_mul(self.every);
+        match self.observer.on_progress(consumed) {
+            true => Ok(()),
+            false => Err(Error::Cancelled { at: consumed }),
+        }
+    }
+}
+
+/// A hook that is fed the raw bytes of a value while it's decoded, eg: to hash the `info` dictionary of a torrent without scanning the source twice.
+///
+/// Any closure that takes a slice of bytes is a sink, so any hasher can be adapted with a closure. With the `digest` feature, a hasher from the `digest` crates can be passed to `Decoder::with_hasher` as is.
+///
+/// # Examples
+///
+/// ```
+/// use serde::Deserialize;
+/// use bende::de::Decoder;
+///
+/// #[derive(Deserialize)]
+/// struct Torrent {
+///     announce: String,
+/// }
+///
+/// let src = b"d8:announce3:foo4:infod6:lengthi1995eee";
+/// let mut info = Vec::new();
+///
+/// let mut de = Decoder::new(src)
+///     .with_digest("info".parse().unwrap(), |bytes: &[u8]| info.extend_from_slice(bytes));
+/// let torrent = Torrent::deserialize(&mut de).unwrap();
+/// drop(de);
+///
+/// assert_eq!(torrent.announce, "foo");
+/// assert_eq!(info, b"d6:lengthi1995ee");
+/// ```
+pub trait DigestSink {
+    /// Called with the raw bytes of the value at the sink's path, once it's been decoded.
+    fn update(&mut self, bytes: &[u8]);
+}
+
+impl<F> DigestSink for F
+where
+    F: FnMut(&[u8]),
+{
+    fn update(&mut self, bytes: &[u8]) {
+        self(bytes)
+    }
+}
+
+/// A hook that can transform or reject the values at the paths it's registered for, before they're decoded into the types they land in.
+///
+/// The trait is implemented for any `FnMut(&Path, &mut Value) -> Result<(), String>` closure.
+///
+/// # Examples
+///
+/// ```
+/// use serde::Deserialize;
+/// use bende::de::{Decoder, Error};
+/// use bende::path::Path;
+/// use bende::Value;
+///
+/// #[derive(Debug, Deserialize)]
+/// struct Torrent {
+///     #[serde(rename = "announce-list")]
+///     announce_list: Vec<Vec<String>>,
+/// }
+///
+/// let cap = |_: &Path, tiers: &mut Value| match tiers.as_list_mut() {
+///     Some(tiers) if tiers.len() > 2 => Err(format!("{} tiers", tiers.len())),
+///     _ => Ok(()),
+/// };
+/// let lowercase = |_: &Path, tracker: &mut Value| {
+///     if let Value::Text(ref mut bytes) = *tracker {
+///         bytes.make_ascii_lowercase();
+///     }
+///     Ok(())
+/// };
+///
+/// let src = b"d13:announce-listll8:UDP://T1el8:udp://t2eee";
+/// let mut de = Decoder::new(src)
+///     .with_interceptor("announce-list".parse().unwrap(), cap)
+///     .with_interceptor("announce-list[*][*]".parse().unwrap(), lowercase);
+///
+/// let torrent = Torrent::deserialize(&mut de).unwrap();
+/// assert_eq!(torrent.announce_list, [["udp://t1"], ["udp://t2"]]);
+///
+/// let src = b"d13:announce-listll1:ael1:bel1:ceee";
+/// let mut de = Decoder::new(src).with_interceptor("announce-list".parse().unwrap(), cap);
+/// assert!(matches!(Torrent::deserialize(&mut de), Err(Error::Rejected { .. })));
+/// ```
+pub trait Interceptor {
+    /// Called with a value at one of the interceptor's paths, after the values inside it have been intercepted.
+    ///
+    /// # Errors
+    ///
+    /// * The value is rejected for the given reason, at which point the decode fails with `Error::Rejected`.
+    fn intercept(&mut self, path: &Path, val: &mut Value)
+        -> Result<(), String>;
+}
+
+impl<F> Interceptor for F
+where
+    F: FnMut(&Path, &mut Value) -> Result<(), String>,
+{
+    fn intercept(
+        &mut self,
+        path: &Path,
+        val: &mut Value,
+    ) -> Result<(), String> {
+        self(path, val)
+    }
+}
+
+/// A digest sink and the path of the value it's fed.
+struct Digest<'de> {
+    path: Path,
+    sink: Box<dyn DigestSink + Send + 'de>,
+    /// The number of leading segments of the path that the decoder is currently inside of.
+    matched: usize,
+}
+
+/// A size-bounded cache of the byte arrays a decoder has recently shared, which evicts the least recently used one when it's full.
+struct StringCache<'de> {
+    capacity: usize,
+    /// The shared copy of each cached byte array, alongside when it was last used.
+    entries: HashMap<&'de [u8], (Arc<Vec<u8>>, u64)>,
+    clock: u64,
+    hits: usize,
+}
+
+impl<'de> StringCache<'de> {
+    fn new(capacity: usize) -> StringCache<'de> {
+        assert!(capacity > 0, "the cache capacity should not be 0");
+        let entries = HashMap::with_capacity(capacity);
+        Self { capacity, entries, clock: 0, hits: 0 }
+    }
+
+    /// Gets the shared copy of the given bytes, caching a new one if there isn't one.
+    ///
+    /// Evicting scans the whole cache, which is cheap for the small capacities a cache is meant to have.
+    fn get(&mut self, bytes: &'de [u8]) -> Arc<Vec<u8>> {
+        self.clock += 1;
+        if let Some((shared, used)) = self.entries.get_mut(bytes) {
+            *used = self.clock;
+            self.hits += 1;
+            return Arc::clone(shared);
+        }
+        if self.entries.len() == self.capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                self.entries.remove(oldest);
+            }
+        }
+        let shared = Arc::new(bytes.to_vec());
+        self.entries.insert(bytes, (Arc::clone(&shared), self.clock));
+        shared
+    }
 }
 
 // Constructor and byte iteration methods.
@@ -126,7 +1032,464 @@ impl<'de> Decoder<'de> {
     /// Constructs a new decoder with the given source.
     #[inline]
     pub fn new(src: &'de [u8]) -> Decoder<'de> {
-        Self { src, pos: 0 }
+        Self {
+            src,
+            pos: 0,
+            observer: None,
+            stats: false,
+            nodes: 0,
+            depth: 0,
+            max_depth: 0,
+            max_int_digits: DEFAULT_MAX_INT_DIGITS,
+            any_bytes: AnyBytes::default(),
+            lenient_ints: false,
+            bools: BoolRepr::default(),
+            digest: None,
+            progress: None,
+            cancel: None,
+            deadline: None,
+            cache: None,
+            interceptors: Vec::new(),
+            path: Path::root(),
+            view: None,
+        }
+    }
+
+    /// Sets the maximum number of digits an integer may have, excluding its sign.
+    ///
+    /// Integers with more digits are rejected with `Error::IntegerTooLong` before they're parsed. Defaults to `19`, which is enough for any `i64`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use bende::de::{Decoder, Error};
+    ///
+    /// let mut de = Decoder::new(b"i-1995e").with_max_int_digits(3);
+    /// assert_eq!(i64::deserialize(&mut de), Err(Error::IntegerTooLong { at: 1, max: 3 }));
+    /// ```
+    #[inline]
+    pub fn with_max_int_digits(mut self, max: usize) -> Decoder<'de> {
+        self.max_int_digits = max;
+        self
+    }
+
+    /// Sets how byte arrays are handed to visitors by `deserialize_any`. Defaults to [`AnyBytes::Bytes`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use bende::de::{AnyBytes, Decoder};
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// #[serde(untagged)]
+    /// enum Flag {
+    ///     Char(char),
+    ///     Int(i64),
+    /// }
+    ///
+    /// let mut de = Decoder::new(b"1:x");
+    /// assert!(Flag::deserialize(&mut de).is_err());
+    ///
+    /// let mut de = Decoder::new(b"1:x").with_any_bytes(AnyBytes::Str);
+    /// assert_eq!(Flag::deserialize(&mut de).unwrap(), Flag::Char('x'));
+    /// ```
+    #[inline]
+    pub fn with_any_bytes(mut self, any_bytes: AnyBytes) -> Decoder<'de> {
+        self.any_bytes = any_bytes;
+        self
+    }
+
+    /// Sets whether integers and numeric byte arrays may stand in for each other. Defaults to `false`.
+    ///
+    /// Some producers encode integers as byte arrays of their digits, eg: `4:1995`, or byte arrays as integers. When enabled, a byte array of an optional `-` followed by ASCII digits is accepted where an integer is expected, and an integer is accepted as its decimal digits where a string or a byte array is expected. Only the types being decoded are affected - `deserialize_any`, and so [`Value`], still sees the source as it is.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use bende::de::Decoder;
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// struct Peer {
+    ///     id: String,
+    ///     port: u16,
+    /// }
+    ///
+    /// let src = b"d2:idi1995e4:port4:6881e";
+    /// assert!(Peer::deserialize(&mut Decoder::new(src)).is_err());
+    ///
+    /// let mut de = Decoder::new(src).with_lenient_ints(true);
+    /// assert_eq!(Peer::deserialize(&mut de).unwrap(), Peer { id: "1995".into(), port: 6881 });
+    /// ```
+    #[inline]
+    pub fn with_lenient_ints(mut self, lenient: bool) -> Decoder<'de> {
+        self.lenient_ints = lenient;
+        self
+    }
+
+    /// Sets which integers and byte arrays are accepted as booleans. Defaults to [`BoolRepr::Strict`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use bende::de::{BoolRepr, Decoder};
+    ///
+    /// assert!(bool::deserialize(&mut Decoder::new(b"i2e")).is_err());
+    ///
+    /// let mut de = Decoder::new(b"i2e").with_bool_repr(BoolRepr::NonZero);
+    /// assert!(bool::deserialize(&mut de).unwrap());
+    ///
+    /// let mut de = Decoder::new(b"5:false").with_bool_repr(BoolRepr::IntOrText);
+    /// assert!(!bool::deserialize(&mut de).unwrap());
+    /// ```
+    #[inline]
+    pub fn with_bool_repr(mut self, bools: BoolRepr) -> Decoder<'de> {
+        self.bools = bools;
+        self
+    }
+
+    /// Applies the decoding knobs of an interop profile. See [`Profile`] for the knobs each one sets.
+    #[inline]
+    pub fn with_profile(self, profile: Profile) -> Decoder<'de> {
+        self.with_bool_repr(match profile {
+            Profile::Strict => BoolRepr::Strict,
+            Profile::Libtorrent => BoolRepr::NonZero,
+            Profile::Transmission => BoolRepr::IntOrText,
+        })
+    }
+
+    /// Gets the number of bytes the decoder has consumed so far.
+    #[inline]
+    pub(crate) fn consumed(&self) -> usize {
+        self.pos
+    }
+
+    /// Counts a value that the decoder has started decoding, notifying the progress observer if it's due.
+    ///
+    /// # Errors
+    ///
+    /// * The decode was cancelled by the cancel token or the progress observer.
+    /// * The deadline has passed.
+    #[inline]
+    fn count_node(&mut self) -> Result<(), Error> {
+        // The count is only read by reports, deadlines and trace events.
+        if cfg!(feature = "tracing") || self.stats || self.deadline.is_some() {
+            self.nodes += 1;
+        }
+        if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+            return Err(Error::Cancelled { at: self.pos });
+        }
+        if let Some(deadline) = self.deadline {
+            if self.nodes % DEADLINE_CHECK_INTERVAL == 1
+                && Instant::now() >= deadline
+            {
+                return Err(Error::TimedOut { at: self.pos });
+            }
+        }
+        match self.progress {
+            Some(ref mut progress) => progress.update(self.pos),
+            None => Ok(()),
+        }
+    }
+
+    /// Counts values and nesting for a [`DecodeReport`].
+    #[inline]
+    pub(crate) fn with_stats(mut self) -> Decoder<'de> {
+        self.stats = true;
+        self
+    }
+
+    /// Gets the number of values the decoder has started decoding so far.
+    ///
+    /// Values are only counted with the `tracing` feature, or with [`with_stats`](Decoder::with_stats) or a deadline set.
+    #[inline]
+    pub(crate) fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    /// Gets the deepest level of nested lists and dictionaries the decoder has reached so far.
+    ///
+    /// Nesting is only tracked with [`with_stats`](Decoder::with_stats) set.
+    #[inline]
+    pub(crate) fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Counts a list or dictionary that the decoder is entering.
+    #[inline]
+    fn enter(&mut self) -> Result<(), Error> {
+        self.count_node()?;
+        if self.tracks_depth() {
+            self.depth += 1;
+            self.max_depth = self.max_depth.max(self.depth);
+        }
+        Ok(())
+    }
+
+    /// Marks the end of the list or dictionary the decoder is in.
+    #[inline]
+    fn leave(&mut self) {
+        if self.tracks_depth() {
+            self.depth = self.depth.saturating_sub(1);
+        }
+    }
+
+    /// Checks if the depth is needed, which is only by reports and digests.
+    #[inline]
+    fn tracks_depth(&self) -> bool {
+        self.stats || self.digest.is_some()
+    }
+
+    /// Sets a hook that is notified of every buffer the decoder allocates.
+    ///
+    /// See [`AllocObserver`] for more details.
+    #[inline]
+    pub fn with_alloc_observer<O>(mut self, observer: O) -> Decoder<'de>
+    where
+        O: AllocObserver + Send + 'de,
+    {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Sets a hook that is notified every time another `every` bytes of the source have been consumed, and that can cancel the decode.
+    ///
+    /// The decoder checks its progress at the start of every value, so a long byte array is reported once it's been consumed. See [`ProgressObserver`] for more details.
+    ///
+    /// # Panics
+    ///
+    /// * `every` is `0`.
+    #[inline]
+    pub fn with_progress<O>(mut self, every: usize, observer: O) -> Decoder<'de>
+    where
+        O: ProgressObserver + Send + 'de,
+    {
+        self.progress = Some(Progress::new(every, observer));
+        self
+    }
+
+    /// Sets a token that cancels the decode once it's cancelled. The token is checked at the start of every value.
+    ///
+    /// See [`CancelToken`] for more details.
+    #[inline]
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Decoder<'de> {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Sets a point in time after which the decode fails with `Error::TimedOut`.
+    ///
+    /// The clock is read at the start of the first value, and then every few hundred values, so a decode may run slightly past its deadline. See [`decode_with_deadline`](super::decode_with_deadline) for a shorthand.
+    #[inline]
+    pub fn with_deadline(mut self, deadline: Instant) -> Decoder<'de> {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets a cache of the `capacity` most recently seen byte arrays, which [`decode_shared`](Decoder::decode_shared) uses to share one allocation between identical byte arrays, eg: the repeated directories of a multi-file torrent.
+    ///
+    /// The cache only lives as long as the decoder. Use an [`Interner`](crate::value::Interner) to share byte arrays across decodes.
+    ///
+    /// # Panics
+    ///
+    /// * `capacity` is `0`.
+    #[inline]
+    pub fn with_string_cache(mut self, capacity: usize) -> Decoder<'de> {
+        self.cache = Some(StringCache::new(capacity));
+        self
+    }
+
+    /// Gets the number of byte arrays that were shared from the string cache, rather than allocated. This is `0` if there's no cache.
+    #[inline]
+    pub fn string_cache_hits(&self) -> usize {
+        self.cache.as_ref().map_or(0, |cache| cache.hits)
+    }
+
+    /// Registers an interceptor for the values inside lists and dictionaries that match the given pattern. The root value is never intercepted.
+    ///
+    /// An intercepted value is first decoded into a [`Value`], which every interceptor that matches its path is given in the order they were registered. If none of them changed it, the value is then decoded into its type from its raw bytes, as if it wasn't intercepted, so byte array keys and borrowed strings still work. Otherwise it's decoded from the changed `Value`. See [`Interceptor`] for more details.
+    pub fn with_interceptor<I>(
+        mut self,
+        pattern: Pattern,
+        interceptor: I,
+    ) -> Decoder<'de>
+    where
+        I: Interceptor + Send + 'de,
+    {
+        self.interceptors.push((pattern, Box::new(interceptor)));
+        self
+    }
+
+    /// Decodes the child value at the given segment of the current path, passing it through the interceptors that match its path first.
+    ///
+    /// The segment is only constructed if there are interceptors.
+    fn decode_child<T, S>(&mut self, seg: S, seed: T) -> Result<T::Value, Error>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+        S: FnOnce() -> Segment,
+    {
+        if self.interceptors.is_empty() {
+            return seed.deserialize(self);
+        }
+        self.path.push(seg());
+        let result = self.decode_intercepted(seed);
+        self.path.pop();
+        result
+    }
+
+    fn decode_intercepted<T>(&mut self, seed: T) -> Result<T::Value, Error>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        let path = &self.path;
+        if !self.interceptors.iter().any(|(p, _)| p.matches(path)) {
+            return seed.deserialize(self);
+        }
+        if self.view.is_some() {
+            // Inside of another intercepted value, which is decoded from its view if anything in it changed.
+            let val = self.decode_view()?;
+            return seed.deserialize(val);
+        }
+
+        let start = self.pos;
+        self.view = Some(View::default());
+        let val = self.decode_view();
+        let view = self.view.take().unwrap_or_default();
+        let val = val?;
+
+        if view.modified {
+            return seed.deserialize(val);
+        }
+
+        // Nothing changed, so the value is decoded again from its raw bytes, as if it wasn't intercepted. The hooks that already saw it are set aside, so they don't see it twice.
+        let (end, nodes) = (self.pos, self.nodes);
+        self.pos = start;
+        let interceptors = std::mem::take(&mut self.interceptors);
+        let digest = self.digest.take();
+        let progress = self.progress.take();
+        let result = seed.deserialize(&mut *self);
+        self.interceptors = interceptors;
+        self.digest = digest;
+        self.progress = progress;
+        self.nodes = nodes;
+        debug_assert!(result.is_err() || self.pos == end);
+        result
+    }
+
+    /// Decodes the current value into a [`Value`], and passes it through the interceptors that match its path, noting whether any of them changed it.
+    fn decode_view(&mut self) -> Result<Value, Error> {
+        let mut val: Value = serde::Deserialize::deserialize(&mut *self)?;
+        for (pattern, interceptor) in &mut self.interceptors {
+            if pattern.matches(&self.path) {
+                let before = val.clone();
+                interceptor.intercept(&self.path, &mut val).map_err(
+                    |reason| Error::Rejected {
+                        path: self.path.clone(),
+                        reason,
+                    },
+                )?;
+                if val != before {
+                    if let Some(ref mut view) = self.view {
+                        view.modified = true;
+                    }
+                }
+            }
+        }
+        Ok(val)
+    }
+
+    /// Sets a hook that is fed the raw bytes of the value at the given path once it's been decoded, even if the type being decoded ignores it.
+    ///
+    /// The sink isn't fed anything if there's no value at the path. See [`DigestSink`] for more details.
+    ///
+    /// # Panics
+    ///
+    /// * The path is the root path. The raw bytes of the root value are the source itself, so it can be hashed directly.
+    #[inline]
+    pub fn with_digest<S>(mut self, path: Path, sink: S) -> Decoder<'de>
+    where
+        S: DigestSink + Send + 'de,
+    {
+        assert!(!path.is_root(), "the digest path must not be the root path");
+        self.digest = Some(Digest { path, sink: Box::new(sink), matched: 0 });
+        self
+    }
+
+    /// Feeds the raw bytes of the value at the given path to a hasher from the `digest` crates, eg: to compute the infohash of a torrent while decoding it.
+    ///
+    /// This works like [`with_digest`](Decoder::with_digest), without having to adapt the hasher with a closure. The hasher is borrowed, so it can be finalized once the decoder is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use sha1::{Digest, Sha1};
+    /// use bende::de::Decoder;
+    /// use bende::Value;
+    ///
+    /// let src = b"d8:announce3:foo4:infod6:lengthi1995eee";
+    ///
+    /// let mut hasher = Sha1::new();
+    /// let mut de = Decoder::new(src).with_hasher("info".parse().unwrap(), &mut hasher);
+    /// Value::deserialize(&mut de).unwrap();
+    /// drop(de);
+    ///
+    /// assert_eq!(hasher.finalize()[..], Sha1::digest(b"d6:lengthi1995ee")[..]);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// * The path is the root path.
+    #[cfg(feature = "digest")]
+    #[inline]
+    pub fn with_hasher<D>(self, path: Path, hasher: &'de mut D) -> Decoder<'de>
+    where
+        D: digest::Update + Send,
+    {
+        self.with_digest(path, |bytes: &[u8]| hasher.update(bytes))
+    }
+
+    /// Checks if the child value about to be decoded, with the given key or index, continues the digest's path, and steps into it if so.
+    fn digest_enter<F>(&mut self, is_next: F) -> bool
+    where
+        F: FnOnce(&Segment) -> bool,
+    {
+        let depth = self.depth;
+        match self.digest {
+            Some(ref mut digest)
+                if digest.matched + 1 == depth
+                    && digest
+                        .path
+                        .segments()
+                        .get(digest.matched)
+                        .is_some_and(is_next) =>
+            {
+                digest.matched += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Steps out of a child value that continues the digest's path, feeding the sink its raw bytes if it's the end of the path.
+    fn digest_leave(&mut self, start: usize) {
+        if let Some(ref mut digest) = self.digest {
+            if digest.matched == digest.path.segments().len() {
+                digest.sink.update(&self.src[start..self.pos]);
+            }
+            digest.matched -= 1;
+        }
+    }
+
+    /// Notifies the observer, if any, that a buffer of `len` bytes is being allocated.
+    #[inline]
+    fn observe_alloc(&mut self, len: usize) {
+        if let Some(ref mut observer) = self.observer {
+            observer.on_alloc(len);
+        }
     }
 
     /// Gets the length of the source.
@@ -137,13 +1500,13 @@ impl<'de> Decoder<'de> {
 
     /// Gets the decoder's current position.
     #[inline]
-    fn pos(&self) -> usize {
+    pub(crate) fn pos(&self) -> usize {
         self.pos
     }
 
     /// Peeks the next byte **without consuming it**.
     #[inline]
-    fn peek(&self) -> Option<u8> {
+    pub(crate) fn peek(&self) -> Option<u8> {
         self.src.get(self.pos()).cloned()
     }
 
@@ -152,7 +1515,10 @@ impl<'de> Decoder<'de> {
     fn next(&mut self) -> Option<u8> {
         if self.pos() < self.len() {
             // SAFETY: We've established that the position is in bounds.
+            #[cfg(feature = "fast-unsafe")]
             let next = unsafe { *self.src.get_unchecked(self.pos()) };
+            #[cfg(not(feature = "fast-unsafe"))]
+            let next = self.src[self.pos()];
             self.pos += 1;
             Some(next)
         } else {
@@ -162,7 +1528,7 @@ impl<'de> Decoder<'de> {
 
     /// Advances the decoder's position by **n**.
     #[inline]
-    fn advance(&mut self, n: usize) {
+    pub(crate) fn advance(&mut self, n: usize) {
         self.pos += n;
     }
 
@@ -173,7 +1539,7 @@ impl<'de> Decoder<'de> {
     /// * The decoder has reached the end of the source.
     /// * The predicate yields `false`, at which point `Error::Wanted` is returned.
     #[inline]
-    fn advance_if<P>(
+    pub(crate) fn advance_if<P>(
         &mut self,
         pred: P,
         expected: &'static str,
@@ -205,18 +1571,38 @@ impl<'de> Decoder<'de> {
     /// * The first byte is not equal to `INT_START`.
     /// * The bytes are not valid digits.
     #[inline]
-    fn decode_int(&mut self) -> Result<i64, Error> {
+    pub(crate) fn decode_int(&mut self) -> Result<i64, Error> {
         self.advance_if(|next| next == INT_START, "an integer")?;
+        self.count_node()?;
+        self.decode_int_digits()
+    }
 
+    /// Decodes an integer, or a byte array of its digits if lenient integers are enabled.
+    ///
+    /// # Errors
+    ///
+    /// * The byte array isn't an integer, or it's out of range, at which point `Error::Wanted` is returned.
+    fn decode_int_lenient(&mut self) -> Result<i64, Error> {
+        if !self.lenient_ints
+            || !self.peek().is_some_and(|b| b.is_ascii_digit())
+        {
+            return self.decode_int();
+        }
         let start = self.pos();
-        while let Some(next) = self.next() {
-            if next == TYPE_END {
-                // We want to exclude the 'TYPE_END' from the slice, so that means its `pos - 1`.
-                let text = str::from_utf8(&self.src[start..self.pos() - 1])?;
-                return text.parse().map_err(|_| Error::Malformed);
-            }
+        let bytes = self.decode_bytes()?;
+        parse_decimal(bytes).ok_or_else(|| Error::Wanted {
+            at: start,
+            expected: "an integer",
+            found: String::from_utf8_lossy(bytes).into_owned(),
+        })
+    }
+
+    /// Decodes an integer into its decimal digits if lenient integers are enabled, and the next value is an integer. Otherwise, `None` is returned.
+    fn decode_int_text(&mut self) -> Result<Option<String>, Error> {
+        if !self.lenient_ints || self.peek() != Some(INT_START) {
+            return Ok(None);
         }
-        Err(Error::Malformed)
+        self.decode_int_unchecked().map(|v| Some(v.to_string()))
     }
 
     /// Decodes an integer from the source without checking if the first byte is equal to `INT_START`.
@@ -224,13 +1610,52 @@ impl<'de> Decoder<'de> {
     fn decode_int_unchecked(&mut self) -> Result<i64, Error> {
         // Skip the integer's denotation.
         self.advance(1);
+        self.count_node()?;
+        self.decode_int_digits()
+    }
 
+    /// Decodes the digits of an integer up until (and including) its `TYPE_END`.
+    ///
+    /// # Errors
+    ///
+    /// * There are more digits than the configured maximum, at which point `Error::IntegerTooLong` is returned.
+    /// * The integer doesn't fit in an `i64`, at which point `Error::IntegerOverflow` is returned.
+    /// * The bytes are not valid digits, or the source ends before `TYPE_END`.
+    #[inline]
+    fn decode_int_digits(&mut self) -> Result<i64, Error> {
         let start = self.pos();
+        // The sign is not counted as a digit.
+        let limit = match self.peek() {
+            Some(b'-') => self.max_int_digits.saturating_add(1),
+            _ => self.max_int_digits,
+        };
+
         while let Some(next) = self.next() {
             if next == TYPE_END {
                 // We want to exclude the 'TYPE_END' from the slice, so that means its `pos - 1`.
                 let text = str::from_utf8(&self.src[start..self.pos() - 1])?;
-                return text.parse().map_err(|_| Error::Malformed);
+                return text.parse().map_err(|e: ParseIntError| {
+                    match e.kind() {
+                        IntErrorKind::PosOverflow
+                        | IntErrorKind::NegOverflow => {
+                            Error::IntegerOverflow { at: start }
+                        }
+                        _ => Error::Malformed,
+                    }
+                });
+            }
+
+            if self.pos() - start > limit {
+                trace!(
+                    warn,
+                    at = start,
+                    max = self.max_int_digits,
+                    "integer too long"
+                );
+                return Err(Error::IntegerTooLong {
+                    at: start,
+                    max: self.max_int_digits,
+                });
             }
         }
         Err(Error::Malformed)
@@ -256,19 +1681,93 @@ impl<'de> Decoder<'de> {
 
     /// Decodes a byte array that may or **may not** be valid UTF-8.
     #[inline]
-    fn decode_bytes(&mut self) -> Result<&'de [u8], Error> {
+    pub(crate) fn decode_bytes(&mut self) -> Result<&'de [u8], Error> {
         let len = self.decode_len()?;
+        self.count_node()?;
+
+        // Check the length against the bytes that are actually left **before** doing anything with it, so a forged length (eg: `999999999:`) can't cause an overflow or a huge allocation.
+        let start = self.pos();
+        if len > self.len() - start {
+            trace!(
+                debug,
+                at = start,
+                len,
+                remaining = self.len() - start,
+                "byte array length exceeds the remaining input"
+            );
+            return Err(Error::EOF);
+        }
 
         // We can avoid iterating over the bytes **entirely** by simply advancing the decoder's position by `len`.
+        self.advance(len);
+        Ok(&self.src[start..self.pos()])
+    }
+
+    /// Decodes the raw bytes of the next value, without decoding the value itself.
+    #[inline]
+    pub(crate) fn decode_raw(&mut self) -> Result<&'de [u8], Error> {
         let start = self.pos();
+        let len = scan_frame(&self.src[start..])?.ok_or(Error::EOF)?;
         self.advance(len);
-        let end = self.pos();
+        Ok(&self.src[start..self.pos()])
+    }
 
-        // Less than or equal to because we're using an **exclusive** range.
-        if end <= self.len() {
-            Ok(&self.src[start..end])
-        } else {
-            Err(Error::EOF)
+    /// Decodes the next value into an [`ArcValue`], sharing the allocations of identical byte arrays through the string cache, if there is one.
+    ///
+    /// Dictionary keys are owned strings, so only the byte arrays of the values are shared.
+    ///
+    /// # Errors
+    ///
+    /// * The value is malformed, or a dictionary key isn't valid UTF-8.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::de::Decoder;
+    ///
+    /// let src = b"ld4:pathl3:doc1:aeed4:pathl3:doc1:beee";
+    /// let mut de = Decoder::new(src).with_string_cache(64);
+    ///
+    /// let files = de.decode_shared().unwrap();
+    /// let dir = |i: usize| files.as_list().unwrap()[i].get("path").unwrap().as_list().unwrap()[0].clone();
+    ///
+    /// assert!(dir(0).ptr_eq(&dir(1)));
+    /// assert_eq!(de.string_cache_hits(), 1);
+    /// ```
+    pub fn decode_shared(&mut self) -> Result<ArcValue, Error> {
+        match self.peek() {
+            Some(INT_START) => self.decode_int_unchecked().map(ArcValue::Int),
+            Some(LIST_START) => {
+                self.advance(1);
+                self.enter()?;
+                let mut list = Vec::new();
+                while self.peek() != Some(TYPE_END) {
+                    list.push(self.decode_shared()?);
+                }
+                self.advance(1);
+                self.leave();
+                Ok(ArcValue::List(Arc::new(list)))
+            }
+            Some(DICT_START) => {
+                self.advance(1);
+                self.enter()?;
+                let mut dict = ArcDict::new();
+                while self.peek() != Some(TYPE_END) {
+                    let key = Key::from(self.decode_bytes()?);
+                    dict.insert(key, self.decode_shared()?);
+                }
+                self.advance(1);
+                self.leave();
+                Ok(ArcValue::Dict(Arc::new(dict)))
+            }
+            Some(_) => {
+                let bytes = self.decode_bytes()?;
+                Ok(ArcValue::Text(match self.cache {
+                    Some(ref mut cache) => cache.get(bytes),
+                    None => Arc::new(bytes.to_vec()),
+                }))
+            }
+            None => Err(Error::EOF),
         }
     }
 
@@ -277,9 +1776,23 @@ impl<'de> Decoder<'de> {
     fn decode_bool(&mut self) -> Result<bool, Error> {
         // If an error occurs, we're going to need the position before we decode the integer.
         let start = self.pos();
+        if self.bools == BoolRepr::IntOrText
+            && self.peek().is_some_and(|next| next.is_ascii_digit())
+        {
+            return match self.decode_bytes()? {
+                b"true" => Ok(true),
+                b"false" => Ok(false),
+                found => Err(Error::Wanted {
+                    at: start,
+                    expected: "a boolean",
+                    found: String::from_utf8_lossy(found).into_owned(),
+                }),
+            };
+        }
         match self.decode_int()? {
             0 => Ok(false),
             1 => Ok(true),
+            _ if self.bools == BoolRepr::NonZero => Ok(true),
             found => Err(Error::Wanted {
                 at: start,
                 expected: "a boolean",
@@ -289,7 +1802,7 @@ impl<'de> Decoder<'de> {
     }
 }
 
-impl<'a, 'de> Deserializer<'de> for &'a mut Decoder<'de> {
+impl<'de> serde::Deserializer<'de> for &mut Decoder<'de> {
     type Error = Error;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -298,18 +1811,32 @@ impl<'a, 'de> Deserializer<'de> for &'a mut Decoder<'de> {
     {
         match self.peek() {
             // We use `decode_int_unchecked` because there is no need to double check if we're working with an integer.
-            Some(INT_START) => visitor.visit_i64(self.decode_int_unchecked()?),
+            Some(INT_START) => visit_int(visitor, self.decode_int_unchecked()?),
             Some(LIST_START) => {
                 // Skip over the 'LIST_START'.
                 self.advance(1);
-                visitor.visit_seq(SeqDecoder::new(self))
+                let mut seq = SeqDecoder::new(self)?;
+                let val =
+                    visitor.visit_seq(&mut seq).map_err(|e| seq.fail(e))?;
+                seq.end()?;
+                Ok(val)
             }
             Some(DICT_START) => {
                 // Skip over the 'DICT_START'.
                 self.advance(1);
-                visitor.visit_map(MapDecoder::new(self))
+                let mut map = MapDecoder::new(self)?;
+                visitor.visit_map(&mut map).map_err(|e| map.fail(e))
+            }
+            Some(_) => {
+                let bytes = self.decode_bytes()?;
+                match self.any_bytes {
+                    AnyBytes::Str => match str::from_utf8(bytes) {
+                        Ok(text) => visitor.visit_borrowed_str(text),
+                        Err(_) => visitor.visit_borrowed_bytes(bytes),
+                    },
+                    AnyBytes::Bytes => visitor.visit_borrowed_bytes(bytes),
+                }
             }
-            Some(_) => self.deserialize_bytes(visitor),
             _ => Err(Error::EOF),
         }
     }
@@ -347,35 +1874,35 @@ impl<'a, 'de> Deserializer<'de> for &'a mut Decoder<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
-        visitor.visit_i64(self.decode_int()?)
+        visitor.visit_i64(self.decode_int_lenient()?)
     }
 
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        self.deserialize_i64(visitor)
+        self.deserialize_u64(visitor)
     }
 
     fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        self.deserialize_i64(visitor)
+        self.deserialize_u64(visitor)
     }
 
     fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        self.deserialize_i64(visitor)
+        self.deserialize_u64(visitor)
     }
 
     fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        self.deserialize_i64(visitor)
+        visit_int(visitor, self.decode_int_lenient()?)
     }
 
     fn deserialize_f32<V>(self, _: V) -> Result<V::Value, Self::Error>
@@ -399,7 +1926,7 @@ impl<'a, 'de> Deserializer<'de> for &'a mut Decoder<'de> {
         // Get our current position before we decode anything.
         let at = self.pos();
 
-        let s: &str = str::from_utf8(&self.decode_bytes()?)?;
+        let s: &str = str::from_utf8(self.decode_bytes()?)?;
         let count = s.chars().count();
 
         match count {
@@ -416,24 +1943,40 @@ impl<'a, 'de> Deserializer<'de> for &'a mut Decoder<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
+        if let Some(text) = self.decode_int_text()? {
+            return visitor.visit_string(text);
+        }
+
         // The borrow checker complains if we don't explicitly say that the string lives for 'de.
-        let text: &'de str = str::from_utf8(&self.decode_bytes()?)?;
-        visitor.visit_borrowed_str(text)
+        let text: &'de str = str::from_utf8(self.decode_bytes()?)?;
+        // A string is never too short for its type, so a length error means it didn't fit, eg: into a `heapless::String`.
+        visitor.visit_borrowed_str(text).map_err(Error::into_overflow)
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        let text: String = String::from_utf8(self.decode_bytes()?.to_vec())?;
-        visitor.visit_string(text)
+        if let Some(text) = self.decode_int_text()? {
+            return visitor.visit_string(text);
+        }
+
+        let bytes = self.decode_bytes()?;
+        self.observe_alloc(bytes.len());
+
+        let text: String = String::from_utf8(bytes.to_vec())?;
+        visitor.visit_string(text).map_err(Error::into_overflow)
     }
 
     fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        visitor.visit_borrowed_bytes(&self.decode_bytes()?)
+        if let Some(text) = self.decode_int_text()? {
+            return visitor.visit_byte_buf(text.into_bytes());
+        }
+
+        visitor.visit_borrowed_bytes(self.decode_bytes()?)
     }
 
     fn deserialize_byte_buf<V>(
@@ -443,7 +1986,14 @@ impl<'a, 'de> Deserializer<'de> for &'a mut Decoder<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
-        visitor.visit_byte_buf(self.decode_bytes()?.to_vec())
+        if let Some(text) = self.decode_int_text()? {
+            return visitor.visit_byte_buf(text.into_bytes());
+        }
+
+        let bytes = self.decode_bytes()?;
+        self.observe_alloc(bytes.len());
+
+        visitor.visit_byte_buf(bytes.to_vec())
     }
 
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -453,10 +2003,28 @@ impl<'a, 'de> Deserializer<'de> for &'a mut Decoder<'de> {
         visitor.visit_some(self)
     }
 
+    // Unit is read from either of the representations the encoder can write it as, see `en::UnitRepr`.
     fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
+        if self.peek() == Some(DICT_START) {
+            self.advance(1);
+            self.advance_if(
+                |next| next == TYPE_END,
+                "the end of an empty dictionary",
+            )?;
+        } else {
+            let at = self.pos();
+            let found = self.decode_bytes()?;
+            if !found.is_empty() {
+                return Err(Error::Wanted {
+                    at,
+                    expected: "an empty dictionary or byte array",
+                    found: String::from_utf8_lossy(found).into_owned(),
+                });
+            }
+        }
         visitor.visit_unit()
     }
 
@@ -485,13 +2053,18 @@ impl<'a, 'de> Deserializer<'de> for &'a mut Decoder<'de> {
 
     fn deserialize_newtype_struct<V>(
         self,
-        _: &'static str,
+        name: &'static str,
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        visitor.visit_newtype_struct(self)
+        if name == RAW_TOKEN {
+            // Hand over the raw bytes of the next value, see `raw::RawValue`.
+            visitor.visit_borrowed_bytes(self.decode_raw()?)
+        } else {
+            visitor.visit_newtype_struct(self)
+        }
     }
 
     // Sequences and tuple types are deserialized as a sequence.
@@ -500,7 +2073,10 @@ impl<'a, 'de> Deserializer<'de> for &'a mut Decoder<'de> {
         V: serde::de::Visitor<'de>,
     {
         self.advance_if(|next| next == LIST_START, "a list of values")?;
-        visitor.visit_seq(SeqDecoder::new(self))
+        let mut seq = SeqDecoder::new(self)?;
+        let val = visitor.visit_seq(&mut seq).map_err(|e| seq.fail(e))?;
+        seq.end()?;
+        Ok(val)
     }
 
     fn deserialize_tuple<V>(
@@ -532,7 +2108,8 @@ impl<'a, 'de> Deserializer<'de> for &'a mut Decoder<'de> {
         V: serde::de::Visitor<'de>,
     {
         self.advance_if(|next| next == DICT_START, "a dictionary")?;
-        visitor.visit_map(MapDecoder::new(self))
+        let mut map = MapDecoder::new(self)?;
+        visitor.visit_map(&mut map).map_err(|e| map.fail(e))
     }
 
     fn deserialize_struct<V>(
@@ -596,7 +2173,7 @@ impl<'a, 'de> Deserializer<'de> for &'a mut Decoder<'de> {
     }
 }
 
-impl<'a, 'de> VariantAccess<'de> for &'a mut Decoder<'de> {
+impl<'de> VariantAccess<'de> for &mut Decoder<'de> {
     type Error = Error;
 
     fn unit_variant(self) -> Result<(), Self::Error> {
@@ -633,7 +2210,7 @@ impl<'a, 'de> VariantAccess<'de> for &'a mut Decoder<'de> {
     }
 }
 
-impl<'a, 'de> EnumAccess<'de> for &'a mut Decoder<'de> {
+impl<'de> EnumAccess<'de> for &mut Decoder<'de> {
     type Error = Error;
 
     type Variant = Self;
@@ -653,13 +2230,42 @@ impl<'a, 'de> EnumAccess<'de> for &'a mut Decoder<'de> {
 #[derive(Debug)]
 struct SeqDecoder<'a, 'de: 'a> {
     de: &'a mut Decoder<'de>,
+    /// The index of the next element.
+    index: usize,
+    /// Whether the end of the list has been consumed.
+    finished: bool,
+    /// Whether the visitor was given the last element, so an error it returns now is about that element.
+    given: bool,
 }
 
 impl<'a, 'de> SeqDecoder<'a, 'de> {
     /// Constructs a new sequence decoder.
     #[inline]
-    fn new(de: &'a mut Decoder<'de>) -> SeqDecoder<'a, 'de> {
-        Self { de }
+    fn new(de: &'a mut Decoder<'de>) -> Result<SeqDecoder<'a, 'de>, Error> {
+        de.enter()?;
+        Ok(Self { de, index: 0, finished: false, given: false })
+    }
+
+    /// Reports an error returned by the visitor, which is turned into `Error::Length` if the visitor ran out of room for an element it was given.
+    fn fail(&self, e: Error) -> Error {
+        if self.given {
+            e.into_overflow()
+        } else {
+            e
+        }
+    }
+
+    /// Consumes the end of the list, if the visitor stopped before reaching it, eg: a tuple that takes a fixed number of elements.
+    ///
+    /// # Errors
+    ///
+    /// * The list has more elements than the visitor took.
+    fn end(self) -> Result<(), Error> {
+        if !self.finished {
+            self.de.advance_if(|next| next == TYPE_END, "the end of a list")?;
+            self.de.leave();
+        }
+        Ok(())
     }
 }
 
@@ -673,13 +2279,29 @@ impl<'a, 'de> SeqAccess<'de> for SeqDecoder<'a, 'de> {
     where
         T: serde::de::DeserializeSeed<'de>,
     {
+        self.given = false;
         match self.de.peek() {
             Some(TYPE_END) => {
                 // Exclude the 'TYPE_END' for the next iteration.
                 self.de.advance(1);
+                self.de.leave();
+                self.finished = true;
                 Ok(None)
             }
-            Some(_) => seed.deserialize(&mut *self.de).map(Some),
+            Some(_) => {
+                let index = self.index;
+                self.index += 1;
+                let start = self.de.pos();
+                let digest =
+                    self.de.digest_enter(|seg| *seg == Segment::Index(index));
+                let seg = || Segment::Index(index);
+                let elem = self.de.decode_child(seg, seed)?;
+                if digest {
+                    self.de.digest_leave(start);
+                }
+                self.given = true;
+                Ok(Some(elem))
+            }
             _ => Err(Error::EOF),
         }
     }
@@ -689,13 +2311,27 @@ impl<'a, 'de> SeqAccess<'de> for SeqDecoder<'a, 'de> {
 #[derive(Debug)]
 struct MapDecoder<'a, 'de: 'a> {
     de: &'a mut Decoder<'de>,
+    /// The content of the last key, which is only kept while a digest or an interceptor is set.
+    key: &'de [u8],
+    /// Whether the visitor was given the last value, so an error it returns now is about that entry.
+    given: bool,
 }
 
 impl<'a, 'de> MapDecoder<'a, 'de> {
     /// Constructs a new dictionary decoder.
     #[inline]
-    fn new(de: &'a mut Decoder<'de>) -> MapDecoder<'a, 'de> {
-        Self { de }
+    fn new(de: &'a mut Decoder<'de>) -> Result<MapDecoder<'a, 'de>, Error> {
+        de.enter()?;
+        Ok(Self { de, key: &[], given: false })
+    }
+
+    /// Reports an error returned by the visitor, which is turned into `Error::Length` if the visitor ran out of room for an entry it was given.
+    fn fail(&self, e: Error) -> Error {
+        if self.given {
+            e.into_overflow()
+        } else {
+            e
+        }
     }
 }
 
@@ -709,13 +2345,28 @@ impl<'a, 'de> MapAccess<'de> for MapDecoder<'a, 'de> {
     where
         K: serde::de::DeserializeSeed<'de>,
     {
+        self.given = false;
         match self.de.peek() {
             Some(TYPE_END) => {
                 // Exclude the 'TYPE_END' for the next iteration.
                 self.de.advance(1);
+                self.de.leave();
                 Ok(None)
             }
-            Some(b'0'..=b'9') => seed.deserialize(&mut *self.de).map(Some),
+            Some(b'0'..=b'9') => {
+                let start = self.de.pos();
+                let key = seed.deserialize(KeyDecoder { de: &mut *self.de })?;
+                if self.de.digest.is_some() || !self.de.interceptors.is_empty()
+                {
+                    let src = self.de.src;
+                    let raw = &src[start..self.de.pos()];
+                    self.key = match raw.iter().position(|&b| b == TEXT_DELIM) {
+                        Some(i) => &raw[i + 1..],
+                        None => raw,
+                    };
+                }
+                Ok(Some(key))
+            }
             Some(_) => Err(Error::Malformed),
             _ => Err(Error::EOF),
         }
@@ -725,13 +2376,168 @@ impl<'a, 'de> MapAccess<'de> for MapDecoder<'a, 'de> {
     where
         V: serde::de::DeserializeSeed<'de>,
     {
-        seed.deserialize(&mut *self.de)
+        let key = self.key;
+        let start = self.de.pos();
+        let digest = self.de.digest_enter(|seg| match *seg {
+            Segment::Key(ref k) => k.as_bytes() == key,
+            Segment::Index(_) => false,
+        });
+        let seg = || Segment::from_key_bytes(key);
+        let val = self.de.decode_child(seg, seed)?;
+        if digest {
+            self.de.digest_leave(start);
+        }
+        self.given = true;
+        Ok(val)
+    }
+}
+
+/// A decoder for dictionary keys, which are always byte arrays.
+///
+/// Key types that deserialize from a sequence of bytes, eg: `Vec<u8>` or `[u8; 20]`, are given the key's bytes one at a time, and integer key types are parsed from the key's decimal digits. Every other type is decoded as usual.
+struct KeyDecoder<'a, 'de: 'a> {
+    de: &'a mut Decoder<'de>,
+}
+
+/// Implements the given `deserialize_*` methods by parsing the key as an integer of the given type, and passing it to the given `visit_*` method.
+macro_rules! deserialize_int_key {
+    ($($method:ident($ty:ty) => $visit:ident),* $(,)?) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: serde::de::Visitor<'de>,
+            {
+                visitor.$visit(self.decode_int_key::<$ty>()?)
+            }
+        )*
+    };
+}
+
+/// Forwards the given `deserialize_*` methods to the underlying decoder.
+macro_rules! forward_to_decoder {
+    ($($method:ident($($arg:ident: $ty:ty),*)),* $(,)?) => {
+        $(
+            fn $method<V>(
+                self,
+                $($arg: $ty,)*
+                visitor: V,
+            ) -> Result<V::Value, Self::Error>
+            where
+                V: serde::de::Visitor<'de>,
+            {
+                self.de.$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'a, 'de> KeyDecoder<'a, 'de> {
+    /// Decodes a key made of ASCII decimal digits, with an optional minus sign, into an integer.
+    ///
+    /// # Errors
+    ///
+    /// * The key isn't a canonical decimal integer, eg: it has a leading zero, or it's out of the type's range, at which point `Error::Wanted` is returned.
+    fn decode_int_key<T>(self) -> Result<T, Error>
+    where
+        T: str::FromStr,
+    {
+        let start = self.de.pos();
+        let key = self.de.decode_bytes()?;
+        let canonical = check_digits(key, true).is_none();
+        canonical.then(|| parse_decimal(key)).flatten().ok_or_else(|| {
+            Error::Wanted {
+                at: start,
+                expected: "an integer key",
+                found: String::from_utf8_lossy(key).into_owned(),
+            }
+        })
+    }
+}
+
+impl<'a, 'de> serde::Deserializer<'de> for KeyDecoder<'a, 'de> {
+    type Error = Error;
+
+    deserialize_int_key!(
+        deserialize_i8(i8) => visit_i8,
+        deserialize_i16(i16) => visit_i16,
+        deserialize_i32(i32) => visit_i32,
+        deserialize_i64(i64) => visit_i64,
+        deserialize_u8(u8) => visit_u8,
+        deserialize_u16(u16) => visit_u16,
+        deserialize_u32(u32) => visit_u32,
+        deserialize_u64(u64) => visit_u64,
+    );
+
+    forward_to_decoder!(
+        deserialize_any(),
+        deserialize_bool(),
+        deserialize_f32(),
+        deserialize_f64(),
+        deserialize_char(),
+        deserialize_str(),
+        deserialize_string(),
+        deserialize_bytes(),
+        deserialize_byte_buf(),
+        deserialize_option(),
+        deserialize_unit(),
+        deserialize_unit_struct(name: &'static str),
+        deserialize_tuple_struct(name: &'static str, len: usize),
+        deserialize_map(),
+        deserialize_struct(
+            name: &'static str,
+            fields: &'static [&'static str]
+        ),
+        deserialize_enum(
+            name: &'static str,
+            variants: &'static [&'static str]
+        ),
+        deserialize_identifier(),
+        deserialize_ignored_any(),
+    );
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match name {
+            RAW_TOKEN => self.de.deserialize_newtype_struct(name, visitor),
+            // Newtypes are transparent, so the key they wrap is still decoded as a key, eg: an integer from its digits.
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let bytes = self.de.decode_bytes()?;
+        let mut seq = SeqDeserializer::<_, Error>::new(bytes.iter().copied());
+        let val = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(val)
+    }
+
+    fn deserialize_tuple<V>(
+        self,
+        _: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
     }
 }
 
 #[cfg(test)]
 mod test {
     use std::collections::HashMap;
+    use std::time::Duration;
+    use std::time::Instant;
 
     use serde::Deserialize;
     use serde_bytes::ByteBuf;
@@ -739,6 +2545,7 @@ mod test {
 
     use super::Decoder;
     use super::Error;
+    use super::ErrorKind;
 
     /// Asserts that the result of decoding the encoded bytes is equal to the given value.
     macro_rules! test_decode {
@@ -912,6 +2719,133 @@ mod test {
         test_decode!(b"d3:agei50ee", Ok(Person { name: None, age: 50 }));
     }
 
+    #[test]
+    fn deserialize_untagged_enum() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        #[serde(untagged)]
+        enum Reply {
+            Unsigned(u32),
+            Signed(i32),
+            Text(String),
+            Bytes(ByteBuf),
+        }
+        test_decode!(b"i1995e", Ok(Reply::Unsigned(1995)));
+        test_decode!(b"i-1995e", Ok(Reply::Signed(-1995)));
+        test_decode!(b"3:foo", Ok(Reply::Text("foo".to_string())));
+        test_decode!(b"2:\xff\x00", Ok(Reply::Bytes(ByteBuf::from([255, 0]))));
+    }
+
+    #[test]
+    fn deserialize_internally_tagged_enum() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        #[serde(tag = "y")]
+        enum Message {
+            #[serde(rename = "q")]
+            Query { t: ByteBuf, q: String, port: Option<u16> },
+            #[serde(rename = "e")]
+            Error { t: ByteBuf, e: (i64, String) },
+        }
+        test_decode!(
+            b"d4:porti6881e1:q4:ping1:t2:\x00\xff1:y1:qe",
+            Ok(Message::Query {
+                t: ByteBuf::from([0, 255]),
+                q: "ping".to_string(),
+                port: Some(6881),
+            })
+        );
+        test_decode!(
+            b"d1:eli201e4:Oopse1:t2:aa1:y1:ee",
+            Ok(Message::Error {
+                t: ByteBuf::from(*b"aa"),
+                e: (201, "Oops".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn deserialize_flatten() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Info {
+            length: u64,
+            pieces: ByteBuf,
+        }
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Torrent {
+            #[serde(flatten)]
+            info: Info,
+            #[serde(flatten)]
+            extra: HashMap<String, crate::Value>,
+        }
+        test_decode!(
+            b"d6:lengthi5e6:pieces1:\xff7:privatei1ee",
+            Ok(Torrent {
+                info: Info { length: 5, pieces: ByteBuf::from([255]) },
+                extra: HashMap::from([(
+                    "private".to_string(),
+                    crate::Value::Int(1)
+                )]),
+            })
+        );
+    }
+
+    #[test]
+    fn deserialize_unsigned_only_visitor() {
+        struct Port(u64);
+
+        impl<'de> Deserialize<'de> for Port {
+            fn deserialize<D>(de: D) -> Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                struct PortVisitor;
+
+                impl serde::de::Visitor<'_> for PortVisitor {
+                    type Value = Port;
+
+                    fn expecting(
+                        &self,
+                        f: &mut std::fmt::Formatter,
+                    ) -> std::fmt::Result {
+                        f.write_str("a port")
+                    }
+
+                    fn visit_u64<E>(self, v: u64) -> Result<Port, E> {
+                        Ok(Port(v))
+                    }
+                }
+
+                de.deserialize_u16(PortVisitor)
+            }
+        }
+
+        let mut de = Decoder::new(b"i6881e");
+        assert_eq!(Port::deserialize(&mut de).map(|p| p.0), Ok(6881));
+        let mut de = Decoder::new(b"i-1e");
+        assert!(Port::deserialize(&mut de).is_err());
+    }
+
+    #[test]
+    fn deserialize_values_with_any_bytes() {
+        use super::AnyBytes;
+        use crate::value::{CowValue, ValueRef};
+
+        let src = b"d1:ald3:foo2:\xff\x00ee1:bi-1ee";
+        for any_bytes in [AnyBytes::Bytes, AnyBytes::Str] {
+            let mut de = Decoder::new(src).with_any_bytes(any_bytes);
+            let val = crate::Value::deserialize(&mut de).unwrap();
+            assert_eq!(crate::encode(&val).unwrap(), src);
+
+            let mut de = Decoder::new(src).with_any_bytes(any_bytes);
+            let val = ValueRef::deserialize(&mut de).unwrap();
+            assert_eq!(val.to_owned(), crate::decode(src).unwrap());
+
+            let mut de = Decoder::new(src).with_any_bytes(any_bytes);
+            let val = CowValue::deserialize(&mut de).unwrap();
+            assert!(val.get("b").is_some());
+            assert_eq!(crate::encode(&val).unwrap(), src);
+        }
+    }
+
     #[test]
     fn deserialize_unit_struct_ok() {
         #[derive(Debug, PartialEq, Deserialize)]
@@ -1030,4 +2964,542 @@ mod test {
             )
         );
     }
+
+    #[test]
+    fn scan_frame_complete() {
+        assert_eq!(super::scan_frame(b"i1995e"), Ok(Some(6)));
+        assert_eq!(super::scan_frame(b"3:fooi1e"), Ok(Some(5)));
+        assert_eq!(super::scan_frame(b"d3:fool1:aeei0e"), Ok(Some(12)));
+    }
+
+    #[test]
+    fn scan_frame_incomplete() {
+        assert_eq!(super::scan_frame(b""), Ok(None));
+        assert_eq!(super::scan_frame(b"i19"), Ok(None));
+        assert_eq!(super::scan_frame(b"4:foo"), Ok(None));
+        assert_eq!(super::scan_frame(b"d3:foo"), Ok(None));
+    }
+
+    #[test]
+    fn scan_frame_malformed() {
+        assert_eq!(super::scan_frame(b"x"), Err(Error::Malformed));
+        assert_eq!(super::scan_frame(b"i1x5e"), Err(Error::Malformed));
+        assert_eq!(super::scan_frame(b"e"), Err(Error::Malformed));
+        // Malformed integers are reported before they're terminated.
+        assert_eq!(super::scan_frame(b"i-e"), Err(Error::Malformed));
+        assert_eq!(super::scan_frame(b"ie"), Err(Error::Malformed));
+        assert_eq!(super::scan_frame(b"li1ei1x"), Err(Error::Malformed));
+        assert_eq!(super::scan_frame(b"12x"), Err(Error::Malformed));
+    }
+
+    #[test]
+    fn frame_scanner_resumes() {
+        let src = b"li1e3:fooi1995ee";
+        let mut scanner = super::FrameScanner::default();
+
+        assert_eq!(scanner.scan(&src[..7]), Ok(None));
+        // The complete tokens are skipped, and the incomplete byte array is scanned again.
+        assert_eq!((scanner.pos, scanner.depth), (4, 1));
+        assert_eq!(scanner.scan(&src[..12]), Ok(None));
+        assert_eq!((scanner.pos, scanner.depth), (9, 1));
+        assert_eq!(scanner.scan(src), Ok(Some(16)));
+    }
+
+    #[test]
+    fn alloc_observer_sees_byte_bufs() {
+        let mut allocs = vec![];
+        let mut de =
+            Decoder::new(b"3:foo").with_alloc_observer(|len| allocs.push(len));
+        assert_eq!(ByteBuf::deserialize(&mut de), Ok(ByteBuf::from(*b"foo")));
+
+        drop(de);
+        assert_eq!(allocs, [3]);
+    }
+
+    #[test]
+    #[cfg(not(feature = "tracing"))]
+    fn counts_only_with_stats() {
+        let mut de = Decoder::new(b"lli1ei2eee");
+        assert!(Vec::<Vec<i32>>::deserialize(&mut de).is_ok());
+        assert_eq!((de.nodes(), de.max_depth()), (0, 0));
+
+        let mut de = Decoder::new(b"lli1ei2eee").with_stats();
+        assert!(Vec::<Vec<i32>>::deserialize(&mut de).is_ok());
+        assert_eq!((de.nodes(), de.max_depth()), (4, 2));
+    }
+
+    #[test]
+    fn decoder_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Decoder<'static>>();
+        assert_send::<super::Progress<'static>>();
+    }
+
+    #[test]
+    fn digest_sees_subtree() {
+        let src = b"d4:infod5:filesl1:a3:fooe6:lengthi1e1:xl2:bbee4:name3:fooe";
+        let mut files = vec![];
+        let mut de = Decoder::new(src)
+            .with_digest("info.files[1]".parse().unwrap(), |bytes: &[u8]| {
+                files.push(bytes.to_vec())
+            });
+        // The subtree is fed even though every field is ignored.
+        assert!(serde::de::IgnoredAny::deserialize(&mut de).is_ok());
+
+        drop(de);
+        assert_eq!(files, [b"3:foo"]);
+
+        let mut info = vec![];
+        let mut de = Decoder::new(src)
+            .with_digest("info".parse().unwrap(), |bytes: &[u8]| {
+                info.extend_from_slice(bytes)
+            });
+        assert!(crate::Value::deserialize(&mut de).is_ok());
+
+        drop(de);
+        assert_eq!(info, b"d5:filesl1:a3:fooe6:lengthi1e1:xl2:bbee");
+    }
+
+    #[cfg(feature = "digest")]
+    #[test]
+    fn hasher_sees_subtree() {
+        use sha1::{Digest, Sha1};
+
+        let src = b"d4:infod6:lengthi1e4:name3:fooe1:xi0ee";
+        let mut hasher = Sha1::new();
+        let mut de =
+            Decoder::new(src).with_hasher("info".parse().unwrap(), &mut hasher);
+        assert!(crate::Value::deserialize(&mut de).is_ok());
+
+        drop(de);
+        let expected = Sha1::digest(b"d6:lengthi1e4:name3:fooe");
+        assert_eq!(hasher.finalize(), expected);
+    }
+
+    #[test]
+    fn progress_cancels_decode() {
+        let src = crate::encode(&vec!["foo"; 1000]).unwrap();
+        let mut calls = 0;
+        let mut de = Decoder::new(&src).with_progress(1024, |consumed| {
+            calls += 1;
+            consumed < 2048
+        });
+        let err = Vec::<String>::deserialize(&mut de).unwrap_err();
+
+        drop(de);
+        assert_eq!(calls, 2);
+        assert_eq!(err, Error::Cancelled { at: 2048 });
+        assert_eq!(err.kind(), ErrorKind::Cancelled);
+    }
+
+    #[test]
+    fn deadline_times_out() {
+        let src = crate::encode(&vec![1; 1000]).unwrap();
+
+        let mut de = Decoder::new(&src).with_deadline(Instant::now());
+        let err = Vec::<i64>::deserialize(&mut de).unwrap_err();
+        assert_eq!(err, Error::TimedOut { at: 1 });
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let mut de = Decoder::new(&src).with_deadline(deadline);
+        assert_eq!(Vec::<i64>::deserialize(&mut de), Ok(vec![1; 1000]));
+    }
+
+    #[test]
+    fn decode_int_too_long() {
+        test_decode!(
+            i64,
+            b"i123456789012345678901234567890e",
+            Err(Error::IntegerTooLong { at: 1, max: 19 })
+        );
+    }
+
+    #[test]
+    fn decode_int_max_digits() {
+        test_decode!(i64, b"i-9223372036854775808e", Ok(i64::MIN));
+        test_decode!(i64, b"i9223372036854775807e", Ok(i64::MAX));
+        // Too many digits is reported apart from an overflow.
+        test_decode!(
+            i64,
+            b"i9223372036854775808e",
+            Err(Error::IntegerOverflow { at: 1 })
+        );
+        test_decode!(
+            u64,
+            b"i-9223372036854775809e",
+            Err(Error::IntegerOverflow { at: 1 })
+        );
+    }
+
+    #[test]
+    fn decode_bytes_forged_len() {
+        test_decode!(ByteBuf, b"18446744073709551615:foo", Err(Error::EOF));
+        test_decode!(ByteBuf, b"999999999:foo", Err(Error::EOF));
+    }
+
+    #[test]
+    fn decode_bytes_forged_len_does_not_allocate() {
+        let mut allocs = vec![];
+        let mut de = Decoder::new(b"999999999:foo")
+            .with_alloc_observer(|len| allocs.push(len));
+        assert_eq!(String::deserialize(&mut de), Err(Error::EOF));
+
+        drop(de);
+        assert!(allocs.is_empty());
+    }
+
+    #[test]
+    fn decode_digits() {
+        test_decode!(b"i-42e", Ok(-42i64));
+        test_decode!(b"i4\xc3\xa9e", Err::<i64, _>(Error::Malformed));
+        assert!(matches!(
+            crate::decode::<i64>(b"i4\xffe"),
+            Err(Error::Utf8(_))
+        ));
+        assert!(matches!(
+            crate::decode::<ByteBuf>(b"3\xff:foo"),
+            Err(Error::Utf8(_))
+        ));
+        assert_eq!(super::scan_frame(b"1\xc3\xa9:a"), Err(Error::Malformed));
+    }
+
+    #[test]
+    fn check_canonical() {
+        use super::NonCanonicalKind as Kind;
+
+        let check = |src: &[u8]| {
+            super::check_canonical(src).map_err(|e| (e.offset, e.kind))
+        };
+        let malformed = |at| Err((at, Kind::Malformed));
+
+        assert_eq!(
+            check(b"d1:ali0ei-1ee1:bd0:0:e0:0:e"),
+            Err((22, Kind::UnsortedKeys))
+        );
+        assert_eq!(check(b"d1:ali0ei-1ee1:bd0:0:e2:cci9ee"), Ok(()));
+        assert_eq!(check(b"i-0e"), Err((0, Kind::NegativeZero)));
+        assert_eq!(check(b"i01e"), Err((0, Kind::LeadingZero)));
+        assert_eq!(check(b"i-9223372036854775808e"), Ok(()));
+        assert_eq!(check(b"i9223372036854775808e"), Err((0, Kind::OutOfRange)));
+        assert_eq!(
+            check(b"li99999999999999999999999ee"),
+            Err((1, Kind::OutOfRange))
+        );
+        assert_eq!(check(b"ie"), malformed(0));
+        assert_eq!(check(b"01:a"), Err((0, Kind::LeadingZero)));
+        assert_eq!(check(b"l0:e0:"), malformed(4));
+        assert_eq!(check(b"d1:bi0e1:ai0ee"), Err((7, Kind::UnsortedKeys)));
+        assert_eq!(check(b"d1:ai0e1:ai0ee"), Err((7, Kind::DuplicateKey)));
+        assert_eq!(check(b"di0e1:ae"), malformed(1));
+        assert_eq!(check(b"d1:ae"), malformed(4));
+        assert_eq!(check(b"l5:ae"), malformed(1));
+        assert_eq!(check(b"li1e"), malformed(4));
+    }
+
+    #[test]
+    fn non_canonical_bytes() {
+        let err = super::check_canonical(b"li1ei-0ee").unwrap_err();
+        assert_eq!(err.bytes, b"i-0e");
+        assert_eq!(err.to_string(), "negative zero at position 4");
+
+        let err = super::check_canonical(b"d2:bb0:1:a0:e").unwrap_err();
+        assert_eq!(err.bytes, b"1:a");
+        assert!(super::check_canonical(b"l").unwrap_err().bytes.is_empty());
+    }
+
+    #[test]
+    fn string_cache_evicts_least_recent() {
+        let src = b"l1:a1:b1:a1:c1:a1:be";
+        let mut de = Decoder::new(src).with_string_cache(2);
+        let val = de.decode_shared().unwrap();
+        let list = val.as_list().unwrap();
+
+        // `b` is evicted by `c`, as `a` was used more recently.
+        assert!(list[0].ptr_eq(&list[2]));
+        assert!(list[0].ptr_eq(&list[4]));
+        assert!(!list[1].ptr_eq(&list[5]));
+        assert_eq!(de.string_cache_hits(), 2);
+        assert_eq!(val.to_value(), crate::decode(src).unwrap());
+
+        let mut de = Decoder::new(b"l1:a1:ae");
+        let val = de.decode_shared().unwrap();
+        assert!(!val.as_list().unwrap()[0].ptr_eq(&val.as_list().unwrap()[1]));
+        assert_eq!(Decoder::new(b"d1:a").decode_shared(), Err(Error::EOF));
+        let val = Decoder::new(b"d1:\xffi1ee").decode_shared().unwrap();
+        assert_eq!(val.get(b"\xff"), Some(&crate::value::ArcValue::Int(1)));
+    }
+
+    #[test]
+    fn interceptors_see_paths() {
+        use crate::Value;
+
+        let mut seen = vec![];
+        let mut de = Decoder::new(b"d1:ali1ei2ee1:bi3ee").with_interceptor(
+            "**".parse().unwrap(),
+            |path: &crate::path::Path, val: &mut Value| {
+                seen.push(path.to_string());
+                if let Value::Int(ref mut v) = *val {
+                    *v *= 10;
+                }
+                Ok(())
+            },
+        );
+        let val = HashMap::<String, Value>::deserialize(&mut de).unwrap();
+        drop(de);
+
+        assert_eq!(seen, ["a[0]", "a[1]", "a", "b"]);
+        assert_eq!(val["a"], Value::List(vec![10.into(), 20.into()].into()));
+        assert_eq!(val["b"], Value::Int(30));
+
+        let mut de = Decoder::new(b"li1ei2ee").with_interceptor(
+            "[1]".parse().unwrap(),
+            |_: &crate::path::Path, _: &mut Value| Err("no".to_string()),
+        );
+        assert_eq!(
+            Vec::<i64>::deserialize(&mut de),
+            Err(Error::Rejected {
+                path: "[1]".parse().unwrap(),
+                reason: "no".into()
+            })
+        );
+    }
+
+    #[test]
+    fn interceptors_keep_raw_values() {
+        use std::collections::BTreeMap;
+
+        use crate::Value;
+
+        let noop = |_: &crate::path::Path, _: &mut Value| Ok(());
+        let src = b"d5:filesd2:\xff\xfei1eee";
+        let mut de =
+            Decoder::new(src).with_interceptor("files".parse().unwrap(), noop);
+        let map =
+            BTreeMap::<String, BTreeMap<ByteBuf, i64>>::deserialize(&mut de)
+                .unwrap();
+        assert_eq!(map["files"][&ByteBuf::from(b"\xff\xfe".to_vec())], 1);
+
+        let src = b"d1:ad1:b1:cee";
+        let mut de =
+            Decoder::new(src).with_interceptor("**".parse().unwrap(), noop);
+        let map = BTreeMap::<&str, BTreeMap<&str, &str>>::deserialize(&mut de)
+            .unwrap();
+        assert_eq!(map["a"]["b"], "c");
+
+        // The digest is fed once, even though the value is decoded twice.
+        let mut fed = vec![];
+        let mut de = Decoder::new(src)
+            .with_digest("a".parse().unwrap(), |bytes: &[u8]| {
+                fed.push(bytes.to_vec())
+            })
+            .with_interceptor("a".parse().unwrap(), noop);
+        BTreeMap::<&str, BTreeMap<&str, &str>>::deserialize(&mut de).unwrap();
+        drop(de);
+        assert_eq!(fed, [b"d1:b1:ce".to_vec()]);
+
+        // A changed value keeps the keys that aren't UTF-8.
+        let src = b"d5:filesd2:\xff\xfei1eee";
+        let mut de = Decoder::new(src).with_interceptor(
+            "files".parse().unwrap(),
+            |_: &crate::path::Path, val: &mut Value| {
+                val.entry("b").unwrap().or_insert(Value::Int(2));
+                Ok(())
+            },
+        );
+        let map = BTreeMap::<String, Value>::deserialize(&mut de).unwrap();
+        assert_eq!(
+            crate::encode(&map).unwrap(),
+            b"d5:filesd1:bi2e2:\xff\xfei1eee"
+        );
+    }
+
+    #[test]
+    fn decode_byte_keys() {
+        use std::collections::BTreeMap;
+
+        let src = b"d2:\x00\xffi1e2:\xff\x00i2ee";
+        let keys = [b"\x00\xff".to_vec(), b"\xff\x00".to_vec()];
+
+        let map: HashMap<ByteBuf, i64> = crate::decode(src).unwrap();
+        assert_eq!(map[&ByteBuf::from(b"\xff\x00".to_vec())], 2);
+        let map: BTreeMap<Vec<u8>, i64> = crate::decode(src).unwrap();
+        assert_eq!(map.into_keys().collect::<Vec<_>>(), keys);
+        let map: BTreeMap<[u8; 2], i64> = crate::decode(src).unwrap();
+        assert_eq!(map[b"\xff\x00"], 2);
+
+        assert!(crate::decode::<BTreeMap<[u8; 3], i64>>(src).is_err());
+        assert!(crate::decode::<BTreeMap<[u8; 1], i64>>(src).is_err());
+        assert!(crate::decode::<BTreeMap<String, i64>>(src).is_err());
+    }
+
+    #[test]
+    fn decode_int_keys() {
+        use std::collections::BTreeMap;
+
+        let map: BTreeMap<u32, i64> =
+            crate::decode(b"d1:0i0e2:10i1e1:9i2ee").unwrap();
+        assert_eq!(
+            map.into_iter().collect::<Vec<_>>(),
+            [(0, 0), (9, 2), (10, 1)]
+        );
+        let map: BTreeMap<i8, i64> = crate::decode(b"d4:-128i0ee").unwrap();
+        assert_eq!(map[&-128], 0);
+
+        let wanted = |at, found: &str| Error::Wanted {
+            at,
+            expected: "an integer key",
+            found: found.into(),
+        };
+        assert_eq!(
+            crate::decode::<BTreeMap<u8, i64>>(b"d3:256i0ee"),
+            Err(wanted(1, "256"))
+        );
+        assert_eq!(
+            crate::decode::<BTreeMap<u8, i64>>(b"d1:0i0e2:+1i0ee"),
+            Err(wanted(7, "+1"))
+        );
+        assert_eq!(
+            crate::decode::<BTreeMap<i64, i64>>(b"d1:-i0ee"),
+            Err(wanted(1, "-"))
+        );
+        assert_eq!(
+            crate::decode::<BTreeMap<u64, i64>>(b"d0:i0ee"),
+            Err(wanted(1, ""))
+        );
+        assert_eq!(
+            crate::decode::<BTreeMap<u8, i64>>(b"d2:01i1e1:1i2ee"),
+            Err(wanted(1, "01"))
+        );
+        assert_eq!(
+            crate::decode::<BTreeMap<i8, i64>>(b"d2:-0i0ee"),
+            Err(wanted(1, "-0"))
+        );
+    }
+
+    #[test]
+    fn decode_lenient_ints() {
+        fn lenient<'de, T: Deserialize<'de>>(
+            src: &'de [u8],
+        ) -> Result<T, Error> {
+            T::deserialize(&mut Decoder::new(src).with_lenient_ints(true))
+        }
+
+        assert_eq!(lenient::<i64>(b"5:-1995"), Ok(-1995));
+        assert_eq!(lenient::<u8>(b"i255e"), Ok(255));
+        assert_eq!(lenient::<String>(b"i-7e"), Ok("-7".into()));
+        assert_eq!(lenient::<ByteBuf>(b"i7e").unwrap(), b"7");
+        assert_eq!(lenient::<Vec<u16>>(b"l1:1i2ee"), Ok(vec![1, 2]));
+        assert_eq!(
+            lenient::<i64>(b"2:+1"),
+            Err(Error::Wanted {
+                at: 0,
+                expected: "an integer",
+                found: "+1".into()
+            })
+        );
+        assert!(lenient::<u8>(b"3:256").is_err());
+        assert!(lenient::<i64>(b"0:").is_err());
+        assert!(Vec::<u16>::deserialize(&mut Decoder::new(b"l1:1e")).is_err());
+    }
+
+    #[test]
+    fn decode_fixed_capacity() {
+        use serde::de::{SeqAccess, Visitor};
+
+        /// A list with room for `N` elements, which reports overflowing like `heapless::Vec` does.
+        #[derive(Debug)]
+        struct Capped<const N: usize>(Vec<i64>);
+
+        impl<'de, const N: usize> Deserialize<'de> for Capped<N> {
+            fn deserialize<D>(de: D) -> Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                struct CappedVisitor<const N: usize>;
+
+                impl<'de, const N: usize> Visitor<'de> for CappedVisitor<N> {
+                    type Value = Capped<N>;
+
+                    fn expecting(
+                        &self,
+                        f: &mut std::fmt::Formatter,
+                    ) -> std::fmt::Result {
+                        write!(f, "a list of at most {} integers", N)
+                    }
+
+                    fn visit_seq<A>(
+                        self,
+                        mut seq: A,
+                    ) -> Result<Capped<N>, A::Error>
+                    where
+                        A: SeqAccess<'de>,
+                    {
+                        let mut elems = Vec::new();
+                        while let Some(elem) = seq.next_element()? {
+                            if elems.len() == N {
+                                return Err(serde::de::Error::invalid_length(
+                                    N + 1,
+                                    &self,
+                                ));
+                            }
+                            elems.push(elem);
+                        }
+                        Ok(Capped(elems))
+                    }
+                }
+
+                de.deserialize_seq(CappedVisitor)
+            }
+        }
+
+        assert_eq!(crate::decode::<Capped<2>>(b"li1ei2ee").unwrap().0, [1, 2]);
+        let err = crate::decode::<Capped<2>>(b"li1ei2ei3ee").unwrap_err();
+        assert_eq!(
+            err,
+            Error::Length {
+                len: 3,
+                expected: "a list of at most 2 integers".into()
+            }
+        );
+        assert_eq!(err.kind(), ErrorKind::Limit);
+
+        // Too few elements isn't running out of room.
+        let err = crate::decode::<[u8; 3]>(b"li1ei2ee").unwrap_err();
+        assert_eq!(
+            err,
+            Error::InvalidLength {
+                len: 2,
+                expected: "an array of length 3".into()
+            }
+        );
+        assert_eq!(err.kind(), ErrorKind::Data);
+        let err = crate::decode::<(u8, u8)>(b"li1ee").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Data);
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn decode_heapless() {
+        #[derive(Debug, PartialEq, Deserialize, serde::Serialize)]
+        struct Peer {
+            ip: heapless::String<15>,
+            ports: heapless::Vec<u16, 2>,
+        }
+
+        let peer = Peer {
+            ip: "127.0.0.1".try_into().unwrap(),
+            ports: heapless::Vec::from_slice(&[6881, 6882]).unwrap(),
+        };
+        let bytes = crate::encode(&peer).unwrap();
+        assert_eq!(bytes, b"d2:ip9:127.0.0.15:portsli6881ei6882eee");
+        assert_eq!(crate::decode::<Peer>(&bytes).unwrap(), peer);
+
+        let err = crate::decode::<Peer>(b"d2:ip1:x5:portsli1ei2ei3eee");
+        assert!(matches!(err, Err(Error::Length { len: 3, .. })));
+        assert_eq!(err.unwrap_err().kind(), ErrorKind::Limit);
+
+        let err = crate::decode::<Peer>(b"d2:ip16:0000:0000:0000:05:portslee");
+        assert!(matches!(err, Err(Error::Length { len: 16, .. })));
+    }
 }
@@ -0,0 +1,266 @@
+//! Decompression of bytes wrapped in gzip, zlib or raw DEFLATE, through `flate2`.
+
+use std::io;
+use std::io::Chain;
+use std::io::Cursor;
+use std::io::Read;
+
+use flate2::read::DeflateDecoder;
+use flate2::read::GzDecoder;
+use flate2::read::ZlibDecoder;
+
+use super::de::Error;
+use super::spec::DICT_START;
+use super::spec::INT_START;
+use super::spec::LIST_START;
+
+/// How the bytes of a reader are wrapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Wrapping {
+    /// Not at all.
+    Identity,
+    /// In gzip, as described in RFC 1952.
+    Gzip,
+    /// In zlib, as described in RFC 1950.
+    Zlib,
+    /// In raw DEFLATE, as described in RFC 1951.
+    Deflate,
+}
+
+impl Wrapping {
+    /// Detects the wrapping from the first two bytes, which is either the header of gzip or zlib, or nothing.
+    ///
+    /// Raw DEFLATE has no header to be detected by, so it's never detected, and is only undone when the caller declares it. Any other bytes are left as they are, so bytes that aren't bencoded fail to decode with a syntax error.
+    pub(crate) fn detect(head: &[u8]) -> Wrapping {
+        match *head {
+            [] | [DICT_START | LIST_START | INT_START | b'0'..=b'9', ..] => {
+                Wrapping::Identity
+            }
+            [0x1f, 0x8b] => Wrapping::Gzip,
+            [cmf, flg]
+                if cmf & 0x0f == 8
+                    && u16::from_be_bytes([cmf, flg]) % 31 == 0 =>
+            {
+                Wrapping::Zlib
+            }
+            _ => Wrapping::Identity,
+        }
+    }
+}
+
+/// A reader that decompresses the bytes of another reader as they're read.
+pub(crate) enum Unwrapped<R> {
+    Identity(R),
+    Gzip(GzDecoder<R>),
+    Zlib(ZlibDecoder<R>),
+    Deflate(DeflateDecoder<R>),
+}
+
+impl<R: Read> Unwrapped<R> {
+    /// Constructs a reader that undoes the given wrapping.
+    pub(crate) fn new(reader: R, wrapping: Wrapping) -> Unwrapped<R> {
+        match wrapping {
+            Wrapping::Identity => Unwrapped::Identity(reader),
+            Wrapping::Gzip => Unwrapped::Gzip(GzDecoder::new(reader)),
+            Wrapping::Zlib => Unwrapped::Zlib(ZlibDecoder::new(reader)),
+            Wrapping::Deflate => {
+                Unwrapped::Deflate(DeflateDecoder::new(reader))
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for Unwrapped<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            Unwrapped::Identity(ref mut r) => r.read(buf),
+            Unwrapped::Gzip(ref mut r) => r.read(buf),
+            Unwrapped::Zlib(ref mut r) => r.read(buf),
+            Unwrapped::Deflate(ref mut r) => r.read(buf),
+        }
+    }
+}
+
+/// A reader whose first bytes were read to detect its wrapping, and are read again before the rest of it.
+pub(crate) type Detected<R> = Chain<Cursor<Vec<u8>>, R>;
+
+/// Wraps the reader in one that decompresses its bytes if they're wrapped in gzip or zlib, which is detected from the first bytes.
+///
+/// # Errors
+///
+/// * The reader fails while reading the first bytes.
+pub(crate) fn detect<R: Read>(
+    mut reader: R,
+) -> Result<Unwrapped<Detected<R>>, Error> {
+    let mut head = Vec::with_capacity(2);
+    reader.by_ref().take(2).read_to_end(&mut head)?;
+    let wrapping = Wrapping::detect(&head);
+    Ok(Unwrapped::new(Cursor::new(head).chain(reader), wrapping))
+}
+
+/// Reads the rest of the reader and discards it, so the checksum at the end of gzip and zlib is still verified after the value has been decoded.
+///
+/// # Errors
+///
+/// * The reader fails, or the compressed bytes fail their checksum, at which point `Error::Io` is returned.
+/// * There are more than `max_len` bytes left, including the `buffered` bytes that were already read, at which point `Error::Length` is returned.
+pub(crate) fn drain<R: Read>(
+    reader: R,
+    buffered: usize,
+    max_len: usize,
+) -> Result<(), Error> {
+    let limit = max_len.saturating_sub(buffered);
+    let read = io::copy(&mut reader.take(read_limit(limit)), &mut io::sink())?;
+    let len =
+        buffered.saturating_add(usize::try_from(read).unwrap_or(usize::MAX));
+    check_len(len, max_len)
+}
+
+/// Reads the reader to its end, decompressing its bytes as they're read.
+///
+/// Reading stops as soon as there are more than `max_len` decompressed bytes, so a small compressed body can't expand into an unbounded amount of memory.
+///
+/// # Errors
+///
+/// * The reader fails, or the compressed bytes are malformed or fail their checksum, at which point `Error::Io` is returned.
+/// * There are more than `max_len` decompressed bytes, at which point `Error::Length` is returned.
+pub(crate) fn unwrap<R: Read>(
+    reader: R,
+    wrapping: Wrapping,
+    max_len: usize,
+) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    Unwrapped::new(reader, wrapping)
+        .take(read_limit(max_len))
+        .read_to_end(&mut out)?;
+    check_len(out.len(), max_len)?;
+    Ok(out)
+}
+
+/// Gets how many bytes to read to enforce a limit of `max_len`.
+///
+/// One more byte than the limit is read, to tell a body that's exactly at the limit apart from a longer one.
+fn read_limit(max_len: usize) -> u64 {
+    u64::try_from(max_len).map_or(u64::MAX, |max| max.saturating_add(1))
+}
+
+/// Checks that `len` decompressed bytes are within the limit.
+fn check_len(len: usize, max_len: usize) -> Result<(), Error> {
+    if len > max_len {
+        return Err(Error::Length {
+            len,
+            expected: format!("at most {} decompressed bytes", max_len),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use flate2::write::{DeflateEncoder, GzEncoder, ZlibEncoder};
+    use flate2::Compression;
+
+    use super::{detect, unwrap, Wrapping};
+    use crate::de::{Error, ErrorKind};
+
+    /// Reads the bytes to their end through the detected wrapping, which the reader returned by `detect` already undoes.
+    fn detect_and_unwrap(
+        bytes: &[u8],
+        max_len: usize,
+    ) -> Result<Vec<u8>, Error> {
+        unwrap(detect(bytes)?, Wrapping::Identity, max_len)
+    }
+
+    #[test]
+    fn decompress_detected_wrapping() {
+        let plain = [
+            &b"d5:peersl"[..],
+            &b"d2:ip8:10.0.0.14:porti6881ee".repeat(8),
+            b"ee",
+        ]
+        .concat();
+
+        let mut gzip = GzEncoder::new(vec![], Compression::default());
+        gzip.write_all(&plain).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let mut zlib = ZlibEncoder::new(vec![], Compression::default());
+        zlib.write_all(&plain).unwrap();
+        let zlib = zlib.finish().unwrap();
+        let mut raw = DeflateEncoder::new(vec![], Compression::default());
+        raw.write_all(&plain).unwrap();
+        let raw = raw.finish().unwrap();
+
+        for bytes in [&gzip, &zlib, &plain] {
+            assert_eq!(detect_and_unwrap(&bytes[..], 1024).unwrap(), plain);
+        }
+
+        // Raw DEFLATE is only undone when it's declared, and other bytes are left as they are.
+        assert_eq!(unwrap(&raw[..], Wrapping::Deflate, 1024).unwrap(), plain);
+        assert_eq!(detect_and_unwrap(&raw[..], 1024).unwrap(), raw);
+        assert_eq!(detect_and_unwrap(&b"xyz"[..], 1024).unwrap(), b"xyz");
+
+        let mut corrupted = zlib.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        let err = detect_and_unwrap(&corrupted[..], 1024).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Io);
+        let err = detect_and_unwrap(&gzip[..gzip.len() - 6], 1024).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Io);
+    }
+
+    #[test]
+    fn decompress_bomb() {
+        // 16 MiB of zeros, which compress to about 16 KiB.
+        let mut gzip = GzEncoder::new(vec![], Compression::best());
+        for _ in 0..16 {
+            gzip.write_all(&[0; 1 << 20]).unwrap();
+        }
+        let gzip = gzip.finish().unwrap();
+        assert!(gzip.len() < 1 << 15);
+
+        let err = detect_and_unwrap(&gzip[..], 1 << 20).unwrap_err();
+        assert!(
+            matches!(err, Error::Length { len, .. } if len == (1 << 20) + 1)
+        );
+        assert_eq!(err.kind(), ErrorKind::Limit);
+
+        let plain = b"l3:fooe";
+        assert_eq!(detect_and_unwrap(&plain[..], 7).unwrap(), plain);
+        assert!(detect_and_unwrap(&plain[..], 6).is_err());
+    }
+
+    #[test]
+    fn decode_compressed_as_read() {
+        let zlib = |plain: &[u8]| {
+            let mut zlib = ZlibEncoder::new(vec![], Compression::default());
+            zlib.write_all(plain).unwrap();
+            zlib.finish().unwrap()
+        };
+
+        let list: Vec<String> =
+            crate::from_reader_compressed(&zlib(b"l3:fooe  ")[..], 8).unwrap();
+        assert_eq!(list, ["foo"]);
+
+        // The checksum is verified after the value is decoded.
+        let mut corrupted = zlib(b"l3:fooe");
+        *corrupted.last_mut().unwrap() ^= 1;
+        let res = crate::from_reader_compressed::<_, Vec<String>>(
+            &corrupted[..],
+            1024,
+        );
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::Io);
+
+        // The bytes after the value are limited too.
+        let res = crate::from_reader_compressed::<_, Vec<String>>(
+            &zlib(&[&b"l3:fooe"[..], &[b' '; 64]].concat())[..],
+            32,
+        );
+        assert_eq!(res.unwrap_err().kind(), ErrorKind::Limit);
+
+        // A value that never ends is rejected once it's longer than the limit.
+        let bomb = zlib(&[&b"l"[..], &b"i0e".repeat(1 << 20)].concat());
+        let res = crate::from_reader_compressed::<_, Vec<i64>>(&bomb[..], 4096);
+        assert!(matches!(res, Err(Error::Length { len, .. }) if len > 4096));
+    }
+}
@@ -1,8 +1,17 @@
 //! Bencode encoding and serialization.
 
+use std::collections::btree_map::Entry;
 use std::collections::BTreeMap;
+use std::hash::Hasher;
+use std::io;
 use std::io::Error as IoError;
-use std::io::Write;
+use std::mem;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
 
 use serde::ser::Impossible;
 use serde::ser::SerializeMap;
@@ -13,13 +22,44 @@ use serde::ser::SerializeTuple;
 use serde::ser::SerializeTupleStruct;
 use serde::ser::SerializeTupleVariant;
 use serde::Serialize;
-use serde::Serializer;
+use serde::Serializer as _;
 
-use super::DICT_START;
-use super::INT_START;
-use super::LIST_START;
-use super::TEXT_DELIM;
-use super::TYPE_END;
+use super::cancel::CancelToken;
+use super::path::Path;
+use super::path::Segment;
+use super::pooled::MAX_RETAINED;
+use super::profile::Profile;
+use super::raw::RAW_TOKEN;
+use super::spec::DICT_START;
+use super::spec::INT_START;
+use super::spec::LIST_START;
+use super::spec::MAX_INT_LEN;
+use super::spec::TEXT_DELIM;
+use super::spec::TYPE_END;
+use super::value::ORDERED_TOKEN;
+use super::Value;
+
+mod layout;
+
+pub use layout::fields_sorted;
+pub use layout::StructLayout;
+
+use layout::StructEncoder;
+
+/// The number of entries up to which a map is considered small.
+const SMALL_MAP_LEN: usize = 8;
+
+/// The initial capacity of the buffers of the values of a small map.
+const SMALL_CAPACITY: usize = 16;
+
+/// The maximum number of emptied buffers an encoder keeps for reuse.
+const MAX_SCRATCH: usize = 64;
+
+/// The largest capacity, in bytes, of an emptied buffer an encoder keeps for reuse.
+const MAX_SCRATCH_CAPACITY: usize = 1 << 16;
+
+/// The maximum number of idle encoders an [`EncoderPool`] keeps.
+const MAX_IDLE_ENCODERS: usize = 64;
 
 /// An error that can occur when encoding types to bencode.
 ///
@@ -29,9 +69,15 @@ use super::TYPE_END;
 /// * `InvalidKeyType` - When you try encoding a map with keys that are not of type string.
 /// * `KeyWithNoValue` - When you try encoding a map entry's key without a value.
 /// * `ValueWithNoKey` - When you try encoding a map entry's value without a key.
-/// * `Unsupported` - When you try encoding a type that is not currently supported by the library.
+/// * `Unsupported` - When you try encoding a type that is not currently supported by the library, or `()` with [`UnitRepr::Error`].
+/// * `IntegerOverflow` - When you try encoding an unsigned integer that doesn't fit in an `i64`.
 /// * `Serialize` - A custom serde serialization error.
+/// * `NotCanonical` - When [`encode_verified`](super::encode_verified) finds that the encoded bytes aren't canonical bencode.
+/// * `Cancelled` - When the encode is cancelled by a [`CancelToken`].
+/// * `DuplicateKey` - When a map has the same key more than once, and the encoder's [`DuplicateKeys`] policy is `Error`.
+/// * `Length` - When an encoded value is longer than a limit, eg: that of a `BencodeSink`.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// A standard I/O error.
     Io(IoError),
@@ -43,8 +89,26 @@ pub enum Error {
     ValueWithNoKey,
     /// Tried encoding a type that is not currently supported by the library.
     Unsupported(&'static str),
+    /// Tried encoding an unsigned integer larger than `i64::MAX`, which bencode decoders, including this one, can't decode.
+    IntegerOverflow(u64),
     /// A serde serialization error.
     Serialize(String),
+    /// The encoded bytes aren't canonical bencode, eg: because a raw value was copied into them as is.
+    NotCanonical {
+        /// The position of the first problem in the encoded bytes.
+        at: usize,
+    },
+    /// The encode was cancelled before it finished.
+    Cancelled,
+    /// A map had the same key more than once, and the encoder's [`DuplicateKeys`] policy couldn't resolve it.
+    DuplicateKey(String),
+    /// An encoded value was longer than a limit.
+    Length {
+        /// The length of the encoded value.
+        len: usize,
+        /// A description of the lengths the limit allows.
+        expected: String,
+    },
 }
 
 impl std::fmt::Display for Error {
@@ -68,12 +132,32 @@ impl std::fmt::Display for Error {
                     ty
                 )
             }
+            Error::IntegerOverflow(v) => {
+                write!(f, "integer {} is out of the range of an i64", v)
+            }
             Error::Serialize(ref e) => e.fmt(f),
+            Error::NotCanonical { at } => {
+                write!(f, "encoded bytes are not canonical at position {}", at)
+            }
+            Error::Cancelled => write!(f, "encoding was cancelled"),
+            Error::DuplicateKey(ref key) => {
+                write!(f, "map has duplicate key {:?}", key)
+            }
+            Error::Length { len, ref expected } => {
+                write!(f, "invalid length {}, expected {}", len, expected)
+            }
         }
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            Error::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 impl serde::ser::Error for Error {
     fn custom<T>(msg: T) -> Self
@@ -90,6 +174,203 @@ impl From<IoError> for Error {
     }
 }
 
+/// A minimal sink for encoded bytes, which the [`Encoder`] writes to.
+///
+/// It's implemented for `Vec<u8>`, for `&mut [u8]`, which fails once the slice is full, and for mutable references to other sinks. With the `heapless` feature, it's also implemented for `heapless::Vec<u8, N>`, which fails once it's full. Any [`io::Write`] type can be written to through an [`IoWriter`], and any [`Hasher`] through a [`HashWriter`].
+///
+/// # Examples
+///
+/// ```
+/// use serde::Serialize;
+/// use bende::en::Encoder;
+///
+/// let mut buf = [0; 8];
+/// let mut en = Encoder::new(&mut buf[..]);
+/// "foo".serialize(&mut en).unwrap();
+///
+/// assert_eq!(&buf[..5], b"3:foo");
+/// assert!("too long".serialize(&mut Encoder::new(&mut buf[..])).is_err());
+/// ```
+pub trait BenWrite {
+    /// Writes all the given bytes.
+    ///
+    /// # Errors
+    ///
+    /// * The sink couldn't take all of the bytes.
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()>;
+
+    /// Hints that at least `additional` more bytes are about to be written, so the sink can make room for them at once.
+    #[inline]
+    fn reserve(&mut self, _additional: usize) {}
+}
+
+impl BenWrite for Vec<u8> {
+    #[inline]
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    #[inline]
+    fn reserve(&mut self, additional: usize) {
+        Vec::reserve(self, additional);
+    }
+}
+
+impl BenWrite for &mut [u8] {
+    /// Writes the bytes to the start of the slice, and advances it past them. Nothing is written if the slice is too short.
+    #[inline]
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if bytes.len() > self.len() {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        let (head, tail) = mem::take(self).split_at_mut(bytes.len());
+        head.copy_from_slice(bytes);
+        *self = tail;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "heapless")]
+impl<const N: usize> BenWrite for heapless::Vec<u8, N> {
+    /// Appends the bytes to the vector. Nothing is written if there's no room for all of them.
+    #[inline]
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.extend_from_slice(bytes)
+            .map_err(|()| io::ErrorKind::WriteZero.into())
+    }
+}
+
+impl<W: ?Sized + BenWrite> BenWrite for &mut W {
+    #[inline]
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        (**self).write_all(bytes)
+    }
+
+    #[inline]
+    fn reserve(&mut self, additional: usize) {
+        (**self).reserve(additional);
+    }
+}
+
+/// An adapter that writes encoded bytes to an [`io::Write`] type, eg: a file or a socket.
+///
+/// This is what [`to_writer`](super::to_writer) wraps its writer in. Writes aren't buffered, so slow writers are best wrapped in a [`BufWriter`](std::io::BufWriter) first.
+///
+/// # Examples
+///
+/// ```
+/// use serde::Serialize;
+/// use bende::en::{Encoder, IoWriter};
+/// use std::io::Cursor;
+///
+/// let mut en = Encoder::new(IoWriter::new(Cursor::new(vec![])));
+/// 1995.serialize(&mut en).unwrap();
+///
+/// assert_eq!(en.into_inner().into_inner().into_inner(), b"i1995e");
+/// ```
+#[derive(Debug)]
+pub struct IoWriter<W>(W);
+
+impl<W: io::Write> IoWriter<W> {
+    /// Constructs a new adapter around the given writer.
+    #[inline]
+    pub fn new(writer: W) -> IoWriter<W> {
+        Self(writer)
+    }
+
+    /// Consumes the adapter and returns the underlying writer.
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.0
+    }
+}
+
+impl<W: io::Write> BenWrite for IoWriter<W> {
+    #[inline]
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.0.write_all(bytes)
+    }
+}
+
+/// An adapter that feeds encoded bytes to a [`Hasher`], so a value can be hashed without encoding it into a buffer first.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::hash_map::DefaultHasher;
+/// use std::hash::Hasher;
+/// use serde::Serialize;
+/// use bende::en::{Encoder, HashWriter};
+///
+/// let mut en = Encoder::new(HashWriter::new(DefaultHasher::new()));
+/// ("foo", 1995).serialize(&mut en).unwrap();
+///
+/// let mut hasher = DefaultHasher::new();
+/// hasher.write(b"l3:fooi1995ee");
+/// assert_eq!(en.into_inner().finish(), hasher.finish());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct HashWriter<H>(H);
+
+impl<H: Hasher> HashWriter<H> {
+    /// Constructs a new adapter around the given hasher.
+    #[inline]
+    pub fn new(hasher: H) -> HashWriter<H> {
+        Self(hasher)
+    }
+
+    /// Returns the hash of the bytes written so far.
+    #[inline]
+    pub fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+
+    /// Consumes the adapter and returns the underlying hasher.
+    #[inline]
+    pub fn into_inner(self) -> H {
+        self.0
+    }
+}
+
+impl<H: Hasher> BenWrite for HashWriter<H> {
+    #[inline]
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.0.write(bytes);
+        Ok(())
+    }
+}
+
+/// How the encoder writes `()`, eg: the value of a `HashMap<String, ()>` used as a set, or of an `Option<()>` flag.
+///
+/// Bencode has no unit type, and writing nothing in its place would leave a key without a value. The decoder reads `()` back from either of the representations below, so they round-trip whichever is picked. Unit structs aren't affected, as they're always encoded as their name, eg: `struct Marker;` as `6:Marker`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum UnitRepr {
+    /// Fails with `Error::Unsupported`.
+    Error,
+    /// Writes an empty dictionary, `de`.
+    #[default]
+    EmptyDict,
+    /// Writes an empty byte array, `0:`.
+    EmptyText,
+}
+
+/// What the encoder does when a map has the same key more than once, eg: a `Vec<(String, Value)>` encoded through [`with::entries`](crate::with::entries).
+///
+/// A dictionary can't have duplicate keys, so only one entry per key is written, as decoders would otherwise reject the dictionary or disagree on its value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum DuplicateKeys {
+    /// Fails with `Error::DuplicateKey`.
+    Error,
+    /// Keeps the first entry.
+    First,
+    /// Keeps the last entry, the same way decoding into a [`Value`] does.
+    #[default]
+    Last,
+    /// Joins the values, which must be lists, into one list, in the order they're in. Any other value fails with `Error::DuplicateKey`.
+    ConcatLists,
+}
+
 /// An encoder used to encode types to bencode representation.
 ///
 /// When encoding types, you'd typically use the [`encode`](super::encode) function, but you can use this when you want more control over the buffer used to write values to.
@@ -102,7 +383,7 @@ impl From<IoError> for Error {
 ///
 /// let x = 1995;
 ///
-/// // Give it anything that implements 'Write'.
+/// // Give it anything that implements 'BenWrite'.
 /// let mut en = Encoder::new(vec![]);
 /// x.serialize(&mut en).unwrap();
 ///
@@ -111,15 +392,289 @@ impl From<IoError> for Error {
 #[derive(Debug)]
 pub struct Encoder<W> {
     buf: W,
+    raw: bool,
+    layouts: Option<Arc<Vec<StructLayout>>>,
+    cancel: Option<CancelToken>,
+    skip_none: bool,
+    int_keys: bool,
+    document_order: bool,
+    unit: UnitRepr,
+    duplicates: DuplicateKeys,
+    // Set while encoding an `OrderedValue` dictionary that may be written in document order.
+    ordered: bool,
+    // Set while encoding the value of a map entry, until anything is written, so only a `None` that is the whole value is left out.
+    entry: bool,
+    // Emptied buffers of nested values, which are reused instead of allocating new ones.
+    scratch: Vec<Vec<u8>>,
 }
 
-impl<W: Write> Encoder<W> {
+/// The [`Encoder`], under the name serde gives to the types that implement [`serde::Serializer`].
+///
+/// This alias is part of the stable API, so frameworks can name the serializer they embed the same way for every serde format.
+pub type Serializer<W> = Encoder<W>;
+
+impl Encoder<Vec<u8>> {
+    /// Constructs a new encoder with an empty buffer that has room for at least `capacity` bytes.
+    ///
+    /// Starting from a capacity close to the encoded size avoids reallocating the buffer as it grows, eg: while writing the `pieces` of a large torrent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use serde::Serialize;
+    /// use bende::en::Encoder;
+    ///
+    /// let mut en = Encoder::with_capacity(1024);
+    /// "foo".serialize(&mut en).unwrap();
+    ///
+    /// let buf = en.into_inner();
+    /// assert_eq!(buf, b"3:foo");
+    /// assert!(buf.capacity() >= 1024);
+    /// ```
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Encoder<Vec<u8>> {
+        Encoder::new(Vec::with_capacity(capacity))
+    }
+
+    /// Encodes a value into the encoder's buffer, replacing whatever the buffer held, and returns the encoded bytes.
+    ///
+    /// The buffer, and the scratch buffers used to sort the entries of dictionaries, are kept between calls, so encoding many values with one encoder, eg: in a hot loop, stops allocating once the buffers are large enough. Use an [`EncoderPool`] to share encoders between threads.
+    ///
+    /// # Errors
+    ///
+    /// * The value can't be encoded, at which point the buffer holds an incomplete value until the next call.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::en::Encoder;
+    ///
+    /// let mut en = Encoder::new(vec![]);
+    ///
+    /// for port in [6881, 6882] {
+    ///     let bytes = en.encode(&("ping", port)).unwrap();
+    ///     assert_eq!(bytes, format!("l4:pingi{}ee", port).as_bytes());
+    /// }
+    /// ```
+    pub fn encode<T>(&mut self, val: &T) -> Result<&[u8], Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.buf.clear();
+        val.serialize(&mut *self)?;
+        Ok(&self.buf)
+    }
+
+    /// Returns the number of bytes the encoder's buffer and scratch buffers have allocated.
+    pub(crate) fn allocated(&self) -> usize {
+        self.scratch.iter().map(Vec::capacity).sum::<usize>()
+            + self.buf.capacity()
+    }
+
+    /// Constructs an encoder with an empty buffer and the same settings as this one.
+    fn fresh(&self) -> Encoder<Vec<u8>> {
+        Encoder {
+            buf: Vec::new(),
+            raw: false,
+            layouts: self.layouts.clone(),
+            cancel: self.cancel.clone(),
+            skip_none: self.skip_none,
+            int_keys: self.int_keys,
+            document_order: self.document_order,
+            unit: self.unit,
+            duplicates: self.duplicates,
+            ordered: false,
+            entry: false,
+            scratch: Vec::new(),
+        }
+    }
+}
+
+impl<W: BenWrite> Encoder<W> {
     /// Constructs a new encoder with the given buffer.
     ///
-    /// The encoder is generic over its buffer, and accepts any type that implements [`Write`].
+    /// The encoder is generic over its buffer, and accepts any type that implements [`BenWrite`]. Use an [`IoWriter`] to write to an [`io::Write`] type.
     #[inline]
     pub fn new(buf: W) -> Encoder<W> {
-        Self { buf }
+        Self {
+            buf,
+            raw: false,
+            layouts: None,
+            cancel: None,
+            skip_none: false,
+            int_keys: false,
+            document_order: false,
+            unit: UnitRepr::EmptyDict,
+            duplicates: DuplicateKeys::Last,
+            ordered: false,
+            entry: false,
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Registers a precomputed layout, which is used to encode the struct it describes without sorting its fields on every call.
+    ///
+    /// Layouts are matched by struct name, and apply to nested values too. Serde only passes the encoder the bare name of a struct, without its module, so two structs with the same name share a layout. See [`StructLayout`] for more details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use serde::Serialize;
+    /// use bende::en::{Encoder, StructLayout};
+    ///
+    /// #[derive(Serialize)]
+    /// struct Peer {
+    ///     port: u16,
+    ///     ip: String,
+    /// }
+    ///
+    /// let layout = StructLayout::new("Peer", &["port", "ip"]);
+    /// let mut en = Encoder::new(vec![]).with_layout(layout);
+    ///
+    /// let peer = Peer { port: 6881, ip: "127.0.0.1".into() };
+    /// peer.serialize(&mut en).unwrap();
+    ///
+    /// assert_eq!(en.into_inner(), b"d2:ip9:127.0.0.14:porti6881ee");
+    /// ```
+    pub fn with_layout(mut self, layout: StructLayout) -> Encoder<W> {
+        let layouts = self.layouts.get_or_insert_with(Default::default);
+        let layouts = Arc::make_mut(layouts);
+        layouts.retain(|l| l.name() != layout.name());
+        layouts.push(layout);
+        self
+    }
+
+    /// Sets a token that cancels the encode once it's cancelled. The token is checked before every value and delimiter is written.
+    ///
+    /// See [`CancelToken`] for more details.
+    #[inline]
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Encoder<W> {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Sets whether `None` is left out instead of failing the encode. Defaults to `false`.
+    ///
+    /// Bencode has no null, so by default `None` fails with `Error::Unsupported`. When enabled, dictionary entries and struct fields whose values are `None` are left out, like missing keys are read by most implementations. A `None` anywhere else, eg: an element of a list or the value being encoded, has nothing to be left out of, and still fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use serde::Serialize;
+    /// use bende::en::Encoder;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Torrent {
+    ///     comment: Option<&'static str>,
+    ///     name: &'static str,
+    /// }
+    ///
+    /// let torrent = Torrent { comment: None, name: "foo" };
+    /// assert!(torrent.serialize(&mut Encoder::new(vec![])).is_err());
+    ///
+    /// let mut en = Encoder::new(vec![]).with_skip_none(true);
+    /// torrent.serialize(&mut en).unwrap();
+    /// assert_eq!(en.into_inner(), b"d4:name3:fooe");
+    /// ```
+    #[inline]
+    pub fn with_skip_none(mut self, skip: bool) -> Encoder<W> {
+        self.skip_none = skip;
+        self
+    }
+
+    /// Sets whether integer and boolean map keys are written as their decimal digits instead of failing the encode. Defaults to `false`.
+    ///
+    /// Keys are ordered by their digits as byte strings, eg: `10` comes before `9`. The decoder reads such keys back into integers either way.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    /// use serde::Serialize;
+    /// use bende::en::Encoder;
+    ///
+    /// let map = BTreeMap::from([(9, "a"), (10, "b")]);
+    /// assert!(map.serialize(&mut Encoder::new(vec![])).is_err());
+    ///
+    /// let mut en = Encoder::new(vec![]).with_int_keys(true);
+    /// map.serialize(&mut en).unwrap();
+    /// assert_eq!(en.into_inner(), b"d2:101:b1:91:ae");
+    /// ```
+    #[inline]
+    pub fn with_int_keys(mut self, coerce: bool) -> Encoder<W> {
+        self.int_keys = coerce;
+        self
+    }
+
+    /// Sets whether the dictionaries of an [`OrderedValue`](crate::value::OrderedValue) are written in the order their entries are in, instead of being sorted. Defaults to `false`.
+    ///
+    /// This produces **non-canonical** bencode, for writing legacy files back byte for byte after editing them. It's never enabled by a [`Profile`], and it only applies to `OrderedValue` dictionaries - maps and structs are still sorted, so types that were never in document order can't be encoded out of order. Don't enable it where the bytes are hashed, eg: for the `info` dictionary of a torrent, unless they have to match the original file.
+    ///
+    /// See [`OrderedValue`](crate::value::OrderedValue) for an example.
+    #[inline]
+    pub fn with_document_order(mut self, preserve: bool) -> Encoder<W> {
+        self.document_order = preserve;
+        self
+    }
+
+    /// Sets how `()` is written. Defaults to [`UnitRepr::EmptyDict`].
+    ///
+    /// An `Option<()>` is a `None` or a `()`, so its `None` is still left out or fails depending on [`with_skip_none`](Encoder::with_skip_none).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    /// use serde::Serialize;
+    /// use bende::en::{Encoder, UnitRepr};
+    ///
+    /// let set = BTreeMap::from([("a", ()), ("b", ())]);
+    /// assert_eq!(bende::encode(&set).unwrap(), b"d1:ade1:bdee");
+    ///
+    /// let mut en = Encoder::new(vec![]).with_unit_repr(UnitRepr::Error);
+    /// assert!(set.serialize(&mut en).is_err());
+    /// ```
+    #[inline]
+    pub fn with_unit_repr(mut self, repr: UnitRepr) -> Encoder<W> {
+        self.unit = repr;
+        self
+    }
+
+    /// Sets what's done with repeated keys of a map. Defaults to [`DuplicateKeys::Last`].
+    ///
+    /// The dictionaries of an [`OrderedValue`](crate::value::OrderedValue) written in document order are written as they are, repeated keys included.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use serde::Serialize;
+    /// use bende::en::{DuplicateKeys, Encoder};
+    ///
+    /// #[derive(Serialize)]
+    /// struct Peers {
+    ///     #[serde(with = "bende::with::entries")]
+    ///     seen: Vec<(&'static str, Vec<u16>)>,
+    /// }
+    ///
+    /// let peers = Peers { seen: vec![("a", vec![1]), ("b", vec![2]), ("a", vec![3])] };
+    /// let mut en = Encoder::new(vec![]).with_duplicate_keys(DuplicateKeys::ConcatLists);
+    /// peers.serialize(&mut en).unwrap();
+    /// assert_eq!(en.into_inner(), b"d4:seend1:ali1ei3ee1:bli2eeee");
+    ///
+    /// let mut en = Encoder::new(vec![]).with_duplicate_keys(DuplicateKeys::Error);
+    /// assert!(peers.serialize(&mut en).is_err());
+    /// ```
+    #[inline]
+    pub fn with_duplicate_keys(mut self, policy: DuplicateKeys) -> Encoder<W> {
+        self.duplicates = policy;
+        self
+    }
+
+    /// Applies the encoding knobs of an interop profile. See [`Profile`] for the knobs each one sets.
+    #[inline]
+    pub fn with_profile(self, profile: Profile) -> Encoder<W> {
+        let lenient = profile != Profile::Strict;
+        self.with_skip_none(lenient).with_int_keys(lenient)
     }
 
     /// Consumes and returns the encoder's underlying buffer.
@@ -128,36 +683,381 @@ impl<W: Write> Encoder<W> {
         self.buf
     }
 
+    /// Encodes a value tree, asking the hook what to do with every value before it's encoded, so values can be redacted, replaced or dropped without cloning the tree first.
+    ///
+    /// The values inside a replacement aren't passed to the hook. If the root value is dropped, nothing is encoded. See [`EncodeHook`] for more details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::en::{EncodeAction, Encoder};
+    /// use bende::Value;
+    ///
+    /// let val: Value = bende::decode(b"d4:infod6:lengthi1995ee6:secret3:fooe").unwrap();
+    ///
+    /// let mut en = Encoder::new(vec![]);
+    /// en.encode_value_with(&val, |path: &bende::path::Path, _: &Value| {
+    ///     match path.to_string().as_str() {
+    ///         "secret" => EncodeAction::Drop,
+    ///         "info.length" => EncodeAction::Replace(Value::Int(0)),
+    ///         _ => EncodeAction::Keep,
+    ///     }
+    /// })
+    /// .unwrap();
+    ///
+    /// assert_eq!(en.into_inner(), b"d4:infod6:lengthi0eee");
+    /// ```
+    pub fn encode_value_with<H>(
+        &mut self,
+        val: &Value,
+        mut hook: H,
+    ) -> Result<(), Error>
+    where
+        H: EncodeHook,
+    {
+        let mut path = Path::root();
+        match hook.before_value(&path, val) {
+            EncodeAction::Keep => self.encode_hooked(val, &mut hook, &mut path),
+            EncodeAction::Drop => Ok(()),
+            EncodeAction::Replace(ref val) => val.serialize(self),
+        }
+    }
+
+    /// Encodes a value that the hook has kept, passing its children to the hook.
+    fn encode_hooked<H>(
+        &mut self,
+        val: &Value,
+        hook: &mut H,
+        path: &mut Path,
+    ) -> Result<(), Error>
+    where
+        H: EncodeHook,
+    {
+        match *val {
+            Value::Int(v) => self.encode_int(v),
+            Value::Text(ref v) => self.encode_bytes(v),
+            Value::List(ref list) => {
+                self.tag(LIST_START)?;
+                for (i, elem) in list.iter().enumerate() {
+                    path.push(i);
+                    match hook.before_value(path, elem) {
+                        EncodeAction::Keep => {
+                            self.encode_hooked(elem, hook, path)?
+                        }
+                        EncodeAction::Drop => {}
+                        EncodeAction::Replace(ref elem) => {
+                            elem.serialize(&mut *self)?
+                        }
+                    }
+                    path.pop();
+                }
+                self.tag(TYPE_END)
+            }
+            Value::Dict(ref dict) => {
+                self.tag(DICT_START)?;
+                for (key, elem) in dict.iter() {
+                    path.push(Segment::from_key_bytes(key));
+                    let action = hook.before_value(path, elem);
+                    if !matches!(action, EncodeAction::Drop) {
+                        self.encode_bytes(key.as_bytes())?;
+                    }
+                    match action {
+                        EncodeAction::Keep => {
+                            self.encode_hooked(elem, hook, path)?
+                        }
+                        EncodeAction::Drop => {}
+                        EncodeAction::Replace(ref elem) => {
+                            elem.serialize(&mut *self)?
+                        }
+                    }
+                    path.pop();
+                }
+                self.tag(TYPE_END)
+            }
+        }
+    }
+
+    /// Encodes a value into a scratch buffer that has room for at least `capacity` bytes, with the same settings as this encoder.
+    fn encode_nested<T>(
+        &mut self,
+        val: &T,
+        capacity: usize,
+    ) -> Result<Vec<u8>, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let mut buf = self.scratch.pop().unwrap_or_default();
+        buf.reserve(capacity);
+        let mut en = Encoder {
+            buf,
+            raw: false,
+            layouts: self.layouts.clone(),
+            cancel: self.cancel.clone(),
+            skip_none: self.skip_none,
+            int_keys: self.int_keys,
+            document_order: self.document_order,
+            unit: self.unit,
+            duplicates: self.duplicates,
+            ordered: false,
+            entry: true,
+            scratch: mem::take(&mut self.scratch),
+        };
+        let res = val.serialize(&mut en);
+        self.scratch = en.scratch;
+        res.map(|()| en.buf)
+    }
+
+    /// Keeps an emptied buffer for the next nested value, unless enough are kept already or it's too large to hold on to.
+    #[inline]
+    fn recycle(&mut self, mut buf: Vec<u8>) {
+        if self.scratch.len() < MAX_SCRATCH
+            && buf.capacity() <= MAX_SCRATCH_CAPACITY
+        {
+            buf.clear();
+            self.scratch.push(buf);
+        }
+    }
+
+    /// Gets the layout registered for the struct with the given name.
+    fn layout(&self, name: &str) -> Option<&StructLayout> {
+        self.layouts.as_ref()?.iter().find(|l| l.name() == name)
+    }
+
     /// Writes a single byte into the buffer.
     #[inline]
     fn tag(&mut self, byte: u8) -> Result<(), Error> {
-        self.buf.write_all(&[byte]).map_err(Into::into)
+        self.write(&[byte])
     }
 
     /// Writes all the given bytes into the buffer.
+    ///
+    /// # Errors
+    ///
+    /// * The encode was cancelled by the cancel token.
+    /// * The buffer couldn't be written to.
     #[inline]
     fn write(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.entry = false;
+        if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+            return Err(Error::Cancelled);
+        }
         self.buf.write_all(bytes).map_err(Into::into)
     }
 
     /// Encodes an integer into the buffer.
     #[inline]
     fn encode_int(&mut self, v: i64) -> Result<(), Error> {
-        self.tag(INT_START)?;
-        self.write(v.to_string().as_bytes())?;
-        self.tag(TYPE_END)
+        // Room for the denotation, a sign, up to 19 digits and the end.
+        let mut buf = DigitBuf::<MAX_INT_LEN>::new();
+        buf.push_front(TYPE_END);
+        buf.push_digits(v.unsigned_abs());
+        if v < 0 {
+            buf.push_front(b'-');
+        }
+        buf.push_front(INT_START);
+        self.write(buf.as_bytes())
     }
 
     /// Encodes a byte array into the buffer.
     #[inline]
     fn encode_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
-        self.write(bytes.len().to_string().as_bytes())?;
-        self.tag(TEXT_DELIM)?;
+        // Room for up to 20 digits and the delimiter.
+        let mut buf = DigitBuf::<21>::new();
+        buf.push_front(TEXT_DELIM);
+        buf.push_digits(bytes.len() as u64);
+        self.buf.reserve(buf.as_bytes().len() + bytes.len());
+        self.write(buf.as_bytes())?;
         self.write(bytes)
     }
 }
 
-impl<'a, W: Write> Serializer for &'a mut Encoder<W> {
+/// A pool of reusable encoders, for servers that encode on many threads at once.
+///
+/// Each thread takes an encoder with [`get`](EncoderPool::get), which returns it to the pool once it's dropped, so its buffers are reused by the next thread instead of being allocated again. Every encoder of the pool has the settings of the encoder the pool was constructed with, eg: its layouts. Encoders whose buffers grow past [`MAX_RETAINED`](crate::pooled::MAX_RETAINED) bytes are dropped instead of being returned, so one large value doesn't hold on to its memory for the life of the pool.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::thread;
+/// use bende::en::{Encoder, EncoderPool};
+///
+/// let pool = Arc::new(EncoderPool::new(Encoder::new(vec![])));
+///
+/// let handles: Vec<_> = (0..4)
+///     .map(|i| {
+///         let pool = Arc::clone(&pool);
+///         thread::spawn(move || pool.get().encode(&i).unwrap().to_vec())
+///     })
+///     .collect();
+///
+/// for (i, handle) in handles.into_iter().enumerate() {
+///     assert_eq!(handle.join().unwrap(), format!("i{}e", i).as_bytes());
+/// }
+/// ```
+#[derive(Debug)]
+pub struct EncoderPool {
+    template: Encoder<Vec<u8>>,
+    idle: Mutex<Vec<Encoder<Vec<u8>>>>,
+}
+
+impl EncoderPool {
+    /// Constructs an empty pool, whose encoders have the settings of the given encoder.
+    pub fn new(template: Encoder<Vec<u8>>) -> EncoderPool {
+        Self { template, idle: Mutex::new(Vec::new()) }
+    }
+
+    /// Takes an idle encoder from the pool, or constructs a new one if there are none.
+    pub fn get(&self) -> PooledEncoder<'_> {
+        let en = self.lock().pop().unwrap_or_else(|| self.template.fresh());
+        PooledEncoder { pool: self, en: Some(en) }
+    }
+
+    /// Returns the number of idle encoders in the pool.
+    pub fn idle(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Locks the idle encoders, ignoring poisoning, as an encoder is left in a valid state by a panic.
+    fn lock(&self) -> MutexGuard<'_, Vec<Encoder<Vec<u8>>>> {
+        self.idle.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// An encoder taken from an [`EncoderPool`], which is returned to the pool when it's dropped.
+#[derive(Debug)]
+pub struct PooledEncoder<'a> {
+    pool: &'a EncoderPool,
+    // Only `None` once the encoder has been returned.
+    en: Option<Encoder<Vec<u8>>>,
+}
+
+impl Deref for PooledEncoder<'_> {
+    type Target = Encoder<Vec<u8>>;
+
+    fn deref(&self) -> &Encoder<Vec<u8>> {
+        self.en.as_ref().expect("a pooled encoder is only taken on drop")
+    }
+}
+
+impl DerefMut for PooledEncoder<'_> {
+    fn deref_mut(&mut self) -> &mut Encoder<Vec<u8>> {
+        self.en.as_mut().expect("a pooled encoder is only taken on drop")
+    }
+}
+
+impl Drop for PooledEncoder<'_> {
+    fn drop(&mut self) {
+        let mut idle = self.pool.lock();
+        if let Some(en) = self.en.take() {
+            if idle.len() < MAX_IDLE_ENCODERS && en.allocated() <= MAX_RETAINED
+            {
+                idle.push(en);
+            }
+        }
+    }
+}
+
+/// What [`Encoder::encode_value_with`] does with a value, as decided by an [`EncodeHook`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodeAction {
+    /// Encodes the value as is, passing its children to the hook.
+    Keep,
+    /// Leaves the value out, alongside its key if it's in a dictionary.
+    Drop,
+    /// Encodes the given value in its place.
+    Replace(Value),
+}
+
+/// A hook that decides what happens to every value of a value tree before it's encoded by [`Encoder::encode_value_with`].
+///
+/// The hook is given the path of each value and the value itself, parents before their children.
+///
+/// The trait is implemented for any `FnMut(&Path, &Value) -> EncodeAction` closure.
+pub trait EncodeHook {
+    /// Called before the value at the given path is encoded.
+    fn before_value(&mut self, path: &Path, val: &Value) -> EncodeAction;
+}
+
+impl<F> EncodeHook for F
+where
+    F: FnMut(&Path, &Value) -> EncodeAction,
+{
+    fn before_value(&mut self, path: &Path, val: &Value) -> EncodeAction {
+        self(path, val)
+    }
+}
+
+/// A stack buffer that an integer and its delimiters are written into back to front, without allocating.
+///
+/// With the `fast-unsafe` feature, the buffer isn't zeroed before it's written to.
+struct DigitBuf<const N: usize> {
+    #[cfg(feature = "fast-unsafe")]
+    buf: [mem::MaybeUninit<u8>; N],
+    #[cfg(not(feature = "fast-unsafe"))]
+    buf: [u8; N],
+    /// The position of the first byte that's been written.
+    start: usize,
+}
+
+impl<const N: usize> DigitBuf<N> {
+    #[inline]
+    fn new() -> DigitBuf<N> {
+        Self {
+            #[cfg(feature = "fast-unsafe")]
+            buf: [mem::MaybeUninit::uninit(); N],
+            #[cfg(not(feature = "fast-unsafe"))]
+            buf: [0; N],
+            start: N,
+        }
+    }
+
+    /// Writes a byte in front of the bytes written so far.
+    ///
+    /// # Panics
+    ///
+    /// * The buffer is full.
+    #[inline]
+    fn push_front(&mut self, byte: u8) {
+        self.start -= 1;
+        #[cfg(feature = "fast-unsafe")]
+        self.buf[self.start].write(byte);
+        #[cfg(not(feature = "fast-unsafe"))]
+        {
+            self.buf[self.start] = byte;
+        }
+    }
+
+    /// Writes the decimal digits of the number in front of the bytes written so far.
+    ///
+    /// # Panics
+    ///
+    /// * The buffer doesn't have room for all the digits.
+    #[inline]
+    fn push_digits(&mut self, mut v: u64) {
+        loop {
+            self.push_front(b'0' + (v % 10) as u8);
+            v /= 10;
+            if v == 0 {
+                return;
+            }
+        }
+    }
+
+    /// Gets the bytes written so far.
+    #[inline]
+    fn as_bytes(&self) -> &[u8] {
+        let written = &self.buf[self.start..];
+        // SAFETY: Every byte from `start` on was written by `push_front`, which is the only thing that moves `start`, and `MaybeUninit<u8>` has the same layout as `u8`.
+        #[cfg(feature = "fast-unsafe")]
+        let written = unsafe {
+            &*(written as *const [mem::MaybeUninit<u8>] as *const [u8])
+        };
+        written
+    }
+}
+
+impl<'a, W: BenWrite> serde::Serializer for &'a mut Encoder<W> {
     type Ok = ();
 
     type Error = Error;
@@ -172,7 +1072,7 @@ impl<'a, W: Write> Serializer for &'a mut Encoder<W> {
 
     type SerializeMap = MapEncoder<'a, W>;
 
-    type SerializeStruct = MapEncoder<'a, W>;
+    type SerializeStruct = StructEncoder<'a, W>;
 
     type SerializeStructVariant = MapEncoder<'a, W>;
 
@@ -209,7 +1109,8 @@ impl<'a, W: Write> Serializer for &'a mut Encoder<W> {
     }
 
     fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
-        self.encode_int(v as i64)
+        let v = i64::try_from(v).map_err(|_| Error::IntegerOverflow(v))?;
+        self.encode_int(v)
     }
 
     fn serialize_f32(self, _: f32) -> Result<Self::Ok, Self::Error> {
@@ -229,22 +1130,36 @@ impl<'a, W: Write> Serializer for &'a mut Encoder<W> {
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
-        self.encode_bytes(v)
+        if self.raw {
+            // The bytes are already bencoded, so they're written as is.
+            self.raw = false;
+            self.write(v)
+        } else {
+            self.encode_bytes(v)
+        }
     }
 
     fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        // Nothing is written, which the encoders of dictionaries take as a value to leave out. Anywhere else, eg: in a list, there's nothing to leave out.
+        if self.skip_none && self.entry {
+            return Ok(());
+        }
         Err(Error::Unsupported("None"))
     }
 
-    fn serialize_some<T: ?Sized>(self, v: &T) -> Result<Self::Ok, Self::Error>
+    fn serialize_some<T>(self, v: &T) -> Result<Self::Ok, Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         v.serialize(self)
     }
 
     fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+        match self.unit {
+            UnitRepr::Error => Err(Error::Unsupported("()")),
+            UnitRepr::EmptyDict => self.write(&[DICT_START, TYPE_END]),
+            UnitRepr::EmptyText => self.encode_bytes(&[]),
+        }
     }
 
     fn serialize_unit_struct(
@@ -263,18 +1178,30 @@ impl<'a, W: Write> Serializer for &'a mut Encoder<W> {
         self.serialize_str(variant)
     }
 
-    fn serialize_newtype_struct<T: ?Sized>(
+    fn serialize_newtype_struct<T>(
         self,
-        _: &'static str,
+        name: &'static str,
         v: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        v.serialize(self)
+        if name == RAW_TOKEN {
+            // The wrapped value serializes already bencoded bytes, see `raw::RawValue`.
+            self.raw = true;
+            let res = v.serialize(&mut *self);
+            self.raw = false;
+            res
+        } else if name == ORDERED_TOKEN {
+            // The wrapped map is an `OrderedValue` dictionary, which is only written in its own order if that was asked for.
+            self.ordered = self.document_order;
+            v.serialize(self)
+        } else {
+            v.serialize(self)
+        }
     }
 
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         _: &'static str,
         _: u32,
@@ -282,7 +1209,7 @@ impl<'a, W: Write> Serializer for &'a mut Encoder<W> {
         value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         self.tag(DICT_START)?;
 
@@ -332,19 +1259,22 @@ impl<'a, W: Write> Serializer for &'a mut Encoder<W> {
 
     fn serialize_map(
         self,
-        _: Option<usize>,
+        len: Option<usize>,
     ) -> Result<Self::SerializeMap, Self::Error> {
+        let ordered = mem::take(&mut self.ordered);
         self.tag(DICT_START)?;
-        Ok(MapEncoder::new(self))
+        let mut map = MapEncoder::new(self, len.unwrap_or(0));
+        map.ordered = ordered;
+        Ok(map)
     }
 
     fn serialize_struct(
         self,
-        _: &'static str,
-        _: usize,
+        name: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeStruct, Self::Error> {
         self.tag(DICT_START)?;
-        Ok(MapEncoder::new(self))
+        Ok(StructEncoder::new(self, name, len))
     }
 
     fn serialize_struct_variant(
@@ -352,12 +1282,12 @@ impl<'a, W: Write> Serializer for &'a mut Encoder<W> {
         _: &'static str,
         _: u32,
         variant: &'static str,
-        _: usize,
+        len: usize,
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
         self.tag(DICT_START)?;
         self.serialize_str(variant)?;
         self.tag(DICT_START)?;
-        Ok(MapEncoder::new(self))
+        Ok(MapEncoder::new(self, len))
     }
 }
 
@@ -376,17 +1306,14 @@ impl<'a, W> SeqEncoder<'a, W> {
     }
 }
 
-impl<'a, W: Write> SerializeSeq for SeqEncoder<'a, W> {
+impl<'a, W: BenWrite> SerializeSeq for SeqEncoder<'a, W> {
     type Ok = ();
 
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(
-        &mut self,
-        value: &T,
-    ) -> Result<(), Self::Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         value.serialize(&mut *self.en)
     }
@@ -396,17 +1323,14 @@ impl<'a, W: Write> SerializeSeq for SeqEncoder<'a, W> {
     }
 }
 
-impl<'a, W: Write> SerializeTuple for SeqEncoder<'a, W> {
+impl<'a, W: BenWrite> SerializeTuple for SeqEncoder<'a, W> {
     type Ok = ();
 
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(
-        &mut self,
-        value: &T,
-    ) -> Result<(), Self::Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         value.serialize(&mut *self.en)
     }
@@ -416,17 +1340,14 @@ impl<'a, W: Write> SerializeTuple for SeqEncoder<'a, W> {
     }
 }
 
-impl<'a, W: Write> SerializeTupleStruct for SeqEncoder<'a, W> {
+impl<'a, W: BenWrite> SerializeTupleStruct for SeqEncoder<'a, W> {
     type Ok = ();
 
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(
-        &mut self,
-        value: &T,
-    ) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         value.serialize(&mut *self.en)
     }
@@ -436,17 +1357,14 @@ impl<'a, W: Write> SerializeTupleStruct for SeqEncoder<'a, W> {
     }
 }
 
-impl<'a, W: Write> SerializeTupleVariant for SeqEncoder<'a, W> {
+impl<'a, W: BenWrite> SerializeTupleVariant for SeqEncoder<'a, W> {
     type Ok = ();
 
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(
-        &mut self,
-        value: &T,
-    ) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         value.serialize(&mut *self.en)
     }
@@ -469,30 +1387,126 @@ pub struct MapEncoder<'a, W> {
     encoder: &'a mut Encoder<W>,
     entries: BTreeMap<Vec<u8>, Vec<u8>>,
     current_key: Option<Vec<u8>>,
+    // The number of entries the map was declared with, or `0` if it's unknown.
+    len: usize,
+    // The number and total length of the values encoded so far.
+    values: usize,
+    encoded: usize,
+    // Whether entries are written as they're inserted, instead of being sorted.
+    ordered: bool,
 }
 
-impl<'a, W> MapEncoder<'a, W> {
-    /// Constructs a new map encoder.
+impl<'a, W: BenWrite> MapEncoder<'a, W> {
+    /// Constructs a new map encoder for a map with the given number of entries, or `0` if it's unknown.
     #[inline]
-    fn new(encoder: &'a mut Encoder<W>) -> MapEncoder<'a, W> {
-        Self { encoder, entries: BTreeMap::new(), current_key: None }
+    fn new(encoder: &'a mut Encoder<W>, len: usize) -> MapEncoder<'a, W> {
+        Self {
+            encoder,
+            entries: BTreeMap::new(),
+            current_key: None,
+            len,
+            values: 0,
+            encoded: 0,
+            ordered: false,
+        }
+    }
+
+    /// Encodes a value into a new buffer, sized after the values encoded before it.
+    fn encode_value<T>(&mut self, val: &T) -> Result<Vec<u8>, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let val = self.encoder.encode_nested(val, self.capacity_hint())?;
+        self.values += 1;
+        self.encoded += val.len();
+        Ok(val)
+    }
+
+    /// Writes the entries in sorted order, recycling their buffers.
+    fn write_entries(&mut self) -> Result<(), Error> {
+        for (key, val) in mem::take(&mut self.entries) {
+            // We need to explicitly use `serialize_bytes` for the keys, otherwise it will be serialized as a list of integers, eg: `li4ei9ei0ee`.
+            self.encoder.serialize_bytes(&key)?;
+
+            // We simply write the values to the buffer, otherwise we'll be encoding them **twice**, eg: `3:foo` then becomes `5:3:foo`.
+            self.encoder.write(&val)?;
+            self.encoder.recycle(key);
+            self.encoder.recycle(val);
+        }
+        Ok(())
+    }
+
+    /// Inserts an encoded entry, unless its value is a `None` that's left out, resolving repeated keys by the encoder's [`DuplicateKeys`] policy. Entries of a map in document order are written right away.
+    #[inline]
+    fn insert(&mut self, key: Vec<u8>, val: Vec<u8>) -> Result<(), Error> {
+        if val.is_empty() && self.encoder.skip_none {
+            return Ok(());
+        }
+        if self.ordered {
+            self.encoder.serialize_bytes(&key)?;
+            self.encoder.write(&val)?;
+            self.encoder.recycle(key);
+            self.encoder.recycle(val);
+        } else {
+            match self.entries.entry(key) {
+                Entry::Vacant(e) => {
+                    e.insert(val);
+                }
+                Entry::Occupied(mut e) => {
+                    let prev = e.get_mut();
+                    match self.encoder.duplicates {
+                        DuplicateKeys::First => self.encoder.recycle(val),
+                        DuplicateKeys::Last => {
+                            self.encoder.recycle(mem::replace(prev, val))
+                        }
+                        DuplicateKeys::ConcatLists
+                            if prev.first() == Some(&LIST_START)
+                                && val.first() == Some(&LIST_START) =>
+                        {
+                            // Drop the end of the first list and the start of the second one.
+                            prev.pop();
+                            prev.extend_from_slice(&val[1..]);
+                            self.encoder.recycle(val);
+                        }
+                        _ => {
+                            return Err(Error::DuplicateKey(
+                                String::from_utf8_lossy(e.key()).into_owned(),
+                            ))
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Estimates the length of the next value.
+    ///
+    /// Values of the same map tend to be of similar size, so this is the average length of the values encoded so far, rounded up to a power of two. Until a value has been encoded, small maps are assumed to have small values.
+    fn capacity_hint(&self) -> usize {
+        match self.values {
+            0 if self.len > 0 && self.len <= SMALL_MAP_LEN => SMALL_CAPACITY,
+            0 => 0,
+            n => (self.encoded / n).next_power_of_two(),
+        }
     }
 }
 
-impl<'a, W: Write> SerializeMap for MapEncoder<'a, W> {
+impl<'a, W: BenWrite> SerializeMap for MapEncoder<'a, W> {
     type Ok = ();
 
     type Error = Error;
 
-    fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result<(), Self::Error>
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         if self.current_key.is_some() {
             return Err(Error::KeyWithNoValue);
         }
 
-        let mut parent = Encoder::new(vec![]);
+        let buf = self.encoder.scratch.pop().unwrap_or_default();
+        let mut parent = Encoder::new(buf).with_int_keys(self.encoder.int_keys);
         let en = KeyEncoder::new(&mut parent);
         key.serialize(en)?;
 
@@ -500,94 +1514,76 @@ impl<'a, W: Write> SerializeMap for MapEncoder<'a, W> {
         Ok(())
     }
 
-    fn serialize_value<T: ?Sized>(
-        &mut self,
-        value: &T,
-    ) -> Result<(), Self::Error>
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         // We don't insert serialized keys into the BTreeMap, otherwise the keys will be sorted by their length first, eg: `1:z` will come before `2:aa`.
         let key = self.current_key.take().ok_or(Error::ValueWithNoKey)?;
-        let val = super::encode(&value)?;
+        let val = self.encode_value(value)?;
 
-        self.entries.insert(key, val);
-        Ok(())
+        self.insert(key, val)
     }
 
-    fn end(self) -> Result<Self::Ok, Self::Error> {
-        for (key, val) in self.entries {
-            // We need to explicitly use `serialize_bytes` for the keys, otherwise it will be serialized as a list of integers, eg: `li4ei9ei0ee`.
-            self.encoder.serialize_bytes(&key)?;
-
-            // We simply write the values to the buffer, otherwise we'll be encoding them **twice**, eg: `3:foo` then becomes `5:3:foo`.
-            self.encoder.write(&val)?;
-        }
+    fn end(mut self) -> Result<Self::Ok, Self::Error> {
+        self.write_entries()?;
         self.encoder.tag(TYPE_END)
     }
 }
 
-impl<'a, W: Write> SerializeStruct for MapEncoder<'a, W> {
+impl<'a, W: BenWrite> SerializeStruct for MapEncoder<'a, W> {
     type Ok = ();
 
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(
+    fn serialize_field<T>(
         &mut self,
         key: &'static str,
         val: &T,
     ) -> Result<(), Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         // No need to use the `KeyEncoder` because we know the key is of type string.
         let key = key.as_bytes().to_vec();
-        let val = super::encode(&val)?;
+        let val = self.encode_value(val)?;
 
-        self.entries.insert(key, val);
-        Ok(())
+        self.insert(key, val)
     }
 
-    fn end(self) -> Result<Self::Ok, Self::Error> {
-        for (key, val) in self.entries {
-            self.encoder.serialize_bytes(&key)?;
-            self.encoder.write(&val)?;
-        }
+    fn end(mut self) -> Result<Self::Ok, Self::Error> {
+        self.write_entries()?;
         self.encoder.tag(TYPE_END)
     }
 }
 
-impl<'a, W: Write> SerializeStructVariant for MapEncoder<'a, W> {
+impl<'a, W: BenWrite> SerializeStructVariant for MapEncoder<'a, W> {
     type Ok = ();
 
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(
+    fn serialize_field<T>(
         &mut self,
         key: &'static str,
         val: &T,
     ) -> Result<(), Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         let key = key.as_bytes().to_vec();
-        let val = super::encode(&val)?;
+        let val = self.encode_value(val)?;
 
-        self.entries.insert(key, val);
-        Ok(())
+        self.insert(key, val)
     }
 
-    fn end(self) -> Result<Self::Ok, Self::Error> {
-        for (key, val) in self.entries {
-            self.encoder.serialize_bytes(&key)?;
-            self.encoder.write(&val)?;
-        }
+    fn end(mut self) -> Result<Self::Ok, Self::Error> {
+        self.write_entries()?;
         // Note that we need to write the 'TYPE_END' **twice**, otherwise the outer dictionary won't have a closing delimiter.
         self.encoder.write(&[TYPE_END, TYPE_END])
     }
 }
 
-/// An encoder exclusively used to ensure that map keys are of type string before encoding them.
+/// An encoder exclusively used to ensure that map keys are of type string (or raw bytes) before encoding them.
 #[derive(Debug)]
 struct KeyEncoder<'a, W> {
     en: &'a mut Encoder<W>,
@@ -601,7 +1597,20 @@ impl<'a, W> KeyEncoder<'a, W> {
     }
 }
 
-impl<'a, W: Write> Serializer for KeyEncoder<'a, W> {
+impl<'a, W: BenWrite> KeyEncoder<'a, W> {
+    /// Writes an integer key as its decimal digits, if the encoder coerces integer keys.
+    fn encode_int_key<T>(self, v: T) -> Result<(), Error>
+    where
+        T: std::fmt::Display,
+    {
+        if !self.en.int_keys {
+            return Err(Error::InvalidKeyType);
+        }
+        self.en.write(v.to_string().as_bytes())
+    }
+}
+
+impl<'a, W: BenWrite> serde::Serializer for KeyEncoder<'a, W> {
     type Ok = ();
 
     type Error = Error;
@@ -620,40 +1629,40 @@ impl<'a, W: Write> Serializer for KeyEncoder<'a, W> {
 
     type SerializeStructVariant = Impossible<(), Error>;
 
-    fn serialize_bool(self, _: bool) -> Result<Self::Ok, Self::Error> {
-        Err(Error::InvalidKeyType)
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        self.encode_int_key(v as u8)
     }
 
-    fn serialize_i8(self, _: i8) -> Result<Self::Ok, Self::Error> {
-        Err(Error::InvalidKeyType)
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.encode_int_key(v)
     }
 
-    fn serialize_i16(self, _: i16) -> Result<Self::Ok, Self::Error> {
-        Err(Error::InvalidKeyType)
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.encode_int_key(v)
     }
 
-    fn serialize_i32(self, _: i32) -> Result<Self::Ok, Self::Error> {
-        Err(Error::InvalidKeyType)
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        self.encode_int_key(v)
     }
 
-    fn serialize_i64(self, _: i64) -> Result<Self::Ok, Self::Error> {
-        Err(Error::InvalidKeyType)
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        self.encode_int_key(v)
     }
 
-    fn serialize_u8(self, _: u8) -> Result<Self::Ok, Self::Error> {
-        Err(Error::InvalidKeyType)
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        self.encode_int_key(v)
     }
 
-    fn serialize_u16(self, _: u16) -> Result<Self::Ok, Self::Error> {
-        Err(Error::InvalidKeyType)
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        self.encode_int_key(v)
     }
 
-    fn serialize_u32(self, _: u32) -> Result<Self::Ok, Self::Error> {
-        Err(Error::InvalidKeyType)
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        self.encode_int_key(v)
     }
 
-    fn serialize_u64(self, _: u64) -> Result<Self::Ok, Self::Error> {
-        Err(Error::InvalidKeyType)
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        self.encode_int_key(v)
     }
 
     fn serialize_f32(self, _: f32) -> Result<Self::Ok, Self::Error> {
@@ -673,17 +1682,18 @@ impl<'a, W: Write> Serializer for KeyEncoder<'a, W> {
         self.en.write(v.as_bytes())
     }
 
-    fn serialize_bytes(self, _: &[u8]) -> Result<Self::Ok, Self::Error> {
-        Err(Error::InvalidKeyType)
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        // Keys are byte strings in bencode, so raw bytes (which may not be valid UTF-8) are written as is.
+        self.en.write(v)
     }
 
     fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
         Err(Error::InvalidKeyType)
     }
 
-    fn serialize_some<T: ?Sized>(self, _: &T) -> Result<Self::Ok, Self::Error>
+    fn serialize_some<T>(self, _: &T) -> Result<Self::Ok, Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         Err(Error::InvalidKeyType)
     }
@@ -708,18 +1718,19 @@ impl<'a, W: Write> Serializer for KeyEncoder<'a, W> {
         Err(Error::InvalidKeyType)
     }
 
-    fn serialize_newtype_struct<T: ?Sized>(
+    fn serialize_newtype_struct<T>(
         self,
         _: &'static str,
-        _: &T,
+        v: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        Err(Error::InvalidKeyType)
+        // Newtypes are transparent, so a key wrapped in any number of them is encoded as the key itself. A value's key (see `value::KEY_TOKEN`) is one of them: its bytes are written as is, whether they were serialized as a string or a byte array.
+        v.serialize(self)
     }
 
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         _: &'static str,
         _: u32,
@@ -727,7 +1738,7 @@ impl<'a, W: Write> Serializer for KeyEncoder<'a, W> {
         _: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         Err(Error::InvalidKeyType)
     }
@@ -797,8 +1808,11 @@ mod test {
     use serde::Serialize;
     use serde_bytes::Bytes;
 
+    use super::BenWrite;
     use super::Encoder;
     use super::KeyEncoder;
+    use super::MapEncoder;
+    use crate::Value;
 
     /// Asserts that the result of encoding the value is equal to the given bencoded bytes.
     macro_rules! test_encode {
@@ -809,6 +1823,34 @@ mod test {
         };
     }
 
+    #[test]
+    fn encode_value_with_hook() {
+        use super::EncodeAction;
+        use crate::path::Path;
+        use crate::Value;
+
+        let val: Value = crate::decode(b"d1:ali1ei2ei3ee1:bd1:ci4eee").unwrap();
+        let mut seen = vec![];
+        let mut en = Encoder::new(vec![]);
+        en.encode_value_with(&val, |path: &Path, _: &Value| {
+            seen.push(path.to_string());
+            match seen.last().unwrap().as_str() {
+                "a[1]" => EncodeAction::Drop,
+                "b" => EncodeAction::Replace(Value::List(Default::default())),
+                _ => EncodeAction::Keep,
+            }
+        })
+        .unwrap();
+
+        assert_eq!(en.buf, b"d1:ali1ei3ee1:blee");
+        assert_eq!(seen, ["", "a", "a[0]", "a[1]", "a[2]", "b"]);
+
+        let mut en = Encoder::new(vec![]);
+        en.encode_value_with(&val, |_: &Path, _: &Value| EncodeAction::Drop)
+            .unwrap();
+        assert!(en.buf.is_empty());
+    }
+
     #[test]
     fn encode_int_unsigned() {
         test_encode!(255u8, b"i255e");
@@ -822,6 +1864,15 @@ mod test {
         test_encode!(127isize, b"i127e");
     }
 
+    #[test]
+    fn encode_int_unsigned_out_of_range() {
+        test_encode!(i64::MAX as u64, b"i9223372036854775807e");
+        assert!(matches!(
+            crate::encode(&u64::MAX),
+            Err(super::Error::IntegerOverflow(u64::MAX))
+        ));
+    }
+
     #[test]
     fn encode_int_signed() {
         test_encode!(-127i8, b"i-127e");
@@ -891,6 +1942,28 @@ mod test {
         test_encode!(Person { name: None, age: 50 }, b"");
     }
 
+    #[test]
+    fn skip_none_only_in_entries() {
+        #[derive(Serialize)]
+        struct Wrapper(Option<u8>);
+
+        fn encode<T: Serialize>(val: &T) -> Result<Vec<u8>, super::Error> {
+            let mut en = Encoder::new(vec![]).with_skip_none(true);
+            val.serialize(&mut en).map(|()| en.into_inner())
+        }
+
+        let map = HashMap::from([("a", None), ("b", Some(1))]);
+        assert_eq!(encode(&map).unwrap(), b"d1:bi1ee");
+        let map = HashMap::from([("a", Wrapper(None))]);
+        assert_eq!(encode(&map).unwrap(), b"de");
+
+        // There's nothing to leave a `None` out of anywhere else.
+        assert!(encode(&None::<u8>).is_err());
+        assert!(encode(&Wrapper(None)).is_err());
+        assert!(encode(&vec![Some(1), None]).is_err());
+        assert!(encode(&HashMap::from([("a", vec![None::<u8>])])).is_err());
+    }
+
     #[test]
     fn serialize_unit_struct() {
         #[derive(Debug, Serialize)]
@@ -914,6 +1987,9 @@ mod test {
 
         let en = KeyEncoder::new(&mut parent);
         assert!("foo".to_string().serialize(en).is_ok());
+
+        let en = KeyEncoder::new(&mut parent);
+        assert!(Bytes::new(b"\xff").serialize(en).is_ok());
     }
 
     #[test]
@@ -1032,4 +2108,283 @@ mod test {
         }
         test_encode!(Foo { c: 3, b: 2, a: 1 }, b"d1:ai1e1:bi2e1:ci3ee");
     }
+
+    #[test]
+    fn encode_struct_with_layout() {
+        use super::StructLayout;
+
+        #[derive(Serialize)]
+        struct Peer {
+            ip: &'static str,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            id: Option<&'static str>,
+            port: u16,
+        }
+
+        #[derive(Serialize)]
+        struct Reply {
+            peers: Vec<Peer>,
+            interval: u32,
+        }
+
+        let reply = Reply {
+            peers: vec![
+                Peer { ip: "a", id: Some("x"), port: 1 },
+                Peer { ip: "b", id: None, port: 2 },
+            ],
+            interval: 1800,
+        };
+        let expected: &[u8] =
+            b"d8:intervali1800e5:peersld2:id1:x2:ip1:a4:porti1eed2:ip1:b4:porti2eeee";
+
+        let reply_layout = StructLayout::new("Reply", &["peers", "interval"]);
+        let peer_layout = StructLayout::new("Peer", &["ip", "id", "port"]);
+        assert!(!reply_layout.is_sorted());
+        assert!(!peer_layout.is_sorted());
+        assert_eq!(
+            peer_layout.sorted_fields().collect::<Vec<_>>(),
+            ["id", "ip", "port"]
+        );
+
+        let mut en = Encoder::new(vec![])
+            .with_layout(reply_layout)
+            .with_layout(peer_layout);
+        reply.serialize(&mut en).unwrap();
+        assert_eq!(en.buf, expected);
+
+        // Without layouts, the fields are sorted on every call.
+        test_encode!(reply, expected);
+    }
+
+    #[test]
+    fn encode_sorted_struct_with_layout() {
+        use super::StructLayout;
+
+        #[derive(Serialize)]
+        struct Info {
+            length: u64,
+            name: &'static str,
+        }
+
+        let layout = StructLayout::new("Info", &["length", "name"]);
+        assert!(layout.is_sorted());
+
+        let mut en = Encoder::new(vec![]).with_layout(layout);
+        Info { length: 5, name: "foo" }.serialize(&mut en).unwrap();
+        assert_eq!(en.buf, b"d6:lengthi5e4:name3:fooe");
+
+        let layout = StructLayout::new("Info", &["length"]);
+        let mut en = Encoder::new(vec![]).with_layout(layout);
+        assert!(Info { length: 5, name: "foo" }.serialize(&mut en).is_err());
+    }
+
+    #[test]
+    fn encode_structs_sharing_a_layout_name() {
+        use super::StructLayout;
+
+        mod tracker {
+            #[derive(serde::Serialize)]
+            pub struct Peer {
+                pub port: u16,
+                pub ip: &'static str,
+            }
+        }
+
+        mod dht {
+            #[derive(serde::Serialize)]
+            pub struct Peer {
+                pub id: &'static str,
+            }
+
+            #[derive(serde::Serialize)]
+            #[serde(rename = "DhtPeer")]
+            pub struct RenamedPeer {
+                pub id: &'static str,
+            }
+        }
+
+        let layout = StructLayout::new("Peer", &["port", "ip"]);
+        let mut en = Encoder::new(vec![]).with_layout(layout);
+        tracker::Peer { port: 1, ip: "a" }.serialize(&mut en).unwrap();
+        assert_eq!(en.buf, b"d2:ip1:a4:porti1ee");
+
+        // Layouts are looked up by name alone, so the other `Peer` is held to the same layout.
+        let err = dht::Peer { id: "x" }.serialize(&mut en).unwrap_err();
+        assert!(err.to_string().contains("missing from the layout of `Peer`"));
+
+        let mut en = Encoder::new(vec![])
+            .with_layout(StructLayout::new("Peer", &["port", "ip"]));
+        dht::RenamedPeer { id: "x" }.serialize(&mut en).unwrap();
+        assert_eq!(en.buf, b"d2:id1:xe");
+    }
+
+    #[test]
+    fn fields_sorted() {
+        use super::fields_sorted;
+
+        assert!(fields_sorted(&[]));
+        assert!(fields_sorted(&["a"]));
+        assert!(fields_sorted(&["a", "aa", "b", "peer id", "peers"]));
+        assert!(!fields_sorted(&["aa", "a"]));
+        assert!(!fields_sorted(&["b", "a"]));
+        assert!(!fields_sorted(&["Z", "a", "a"]));
+    }
+
+    #[test]
+    fn map_capacity_hint() {
+        let mut en = Encoder::new(vec![]);
+
+        let mut map = MapEncoder::new(&mut en, 3);
+        assert_eq!(map.capacity_hint(), 16);
+        assert_eq!(map.encode_value(&Bytes::new(&[0; 20])).unwrap().len(), 23);
+        assert_eq!(map.encode_value(&1995).unwrap().len(), 6);
+        assert_eq!(map.capacity_hint(), 16);
+        assert_eq!(map.encode_value(&Bytes::new(&[0; 90])).unwrap().len(), 93);
+        assert_eq!(map.capacity_hint(), 64);
+
+        assert_eq!(MapEncoder::new(&mut en, 0).capacity_hint(), 0);
+        assert_eq!(MapEncoder::new(&mut en, 100).capacity_hint(), 0);
+    }
+
+    #[test]
+    fn encode_digits() {
+        test_encode!(0, b"i0e");
+        test_encode!(i64::MIN, b"i-9223372036854775808e");
+        test_encode!(i64::MAX, b"i9223372036854775807e");
+        test_encode!(Bytes::new(&[0; 10]), b"10:\0\0\0\0\0\0\0\0\0\0");
+    }
+
+    #[test]
+    fn encode_to_custom_sinks() {
+        #[derive(Default)]
+        struct Sink {
+            bytes: Vec<u8>,
+            reserved: usize,
+        }
+
+        impl BenWrite for Sink {
+            fn write_all(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+                self.bytes.extend_from_slice(bytes);
+                Ok(())
+            }
+
+            fn reserve(&mut self, additional: usize) {
+                self.reserved += additional;
+            }
+        }
+
+        let mut en = Encoder::new(Sink::default());
+        ("foo", Bytes::new(&[0; 20])).serialize(&mut en).unwrap();
+        let sink = en.into_inner();
+        assert_eq!(sink.bytes.len(), 30);
+        assert_eq!(sink.reserved, 28);
+
+        let mut buf = [0; 6];
+        let mut slice = &mut buf[..];
+        "foo".serialize(&mut Encoder::new(&mut slice)).unwrap();
+        assert_eq!(slice.len(), 1);
+        assert!(1.serialize(&mut Encoder::new(&mut slice)).is_err());
+        assert_eq!(buf, *b"3:foo\0");
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn encode_into_heapless_vec() {
+        let mut en = Encoder::new(heapless::Vec::<u8, 6>::new());
+        "foo".serialize(&mut en).unwrap();
+        assert!(1.serialize(&mut en).is_err());
+        assert_eq!(en.into_inner(), b"3:foo");
+    }
+
+    #[test]
+    fn encode_reuses_buffers() {
+        use super::EncoderPool;
+
+        let map = HashMap::from([("b", vec![1, 2]), ("a", vec![3])]);
+        let mut en = Encoder::new(vec![]);
+        assert_eq!(en.encode(&map).unwrap(), b"d1:ali3ee1:bli1ei2eee");
+        // The buffers of both keys and both values are kept.
+        assert_eq!(en.scratch.len(), 4);
+        assert_eq!(en.encode(&map).unwrap(), b"d1:ali3ee1:bli1ei2eee");
+        assert_eq!(en.scratch.len(), 4);
+        assert!(en.scratch.iter().all(Vec::is_empty));
+
+        let pool = EncoderPool::new(Encoder::new(vec![]).with_int_keys(true));
+        {
+            let mut a = pool.get();
+            let mut b = pool.get();
+            assert_eq!(
+                a.encode(&HashMap::from([(1, 2)])).unwrap(),
+                b"d1:1i2ee"
+            );
+            assert_eq!(b.encode(&1).unwrap(), b"i1e");
+        }
+        assert_eq!(pool.idle(), 2);
+        assert_eq!(pool.get().encode(&2).unwrap(), b"i2e");
+        assert_eq!(pool.idle(), 2);
+    }
+
+    #[test]
+    fn encode_drops_large_buffers() {
+        use super::{EncoderPool, MAX_RETAINED, MAX_SCRATCH_CAPACITY};
+
+        let large =
+            HashMap::from([("a", "x".repeat(MAX_SCRATCH_CAPACITY + 1))]);
+        let mut en = Encoder::new(vec![]);
+        en.encode(&large).unwrap();
+        // Only the key's buffer is kept.
+        assert_eq!(en.scratch.len(), 1);
+        assert!(en
+            .scratch
+            .iter()
+            .all(|buf| buf.capacity() <= MAX_SCRATCH_CAPACITY));
+
+        let pool = EncoderPool::new(Encoder::new(vec![]));
+        pool.get().encode(&"x".repeat(MAX_RETAINED + 1)).unwrap();
+        assert_eq!(pool.idle(), 0);
+        pool.get().encode(&1).unwrap();
+        assert_eq!(pool.idle(), 1);
+    }
+
+    #[test]
+    fn duplicate_keys_policy() {
+        use super::{DuplicateKeys, Error};
+
+        #[derive(Serialize)]
+        struct Entries(
+            #[serde(with = "crate::with::entries")] Vec<(&'static str, Value)>,
+        );
+
+        let entries = Entries(vec![
+            ("b", Value::List(vec![Value::Int(1)].into())),
+            ("a", Value::Int(0)),
+            ("b", Value::List(vec![Value::Int(2), Value::Int(3)].into())),
+        ]);
+        let encode = |policy| {
+            Encoder::new(vec![])
+                .with_duplicate_keys(policy)
+                .encode(&entries)
+                .map(<[u8]>::to_vec)
+        };
+
+        assert_eq!(encode(DuplicateKeys::First).unwrap(), b"d1:ai0e1:bli1eee");
+        assert_eq!(
+            encode(DuplicateKeys::Last).unwrap(),
+            b"d1:ai0e1:bli2ei3eee"
+        );
+        assert_eq!(
+            encode(DuplicateKeys::ConcatLists).unwrap(),
+            b"d1:ai0e1:bli1ei2ei3eee"
+        );
+        assert!(matches!(
+            encode(DuplicateKeys::Error),
+            Err(Error::DuplicateKey(key)) if key == "b"
+        ));
+
+        let entries = Entries(vec![("a", Value::Int(0)), ("a", Value::Int(1))]);
+        assert!(Encoder::new(vec![])
+            .with_duplicate_keys(DuplicateKeys::ConcatLists)
+            .encode(&entries)
+            .is_err());
+    }
 }
@@ -0,0 +1,194 @@
+//! Precomputed struct layouts, and the encoder of struct fields that follows them.
+
+use std::sync::Arc;
+
+use serde::ser::SerializeStruct;
+
+use super::BenWrite;
+use super::Encoder;
+use super::Error;
+use super::MapEncoder;
+use crate::spec::cmp_keys;
+use crate::spec::TYPE_END;
+
+/// An encoder used to encode the fields of a struct, following its [`StructLayout`] if one was registered.
+///
+/// The encoder names it as its `SerializeStruct` type, so it has to be `pub`, but this module is private, so it's not part of the public API.
+#[derive(Debug)]
+pub struct StructEncoder<'a, W> {
+    map: MapEncoder<'a, W>,
+    layout: Option<StructLayout>,
+    cursor: usize,
+    slots: Vec<Option<Vec<u8>>>,
+}
+
+impl<'a, W: BenWrite> StructEncoder<'a, W> {
+    /// Constructs a new struct encoder for the struct with the given name.
+    #[inline]
+    pub(super) fn new(
+        encoder: &'a mut Encoder<W>,
+        name: &str,
+        len: usize,
+    ) -> StructEncoder<'a, W> {
+        let layout = encoder.layout(name).cloned();
+        let slots = match layout {
+            Some(ref l) if !l.is_sorted() => vec![None; l.fields.len()],
+            _ => vec![],
+        };
+        Self { map: MapEncoder::new(encoder, len), layout, cursor: 0, slots }
+    }
+}
+
+impl<'a, W: BenWrite> SerializeStruct for StructEncoder<'a, W> {
+    type Ok = ();
+
+    type Error = Error;
+
+    fn serialize_field<T>(
+        &mut self,
+        key: &'static str,
+        val: &T,
+    ) -> Result<(), Self::Error>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        let Some(ref layout) = self.layout else {
+            return SerializeStruct::serialize_field(&mut self.map, key, val);
+        };
+
+        // Fields are serialized in the order they're declared in, so the
+        // next one is always after the previous one.
+        let i = layout.fields[self.cursor..]
+            .iter()
+            .position(|field| *field == key)
+            .map(|i| self.cursor + i)
+            .ok_or_else(|| {
+                Error::Serialize(format!(
+                    "field `{}` is missing from the layout of `{}`, or is out of order",
+                    key, layout.name
+                ))
+            })?;
+        self.cursor = i + 1;
+
+        if layout.is_sorted() && !self.map.encoder.skip_none {
+            self.map.encoder.encode_bytes(key.as_bytes())?;
+            return val.serialize(&mut *self.map.encoder);
+        }
+
+        // The value is buffered, as a `None` that's left out has to take its key with it.
+        let val = self.map.encode_value(val)?;
+        if val.is_empty() && self.map.encoder.skip_none {
+            return Ok(());
+        }
+        if layout.is_sorted() {
+            self.map.encoder.encode_bytes(key.as_bytes())?;
+            self.map.encoder.write(&val)
+        } else {
+            self.slots[i] = Some(val);
+            Ok(())
+        }
+    }
+
+    fn end(mut self) -> Result<Self::Ok, Self::Error> {
+        let Some(ref layout) = self.layout else {
+            return SerializeStruct::end(self.map);
+        };
+
+        if let Some(ref order) = layout.order {
+            for &i in order.iter() {
+                if let Some(val) = self.slots[i].take() {
+                    self.map
+                        .encoder
+                        .encode_bytes(layout.fields[i].as_bytes())?;
+                    self.map.encoder.write(&val)?;
+                    self.map.encoder.recycle(val);
+                }
+            }
+        }
+        self.map.encoder.tag(TYPE_END)
+    }
+}
+
+/// The precomputed encoding order of a struct's fields, so the encoder doesn't have to sort them on every call.
+///
+/// Bencode requires dictionary keys to be sorted, so the encoder normally buffers and sorts the fields of every struct it encodes. Once a layout is registered with [`Encoder::with_layout`], the fields of a struct whose fields are declared in sorted order are written straight to the buffer, and the fields of any other struct are put in their precomputed place.
+///
+/// The fields must be given in the order they're declared in, using their serialized names, eg: after `#[serde(rename)]`. Encoding fails if a struct serializes a field that its layout doesn't have. Use [`fields_sorted`] to check at compile time that a struct's fields are declared in sorted order.
+///
+/// A layout applies to every struct with its name, as the name serde passes to the encoder is all there is to tell structs apart - it doesn't include the struct's module or type. If two structs share a name, eg: a `Peer` in two modules, only register a layout for one of them if their fields are the same, or give one of them a distinct name with `#[serde(rename)]`. Otherwise, encoding the other struct fails as its fields are missing from the layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructLayout {
+    name: &'static str,
+    fields: &'static [&'static str],
+    // The indices of the fields in encoding order, or `None` if they're declared in that order already.
+    order: Option<Arc<[usize]>>,
+}
+
+impl StructLayout {
+    /// Constructs the layout of the struct with the given name, from its serialized field names in the order they're declared in.
+    pub fn new(
+        name: &'static str,
+        fields: &'static [&'static str],
+    ) -> StructLayout {
+        let order = (!fields_sorted(fields)).then(|| {
+            let mut order: Vec<usize> = (0..fields.len()).collect();
+            order.sort_by_key(|&i| fields[i].as_bytes());
+            order.into()
+        });
+        Self { name, fields, order }
+    }
+
+    /// Gets the name of the struct.
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Gets the field names of the struct, in the order they're declared in.
+    #[inline]
+    pub fn fields(&self) -> &'static [&'static str] {
+        self.fields
+    }
+
+    /// Checks if the fields are declared in the order they're encoded in, so they can be written without being buffered.
+    #[inline]
+    pub fn is_sorted(&self) -> bool {
+        self.order.is_none()
+    }
+
+    /// Returns an iterator over the field names, in the order they're encoded in.
+    pub fn sorted_fields(&self) -> impl Iterator<Item = &'static str> + '_ {
+        let fields = self.fields;
+        (0..fields.len()).map(move |i| match self.order {
+            Some(ref order) => fields[order[i]],
+            None => fields[i],
+        })
+    }
+}
+
+/// Checks if the given field names are in the order bencode requires dictionary keys to be in - strictly ascending, byte by byte.
+///
+/// This is a `const fn`, so it can check the fields of a struct at compile time.
+///
+/// # Examples
+///
+/// ```
+/// use bende::en::fields_sorted;
+///
+/// const _: () = assert!(fields_sorted(&["ip", "peer id", "port"]));
+///
+/// assert!(!fields_sorted(&["port", "ip"]));
+/// assert!(!fields_sorted(&["ip", "ip"]));
+/// ```
+pub const fn fields_sorted(fields: &[&str]) -> bool {
+    let mut i = 1;
+    while i < fields.len() {
+        let (a, b) = (fields[i - 1].as_bytes(), fields[i].as_bytes());
+        // Equal names are not allowed either.
+        if !cmp_keys(a, b).is_lt() {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
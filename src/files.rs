@@ -0,0 +1,281 @@
+//! Safety checks of the file lists of decoded torrents.
+//!
+//! The file names of a torrent come from whoever made it, and a downloader that joins them onto its download directory as is can be made to write anywhere. The functions included in this module are:
+//!
+//! * [`check_files`] - Finds the files of a torrent whose paths are unsafe to create, reporting a [`Finding`] for each problem.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use super::path::Path;
+use super::value::Dict;
+use super::Value;
+
+/// The maximum length of a single path component, in bytes, that common file systems allow.
+pub const MAX_COMPONENT_LEN: usize = 255;
+
+/// Names that refer to devices on Windows, regardless of their extension or case.
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6",
+    "COM7", "COM8", "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6",
+    "LPT7", "LPT8", "LPT9",
+];
+
+/// What makes the path of a file unsafe to create.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FindingKind {
+    /// A component can point outside of the download directory, eg: `..`, or one with a path separator in it.
+    Traversal,
+    /// A component is a reserved name or has reserved characters on Windows, eg: `CON.txt` or `a:b`.
+    ReservedName,
+    /// A component is longer than [`MAX_COMPONENT_LEN`].
+    Overlong,
+    /// The file ends up at the same path as an earlier one, ignoring ASCII case.
+    Duplicate {
+        /// The path of the earlier file's `path` list.
+        of: Path,
+    },
+    /// The path is missing, empty or isn't a list of byte arrays.
+    Malformed,
+}
+
+/// A problem with the path of a file in a torrent.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Finding {
+    /// The path of the offending value, eg: `info.files[2].path[0]`.
+    pub path: Path,
+    /// What's wrong with the value.
+    pub kind: FindingKind,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            FindingKind::Traversal => {
+                write!(f, "{} escapes the download directory", self.path)
+            }
+            FindingKind::ReservedName => {
+                write!(f, "{} is reserved on Windows", self.path)
+            }
+            FindingKind::Overlong => write!(
+                f,
+                "{} is longer than {} bytes",
+                self.path, MAX_COMPONENT_LEN
+            ),
+            FindingKind::Duplicate { ref of } => {
+                write!(f, "{} is a duplicate of {}", self.path, of)
+            }
+            FindingKind::Malformed => {
+                write!(f, "{} is not a valid file path", self.path)
+            }
+        }
+    }
+}
+
+/// Checks the paths of the files of a torrent, or of its `info` dictionary if it's given directly, returning every problem found in the order of the files.
+///
+/// The `name` of the torrent is checked as the directory the files are created in, or as the file itself in a single-file torrent. Each component is checked for:
+///
+/// * Traversal - It's empty, `.` or `..`, or contains a `/`, `\` or NUL byte, which also covers absolute paths.
+/// * Reserved names - It's a device name on Windows, eg: `NUL` or `com1.txt`, contains one of `<>:"|?*` or a control character, or ends with a dot or a space.
+/// * Length - It's longer than [`MAX_COMPONENT_LEN`] bytes.
+///
+/// Files whose paths are equal, ignoring ASCII case, are reported as duplicates of the first one, as they'd overwrite each other on case-insensitive file systems.
+///
+/// # Examples
+///
+/// ```
+/// use bende::files::{check_files, FindingKind};
+/// use bende::Value;
+///
+/// let torrent: Value = bende::decode(b"d4:infod5:filesld6:lengthi1e4:pathl2:..6:passwdeed6:lengthi1e4:pathl5:a.txteed6:lengthi1e4:pathl5:A.TXTeee4:name4:docsee").unwrap();
+///
+/// let findings = check_files(&torrent);
+/// assert_eq!(findings.len(), 2);
+/// assert_eq!(findings[0].path.to_string(), "info.files[0].path[0]");
+/// assert_eq!(findings[0].kind, FindingKind::Traversal);
+/// assert_eq!(findings[1].path.to_string(), "info.files[2].path");
+/// ```
+pub fn check_files(torrent: &Value) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let (info, mut path) = match torrent.as_dict() {
+        Some(dict) => match dict.get("info") {
+            Some(Value::Dict(info)) => (info, Path::root().join("info")),
+            _ => (dict, Path::root()),
+        },
+        None => {
+            findings.push(Finding {
+                path: Path::root(),
+                kind: FindingKind::Malformed,
+            });
+            return findings;
+        }
+    };
+
+    let name = info.get("name").and_then(Value::as_bytes);
+    path.push("name");
+    match name {
+        Some(name) => push_findings(name, &path, &mut findings),
+        None => findings
+            .push(Finding { path: path.clone(), kind: FindingKind::Malformed }),
+    }
+    path.pop();
+
+    if let Some(files) = info.get("files") {
+        check_file_list(files, &mut path, &mut findings);
+    }
+    findings
+}
+
+/// Checks the `files` list of a multi-file torrent, where the path is that of its `info` dictionary.
+fn check_file_list(
+    files: &Value,
+    path: &mut Path,
+    findings: &mut Vec<Finding>,
+) {
+    path.push("files");
+    let files = match files.as_list() {
+        Some(files) => files,
+        None => {
+            findings.push(Finding {
+                path: path.clone(),
+                kind: FindingKind::Malformed,
+            });
+            path.pop();
+            return;
+        }
+    };
+
+    // The lowercased target paths seen so far, alongside the first file at each of them.
+    let mut seen: HashMap<Vec<Vec<u8>>, Path> = HashMap::new();
+    for (i, file) in files.iter().enumerate() {
+        path.push(i);
+        path.push("path");
+        let components = file
+            .as_dict()
+            .and_then(|file: &Dict| file.get("path"))
+            .and_then(Value::as_list)
+            .filter(|components| !components.is_empty())
+            .and_then(|components| {
+                components
+                    .iter()
+                    .map(Value::as_bytes)
+                    .collect::<Option<Vec<_>>>()
+            });
+
+        match components {
+            Some(components) => {
+                for (j, component) in components.iter().enumerate() {
+                    push_findings(component, &path.join(j), findings);
+                }
+                let target =
+                    components.iter().map(|c| c.to_ascii_lowercase()).collect();
+                if let Some(of) = seen.get(&target) {
+                    let kind = FindingKind::Duplicate { of: of.clone() };
+                    findings.push(Finding { path: path.clone(), kind });
+                } else {
+                    seen.insert(target, path.clone());
+                }
+            }
+            None => findings.push(Finding {
+                path: path.clone(),
+                kind: FindingKind::Malformed,
+            }),
+        }
+        path.pop();
+        path.pop();
+    }
+    path.pop();
+}
+
+/// Checks a single path component, returning its first problem.
+///
+/// This is also what [`path_list::safe`](crate::with::path_list::safe) checks decoded components with.
+pub(crate) fn check_component(component: &[u8]) -> Option<FindingKind> {
+    if matches!(component, b"" | b"." | b"..")
+        || component.iter().any(|b| matches!(b, b'/' | b'\\' | b'\0'))
+    {
+        Some(FindingKind::Traversal)
+    } else if is_reserved(component) {
+        Some(FindingKind::ReservedName)
+    } else if component.len() > MAX_COMPONENT_LEN {
+        Some(FindingKind::Overlong)
+    } else {
+        None
+    }
+}
+
+/// Checks a single path component, reporting its first problem at the given path.
+fn push_findings(component: &[u8], path: &Path, findings: &mut Vec<Finding>) {
+    if let Some(kind) = check_component(component) {
+        findings.push(Finding { path: path.clone(), kind });
+    }
+}
+
+/// Checks if a component can't be created on Windows.
+fn is_reserved(component: &[u8]) -> bool {
+    let stem = component.split(|&b| b == b'.').next().unwrap_or_default();
+    RESERVED_NAMES.iter().any(|name| stem.eq_ignore_ascii_case(name.as_bytes()))
+        || component.iter().any(|&b| b < 0x20 || b"<>:\"|?*".contains(&b))
+        || matches!(component.last(), Some(b'.' | b' '))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_files, Finding, FindingKind};
+    use crate::Value;
+
+    fn findings(src: &[u8]) -> Vec<(String, FindingKind)> {
+        let val: Value = crate::decode(src).unwrap();
+        check_files(&val)
+            .into_iter()
+            .map(|Finding { path, kind }| (path.to_string(), kind))
+            .collect()
+    }
+
+    #[test]
+    fn check_files_components() {
+        let long = "a".repeat(256);
+        let src = format!(
+            "d5:filesld4:pathl3:aux4:a.b.eed4:pathl{}:{}eed4:pathl3:a\\bee\
+             d4:pathleed4:pathi1eee4:name3:dire",
+            long.len(),
+            long
+        );
+
+        assert_eq!(
+            findings(src.as_bytes()),
+            [
+                ("files[0].path[0]".into(), FindingKind::ReservedName),
+                ("files[0].path[1]".into(), FindingKind::ReservedName),
+                ("files[1].path[0]".into(), FindingKind::Overlong),
+                ("files[2].path[0]".into(), FindingKind::Traversal),
+                ("files[3].path".into(), FindingKind::Malformed),
+                ("files[4].path".into(), FindingKind::Malformed),
+            ]
+        );
+        assert!(findings(b"d6:lengthi1e4:name5:a.txte").is_empty());
+        assert_eq!(
+            findings(b"d4:infod6:lengthi1e4:name2:..ee"),
+            [("info.name".into(), FindingKind::Traversal)]
+        );
+    }
+
+    #[test]
+    fn check_files_duplicates() {
+        let src = b"d5:filesld4:pathl1:a1:beed4:pathl1:A1:Beed4:pathl1:a1:beee\
+                    4:name1:xe";
+        let of: crate::path::Path = "files[0].path".parse().unwrap();
+
+        assert_eq!(
+            findings(src),
+            [
+                (
+                    "files[1].path".into(),
+                    FindingKind::Duplicate { of: of.clone() }
+                ),
+                ("files[2].path".into(), FindingKind::Duplicate { of }),
+            ]
+        );
+    }
+}
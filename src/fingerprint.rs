@@ -0,0 +1,124 @@
+//! Content fingerprints of torrents, for deduplication.
+
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
+
+use super::en::Encoder;
+use super::en::IoWriter;
+use super::value::Dict;
+use super::value::List;
+use super::value::NormalizeConfig;
+use super::Value;
+
+/// A prefix hashed before a file list, so a fingerprint of a file list can't collide with the fingerprint of an `info` dictionary.
+const FILES_DOMAIN: &[u8] = b"bende-files:";
+
+/// A prefix hashed before any other value.
+const VALUE_DOMAIN: &[u8] = b"bende-value:";
+
+/// Computes a stable identity of a torrent's content, which doesn't change when the torrent is re-encoded or only differs cosmetically, eg: in its trackers or comment.
+///
+/// * If the value has an `info` dictionary, the fingerprint is the SHA-256 hash of its canonical encoding, which matches the infohash of a v2 torrent.
+/// * Otherwise, if the value has a `files` list or a `length`, like an `info` dictionary without its pieces, the fingerprint is a hash of its files' paths and sizes, in sorted order.
+/// * Otherwise, the fingerprint is a hash of the value, normalized like a torrent. See [`NormalizeConfig::torrent`].
+///
+/// # Examples
+///
+/// ```
+/// let a: bende::Value = bende::decode(b"d8:announce1:a4:infod6:lengthi1e4:name1:xee").unwrap();
+/// let b: bende::Value = bende::decode(b"d7:comment1:c4:infod6:lengthi1e4:name1:xee").unwrap();
+///
+/// assert_eq!(bende::fingerprint(&a), bende::fingerprint(&b));
+///
+/// let files: bende::Value = bende::decode(b"d5:filesld6:lengthi1e4:pathl1:beed6:lengthi2e4:pathl1:aeeee").unwrap();
+/// let reordered: bende::Value = bende::decode(b"d5:filesld6:lengthi2e4:pathl1:aeed6:lengthi1e4:pathl1:beeee").unwrap();
+///
+/// assert_eq!(bende::fingerprint(&files), bende::fingerprint(&reordered));
+/// ```
+pub fn fingerprint(val: &Value) -> [u8; 32] {
+    let dict = val.as_dict();
+    let mut hasher = Sha256::new();
+
+    if let Some(info @ Value::Dict(_)) = dict.and_then(|d| d.get("info")) {
+        // The info dictionary can be large, so it's hashed as it's encoded.
+        info.serialize(&mut Encoder::new(IoWriter::new(&mut hasher)))
+            .expect("a value should always be encodable");
+    } else if let Some(files) = dict.and_then(files) {
+        hasher.update(FILES_DOMAIN);
+        hasher.update(encode(&files.into()));
+    } else {
+        hasher.update(VALUE_DOMAIN);
+        hasher.update(encode(&val.normalized(&NormalizeConfig::torrent())));
+    }
+    hasher.finalize().into()
+}
+
+/// Projects the files of a torrent, or of its `info` dictionary, onto a sorted list of `[path, length]` pairs.
+fn files(dict: &Dict) -> Option<List> {
+    let mut files: List = match (dict.get("files"), dict.get("length")) {
+        (Some(Value::List(files)), _) => files
+            .iter()
+            .map(|file| {
+                let file = file.as_dict();
+                let path = file.and_then(|f| f.get("path"));
+                let len = file.and_then(|f| f.get("length"));
+                file_entry(path.cloned(), len.cloned())
+            })
+            .collect(),
+        (_, Some(len)) => {
+            let name = dict
+                .get("name")
+                .cloned()
+                .map(|name| Value::List(List::from([name])));
+            List::from([file_entry(name, Some(len.clone()))])
+        }
+        _ => return None,
+    };
+    files.sort_by_cached_key(encode);
+    Some(files)
+}
+
+/// Constructs the `[path, length]` pair of a file, where missing fields are empty lists.
+fn file_entry(path: Option<Value>, len: Option<Value>) -> Value {
+    let missing = || Value::List(List::new());
+    Value::List(List::from([
+        path.unwrap_or_else(missing),
+        len.unwrap_or_else(missing),
+    ]))
+}
+
+/// Encodes a value, which can't fail as every value is representable.
+fn encode(val: &Value) -> Vec<u8> {
+    super::encode(val).expect("a value should always be encodable")
+}
+
+#[cfg(test)]
+mod test {
+    use sha2::{Digest, Sha256};
+
+    use super::fingerprint;
+    use crate::Value;
+
+    #[test]
+    fn fingerprint_info_hash() {
+        let info = b"d6:lengthi1995e4:name3:fooe";
+        let torrent = [&b"d4:info"[..], info, b"e"].concat();
+        let val: Value = crate::decode(&torrent).unwrap();
+
+        assert_eq!(fingerprint(&val), <[u8; 32]>::from(Sha256::digest(info)));
+    }
+
+    #[test]
+    fn fingerprint_files() {
+        let single: Value = crate::decode(b"d6:lengthi1e4:name1:ae").unwrap();
+        let multi: Value =
+            crate::decode(b"d5:filesld6:lengthi1e4:pathl1:aeeee").unwrap();
+        let other: Value =
+            crate::decode(b"d5:filesld6:lengthi2e4:pathl1:aeeee").unwrap();
+
+        assert_eq!(fingerprint(&single), fingerprint(&multi));
+        assert_ne!(fingerprint(&multi), fingerprint(&other));
+        assert_ne!(fingerprint(&single), fingerprint(&Value::Int(1)));
+    }
+}
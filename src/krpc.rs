@@ -0,0 +1,432 @@
+//! Helpers for the KRPC messages of the BitTorrent DHT, at the boundary of the UDP datagrams that carry them.
+//!
+//! Every KRPC message is a single dictionary in a single datagram, with its transaction ID under the `t` key. Datagrams come from anyone on the network, so they're checked for the shape of a message before they're decoded, and the ways they can fail are told apart for rate-limiting decisions. The items included in this module are:
+//!
+//! * [`encode_datagram`] - Encodes a message, checking that it fits in a datagram and has a valid transaction ID.
+//! * [`decode_datagram`] - Checks a datagram, and decodes the message in it.
+//! * [`txn_id`] - Checks a datagram, and gets the transaction ID of the message in it without decoding it.
+//! * [`TxnMap`] - Matches responses to the queries they answer by transaction ID, handing back queries that time out so they can be retried or failed.
+//! * [`DatagramError`] - An error returned when a datagram or message is rejected.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::mem;
+use std::time::Duration;
+use std::time::Instant;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::de;
+use super::en;
+use super::path::Path;
+use super::spec::DICT_START;
+
+/// The practical maximum length of a datagram, in bytes, which stays below the MTU of common links once the IP and UDP headers are added.
+pub const MAX_DATAGRAM_LEN: usize = 1400;
+
+/// The maximum length of a transaction ID, in bytes. IDs are usually 2 bytes long, so this leaves room for implementations that use longer ones.
+pub const MAX_TXN_ID_LEN: usize = 16;
+
+/// An error returned when a datagram, or a message that's about to be sent in one, is rejected.
+///
+/// The errors for which [`is_malformed`](DatagramError::is_malformed) returns `true` are ones no working implementation sends, so their senders can be rate-limited or ignored. A well-formed message that can't be decoded into the expected type, eg: a query with a method that isn't known yet, is reported as `Decode` instead, alongside its transaction ID, so an error can be sent back. Messages that are about to be sent fail with `Encode` or `Unsendable`, which are never malformed.
+#[derive(Debug)]
+pub enum DatagramError {
+    /// The datagram is longer than [`MAX_DATAGRAM_LEN`].
+    TooLong {
+        /// The length of the datagram.
+        len: usize,
+    },
+    /// The datagram isn't a single, well-formed bencode value.
+    Malformed(de::Error),
+    /// The datagram is a single value, but not a dictionary.
+    NotDict,
+    /// The message has no `t` key.
+    MissingTxnId,
+    /// The transaction ID isn't a byte array of 1 to [`MAX_TXN_ID_LEN`] bytes.
+    InvalidTxnId,
+    /// The message is well-formed, but can't be decoded into the expected type.
+    Decode {
+        /// The transaction ID of the message.
+        txn_id: Vec<u8>,
+        /// Why the message can't be decoded.
+        error: de::Error,
+    },
+    /// The message can't be encoded.
+    Encode(en::Error),
+    /// The message was encoded, but its datagram would be rejected by its receiver, for the reason inside, eg: `TooLong`.
+    Unsendable(Box<DatagramError>),
+}
+
+impl DatagramError {
+    /// Checks if the datagram is malformed in a way that no working implementation would send, and not only a message that isn't understood.
+    pub fn is_malformed(&self) -> bool {
+        !matches!(
+            self,
+            DatagramError::Decode { .. }
+                | DatagramError::Encode(_)
+                | DatagramError::Unsendable(_)
+        )
+    }
+}
+
+impl fmt::Display for DatagramError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DatagramError::TooLong { len } => write!(
+                f,
+                "datagram is {} bytes long, at most {} are allowed",
+                len, MAX_DATAGRAM_LEN
+            ),
+            DatagramError::Malformed(e) => {
+                write!(f, "malformed datagram: {}", e)
+            }
+            DatagramError::NotDict => write!(f, "message isn't a dictionary"),
+            DatagramError::MissingTxnId => {
+                write!(f, "message has no transaction ID")
+            }
+            DatagramError::InvalidTxnId => {
+                write!(f, "message has an invalid transaction ID")
+            }
+            DatagramError::Decode { error, .. } => error.fmt(f),
+            DatagramError::Encode(e) => e.fmt(f),
+            DatagramError::Unsendable(e) => {
+                write!(f, "message can't be sent: {}", e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DatagramError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DatagramError::Malformed(e) => Some(e),
+            DatagramError::Decode { error, .. } => Some(error),
+            DatagramError::Encode(e) => Some(e),
+            DatagramError::Unsendable(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Encodes a KRPC message into a datagram.
+///
+/// # Errors
+///
+/// * The message can't be encoded, at which point `DatagramError::Encode` is returned.
+/// * The message isn't a dictionary, doesn't have a valid transaction ID, or its datagram would be longer than [`MAX_DATAGRAM_LEN`], at which point `DatagramError::Unsendable` is returned, with the error the receiver would decode it with.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::BTreeMap;
+/// use bende::krpc::{self, DatagramError};
+///
+/// let ping = BTreeMap::from([("t", "aa"), ("y", "q"), ("q", "ping")]);
+/// assert_eq!(krpc::encode_datagram(&ping).unwrap(), b"d1:q4:ping1:t2:aa1:y1:qe");
+///
+/// let no_txn_id = BTreeMap::from([("y", "q"), ("q", "ping")]);
+/// let err = krpc::encode_datagram(&no_txn_id).unwrap_err();
+/// assert!(matches!(err, DatagramError::Unsendable(ref e) if matches!(**e, DatagramError::MissingTxnId)));
+/// assert!(!err.is_malformed());
+/// ```
+pub fn encode_datagram<T>(msg: &T) -> Result<Vec<u8>, DatagramError>
+where
+    T: ?Sized + Serialize,
+{
+    let mut en = en::Encoder::new(vec![]);
+    msg.serialize(&mut en).map_err(DatagramError::Encode)?;
+    let bytes = en.into_inner();
+    check_datagram(&bytes)
+        .map_err(|e| DatagramError::Unsendable(Box::new(e)))?;
+    Ok(bytes)
+}
+
+/// Checks a datagram for the shape of a KRPC message, and decodes it.
+///
+/// The checks don't decode anything, so a datagram is rejected before any of it is allocated.
+///
+/// # Errors
+///
+/// * The datagram is malformed. See [`DatagramError::is_malformed`].
+/// * The message can't be decoded into the type, at which point `DatagramError::Decode` is returned.
+///
+/// # Examples
+///
+/// ```
+/// use serde::Deserialize;
+/// use bende::krpc::{self, DatagramError};
+///
+/// #[derive(Debug, Deserialize)]
+/// struct Query<'a> {
+///     #[serde(borrow, rename = "t")]
+///     txn_id: &'a [u8],
+///     q: &'a str,
+/// }
+///
+/// let query: Query = krpc::decode_datagram(b"d1:q4:ping1:t2:aa1:y1:qe").unwrap();
+/// assert_eq!((query.txn_id, query.q), (&b"aa"[..], "ping"));
+///
+/// let err = krpc::decode_datagram::<Query>(b"d1:t2:aa1:y1:qe").unwrap_err();
+/// assert!(matches!(err, DatagramError::Decode { ref txn_id, .. } if txn_id == b"aa"));
+/// assert!(!err.is_malformed());
+///
+/// let err = krpc::decode_datagram::<Query>(b"d1:t2:aa1:y1:qee").unwrap_err();
+/// assert!(err.is_malformed());
+/// ```
+pub fn decode_datagram<'de, T>(bytes: &'de [u8]) -> Result<T, DatagramError>
+where
+    T: Deserialize<'de>,
+{
+    let txn_id = check_datagram(bytes)?;
+    super::decode(bytes).map_err(|error| DatagramError::Decode {
+        txn_id: txn_id.to_vec(),
+        error,
+    })
+}
+
+/// Checks a datagram for the shape of a KRPC message, and gets its transaction ID.
+///
+/// # Errors
+///
+/// * The datagram is malformed. See [`DatagramError::is_malformed`].
+///
+/// # Examples
+///
+/// ```
+/// let id = bende::krpc::txn_id(b"d1:rd2:id2:abe1:t2:\x00\xff1:y1:re").unwrap();
+/// assert_eq!(id, [0x00, 0xff]);
+/// ```
+#[inline]
+pub fn txn_id(bytes: &[u8]) -> Result<&[u8], DatagramError> {
+    check_datagram(bytes)
+}
+
+/// Pending requests, keyed by the transaction IDs of their queries, which time out after a while.
+///
+/// An entry that has timed out is never returned by [`take`](TxnMap::take) or [`insert`](TxnMap::insert), but it's kept until [`evict_expired`](TxnMap::evict_expired) returns it, so every request can be retried or failed. Transaction IDs are arbitrary bytes, and usually not valid UTF-8, so they're compared as bytes, exactly as they were encoded. Use [`next_id`](TxnMap::next_id) to pick IDs that aren't in use, and [`txn_id`] to get the ID of a response without decoding it.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use bende::krpc::{self, TxnMap};
+///
+/// let mut pending = TxnMap::new(Duration::from_secs(10));
+///
+/// let id = pending.next_id();
+/// pending.insert(id.clone(), "ping");
+///
+/// let mut response = b"d1:rd2:id2:abe1:t2:".to_vec();
+/// response.extend_from_slice(&id);
+/// response.extend_from_slice(b"1:y1:re");
+///
+/// let id = krpc::txn_id(&response).unwrap();
+/// assert_eq!(pending.take(id), Some("ping"));
+/// assert_eq!(pending.take(id), None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TxnMap<T> {
+    entries: HashMap<Vec<u8>, (Instant, T)>,
+    // Entries that timed out and were pushed out of `entries` by `take` or `insert`.
+    timed_out: Vec<(Vec<u8>, T)>,
+    timeout: Duration,
+    next: u16,
+}
+
+impl<T> TxnMap<T> {
+    /// Constructs an empty map, whose entries time out once they're older than `timeout`.
+    pub fn new(timeout: Duration) -> TxnMap<T> {
+        Self {
+            entries: HashMap::new(),
+            timed_out: Vec::new(),
+            timeout,
+            next: 0,
+        }
+    }
+
+    /// Gets the number of entries, including the ones that timed out but haven't been evicted yet.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len() + self.timed_out.len()
+    }
+
+    /// Checks if there are no entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.timed_out.is_empty()
+    }
+
+    /// Picks a 2-byte transaction ID that isn't in use.
+    ///
+    /// # Panics
+    ///
+    /// * Every 2-byte ID is in use.
+    pub fn next_id(&mut self) -> Vec<u8> {
+        for _ in 0..=u16::MAX {
+            let id = self.next.to_be_bytes();
+            self.next = self.next.wrapping_add(1);
+            if !self.entries.contains_key(&id[..]) {
+                return id.to_vec();
+            }
+        }
+        panic!("every 2-byte transaction ID is in use");
+    }
+
+    /// Inserts an entry, which times out after the map's timeout, returning the entry that had the same ID if it hadn't timed out.
+    ///
+    /// An entry with the same ID that has timed out is kept for [`evict_expired`](TxnMap::evict_expired) instead.
+    pub fn insert<I>(&mut self, txn_id: I, val: T) -> Option<T>
+    where
+        I: Into<Vec<u8>>,
+    {
+        let txn_id = txn_id.into();
+        self.retire(&txn_id);
+        let (_, old) = self.entries.insert(txn_id, (Instant::now(), val))?;
+        Some(old)
+    }
+
+    /// Removes and returns the entry with the given ID, unless it has timed out.
+    ///
+    /// An entry that has timed out is kept for [`evict_expired`](TxnMap::evict_expired) instead.
+    pub fn take(&mut self, txn_id: &[u8]) -> Option<T> {
+        self.retire(txn_id);
+        self.entries.remove(txn_id).map(|(_, val)| val)
+    }
+
+    /// Removes the entries that have timed out, and returns them with their IDs, so their requests can be retried or failed.
+    pub fn evict_expired(&mut self) -> Vec<(Vec<u8>, T)> {
+        let mut evicted = mem::take(&mut self.timed_out);
+        let now = Instant::now();
+        let expired: Vec<Vec<u8>> = self
+            .entries
+            .iter()
+            .filter(|(_, (at, _))| self.expired(*at, now))
+            .map(|(id, _)| id.clone())
+            .collect();
+        evicted.extend(expired.into_iter().filter_map(|id| {
+            let (_, val) = self.entries.remove(&id)?;
+            Some((id, val))
+        }));
+        evicted
+    }
+
+    /// Moves the entry with the given ID to the ones [`evict_expired`](TxnMap::evict_expired) returns, if it has timed out.
+    fn retire(&mut self, txn_id: &[u8]) {
+        let now = Instant::now();
+        let expired = self
+            .entries
+            .get(txn_id)
+            .is_some_and(|&(at, _)| self.expired(at, now));
+        if expired {
+            if let Some((id, (_, val))) = self.entries.remove_entry(txn_id) {
+                self.timed_out.push((id, val));
+            }
+        }
+    }
+
+    /// Checks if an entry that was inserted at the given time has timed out.
+    #[inline]
+    fn expired(&self, at: Instant, now: Instant) -> bool {
+        now.saturating_duration_since(at) >= self.timeout
+    }
+}
+
+/// Checks that the bytes are a single dictionary that fits in a datagram, returning its transaction ID.
+fn check_datagram(bytes: &[u8]) -> Result<&[u8], DatagramError> {
+    if bytes.len() > MAX_DATAGRAM_LEN {
+        return Err(DatagramError::TooLong { len: bytes.len() });
+    }
+    match super::frame_len(bytes) {
+        Ok(Some(len)) if len == bytes.len() => {}
+        Ok(Some(_)) => {
+            return Err(DatagramError::Malformed(de::Error::Malformed))
+        }
+        Ok(None) => return Err(DatagramError::Malformed(de::Error::EOF)),
+        Err(e) => return Err(DatagramError::Malformed(e)),
+    }
+    if bytes[0] != DICT_START {
+        return Err(DatagramError::NotDict);
+    }
+
+    let raw = super::raw_slice(bytes, &Path::root().join("t"))
+        .map_err(DatagramError::Malformed)?
+        .ok_or(DatagramError::MissingTxnId)?;
+    match super::decode::<&[u8]>(raw) {
+        Ok(id) if (1..=MAX_TXN_ID_LEN).contains(&id.len()) => Ok(id),
+        _ => Err(DatagramError::InvalidTxnId),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use std::time::Duration;
+
+    use super::{
+        decode_datagram, encode_datagram, DatagramError, TxnMap,
+        MAX_DATAGRAM_LEN,
+    };
+    use crate::Value;
+
+    #[test]
+    fn datagram_checks() {
+        let reject = |src: &[u8]| decode_datagram::<Value>(src).unwrap_err();
+
+        assert!(matches!(reject(b"d1:t2:aa"), DatagramError::Malformed(_)));
+        assert!(matches!(reject(b"l2:aae"), DatagramError::NotDict));
+        assert!(matches!(reject(b"d1:y1:qe"), DatagramError::MissingTxnId));
+        assert!(matches!(reject(b"d1:ti1ee"), DatagramError::InvalidTxnId));
+        assert!(matches!(reject(b"d1:t0:e"), DatagramError::InvalidTxnId));
+        assert!(matches!(
+            reject(b"d1:t17:aaaaaaaaaaaaaaaaae"),
+            DatagramError::InvalidTxnId
+        ));
+
+        let padding = "x".repeat(MAX_DATAGRAM_LEN);
+        let big = BTreeMap::from([("t", "aa"), ("v", padding.as_str())]);
+        let DatagramError::Unsendable(err) = encode_datagram(&big).unwrap_err()
+        else {
+            panic!("expected an unsendable datagram");
+        };
+        assert!(
+            matches!(*err, DatagramError::TooLong { len } if len > MAX_DATAGRAM_LEN)
+        );
+        assert!(err.is_malformed());
+        assert!(!DatagramError::Unsendable(err).is_malformed());
+    }
+
+    #[test]
+    fn txn_map_times_out() {
+        let mut pending = TxnMap::new(Duration::from_secs(60));
+        assert_eq!(pending.next_id(), [0, 0]);
+        pending.insert([0, 1], 'a');
+        assert_eq!(pending.next_id(), [0, 2]);
+        assert_eq!(pending.insert(&b"\xff"[..], 'b'), None);
+        assert_eq!(pending.insert(&b"\xff"[..], 'c'), Some('b'));
+        assert!(pending.evict_expired().is_empty());
+        assert_eq!(pending.take(b"\xff"), Some('c'));
+
+        let mut expired = TxnMap::new(Duration::ZERO);
+        expired.insert(*b"aa", 'a');
+        assert_eq!(expired.evict_expired(), [(b"aa".to_vec(), 'a')]);
+        expired.insert(*b"aa", 'a');
+        assert_eq!(expired.take(b"aa"), None);
+        assert_eq!(expired.insert(*b"bb", 'b'), None);
+        assert_eq!(expired.insert(*b"bb", 'c'), None);
+        assert_eq!(expired.len(), 3);
+        let mut evicted = expired.evict_expired();
+        evicted.sort();
+        assert_eq!(
+            evicted,
+            [
+                (b"aa".to_vec(), 'a'),
+                (b"bb".to_vec(), 'b'),
+                (b"bb".to_vec(), 'c')
+            ]
+        );
+        assert!(expired.is_empty());
+    }
+}
@@ -1,36 +1,165 @@
 //! A bencode encoding/decoding implementation backed by serde.
 //!
-//! This library exposes two simple functions:
+//! This library exposes a few simple functions:
 //!
 //! * [`decode`] - Which you can use to decode bencoded bytes into a **deserializable** type.
 //! * [`encode`] - Which you can use to encode a **serializable** type into bencoded bytes.
+//! * [`encode_with_capacity`] - Which works like [`encode`], but starts from a buffer of the given capacity.
+//! * [`encode_verified`] - Which works like [`encode`], but also checks that the encoded bytes are canonical when the default `verify` feature is enabled.
+//! * [`check_canonical`] - Which you can use to find out why bencoded bytes aren't canonical, eg: to explain an infohash mismatch.
+//! * [`to_writer`] - Which you can use to encode a **serializable** type into anything that implements [`Write`].
+//! * [`encode_all`] - Which you can use to encode multiple values into one buffer, one after another.
+//! * [`from_reader`] - Which you can use to decode a **deserializable** type from anything that implements [`Read`].
+//! * [`from_reader_with_progress`] - Which works like [`from_reader`], but reports how much has been read and can be cancelled.
+//! * [`decode_seed`] - Which works like [`decode`], but decodes through a [`DeserializeSeed`], eg: to decode with runtime state.
+//! * [`decode_utf8_map`] - Which you can use to decode a dictionary into a flat map of strings, for quick scripting.
+//! * [`decode_ref`] - Which you can use to decode a borrowed value, without copying any byte arrays.
+//! * [`frame_len`] - Which you can use to find where the first complete value in a buffer ends.
+//! * [`tokens`] - Which you can use to scan the tokens of bencoded bytes, without decoding or allocating anything.
+//! * [`raw_slice`] - Which you can use to get the encoded bytes of a nested value, without decoding the rest.
+//! * [`decode_with_report`] - Which works like [`decode`], but also reports statistics about the decode.
+//! * [`decode_with_deadline`] - Which works like [`decode`], but fails if decoding takes longer than a given budget.
 //!
 //! Additionally, we have the [`Value`] type that represents any valid bencode data type. It also implements [`Serialize`] and [`Deserialize`].
 //!
-//! You'd also find error types for both encoding and decoding, alongside the [`Encoder`](en::Encoder) and [`Decoder`](de::Decoder) types.
+//! Newtype structs are transparent, however deeply they're nested, and that's guaranteed on both sides: `Wrapper(Meters(5))` is encoded as `i5e`, both as a value and as a dictionary key, and decodes back from it.
+//!
+//! Bencode has no unit type, so `()` is encoded as an empty dictionary, unless the encoder is told to write it another way, or to fail, with [`UnitRepr`](en::UnitRepr), while unit structs are encoded as their names. The decoder reads `()` from either representation.
+//!
+//! You'd also find error types for both encoding and decoding, alongside the [`Encoder`](en::Encoder) and [`Decoder`](de::Decoder) types. The encoder writes to any sink that implements [`BenWrite`](en::BenWrite), eg: a fixed-size buffer or a hasher. The other modules of the crate are:
+//!
+//! * [`cancel`] - Cancels long encodes and decodes from another thread.
+//! * [`cursor`] - A checked reader for protocols that embed bencode in their own framing.
+//! * [`files`] - Checks the file lists of decoded torrents for unsafe paths.
+//! * [`krpc`] - Checks the messages of the BitTorrent DHT at the datagram boundary.
+//! * [`path`] - Addresses nested values with the [`Path`](path::Path) type.
+//! * [`pooled`] - Reuses thread-local buffers for code that encodes many small values.
+//! * [`profile`] - Interop profiles that match the quirks of other implementations.
+//! * [`raw`] - Gives access to the raw bytes of encoded values.
+//! * [`relaxed`] - Decodes hand-edited files with whitespace between their tokens.
+//! * [`rewrite`] - Edits large documents without decoding them, built on the scanner of the `token` module.
+//! * [`schema`] - Decodes dictionaries with known keys without serde.
+//! * [`signature`] - Supports signed torrents.
+//! * [`spec`] - The tokens and canonical ordering the crate follows.
+//! * [`stream`] - A decoder for non-blocking readers.
+//! * [`token`] - Scans the tokens of bencoded bytes.
+//! * [`tracker`] - Decodes the responses of trackers into typed values.
+//!
+//! Serde adapters for common field types live in the [`with`] module, some of which require optional features:
+//!
+//! * `chrono` - Conversions and adapters for `chrono::DateTime`.
+//! * `time` - Conversions and adapters for `time::OffsetDateTime`.
+//! * `url` - An adapter for `url::Url`.
+//! * `uuid` - An adapter for `uuid::Uuid`.
+//!
+//! Enabling the `query` feature adds `Predicate`s to the patterns of the [`path`] module, so selected values can be filtered by their contents.
+//!
+//! Enabling the `smallvec` feature adds the `SmallList` type to the [`value`] module, for decoding tiny lists without allocating.
+//!
+//! Enabling the `hashbrown` feature adds the `FastValue` type to the [`value`] module, whose dictionaries are the hash maps of [`hashbrown`](https://docs.rs/hashbrown) and are only sorted when they're encoded, for building huge dictionaries quickly.
+//!
+//! Enabling the `compact` feature adds the `CompactValue` type to the [`value`] module, a read-only value that takes far less memory than a [`Value`], for caches that hold many decoded values.
+//!
+//! Enabling the `zeroize` feature implements [`Zeroize`](https://docs.rs/zeroize) for [`Value`], its [`List`](value::List) and [`Dict`](value::Dict), and [`Cached`](raw::Cached), and scrubs the buffers of the [`stream`] decoder, including every allocation they outgrow, so secrets transported in bencoded messages can be wiped from memory.
+//!
+//! Enabling the `tracing` feature emits [tracing](https://docs.rs/tracing) spans and events under the `bende` target when encoding and decoding, including the number of bytes consumed and values decoded. Without the feature, none of the instrumentation is compiled in.
+//!
+//! Enabling the `comments` feature allows `#` comments in the dialect of the [`relaxed`] module, for configuration files maintained by hand. Without the feature, none of the comment handling is compiled in.
+//!
+//! Enabling the `digest` feature adds `with_hasher` to the [`Decoder`](de::Decoder), which feeds the raw bytes of a value to any hasher from the [`digest`](https://docs.rs/digest) crates, eg: SHA-1 for the infohash of a torrent.
+//!
+//! Enabling the `chunk` feature, which enables `digest`, adds the `chunk` module, which sends encoded values in pieces that can be verified on their own, with the hasher of your choice, eg: SHA-1 for the metadata pieces of BEP 9.
+//!
+//! Enabling the `fingerprint` feature adds `fingerprint`, which identifies the content of a torrent regardless of cosmetic differences, using the SHA-256 implementation of the [`sha2`](https://docs.rs/sha2) crate.
+//!
+//! Enabling the `heapless` feature lets the encoder write into a fixed-capacity `heapless::Vec<u8, N>`, and enables the serde support of [`heapless`](https://docs.rs/heapless), whose types fail to decode with `de::Error::Length` once they run out of room.
+//!
+//! Enabling the `tokio` feature adds the `codec` module, which has a [`tokio-util`](https://docs.rs/tokio-util) codec for streams of concatenated values, and typed `Stream` and `Sink` wrappers over async readers and writers, with limits on the length of a value.
+//!
+//! Enabling the `compress` feature adds `from_reader_compressed`, which decodes bytes wrapped in gzip or zlib, like the responses of trackers that compress them, using the [`flate2`](https://docs.rs/flate2) crate.
+//!
+//! Enabling the `http` feature, which enables `compress`, adds `decode_http_response` to the [`tracker`] module, which decodes the body of a tracker's HTTP response given its headers.
+//!
+//! Enabling the `quirks` feature adds the `quirks` module, which normalizes torrents with known real-world malformations, eg: a negative `creation date` or a stray `e` after the torrent, each of which can be accepted or denied on its own.
+//!
+//! Enabling the `unicode` feature adds the `nfc` adapter to the [`with`] module, and lets [`NormalizeConfig`](value::NormalizeConfig) normalize strings to Unicode NFC, so display names and file paths from different platforms compare equal.
+//!
+//! The `value-io` feature, which is enabled by default, adds the reader, writer and file methods of [`Value`]: `Value::from_reader`, `Value::to_writer`, `Value::from_file` and `Value::to_file`. It controls nothing else, the rest of the crate's I/O, eg: [`from_reader`], [`to_writer`] and the [`stream`] module, is always available.
+//!
+//! The `verify` feature, which is enabled by default, makes [`encode_verified`] check the bytes it encodes. Disabling it keeps the function, which then works like [`encode`], so builds that trust their encoder can skip the check without changing any code.
+//!
+//! The crate forbids `unsafe` code by default. Enabling the `fast-unsafe` feature opts into a few vetted uses of it on the hot paths, for users who'd rather trade the guarantee for speed: the decoder skips the bounds check when reading the next byte and the UTF-8 validation of integer keys and lengths it has already checked to be ASCII digits, and the encoder writes integers into a stack buffer that isn't zeroed first. Both builds run the same test suite.
+//!
+//! # Stability
+//!
+//! The types meant to be embedded by other frameworks follow semver like the rest of the public API:
+//!
+//! * The [`ser`] module, which is the [`en`] module under the name serde uses, with the [`Serializer`](ser::Serializer) and [`Encoder`](en::Encoder) types, their builders and the [`BenWrite`](en::BenWrite) sinks they write to.
+//! * The [`de`] module, with the [`Deserializer`](de::Deserializer) and [`Decoder`](de::Decoder) types and their builders.
+//! * The serde trait implementations of both, and the seed-based entry point [`decode_seed`].
+//!
+//! The error enums of both modules, and [`de::ErrorKind`], are `#[non_exhaustive]`, as they may gain variants in minor releases, so matching on them outside of the crate requires a wildcard arm. Decoding errors can also be matched through [`de::Error::kind`]. Private types that only show up in trait implementations, such as the encoders of sequences and maps, aren't part of the stable API.
+
+#![cfg_attr(not(feature = "fast-unsafe"), forbid(unsafe_code))]
 
+/// Emits a `tracing` event when the `tracing` feature is enabled. Otherwise, this expands to nothing.
+macro_rules! trace {
+    ($level:ident, $($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        tracing::$level!(target: "bende", $($arg)*);
+    }};
+}
+
+pub mod cancel;
+#[cfg(feature = "chunk")]
+pub mod chunk;
+#[cfg(feature = "tokio")]
+pub mod codec;
+pub mod cursor;
 pub mod de;
+#[cfg(feature = "compress")]
+mod decompress;
 pub mod en;
+pub mod files;
+#[cfg(feature = "fingerprint")]
+mod fingerprint;
+pub mod krpc;
+pub mod path;
+pub mod pooled;
+pub mod profile;
+#[cfg(feature = "quirks")]
+pub mod quirks;
+pub mod raw;
+pub mod relaxed;
+pub mod rewrite;
+pub mod schema;
+pub mod signature;
+pub mod spec;
+pub mod stream;
+pub mod token;
+pub mod tracker;
 pub mod value;
+pub mod with;
 
+pub use en as ser;
+#[cfg(feature = "fingerprint")]
+pub use fingerprint::fingerprint;
 pub use value::Value;
 
-use serde::{Deserialize, Serialize};
+use std::collections::btree_map;
+use std::collections::BTreeMap;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::time::Duration;
+use std::time::Instant;
 
-/// Denotes the start of an integer - `i`.
-const INT_START: u8 = 0x69;
-
-/// Denotes that start of a list - `l`.
-const LIST_START: u8 = 0x6C;
-
-/// Denotes the start of a dictionary - `d`.
-const DICT_START: u8 = 0x64;
-
-/// A symbol used to separate a byte array's length from its content - `:`.
-const TEXT_DELIM: u8 = 0x3A;
+use serde::de::DeserializeOwned;
+use serde::de::DeserializeSeed;
+use serde::{Deserialize, Serialize};
 
-/// Denotes the end of a type - `e`.
-const TYPE_END: u8 = 0x65;
+/// The initial capacity of the buffer used by [`encode`], which fits most small messages without reallocating.
+const INITIAL_CAPACITY: usize = 64;
 
 /// Encodes the given value into bencode representation.
 ///
@@ -65,9 +194,145 @@ pub fn encode<T>(val: &T) -> Result<Vec<u8>, en::Error>
 where
     T: Serialize,
 {
-    let mut en = en::Encoder::new(vec![]);
-    val.serialize(&mut en)?;
-    Ok(en.into_inner())
+    encode_with_capacity(val, INITIAL_CAPACITY)
+}
+
+/// Encodes the given value into bencode representation, starting from a buffer with room for at least `capacity` bytes.
+///
+/// When the encoded size can be estimated up front, eg: from the number of pieces of a torrent, this avoids reallocating the buffer as it grows.
+///
+/// # Examples
+///
+/// ```
+/// let bytes = bende::encode_with_capacity(&vec![0u8; 20], 128).unwrap();
+///
+/// assert_eq!(bytes.len(), 62);
+/// assert!(bytes.capacity() >= 128);
+/// ```
+pub fn encode_with_capacity<T>(
+    val: &T,
+    capacity: usize,
+) -> Result<Vec<u8>, en::Error>
+where
+    T: Serialize,
+{
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!(target: "bende", "encode").entered();
+
+    let mut en = en::Encoder::with_capacity(capacity);
+    if let Err(e) = val.serialize(&mut en) {
+        trace!(debug, error = %e, "encode failed");
+        return Err(e);
+    }
+
+    let bytes = en.into_inner();
+    trace!(debug, len = bytes.len(), "encode finished");
+    Ok(bytes)
+}
+
+/// Encodes the given value into bencode representation, then checks that the encoded bytes are a single value in canonical form before returning them.
+///
+/// This is a paranoid mode for systems where emitting malformed bencode is costly, eg: trackers and DHT nodes. The check is [`check_canonical`], which only scans the bytes, without decoding them into anything, and catches bytes that were copied in as is, eg: from a [`RawValue`](raw::RawValue), that aren't canonical or have an integer the decoder would reject.
+///
+/// The check only runs with the `verify` feature, which is enabled by default. Without it, this works like [`encode`].
+///
+/// # Errors
+///
+/// * The value can't be encoded.
+/// * The encoded bytes aren't canonical, at which point `Error::NotCanonical` is returned. Only with the `verify` feature.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(bende::encode_verified(&("foo", 1995)).unwrap(), b"l3:fooi1995ee");
+/// ```
+pub fn encode_verified<T>(val: &T) -> Result<Vec<u8>, en::Error>
+where
+    T: Serialize,
+{
+    let bytes = encode(val)?;
+    #[cfg(feature = "verify")]
+    if let Err(e) = de::check_canonical(&bytes) {
+        trace!(warn, at = e.offset, "encoded bytes are not canonical");
+        return Err(en::Error::NotCanonical { at: e.offset });
+    }
+    Ok(bytes)
+}
+
+/// Checks that the given bytes are exactly one value in canonical form, as the crate encodes it.
+///
+/// Bytes that decode fine may still not be canonical, in which case re-encoding them changes them, eg: the infohash of a torrent with unsorted keys doesn't match the one computed from its decoded `info` dictionary. This finds the first construct that causes it. Unlike the check of [`encode_verified`], this doesn't depend on the `verify` feature.
+///
+/// # Errors
+///
+/// * The bytes have a leading zero, a negative zero, or an unsorted or repeated dictionary key, or aren't well-formed bencode, at which point the first such construct is returned. See [`NonCanonical`](de::NonCanonical).
+///
+/// # Examples
+///
+/// ```
+/// use bende::de::NonCanonicalKind;
+///
+/// assert!(bende::check_canonical(b"d1:ai1e1:bi2ee").is_ok());
+///
+/// let err = bende::check_canonical(b"d1:bi1e1:ai2ee").unwrap_err();
+/// assert_eq!(err.offset, 7);
+/// assert_eq!(err.kind, NonCanonicalKind::UnsortedKeys);
+/// assert_eq!(err.bytes, b"1:a");
+/// ```
+pub fn check_canonical(bytes: &[u8]) -> Result<(), de::NonCanonical> {
+    de::check_canonical(bytes)
+}
+
+/// Encodes the given values into one buffer, as concatenated top-level values.
+///
+/// This is the wire form used by some stream protocols, which the [`NonBlockingDecoder`](stream::NonBlockingDecoder) can decode one value at a time.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(bende::encode_all(&[1995, 2024]).unwrap(), b"i1995ei2024e");
+/// ```
+pub fn encode_all<T>(vals: &[T]) -> Result<Vec<u8>, en::Error>
+where
+    T: Serialize,
+{
+    let mut bytes = vec![];
+    to_writer_all(&mut bytes, vals)?;
+    Ok(bytes)
+}
+
+/// Encodes the given value into bencode representation, writing it to the given writer.
+///
+/// # Examples
+///
+/// ```
+/// let mut buf = vec![];
+/// bende::to_writer(&mut buf, &("foo", 1995)).unwrap();
+///
+/// assert_eq!(buf, b"l3:fooi1995ee");
+/// ```
+pub fn to_writer<W, T>(writer: W, val: &T) -> Result<(), en::Error>
+where
+    W: Write,
+    T: ?Sized + Serialize,
+{
+    let mut en = en::Encoder::new(en::IoWriter::new(writer));
+    val.serialize(&mut en)
+}
+
+/// Encodes the given values as concatenated top-level values, writing them to the given writer.
+///
+/// See [`encode_all`] for more details.
+pub fn to_writer_all<W, T>(writer: W, vals: &[T]) -> Result<(), en::Error>
+where
+    W: Write,
+    T: Serialize,
+{
+    let mut en = en::Encoder::new(en::IoWriter::new(writer));
+    for val in vals {
+        val.serialize(&mut en)?;
+    }
+    Ok(())
 }
 
 /// Decodes a type from the given bencoded bytes.
@@ -102,16 +367,415 @@ pub fn decode<'de, T>(bytes: &'de [u8]) -> Result<T, de::Error>
 where
     T: Deserialize<'de>,
 {
+    #[cfg(feature = "tracing")]
+    let _span =
+        tracing::debug_span!(target: "bende", "decode", len = bytes.len())
+            .entered();
+
     let mut de = de::Decoder::new(bytes);
+    let res = T::deserialize(&mut de);
+
+    #[cfg(feature = "tracing")]
+    match res {
+        Ok(_) => trace!(
+            debug,
+            consumed = de.consumed(),
+            values = de.nodes(),
+            "decode finished"
+        ),
+        Err(ref e) => trace!(
+            debug,
+            at = de.consumed(),
+            values = de.nodes(),
+            error = %e,
+            "decode failed"
+        ),
+    }
+    res
+}
+
+/// Decodes a value from the given bencoded bytes through a [`DeserializeSeed`], which can carry state into the decode.
+///
+/// # Examples
+///
+/// ```
+/// use std::marker::PhantomData;
+///
+/// let n: u16 = bende::decode_seed(b"i1995e", PhantomData).unwrap();
+///
+/// assert_eq!(n, 1995);
+/// ```
+pub fn decode_seed<'de, S>(
+    bytes: &'de [u8],
+    seed: S,
+) -> Result<S::Value, de::Error>
+where
+    S: DeserializeSeed<'de>,
+{
+    let mut de = de::Decoder::new(bytes);
+    seed.deserialize(&mut de)
+}
+
+/// Decodes a dictionary into a flat map of strings, for scripts that only want its entries as text.
+///
+/// Integers are written in decimal and UTF-8 byte arrays are kept as is. Keys and values that aren't valid UTF-8, and nested lists and dictionaries, are handled by the given policy. See [`TextPolicy`](de::TextPolicy) for more details.
+///
+/// # Errors
+///
+/// * The bytes aren't a single dictionary.
+/// * A key or value isn't valid UTF-8, or is nested, under [`TextPolicy::Error`](de::TextPolicy::Error).
+/// * Two keys are converted into the same string, at which point `de::Error::Deserialize` is returned.
+///
+/// # Examples
+///
+/// ```
+/// use bende::de::TextPolicy;
+///
+/// let bytes = b"d4:hash2:\xab\xcd4:listl1:ae4:name3:foo4:sizei1995ee";
+///
+/// let map = bende::decode_utf8_map(bytes, TextPolicy::Hex).unwrap();
+/// assert_eq!(map["hash"], "abcd");
+/// assert_eq!(map["list"], "6c313a6165");
+/// assert_eq!(map["name"], "foo");
+/// assert_eq!(map["size"], "1995");
+///
+/// let map = bende::decode_utf8_map(bytes, TextPolicy::Lossy).unwrap();
+/// assert_eq!(map["list"], "l1:ae");
+///
+/// assert!(bende::decode_utf8_map(bytes, TextPolicy::Error).is_err());
+/// ```
+pub fn decode_utf8_map(
+    bytes: &[u8],
+    policy: de::TextPolicy,
+) -> Result<BTreeMap<String, String>, de::Error> {
+    let entries: BTreeMap<&[u8], raw::RawValue<'_>> = decode(bytes)?;
+
+    let mut map = BTreeMap::new();
+    for (key, val) in entries {
+        let key = match std::str::from_utf8(key) {
+            Ok(key) => key.to_owned(),
+            Err(_) => policy.convert(key)?,
+        };
+        let raw = val.as_bytes();
+        let val = match raw[0] {
+            spec::INT_START => decode::<i64>(raw)?.to_string(),
+            spec::LIST_START | spec::DICT_START => {
+                if policy == de::TextPolicy::Error {
+                    return Err(de::Error::Wanted {
+                        // The raw value borrows from the given bytes.
+                        at: raw.as_ptr() as usize - bytes.as_ptr() as usize,
+                        expected: "an integer or a byte array",
+                        found: (raw[0] as char).to_string(),
+                    });
+                }
+                policy.convert(raw)?
+            }
+            _ => {
+                let text: &[u8] = decode(raw)?;
+                match std::str::from_utf8(text) {
+                    Ok(text) => text.to_owned(),
+                    Err(_) => policy.convert(text)?,
+                }
+            }
+        };
+        // Distinct keys can be converted into the same string, eg: `\xff` and `\xfe` under `Lossy`, and neither is kept over the other.
+        match map.entry(key) {
+            btree_map::Entry::Vacant(e) => {
+                e.insert(val);
+            }
+            btree_map::Entry::Occupied(e) => {
+                return Err(de::Error::Deserialize(format!(
+                    "more than one key is converted into {:?}",
+                    e.key()
+                )));
+            }
+        }
+    }
+    Ok(map)
+}
+
+/// Decodes a [`ValueRef`](value::ValueRef) that borrows its byte arrays and dictionary keys from the given bencoded bytes.
+///
+/// # Examples
+///
+/// ```
+/// use bende::value::ValueRef;
+///
+/// let val = bende::decode_ref(b"l3:fooi1995ee").unwrap();
+/// assert_eq!(val, ValueRef::List(vec![ValueRef::Text(b"foo"), ValueRef::Int(1995)]));
+/// ```
+pub fn decode_ref(bytes: &[u8]) -> Result<value::ValueRef<'_>, de::Error> {
+    decode(bytes)
+}
+
+/// Returns the length in bytes of the first complete value in the given buffer, or `None` if the buffer ends before the value is complete.
+///
+/// This is useful for framing values read off a stream, before decoding them. The scan doesn't allocate.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(bende::frame_len(b"l3:fooi1995eei1e"), Ok(Some(13)));
+/// assert_eq!(bende::frame_len(b"l3:fooi19"), Ok(None));
+/// assert!(bende::frame_len(b"x").is_err());
+/// ```
+pub fn frame_len(bytes: &[u8]) -> Result<Option<usize>, de::Error> {
+    de::scan_frame(bytes)
+}
+
+/// Returns an iterator over the tokens of the given bencoded bytes, alongside their offsets.
+///
+/// See [`Tokens`](token::Tokens) for more details.
+///
+/// # Examples
+///
+/// ```
+/// use bende::token::Token;
+///
+/// let ints = bende::tokens(b"li1ei2e3:fooe")
+///     .filter(|token| matches!(token, Ok((_, Token::Int(_)))))
+///     .count();
+///
+/// assert_eq!(ints, 2);
+/// ```
+#[inline]
+pub fn tokens(bytes: &[u8]) -> token::Tokens<'_> {
+    token::Tokens::new(bytes)
+}
+
+/// Returns the exact encoded bytes of the value at the given path, or `None` if there's no value at the path.
+///
+/// Only the values before the one at the path are scanned, and none of them are decoded, so this is a cheap way of getting the bytes a hash or signature covers, eg: the `info` dictionary of a torrent. The bytes are returned as they appear in the source, even if they aren't canonically encoded.
+///
+/// # Errors
+///
+/// * The source is malformed before the end of the value at the path.
+/// * The source ends before the end of the value at the path, at which point `Error::EOF` is returned.
+///
+/// # Examples
+///
+/// ```
+/// let torrent = b"d8:announce3:foo4:infod5:filesl1:a1:be6:lengthi1995eee";
+///
+/// let info = bende::raw_slice(torrent, &"info".parse().unwrap()).unwrap();
+/// assert_eq!(info, Some(&b"d5:filesl1:a1:be6:lengthi1995ee"[..]));
+///
+/// let file = bende::raw_slice(torrent, &"info.files[1]".parse().unwrap()).unwrap();
+/// assert_eq!(file, Some(&b"1:b"[..]));
+/// assert_eq!(bende::raw_slice(torrent, &"info.name".parse().unwrap()), Ok(None));
+/// ```
+pub fn raw_slice<'a>(
+    bytes: &'a [u8],
+    path: &path::Path,
+) -> Result<Option<&'a [u8]>, de::Error> {
+    raw::slice_at(bytes, path)
+}
+
+/// Decodes a type from the given bencoded bytes, alongside a [`DecodeReport`](de::DecodeReport) of how the decode went.
+///
+/// # Examples
+///
+/// ```
+/// let (list, report) = bende::decode_with_report::<Vec<Vec<i32>>>(b"lli1ei2eeli3eee").unwrap();
+///
+/// assert_eq!(list, [vec![1, 2], vec![3]]);
+/// assert_eq!(report.consumed, 15);
+/// assert_eq!(report.nodes, 6);
+/// assert_eq!(report.max_depth, 2);
+/// ```
+pub fn decode_with_report<'de, T>(
+    bytes: &'de [u8],
+) -> Result<(T, de::DecodeReport), de::Error>
+where
+    T: Deserialize<'de>,
+{
+    let start = Instant::now();
+
+    let mut de = de::Decoder::new(bytes).with_stats();
+    let val = T::deserialize(&mut de)?;
+
+    let report = de::DecodeReport {
+        consumed: de.consumed(),
+        nodes: de.nodes(),
+        max_depth: de.max_depth(),
+        elapsed: start.elapsed(),
+    };
+    Ok((val, report))
+}
+
+/// Decodes a type from the given bencoded bytes, failing with [`de::Error::TimedOut`] if decoding takes longer than the given budget.
+///
+/// This protects latency-sensitive services from inputs that are slow to decode, eg: millions of tiny values. The budget is checked every few hundred values, so decoding may run slightly past it. A budget too large for a point in time to be computed from it, eg: `Duration::MAX`, never runs out.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use bende::de::ErrorKind;
+///
+/// let list: Vec<u32> = bende::decode_with_deadline(b"li1ei2ee", Duration::from_secs(1)).unwrap();
+/// assert_eq!(list, [1, 2]);
+///
+/// let err = bende::decode_with_deadline::<Vec<u32>>(b"li1ei2ee", Duration::ZERO).unwrap_err();
+/// assert_eq!(err.kind(), ErrorKind::TimedOut);
+///
+/// let list: Vec<u32> = bende::decode_with_deadline(b"li1ei2ee", Duration::MAX).unwrap();
+/// assert_eq!(list, [1, 2]);
+/// ```
+pub fn decode_with_deadline<'de, T>(
+    bytes: &'de [u8],
+    budget: Duration,
+) -> Result<T, de::Error>
+where
+    T: Deserialize<'de>,
+{
+    let mut de = de::Decoder::new(bytes);
+    // A budget too large to be represented, eg: `Duration::MAX`, is no deadline at all.
+    if let Some(deadline) = Instant::now().checked_add(budget) {
+        de = de.with_deadline(deadline);
+    }
     T::deserialize(&mut de)
 }
 
+/// Decodes a type from the bencoded bytes of the given reader.
+///
+/// The reader is read until it reaches the end, and the bytes are then decoded. Reads that are interrupted are retried, and any other I/O error is returned as [`de::Error::Io`] with the original error preserved.
+///
+/// # Examples
+///
+/// ```
+/// let reader = std::io::Cursor::new(b"li1995e3:fooe");
+/// let (year, name): (u16, String) = bende::from_reader(reader).unwrap();
+///
+/// assert_eq!(year, 1995);
+/// assert_eq!(name, "foo");
+/// ```
+pub fn from_reader<R, T>(mut reader: R) -> Result<T, de::Error>
+where
+    R: Read,
+    T: DeserializeOwned,
+{
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    decode(&bytes)
+}
+
+/// Decodes a type from the bencoded bytes of the given reader, notifying the observer every time another `every` bytes have been read.
+///
+/// Works like [`from_reader`], but the observer can cancel the read by returning `false`, at which point [`de::Error::Cancelled`] is returned. Decoding the bytes once they've all been read is not reported, as it's much faster than reading them - use [`Decoder::with_progress`](de::Decoder::with_progress) to observe a decode on its own.
+///
+/// # Panics
+///
+/// * `every` is `0`.
+///
+/// # Examples
+///
+/// ```
+/// use bende::de::Error;
+///
+/// let src = b"l3:foo3:bar3:baze";
+///
+/// let mut seen = Vec::new();
+/// let list: Vec<String> = bende::from_reader_with_progress(&src[..], 8, |read| {
+///     seen.push(read);
+///     true
+/// })
+/// .unwrap();
+/// assert_eq!(list, ["foo", "bar", "baz"]);
+/// assert_eq!(seen, [17]);
+///
+/// let res = bende::from_reader_with_progress::<_, Vec<String>, _>(&src[..], 8, |_| false);
+/// assert_eq!(res, Err(Error::Cancelled { at: 17 }));
+/// ```
+pub fn from_reader_with_progress<R, T, O>(
+    mut reader: R,
+    every: usize,
+    observer: O,
+) -> Result<T, de::Error>
+where
+    R: Read,
+    T: DeserializeOwned,
+    O: de::ProgressObserver + Send,
+{
+    let mut progress = de::Progress::new(every, observer);
+    let mut bytes = Vec::new();
+    let mut buf = [0; 8192];
+    loop {
+        let len = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        bytes.extend_from_slice(&buf[..len]);
+        progress.update(bytes.len())?;
+    }
+    decode(&bytes)
+}
+
+/// Decodes a type from the bencoded bytes of the given reader, decompressing them first if they're wrapped in gzip or zlib, eg: the response of a tracker that sends `Content-Encoding: gzip`.
+///
+/// The wrapping is detected from the header of gzip or zlib, and any other bytes are decoded as is, like [`from_reader`] does. Raw DEFLATE has no header, so it isn't detected, and fails to decode with a syntax error like any other bytes that aren't bencoded.
+///
+/// The decompressed bytes are fed to a [`NonBlockingDecoder`](stream::NonBlockingDecoder) as they're read, so only the bytes of the value are buffered, and malformed bytes are rejected as soon as they're read. A value longer than `max_len` decompressed bytes is rejected as soon as that's known, so a small compressed body can't expand into an unbounded amount of memory.
+///
+/// Any bytes after the value are read and discarded, so the checksum at the end of gzip and zlib is still verified, but at most another `max_len` of them.
+///
+/// # Errors
+///
+/// * The reader fails, or the compressed bytes are malformed or fail their checksum, at which point `de::Error::Io` is returned.
+/// * The value, or the bytes after it, are longer than `max_len` decompressed bytes, at which point `de::Error::Length` is returned.
+/// * The reader ends before a complete value, at which point `de::Error::EOF` is returned.
+/// * The decompressed bytes can't be decoded into the type.
+///
+/// # Examples
+///
+/// ```
+/// // `l3:fooe`, gzipped.
+/// let gzipped = [
+///     0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xcb, 0x31,
+///     0xb6, 0x4a, 0xcb, 0xcf, 0x4f, 0x05, 0x00, 0xb6, 0xea, 0x7e, 0x3a, 0x07,
+///     0x00, 0x00, 0x00,
+/// ];
+/// let list: Vec<String> = bende::from_reader_compressed(&gzipped[..], 1024).unwrap();
+/// assert_eq!(list, ["foo"]);
+///
+/// let list: Vec<String> = bende::from_reader_compressed(&b"l3:fooe"[..], 1024).unwrap();
+/// assert_eq!(list, ["foo"]);
+///
+/// let res = bende::from_reader_compressed::<_, Vec<String>>(&gzipped[..], 4);
+/// assert_eq!(res.unwrap_err().kind(), bende::de::ErrorKind::Limit);
+///
+/// let res = bende::from_reader_compressed::<_, bende::Value>(&b"garbage"[..], 1024);
+/// assert_eq!(res.unwrap_err().kind(), bende::de::ErrorKind::Syntax);
+/// ```
+#[cfg(feature = "compress")]
+pub fn from_reader_compressed<R, T>(
+    reader: R,
+    max_len: usize,
+) -> Result<T, de::Error>
+where
+    R: Read,
+    T: DeserializeOwned,
+{
+    let reader = decompress::detect(reader)?;
+    let mut de = stream::NonBlockingDecoder::new(reader).with_max_len(max_len);
+    let val = de.poll()?.ok_or_else(|| {
+        de::Error::Io(io::Error::from(io::ErrorKind::WouldBlock))
+    })?;
+    let buffered = de.buffered().len();
+    decompress::drain(de.into_inner(), buffered, max_len)?;
+    Ok(val)
+}
+
 #[cfg(test)]
 mod test {
     use serde::{Deserialize, Serialize};
 
     use super::decode;
     use super::encode;
+    use super::from_reader;
+    use crate::de::{Error, ErrorKind};
 
     /// Asserts that passing the encoded value's bytes to the decoder will yield the same value.
     macro_rules! test_bende {
@@ -135,6 +799,39 @@ mod test {
         test_bende!(Foo, Foo(1995));
     }
 
+    #[test]
+    fn nested_newtypes_are_transparent() {
+        use std::collections::BTreeMap;
+
+        #[derive(
+            Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+        )]
+        struct Wrapper<T>(T);
+        #[derive(
+            Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+        )]
+        struct Meters<T>(T);
+
+        type Deep = Wrapper<Meters<Wrapper<u32>>>;
+        test_bende!(Deep, Wrapper(Meters(Wrapper(5))));
+        assert_eq!(encode(&Wrapper(Meters(Wrapper(5)))).unwrap(), b"i5e");
+
+        type Keyed = BTreeMap<Wrapper<Meters<String>>, Vec<Meters<i64>>>;
+        let map = Keyed::from([(Wrapper(Meters("a".into())), vec![Meters(1)])]);
+        assert_eq!(encode(&map).unwrap(), b"d1:ali1eee");
+        test_bende!(Keyed, map);
+
+        let mut en = crate::en::Encoder::new(vec![]).with_int_keys(true);
+        assert_eq!(
+            en.encode(&BTreeMap::from([(Wrapper(Meters(10)), 1)])).unwrap(),
+            b"d2:10i1ee"
+        );
+        assert_eq!(
+            decode::<BTreeMap<Wrapper<Meters<i32>>, i32>>(b"d2:10i1ee"),
+            Ok(BTreeMap::from([(Wrapper(Meters(10)), 1)]))
+        );
+    }
+
     #[test]
     fn encode_and_decode_option() {
         #[derive(Debug, PartialEq, Serialize, Deserialize)]
@@ -195,6 +892,95 @@ mod test {
         );
     }
 
+    #[test]
+    fn encode_and_decode_unit_values() {
+        use std::collections::BTreeMap;
+
+        use crate::en::{Encoder, UnitRepr};
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Marker;
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Flags {
+            seen: Option<()>,
+            unit: (),
+            marker: Marker,
+        }
+
+        let flags = Flags { seen: Some(()), unit: (), marker: Marker };
+        assert_eq!(
+            encode(&flags).unwrap(),
+            b"d6:marker6:Marker4:seende4:unitdee"
+        );
+        assert_eq!(encode(&Ok::<(), ()>(())).unwrap(), b"d2:Okdee");
+        let mut en = Encoder::new(vec![]).with_unit_repr(UnitRepr::Error);
+        assert_eq!(
+            en.encode(&flags).unwrap_err().to_string(),
+            "encoding of type '()' is not supported by the library"
+        );
+
+        for (repr, bytes) in [
+            (UnitRepr::EmptyDict, &b"d6:marker6:Marker4:seende4:unitdee"[..]),
+            (UnitRepr::EmptyText, b"d6:marker6:Marker4:seen0:4:unit0:e"),
+        ] {
+            let mut en = Encoder::new(vec![]).with_unit_repr(repr);
+            assert_eq!(en.encode(&flags).unwrap(), bytes);
+            assert_eq!(decode::<Flags>(bytes).as_ref(), Ok(&flags));
+        }
+
+        // A left out `None` round-trips through a missing key.
+        let flags = Flags { seen: None, ..flags };
+        let mut en = Encoder::new(vec![])
+            .with_skip_none(true)
+            .with_unit_repr(UnitRepr::EmptyDict);
+        let bytes = en.encode(&flags).unwrap().to_vec();
+        assert_eq!(bytes, b"d6:marker6:Marker4:unitdee");
+        assert_eq!(decode::<Flags>(&bytes), Ok(flags));
+
+        assert!(decode::<BTreeMap<String, ()>>(b"d1:a1:xe").is_err());
+        assert!(decode::<BTreeMap<String, ()>>(b"d1:ai0ee").is_err());
+    }
+
+    #[test]
+    fn encode_and_decode_data_carrying_variants() {
+        use std::collections::BTreeMap;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        enum Shape {
+            Point,
+            Circle(u32),
+            Line(i64, i64),
+            Rect { width: u32, height: u32 },
+        }
+
+        let shapes = vec![
+            Shape::Line(-1, 1),
+            Shape::Rect { width: 2, height: 3 },
+            Shape::Circle(4),
+            Shape::Point,
+        ];
+        assert_eq!(
+            encode(&shapes).unwrap(),
+            b"ld4:Lineli-1ei1eeed4:Rectd6:heighti3e5:widthi2eeed6:Circlei4ee5:Pointe"
+        );
+        type Shapes = Vec<Shape>;
+        test_bende!(Shapes, shapes);
+
+        type Named = BTreeMap<String, Shape>;
+        test_bende!(
+            Named,
+            Named::from([
+                ("a".into(), Shape::Line(5, 6)),
+                ("b".into(), Shape::Rect { width: 0, height: 1 }),
+            ])
+        );
+
+        // Fixed-length tuples don't take elements past their length.
+        assert!(decode::<(i64, i64)>(b"li1ei2ei3ee").is_err());
+        type Pairs = Vec<(u8, u8)>;
+        test_bende!(Pairs, vec![(1, 2), (3, 4)]);
+    }
+
     #[test]
     fn encode_and_decode_nested_struct() {
         #[derive(Debug, PartialEq, Serialize, Deserialize)]
@@ -230,4 +1016,137 @@ mod test {
             }
         );
     }
+
+    #[test]
+    fn from_reader_preserves_io_error() {
+        use std::error::Error as _;
+        use std::io;
+
+        struct Broken;
+
+        impl io::Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::ConnectionReset, "reset"))
+            }
+        }
+
+        let err = from_reader::<_, i32>(Broken).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Io);
+        assert!(err.source().is_some());
+        match err {
+            Error::Io(e) => {
+                assert_eq!(e.kind(), io::ErrorKind::ConnectionReset)
+            }
+            e => panic!("expected an I/O error, found {:?}", e),
+        }
+    }
+
+    #[test]
+    fn error_kinds() {
+        assert_eq!(
+            decode::<i32>(b"i19").unwrap_err().kind(),
+            ErrorKind::Syntax
+        );
+        assert_eq!(
+            decode::<String>(b"4:foo").unwrap_err().kind(),
+            ErrorKind::Eof
+        );
+        assert_eq!(decode::<bool>(b"i2e").unwrap_err().kind(), ErrorKind::Data);
+    }
+
+    #[test]
+    fn encode_all_values() {
+        use crate::value::List;
+        use crate::Value;
+
+        let vals =
+            [Value::Int(1995), Value::from("foo"), Value::List(List::new())];
+        let bytes = super::encode_all(&vals).unwrap();
+        assert_eq!(bytes, b"i1995e3:foole");
+
+        let mut de = crate::stream::NonBlockingDecoder::new(&bytes[..]);
+        for val in vals {
+            assert_eq!(de.poll::<Value>(), Ok(Some(val)));
+        }
+    }
+
+    #[cfg(feature = "verify")]
+    #[test]
+    fn encode_verified_rejects_raw() {
+        use crate::raw::RawValue;
+
+        // The keys of the raw dictionary are not sorted.
+        let raw: RawValue = decode(b"d1:bi0e1:ai0ee").unwrap();
+        assert!(matches!(
+            crate::encode_verified(&[raw]),
+            Err(crate::en::Error::NotCanonical { at: 8 })
+        ));
+
+        // The integer is canonical, but too large for the decoder.
+        let raw: RawValue = decode(b"i99999999999999999999999e").unwrap();
+        assert!(matches!(
+            crate::encode_verified(&raw),
+            Err(crate::en::Error::NotCanonical { at: 0 })
+        ));
+    }
+
+    #[test]
+    fn decode_seed_through_aliases() {
+        use serde::de::DeserializeSeed;
+        use serde::Serializer as _;
+
+        /// Decodes an integer, adding it to a running total.
+        struct Add<'a>(&'a mut i64);
+
+        impl<'de> DeserializeSeed<'de> for Add<'_> {
+            type Value = ();
+
+            fn deserialize<D>(self, de: D) -> Result<(), D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                *self.0 += i64::deserialize(de)?;
+                Ok(())
+            }
+        }
+
+        let mut total = 0;
+        crate::decode_seed(b"i1995e", Add(&mut total)).unwrap();
+        let mut de = crate::de::Deserializer::new(b"i29e");
+        Add(&mut total).deserialize(&mut de).unwrap();
+        assert_eq!(total, 2024);
+
+        let mut ser = crate::ser::Serializer::new(vec![]);
+        (&mut ser).serialize_i64(total).unwrap();
+        assert_eq!(ser.into_inner(), b"i2024e");
+    }
+
+    #[test]
+    fn decode_utf8_map_policies() {
+        use crate::de::TextPolicy;
+
+        let map =
+            crate::decode_utf8_map(b"d1:\xffi-1ee", TextPolicy::Hex).unwrap();
+        assert_eq!(map["ff"], "-1");
+        let map =
+            crate::decode_utf8_map(b"d1:\xffi-1ee", TextPolicy::Lossy).unwrap();
+        assert_eq!(map["\u{fffd}"], "-1");
+
+        assert_eq!(
+            crate::decode_utf8_map(b"d1:a1:b1:cdee", TextPolicy::Error),
+            Err(Error::Wanted {
+                at: 10,
+                expected: "an integer or a byte array",
+                found: "d".into()
+            })
+        );
+        assert!(crate::decode_utf8_map(b"li1ee", TextPolicy::Lossy).is_err());
+
+        // Keys that are converted into the same string collide.
+        let src = b"d1:\xfei1e1:\xffi2ee";
+        assert!(crate::decode_utf8_map(src, TextPolicy::Lossy).is_err());
+        assert!(crate::decode_utf8_map(src, TextPolicy::Hex).is_ok());
+        let src = b"d2:ffi1e1:\xffi2ee";
+        assert!(crate::decode_utf8_map(src, TextPolicy::Hex).is_err());
+    }
 }
@@ -0,0 +1,743 @@
+//! Paths that address values nested in a value tree.
+//!
+//! The types included in this module are:
+//!
+//! * [`Path`] - A sequence of dictionary keys and list indices, eg: `info.files[2].path`.
+//! * [`Segment`] - A single step of a path.
+//! * [`Pattern`] - A path that may contain wildcards, eg: `info.files[*].length`.
+//! * [`PatternSegment`] - A single step of a pattern.
+//! * `Predicate` - A condition that filters the values matched by a pattern, eg: `info.files[*][?(length > 1000)]`. This requires the `query` feature.
+//! * [`ParsePathError`] - An error returned when a string isn't a valid path.
+//!
+//! # Syntax
+//!
+//! Keys are separated by dots, and list indices are written in brackets. The first segment of a path doesn't need a leading dot, and an empty string is the path of the root value. A backslash escapes the next character, so keys containing `.`, `[`, `]`, `*` or `\` can be written as `a\.b`. Keys can't be empty.
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::value::Edge;
+use super::Value;
+
+#[cfg(feature = "query")]
+mod query;
+
+#[cfg(feature = "query")]
+pub use query::Comparison;
+#[cfg(feature = "query")]
+pub use query::Literal;
+#[cfg(feature = "query")]
+pub use query::Predicate;
+
+/// A single step of a [`Path`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Segment {
+    /// A dictionary key.
+    Key(String),
+    /// A list index.
+    Index(usize),
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Segment::Key(ref key) => write_key(f, key),
+            Segment::Index(i) => write!(f, "[{}]", i),
+        }
+    }
+}
+
+impl From<&str> for Segment {
+    fn from(key: &str) -> Self {
+        Segment::Key(key.to_owned())
+    }
+}
+
+impl From<String> for Segment {
+    fn from(key: String) -> Self {
+        Segment::Key(key)
+    }
+}
+
+impl From<usize> for Segment {
+    fn from(i: usize) -> Self {
+        Segment::Index(i)
+    }
+}
+
+impl Segment {
+    /// Constructs the segment of a dictionary key given as bytes.
+    ///
+    /// Paths are text, so a key that isn't valid UTF-8 is named by its lossy conversion, which only serves to report where a value is.
+    pub(crate) fn from_key_bytes(key: &[u8]) -> Self {
+        Segment::Key(String::from_utf8_lossy(key).into_owned())
+    }
+}
+
+/// A sequence of dictionary keys and list indices, that addresses a value nested in a value tree.
+///
+/// See the [module documentation](self) for the syntax of paths.
+///
+/// # Examples
+///
+/// ```
+/// use bende::path::{Path, Segment};
+///
+/// let path: Path = "info.files[2].path".parse().unwrap();
+///
+/// assert_eq!(
+///     path.segments(),
+///     [
+///         Segment::Key("info".into()),
+///         Segment::Key("files".into()),
+///         Segment::Index(2),
+///         Segment::Key("path".into()),
+///     ]
+/// );
+/// assert_eq!(path.to_string(), "info.files[2].path");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Path {
+    segments: Vec<Segment>,
+}
+
+impl Path {
+    /// Constructs the path of the root value, which has no segments.
+    #[inline]
+    pub fn root() -> Path {
+        Path::default()
+    }
+
+    /// Parses a path from a string.
+    pub fn parse(s: &str) -> Result<Path, ParsePathError> {
+        parse_segments(s)?
+            .into_iter()
+            .map(|(at, seg)| match seg {
+                PatternSegment::Key(key) => Ok(Segment::Key(key)),
+                PatternSegment::Index(i) => Ok(Segment::Index(i)),
+                _ => Err(ParsePathError(at)),
+            })
+            .collect()
+    }
+
+    /// Gets the segments of the path.
+    #[inline]
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Checks if this is the path of the root value.
+    #[inline]
+    pub fn is_root(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Appends a segment to the end of the path.
+    #[inline]
+    pub fn push<S>(&mut self, seg: S)
+    where
+        S: Into<Segment>,
+    {
+        self.segments.push(seg.into());
+    }
+
+    /// Removes the last segment of the path and returns it, or `None` if this is the root path.
+    #[inline]
+    pub fn pop(&mut self) -> Option<Segment> {
+        self.segments.pop()
+    }
+
+    /// Returns a new path, with the given segment appended to it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::path::Path;
+    ///
+    /// let path = Path::root().join("info").join("files").join(0);
+    /// assert_eq!(path.to_string(), "info.files[0]");
+    /// ```
+    pub fn join<S>(&self, seg: S) -> Path
+    where
+        S: Into<Segment>,
+    {
+        let mut path = self.clone();
+        path.push(seg);
+        path
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, seg) in self.segments.iter().enumerate() {
+            if i > 0 && matches!(seg, Segment::Key(_)) {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", seg)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Path {
+    type Err = ParsePathError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Path::parse(s)
+    }
+}
+
+impl From<Vec<Segment>> for Path {
+    fn from(segments: Vec<Segment>) -> Self {
+        Path { segments }
+    }
+}
+
+impl FromIterator<Segment> for Path {
+    fn from_iter<I: IntoIterator<Item = Segment>>(iter: I) -> Self {
+        Path { segments: iter.into_iter().collect() }
+    }
+}
+
+/// A single step of a [`Pattern`].
+///
+/// The `Filter` variant only exists with the `query` feature, so the enum is non-exhaustive, and enabling the feature doesn't break matches on it.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum PatternSegment {
+    /// A dictionary key.
+    Key(String),
+    /// A list index.
+    Index(usize),
+    /// Any dictionary key - `*`.
+    AnyKey,
+    /// Any list index - `[*]`.
+    AnyIndex,
+    /// Any number of keys and indices, including none - `**`.
+    AnyDepth,
+    /// Keeps the value matched so far only if the predicate holds for it - `[?(...)]`.
+    #[cfg(feature = "query")]
+    Filter(Predicate),
+}
+
+impl fmt::Display for PatternSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            PatternSegment::Key(ref key) => write_key(f, key),
+            PatternSegment::Index(i) => write!(f, "[{}]", i),
+            PatternSegment::AnyKey => write!(f, "*"),
+            PatternSegment::AnyIndex => write!(f, "[*]"),
+            PatternSegment::AnyDepth => write!(f, "**"),
+            #[cfg(feature = "query")]
+            PatternSegment::Filter(ref pred) => write!(f, "[?({})]", pred),
+        }
+    }
+}
+
+impl From<Segment> for PatternSegment {
+    fn from(seg: Segment) -> Self {
+        match seg {
+            Segment::Key(key) => PatternSegment::Key(key),
+            Segment::Index(i) => PatternSegment::Index(i),
+        }
+    }
+}
+
+/// A [`Path`] that may contain wildcards, so it can address many values at once.
+///
+/// Patterns share the syntax of paths, with the addition of three wildcards: `*` matches any dictionary key, `[*]` matches any list index, and `**` matches any number of keys and indices, including none. Wildcards must make up a whole segment, so a key like `a*` has to be written as `a\*`.
+///
+/// With the `query` feature, patterns can also contain predicates, which filter the values matched so far.
+///
+/// Use [`Value::select`](crate::Value::select) to find the values that match a pattern.
+///
+/// # Examples
+///
+/// ```
+/// use bende::path::Pattern;
+///
+/// let pattern: Pattern = "info.files[*].length".parse().unwrap();
+/// assert!(pattern.matches(&"info.files[3].length".parse().unwrap()));
+/// assert!(!pattern.matches(&"info.length".parse().unwrap()));
+///
+/// let pattern: Pattern = "**.announce".parse().unwrap();
+/// assert!(pattern.matches(&"announce".parse().unwrap()));
+/// assert!(pattern.matches(&"info.sources[0].announce".parse().unwrap()));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pattern {
+    segments: Vec<PatternSegment>,
+}
+
+impl Pattern {
+    /// Parses a pattern from a string.
+    pub fn parse(s: &str) -> Result<Pattern, ParsePathError> {
+        let mut segments: Vec<PatternSegment> = vec![];
+        for (_, seg) in parse_segments(s)? {
+            // Consecutive `**`s match the same paths as a single one.
+            if seg == PatternSegment::AnyDepth
+                && segments.last() == Some(&PatternSegment::AnyDepth)
+            {
+                continue;
+            }
+            segments.push(seg);
+        }
+        Ok(Pattern { segments })
+    }
+
+    /// Gets the segments of the pattern.
+    #[inline]
+    pub fn segments(&self) -> &[PatternSegment] {
+        &self.segments
+    }
+
+    /// Checks if the pattern matches the given path.
+    ///
+    /// Predicates can't be checked without a value, so they're ignored.
+    pub fn matches(&self, path: &Path) -> bool {
+        matches_from(&self.segments, path.segments())
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, seg) in self.segments.iter().enumerate() {
+            let is_index = match *seg {
+                PatternSegment::Index(_) | PatternSegment::AnyIndex => true,
+                #[cfg(feature = "query")]
+                PatternSegment::Filter(_) => true,
+                _ => false,
+            };
+            if i > 0 && !is_index {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", seg)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = ParsePathError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Pattern::parse(s)
+    }
+}
+
+impl From<Path> for Pattern {
+    fn from(path: Path) -> Self {
+        Pattern {
+            segments: path.segments.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<Vec<PatternSegment>> for Pattern {
+    fn from(segments: Vec<PatternSegment>) -> Self {
+        Pattern { segments }
+    }
+}
+
+/// An error returned when a string isn't a valid [`Path`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsePathError(usize);
+
+impl ParsePathError {
+    /// Gets the byte offset into the string at which the error was found.
+    #[inline]
+    pub fn at(&self) -> usize {
+        self.0
+    }
+}
+
+impl fmt::Display for ParsePathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid path at column {}", self.0)
+    }
+}
+
+impl std::error::Error for ParsePathError {}
+
+/// Checks if the character has to be escaped in a key.
+fn is_special(c: char) -> bool {
+    matches!(c, '.' | '[' | ']' | '\\' | '*')
+}
+
+/// Writes a key, escaping its special characters.
+fn write_key(f: &mut fmt::Formatter<'_>, key: &str) -> fmt::Result {
+    for c in key.chars() {
+        if is_special(c) {
+            write!(f, "\\")?;
+        }
+        write!(f, "{}", c)?;
+    }
+    Ok(())
+}
+
+/// Checks if the pattern segments match the path segments.
+fn matches_from(pattern: &[PatternSegment], path: &[Segment]) -> bool {
+    let Some((first, rest)) = pattern.split_first() else {
+        return path.is_empty();
+    };
+    match *first {
+        PatternSegment::AnyDepth => {
+            return (0..=path.len()).any(|i| matches_from(rest, &path[i..]))
+        }
+        #[cfg(feature = "query")]
+        PatternSegment::Filter(_) => return matches_from(rest, path),
+        _ => {}
+    }
+    let Some((seg, path)) = path.split_first() else { return false };
+
+    let matched = match (first, seg) {
+        (PatternSegment::Key(a), Segment::Key(b)) => a == b,
+        (PatternSegment::Index(a), Segment::Index(b)) => a == b,
+        (PatternSegment::AnyKey, Segment::Key(_)) => true,
+        (PatternSegment::AnyIndex, Segment::Index(_)) => true,
+        _ => false,
+    };
+    matched && matches_from(rest, path)
+}
+
+/// Collects the values of the tree that match the pattern, alongside their paths.
+pub(crate) fn select<'a>(
+    pattern: &Pattern,
+    val: &'a Value,
+) -> Vec<(Path, &'a Value)> {
+    let mut found = vec![];
+    select_from(&pattern.segments, vec![0], val, &mut Path::root(), &mut found);
+    found
+}
+
+/// Visits a value with the given states of the pattern, where each state is the index of the next segment to match.
+fn select_from<'a>(
+    pattern: &[PatternSegment],
+    mut states: Vec<usize>,
+    val: &'a Value,
+    path: &mut Path,
+    found: &mut Vec<(Path, &'a Value)>,
+) {
+    // `**` can match no segments at all, and filters never match any, so the
+    // segment after them can be matched right away.
+    let mut i = 0;
+    while i < states.len() {
+        let state = states[i];
+        let skip = match pattern.get(state) {
+            Some(PatternSegment::AnyDepth) => true,
+            #[cfg(feature = "query")]
+            Some(PatternSegment::Filter(pred)) => pred.eval(val),
+            _ => false,
+        };
+        if skip && !states.contains(&(state + 1)) {
+            states.push(state + 1);
+        }
+        i += 1;
+    }
+    if states.contains(&pattern.len()) {
+        found.push((path.clone(), val));
+    }
+
+    let mut visit = |edge: Edge<'_>, child: &'a Value| {
+        let mut next = vec![];
+        for &state in &states {
+            let Some(pat) = pattern.get(state) else { continue };
+            let next_state = match (pat, edge) {
+                (PatternSegment::AnyDepth, _) => state,
+                (PatternSegment::Key(a), Edge::Key(b)) if a.as_bytes() == b => {
+                    state + 1
+                }
+                (PatternSegment::Index(a), Edge::Index(b)) if *a == b => {
+                    state + 1
+                }
+                (PatternSegment::AnyKey, Edge::Key(_))
+                | (PatternSegment::AnyIndex, Edge::Index(_)) => state + 1,
+                _ => continue,
+            };
+            if !next.contains(&next_state) {
+                next.push(next_state);
+            }
+        }
+        if !next.is_empty() {
+            path.push(match edge {
+                Edge::Key(key) => Segment::from_key_bytes(key),
+                Edge::Index(i) => Segment::Index(i),
+                Edge::Root => unreachable!("children are never the root"),
+            });
+            select_from(pattern, next, child, path, found);
+            path.pop();
+        }
+    };
+
+    match *val {
+        Value::List(ref list) => {
+            for (i, child) in list.iter().enumerate() {
+                visit(Edge::Index(i), child);
+            }
+        }
+        Value::Dict(ref dict) => {
+            // Paths can only address keys that are valid UTF-8.
+            for (key, child) in dict.iter().filter(|e| e.0.as_str().is_some()) {
+                visit(Edge::Key(key), child);
+            }
+        }
+        Value::Int(_) | Value::Text(_) => {}
+    }
+}
+
+/// Gets the values at the given paths in a single walk of the tree, so paths that share a prefix only follow it once.
+pub(crate) fn get_many<'a>(
+    paths: &[Path],
+    val: &'a Value,
+) -> Vec<Option<&'a Value>> {
+    let mut order: Vec<usize> = (0..paths.len()).collect();
+    order.sort_unstable_by(|&a, &b| paths[a].cmp(&paths[b]));
+
+    let mut found = vec![None; paths.len()];
+    get_many_from(paths, &order, 0, val, &mut found);
+    found
+}
+
+/// Visits a value with a sorted group of paths, which share their first `depth` segments and lead to the value.
+fn get_many_from<'a>(
+    paths: &[Path],
+    mut group: &[usize],
+    depth: usize,
+    val: &'a Value,
+    found: &mut [Option<&'a Value>],
+) {
+    while let Some(&first) = group.first() {
+        let Some(seg) = paths[first].segments.get(depth) else {
+            // Shorter paths sort first, so the paths that end here are at the front.
+            found[first] = Some(val);
+            group = &group[1..];
+            continue;
+        };
+
+        let len = group
+            .iter()
+            .take_while(|&&i| paths[i].segments.get(depth) == Some(seg))
+            .count();
+        let (same, rest) = group.split_at(len);
+        let child = match *seg {
+            Segment::Key(ref key) => val.as_dict().and_then(|d| d.get(key)),
+            Segment::Index(i) => val.as_list().and_then(|l| l.get(i)),
+        };
+        if let Some(child) = child {
+            get_many_from(paths, same, depth + 1, child, found);
+        }
+        group = rest;
+    }
+}
+
+/// Parses the segments of a pattern, alongside the byte offset each of them starts at.
+fn parse_segments(
+    s: &str,
+) -> Result<Vec<(usize, PatternSegment)>, ParsePathError> {
+    let mut segments = vec![];
+    let mut chars = s.char_indices().peekable();
+
+    while let Some(&(at, c)) = chars.peek() {
+        match c {
+            '[' => {
+                chars.next();
+                #[cfg(feature = "query")]
+                if let Some(&(start, '?')) = chars.peek() {
+                    let (pred, end) = query::parse(s, start + 1)?;
+                    while chars.next_if(|&(i, _)| i < end).is_some() {}
+                    segments.push((at, PatternSegment::Filter(pred)));
+                    continue;
+                }
+                let mut digits = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, ']')) => break,
+                        Some((_, d)) if d.is_ascii_digit() || d == '*' => {
+                            digits.push(d)
+                        }
+                        Some((at, _)) => return Err(ParsePathError(at)),
+                        None => return Err(ParsePathError(s.len())),
+                    }
+                }
+                let seg = if digits == "*" {
+                    PatternSegment::AnyIndex
+                } else {
+                    let i = digits.parse().map_err(|_| ParsePathError(at))?;
+                    PatternSegment::Index(i)
+                };
+                segments.push((at, seg));
+            }
+            '.' if segments.is_empty() => return Err(ParsePathError(at)),
+            _ => {
+                if c == '.' {
+                    chars.next();
+                } else if !segments.is_empty() {
+                    return Err(ParsePathError(at));
+                }
+                let mut key = String::new();
+                let mut escaped = false;
+                let mut wild = false;
+                while let Some(&(at, c)) = chars.peek() {
+                    match c {
+                        '.' | '[' => break,
+                        ']' => return Err(ParsePathError(at)),
+                        '\\' => {
+                            chars.next();
+                            let (_, c) =
+                                chars.next().ok_or(ParsePathError(s.len()))?;
+                            key.push(c);
+                            escaped = true;
+                            continue;
+                        }
+                        '*' => {
+                            key.push(c);
+                            wild = true;
+                        }
+                        _ => key.push(c),
+                    }
+                    chars.next();
+                }
+                let seg = match (key.as_str(), escaped, wild) {
+                    ("", ..) => return Err(ParsePathError(at)),
+                    ("*", false, true) => PatternSegment::AnyKey,
+                    ("**", false, true) => PatternSegment::AnyDepth,
+                    (_, _, true) => return Err(ParsePathError(at)),
+                    (..) => PatternSegment::Key(key),
+                };
+                segments.push((at, seg));
+            }
+        }
+    }
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ParsePathError, Path, Pattern, PatternSegment, Segment};
+    use crate::Value;
+
+    fn key(k: &str) -> Segment {
+        Segment::Key(k.to_owned())
+    }
+
+    #[test]
+    fn parse_path() {
+        assert_eq!(Path::parse(""), Ok(Path::root()));
+        assert_eq!(Path::parse("[3]"), Ok(Path::from(vec![Segment::Index(3)])));
+        assert_eq!(
+            Path::parse("a[0][1].b"),
+            Ok(Path::from(vec![
+                key("a"),
+                Segment::Index(0),
+                Segment::Index(1),
+                key("b")
+            ]))
+        );
+    }
+
+    #[test]
+    fn parse_path_escapes() {
+        let path = Path::parse(r"a\.b.c\[0\]\\").unwrap();
+        assert_eq!(path.segments(), [key("a.b"), key(r"c[0]\")]);
+        assert_eq!(path.to_string(), r"a\.b.c\[0\]\\");
+    }
+
+    #[test]
+    fn parse_path_invalid() {
+        assert_eq!(Path::parse(".a"), Err(ParsePathError(0)));
+        assert_eq!(Path::parse("a..b"), Err(ParsePathError(1)));
+        assert_eq!(Path::parse("a."), Err(ParsePathError(1)));
+        assert_eq!(Path::parse("a[x]"), Err(ParsePathError(2)));
+        assert_eq!(Path::parse("a[1"), Err(ParsePathError(3)));
+        assert_eq!(Path::parse("a[]"), Err(ParsePathError(1)));
+        assert_eq!(Path::parse("a]"), Err(ParsePathError(1)));
+        assert_eq!(Path::parse(r"a\"), Err(ParsePathError(2)));
+        assert_eq!(Path::parse("a[0]b"), Err(ParsePathError(4)));
+    }
+
+    #[test]
+    fn path_round_trips() {
+        let path = Path::root().join("a.b").join(1).join("").join("c");
+        // Empty keys can be constructed, but not parsed.
+        assert!(Path::parse(&path.to_string()).is_err());
+
+        let path = Path::root().join("announce-list").join(0).join(1);
+        assert_eq!(Path::parse(&path.to_string()), Ok(path));
+    }
+
+    #[test]
+    fn parse_pattern() {
+        let pattern = Pattern::parse(r"**.**.a[*].*.\*").unwrap();
+        assert_eq!(
+            pattern.segments(),
+            [
+                PatternSegment::AnyDepth,
+                PatternSegment::Key("a".into()),
+                PatternSegment::AnyIndex,
+                PatternSegment::AnyKey,
+                PatternSegment::Key("*".into()),
+            ]
+        );
+        assert_eq!(pattern.to_string(), r"**.a[*].*.\*");
+
+        assert_eq!(Pattern::parse("a*"), Err(ParsePathError(0)));
+        assert_eq!(Pattern::parse("a.***"), Err(ParsePathError(1)));
+        assert_eq!(Pattern::parse("a[*1]"), Err(ParsePathError(1)));
+        assert_eq!(Path::parse("a.*"), Err(ParsePathError(1)));
+        assert_eq!(Path::parse("[*]"), Err(ParsePathError(0)));
+    }
+
+    #[test]
+    fn pattern_matches() {
+        let matches = |pattern: &str, path: &str| {
+            Pattern::parse(pattern).unwrap().matches(&path.parse().unwrap())
+        };
+
+        assert!(matches("", ""));
+        assert!(matches("**", ""));
+        assert!(matches("**", "a[0].b"));
+        assert!(matches("a.**.b", "a.b"));
+        assert!(matches("a.**.b", "a[1].c.b"));
+        assert!(!matches("a.**.b", "a.b.c"));
+        assert!(matches("*[*]", "a[1]"));
+        assert!(!matches("*", "[1]"));
+        assert!(!matches("[*]", "a"));
+    }
+
+    #[test]
+    fn select_values() {
+        let val: Value =
+            crate::decode(b"d1:ad1:bi1ee1:bld1:bi2eei3ee1:ci4ee").unwrap();
+        let select = |pattern: &str| {
+            val.select(&pattern.parse().unwrap())
+                .into_iter()
+                .map(|(path, val)| (path.to_string(), val.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            select("**.b"),
+            [
+                ("a.b".into(), Value::Int(1)),
+                ("b".into(), val.as_dict().unwrap()["b"].clone()),
+                ("b[0].b".into(), Value::Int(2)),
+            ]
+        );
+        assert_eq!(
+            select("b[*]"),
+            [
+                (
+                    "b[0]".into(),
+                    val.get_path(&"b[0]".parse().unwrap()).unwrap().clone()
+                ),
+                ("b[1]".into(), Value::Int(3)),
+            ]
+        );
+        // Overlapping wildcards still select each value once.
+        assert_eq!(select("**.**").len(), 8);
+        assert_eq!(select("**.*.**").len(), 7);
+        assert!(select("c.d").is_empty());
+    }
+}
@@ -0,0 +1,456 @@
+//! Predicates that filter the values matched by a pattern.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use super::ParsePathError;
+use super::Path;
+use crate::Value;
+
+/// A comparison operator of a [`Predicate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Comparison {
+    /// `==`
+    Eq,
+    /// `!=`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+}
+
+impl Comparison {
+    /// Checks if the ordering of two values satisfies the comparison, where `None` means the values can't be compared.
+    fn holds(self, ord: Option<Ordering>) -> bool {
+        match self {
+            Comparison::Eq => ord == Some(Ordering::Equal),
+            Comparison::Ne => ord != Some(Ordering::Equal),
+            Comparison::Lt => ord == Some(Ordering::Less),
+            Comparison::Le => {
+                matches!(ord, Some(Ordering::Less | Ordering::Equal))
+            }
+            Comparison::Gt => ord == Some(Ordering::Greater),
+            Comparison::Ge => {
+                matches!(ord, Some(Ordering::Greater | Ordering::Equal))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match *self {
+            Comparison::Eq => "==",
+            Comparison::Ne => "!=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+/// A value that a [`Predicate`] compares against.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Literal {
+    /// An integer, eg: `1995`.
+    Int(i64),
+    /// A byte array, written as a quoted string, eg: `"foo"`.
+    Text(Vec<u8>),
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Literal::Int(v) => write!(f, "{}", v),
+            Literal::Text(ref v) => {
+                write!(f, "\"")?;
+                for c in String::from_utf8_lossy(v).chars() {
+                    if c == '"' || c == '\\' {
+                        write!(f, "\\")?;
+                    }
+                    write!(f, "{}", c)?;
+                }
+                write!(f, "\"")
+            }
+        }
+    }
+}
+
+impl From<i64> for Literal {
+    fn from(v: i64) -> Self {
+        Literal::Int(v)
+    }
+}
+
+impl From<&str> for Literal {
+    fn from(v: &str) -> Self {
+        Literal::Text(v.as_bytes().to_vec())
+    }
+}
+
+impl From<&[u8]> for Literal {
+    fn from(v: &[u8]) -> Self {
+        Literal::Text(v.to_vec())
+    }
+}
+
+/// A condition on a value, used to filter the values matched by a [`Pattern`](super::Pattern).
+///
+/// In a pattern, a predicate is written as `[?(...)]` and keeps the value matched so far only if the condition holds for it. The condition compares a value at a path relative to the matched value - or the matched value itself, written as `@` - against an integer or a quoted string. Conditions can be combined with `&&` and `||`, and grouped with parentheses. A relative path on its own checks that there's a value at it.
+///
+/// Values of different types are never equal, and never ordered.
+///
+/// # Examples
+///
+/// ```
+/// use bende::Value;
+/// use bende::path::{Comparison, Predicate};
+///
+/// let val: Value = bende::decode(b"d4:infod5:filesld6:lengthi3eed6:lengthi5eeeee").unwrap();
+///
+/// let large = val.select(&"info.files[*][?(length > 4)]".parse().unwrap());
+/// assert_eq!(large[0].0.to_string(), "info.files[1]");
+///
+/// let pred = Predicate::compare("length".parse().unwrap(), Comparison::Gt, 4);
+/// assert_eq!(pred.to_string(), "length > 4");
+/// assert!(pred.eval(large[0].1));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Predicate {
+    /// Holds if there's a value at the path.
+    Exists(Path),
+    /// Holds if the value at the path compares to the literal.
+    Compare(Path, Comparison, Literal),
+    /// Holds if both predicates hold.
+    And(Box<Predicate>, Box<Predicate>),
+    /// Holds if either predicate holds.
+    Or(Box<Predicate>, Box<Predicate>),
+}
+
+impl Predicate {
+    /// Constructs a predicate that holds if there's a value at the given path.
+    #[inline]
+    pub fn exists(path: Path) -> Predicate {
+        Predicate::Exists(path)
+    }
+
+    /// Constructs a predicate that holds if the value at the given path compares to the literal.
+    #[inline]
+    pub fn compare<L>(path: Path, cmp: Comparison, lit: L) -> Predicate
+    where
+        L: Into<Literal>,
+    {
+        Predicate::Compare(path, cmp, lit.into())
+    }
+
+    /// Combines the predicates, so that both of them have to hold.
+    #[inline]
+    pub fn and(self, other: Predicate) -> Predicate {
+        Predicate::And(Box::new(self), Box::new(other))
+    }
+
+    /// Combines the predicates, so that either of them has to hold.
+    #[inline]
+    pub fn or(self, other: Predicate) -> Predicate {
+        Predicate::Or(Box::new(self), Box::new(other))
+    }
+
+    /// Checks if the predicate holds for the given value.
+    pub fn eval(&self, val: &Value) -> bool {
+        match *self {
+            Predicate::Exists(ref path) => val.get_path(path).is_some(),
+            Predicate::Compare(ref path, cmp, ref lit) => {
+                let ord = match (val.get_path(path), lit) {
+                    (Some(Value::Int(a)), Literal::Int(b)) => Some(a.cmp(b)),
+                    (Some(Value::Text(a)), Literal::Text(b)) => {
+                        Some(a.as_slice().cmp(b))
+                    }
+                    _ => None,
+                };
+                cmp.holds(ord)
+            }
+            Predicate::And(ref a, ref b) => a.eval(val) && b.eval(val),
+            Predicate::Or(ref a, ref b) => a.eval(val) || b.eval(val),
+        }
+    }
+}
+
+impl fmt::Display for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn write_path(f: &mut fmt::Formatter<'_>, path: &Path) -> fmt::Result {
+            if path.is_root() {
+                write!(f, "@")
+            } else {
+                write!(f, "{}", path)
+            }
+        }
+
+        match *self {
+            Predicate::Exists(ref path) => write_path(f, path),
+            Predicate::Compare(ref path, cmp, ref lit) => {
+                write_path(f, path)?;
+                write!(f, " {} {}", cmp, lit)
+            }
+            Predicate::And(ref a, ref b) => {
+                for (i, p) in [a, b].into_iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " && ")?;
+                    }
+                    match **p {
+                        Predicate::Or(..) => write!(f, "({})", p)?,
+                        _ => write!(f, "{}", p)?,
+                    }
+                }
+                Ok(())
+            }
+            Predicate::Or(ref a, ref b) => write!(f, "{} || {}", a, b),
+        }
+    }
+}
+
+/// Parses a predicate that starts at the given byte offset of the pattern, right after its `[?`.
+///
+/// Returns the predicate, alongside the offset right after its closing `]`.
+pub(super) fn parse(
+    s: &str,
+    start: usize,
+) -> Result<(Predicate, usize), ParsePathError> {
+    let mut parser = Parser { s, pos: start };
+    let pred = parser.parse_or()?;
+    parser.skip_whitespace();
+    parser.expect("]")?;
+    Ok((pred, parser.pos))
+}
+
+/// A recursive-descent parser over a predicate.
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn rest(&self) -> &str {
+        &self.s[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consumes the token if it's next, after any whitespace.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        let found = self.rest().starts_with(token);
+        if found {
+            self.pos += token.len();
+        }
+        found
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), ParsePathError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(ParsePathError(self.pos))
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Predicate, ParsePathError> {
+        let mut pred = self.parse_and()?;
+        while self.eat("||") {
+            pred = pred.or(self.parse_and()?);
+        }
+        Ok(pred)
+    }
+
+    fn parse_and(&mut self) -> Result<Predicate, ParsePathError> {
+        let mut pred = self.parse_atom()?;
+        while self.eat("&&") {
+            pred = pred.and(self.parse_atom()?);
+        }
+        Ok(pred)
+    }
+
+    fn parse_atom(&mut self) -> Result<Predicate, ParsePathError> {
+        if self.eat("(") {
+            let pred = self.parse_or()?;
+            self.expect(")")?;
+            return Ok(pred);
+        }
+
+        let path = self.parse_path()?;
+        let cmp = if self.eat("==") {
+            Comparison::Eq
+        } else if self.eat("!=") {
+            Comparison::Ne
+        } else if self.eat("<=") {
+            Comparison::Le
+        } else if self.eat(">=") {
+            Comparison::Ge
+        } else if self.eat("<") {
+            Comparison::Lt
+        } else if self.eat(">") {
+            Comparison::Gt
+        } else {
+            return Ok(Predicate::Exists(path));
+        };
+        Ok(Predicate::Compare(path, cmp, self.parse_literal()?))
+    }
+
+    fn parse_path(&mut self) -> Result<Path, ParsePathError> {
+        if self.eat("@") {
+            return Ok(Path::root());
+        }
+
+        let start = self.pos;
+        let mut chars = self.rest().char_indices();
+        let mut len = self.rest().len();
+        // Brackets of list indices don't end the path, unlike the bracket that
+        // closes the predicate.
+        let mut indices = 0;
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => {
+                    chars.next();
+                }
+                '[' => indices += 1,
+                ']' if indices > 0 => indices -= 1,
+                '=' | '!' | '<' | '>' | '&' | '|' | '(' | ')' | ']' => {
+                    len = i;
+                    break;
+                }
+                _ if c.is_whitespace() => {
+                    len = i;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        if len == 0 {
+            return Err(ParsePathError(start));
+        }
+        self.pos += len;
+        Path::parse(&self.s[start..self.pos])
+            .map_err(|e| ParsePathError(start + e.at()))
+    }
+
+    fn parse_literal(&mut self) -> Result<Literal, ParsePathError> {
+        self.skip_whitespace();
+        let start = self.pos;
+
+        if self.eat("\"") {
+            let mut text = String::new();
+            let mut chars = self.rest().char_indices();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '"' => {
+                        self.pos += i + 1;
+                        return Ok(Literal::Text(text.into_bytes()));
+                    }
+                    '\\' => match chars.next() {
+                        Some((_, c)) => text.push(c),
+                        None => break,
+                    },
+                    _ => text.push(c),
+                }
+            }
+            return Err(ParsePathError(self.s.len()));
+        }
+
+        let rest = self.rest();
+        let sign = usize::from(rest.starts_with('-'));
+        let len =
+            sign + rest[sign..].bytes().take_while(u8::is_ascii_digit).count();
+        let v = rest[..len].parse().map_err(|_| ParsePathError(start))?;
+        self.pos += len;
+        Ok(Literal::Int(v))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Comparison, Literal, Predicate};
+    use crate::path::{ParsePathError, Path, Pattern};
+    use crate::Value;
+
+    fn parse(s: &str) -> Result<Predicate, ParsePathError> {
+        let (pred, end) = super::parse(s, 0)?;
+        assert_eq!(end, s.len());
+        Ok(pred)
+    }
+
+    fn path(s: &str) -> Path {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_predicate() {
+        assert_eq!(parse("(private)]"), Ok(Predicate::exists(path("private"))));
+        assert_eq!(
+            parse(r#"( @ != "a\"b" )]"#),
+            Ok(Predicate::compare(Path::root(), Comparison::Ne, "a\"b"))
+        );
+        assert_eq!(
+            parse("(a[0]>=-1 && (b || c<2))]"),
+            Ok(Predicate::compare(path("a[0]"), Comparison::Ge, -1).and(
+                Predicate::exists(path("b")).or(Predicate::compare(
+                    path("c"),
+                    Comparison::Lt,
+                    2
+                ))
+            ))
+        );
+
+        assert_eq!(parse("(a > )]"), Err(ParsePathError(5)));
+        assert_eq!(parse("(a > 1]"), Err(ParsePathError(6)));
+        assert_eq!(parse("(a > 1)"), Err(ParsePathError(7)));
+        assert_eq!(parse("(a.* > 1)]"), Err(ParsePathError(2)));
+        assert_eq!(parse(r#"(a == "b)]"#), Err(ParsePathError(10)));
+    }
+
+    #[test]
+    fn predicate_round_trips() {
+        let pattern: Pattern =
+            r#"a[*][?((b || c) && @ == "x\\y")].d[?(e)]"#.parse().unwrap();
+        assert_eq!(
+            pattern.to_string(),
+            r#"a[*][?((b || c) && @ == "x\\y")].d[?(e)]"#
+        );
+    }
+
+    #[test]
+    fn eval_predicate() {
+        let val: Value = crate::decode(b"d1:ai5e1:b3:fooe").unwrap();
+
+        assert!(Predicate::compare(path("a"), Comparison::Ge, 5).eval(&val));
+        assert!(!Predicate::compare(path("a"), Comparison::Gt, 5).eval(&val));
+        assert!(Predicate::compare(path("b"), Comparison::Lt, "g").eval(&val));
+        assert!(!Predicate::compare(path("b"), Comparison::Lt, 9).eval(&val));
+        assert!(!Predicate::compare(path("b"), Comparison::Ge, 9).eval(&val));
+        assert!(Predicate::compare(path("b"), Comparison::Ne, 9).eval(&val));
+        assert!(!Predicate::compare(path("c"), Comparison::Eq, 9).eval(&val));
+        assert!(Predicate::exists(path("c"))
+            .or(Predicate::Compare(path("a"), Comparison::Eq, Literal::Int(5)))
+            .eval(&val));
+    }
+
+    #[test]
+    fn select_with_predicate() {
+        let val: Value =
+            crate::decode(b"ld4:infod7:privatei1eeed4:infod7:privatei0eeee")
+                .unwrap();
+        let private =
+            val.select(&"[*][?(info.private == 1)].info".parse().unwrap());
+
+        assert_eq!(private.len(), 1);
+        assert_eq!(private[0].0.to_string(), "[0].info");
+    }
+}
@@ -0,0 +1,147 @@
+//! Encoding and decoding with thread-local buffers.
+//!
+//! The functions of this module work like the ones at the root of the crate, but each thread keeps its buffers between calls, so encoding many values, or reading many of them, stops allocating buffers once they're large enough. Buffers that grow past [`MAX_RETAINED`] bytes are released after the call, so one large value doesn't hold on to its memory for the rest of the thread's life. The functions included in this module are:
+//!
+//! * [`encode`] - Encodes a value through the thread's [`Encoder`], copying the bytes into a buffer of their exact size.
+//! * [`encode_with`] - Encodes a value through the thread's encoder, and lends the bytes to a closure without copying them.
+//! * [`decode`] - Decodes a value, exactly like [`crate::decode`], as decoding from a slice doesn't allocate any buffers of its own.
+//! * [`from_reader`] - Reads a value into the thread's read buffer, and decodes it.
+//!
+//! For control over the encoder's settings, or an encoder shared between threads, use [`Encoder::encode`] and [`EncoderPool`](crate::en::EncoderPool) instead.
+
+use std::cell::RefCell;
+use std::io::Read;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::de;
+use super::en;
+use super::en::Encoder;
+
+/// The number of bytes a thread's buffers may keep allocated between calls.
+pub const MAX_RETAINED: usize = 1 << 20;
+
+thread_local! {
+    static ENCODER: RefCell<Encoder<Vec<u8>>> = RefCell::new(Encoder::new(Vec::new()));
+    static READ_BUF: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Encodes the given value into bencode representation, reusing the thread's buffers.
+///
+/// # Examples
+///
+/// ```
+/// for port in [6881, 6882] {
+///     let bytes = bende::pooled::encode(&("ping", port)).unwrap();
+///     assert_eq!(bytes, bende::encode(&("ping", port)).unwrap());
+/// }
+/// ```
+pub fn encode<T>(val: &T) -> Result<Vec<u8>, en::Error>
+where
+    T: ?Sized + Serialize,
+{
+    encode_with(val, <[u8]>::to_vec)
+}
+
+/// Encodes the given value into bencode representation, reusing the thread's buffers, and passes the encoded bytes to the closure.
+///
+/// The closure may encode other values through this module, though they won't reuse the thread's buffers, as the closure is still using them.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Write;
+///
+/// let mut socket = Vec::new();
+/// bende::pooled::encode_with(&("ping", 6881), |bytes| socket.write_all(bytes))
+///     .unwrap()
+///     .unwrap();
+///
+/// assert_eq!(socket, b"l4:pingi6881ee");
+/// ```
+pub fn encode_with<T, F, R>(val: &T, f: F) -> Result<R, en::Error>
+where
+    T: ?Sized + Serialize,
+    F: FnOnce(&[u8]) -> R,
+{
+    ENCODER.with(|cell| {
+        // The encoder is already in use if a value's `Serialize` implementation, or the closure, encodes another value.
+        let Ok(mut en) = cell.try_borrow_mut() else {
+            return Encoder::new(Vec::new()).encode(val).map(f);
+        };
+        let res = en.encode(val).map(f);
+        if en.allocated() > MAX_RETAINED {
+            *en = Encoder::new(Vec::new());
+        }
+        res
+    })
+}
+
+/// Decodes a type from the given bencoded bytes.
+///
+/// This is the same as [`crate::decode`], which has no buffers to reuse, and is only here so code can switch to this module wholesale.
+pub fn decode<'de, T>(bytes: &'de [u8]) -> Result<T, de::Error>
+where
+    T: Deserialize<'de>,
+{
+    super::decode(bytes)
+}
+
+/// Decodes a type from the bencoded bytes of the given reader, reading them into the thread's read buffer.
+///
+/// # Examples
+///
+/// ```
+/// let list: Vec<String> = bende::pooled::from_reader(&b"l3:foo3:bare"[..]).unwrap();
+/// assert_eq!(list, ["foo", "bar"]);
+/// ```
+pub fn from_reader<R, T>(mut reader: R) -> Result<T, de::Error>
+where
+    R: Read,
+    T: DeserializeOwned,
+{
+    READ_BUF.with(|cell| {
+        let Ok(mut buf) = cell.try_borrow_mut() else {
+            return super::from_reader(reader);
+        };
+        buf.clear();
+        let res = match reader.read_to_end(&mut buf) {
+            Ok(_) => super::decode(&buf),
+            Err(e) => Err(e.into()),
+        };
+        if buf.capacity() > MAX_RETAINED {
+            *buf = Vec::new();
+        }
+        res
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::{encode, encode_with, from_reader, ENCODER, MAX_RETAINED};
+
+    #[test]
+    fn pooled_encode_reuses_and_releases() {
+        let map = BTreeMap::from([("a", vec![1]), ("b", vec![2, 3])]);
+        assert_eq!(encode(&map).unwrap(), crate::encode(&map).unwrap());
+        let allocated = ENCODER.with(|cell| cell.borrow().allocated());
+        assert!(allocated > 0);
+
+        // Encoding from inside the closure falls back to a new encoder.
+        let nested =
+            encode_with(&1, |outer| (outer.to_vec(), encode(&2).unwrap()));
+        assert_eq!(nested.unwrap(), (b"i1e".to_vec(), b"i2e".to_vec()));
+
+        let large = serde_bytes::ByteBuf::from(vec![0; MAX_RETAINED]);
+        assert_eq!(encode(&large).unwrap().len(), MAX_RETAINED + 8);
+        assert_eq!(ENCODER.with(|cell| cell.borrow().allocated()), 0);
+
+        let list: Vec<i64> = from_reader(&b"li1ei2ee"[..]).unwrap();
+        assert_eq!(list, [1, 2]);
+        assert!(from_reader::<_, Vec<i64>>(&b"li1e"[..]).is_err());
+    }
+}
@@ -0,0 +1,130 @@
+//! Named bundles of encoding and decoding knobs that match other bencode implementations.
+//!
+//! Most interop problems come down to how an implementation reads booleans, missing values and integer keys. A profile sets all of those knobs at once, on both the [`Encoder`](crate::en::Encoder) and the [`Decoder`](crate::de::Decoder):
+//!
+//! * [`Profile`] - The supported profiles, which can also be parsed from their names, eg: from a configuration file.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// An interop profile, selected with `Encoder::with_profile` and `Decoder::with_profile`.
+///
+/// | Profile | `None` values | Integer keys | Booleans read |
+/// |---|---|---|---|
+/// | `Strict` | Fail the encode | Fail the encode | `i0e` and `i1e` |
+/// | `Libtorrent` | Left out of dictionaries | Written as digits | Any integer, non-zero is `true` |
+/// | `Transmission` | Left out of dictionaries | Written as digits | `i0e`, `i1e`, `4:true` and `5:false` |
+///
+/// # Examples
+///
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// use bende::de::Decoder;
+/// use bende::en::Encoder;
+/// use bende::profile::Profile;
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Info {
+///     comment: Option<String>,
+///     private: bool,
+/// }
+///
+/// let profile: Profile = "libtorrent-compatible".parse().unwrap();
+///
+/// let mut en = Encoder::new(vec![]).with_profile(profile);
+/// Info { comment: None, private: true }.serialize(&mut en).unwrap();
+/// assert_eq!(en.into_inner(), b"d7:privatei1ee");
+///
+/// let mut de = Decoder::new(b"d7:privatei2ee").with_profile(profile);
+/// assert_eq!(Info::deserialize(&mut de).unwrap(), Info { comment: None, private: true });
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Profile {
+    /// The defaults of this crate, which only produce and accept canonical values.
+    #[default]
+    Strict,
+    /// Matches libtorrent, named `libtorrent-compatible`.
+    Libtorrent,
+    /// Matches Transmission, named `transmission-compatible`.
+    Transmission,
+}
+
+impl Profile {
+    /// Every profile, in the order they're declared in.
+    pub const ALL: [Profile; 3] =
+        [Profile::Strict, Profile::Libtorrent, Profile::Transmission];
+
+    /// Gets the name of the profile, which is what it's parsed from.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Profile::Strict => "strict",
+            Profile::Libtorrent => "libtorrent-compatible",
+            Profile::Transmission => "transmission-compatible",
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Profile {
+    type Err = ParseProfileError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Profile::ALL
+            .into_iter()
+            .find(|profile| profile.name() == s)
+            .ok_or_else(|| ParseProfileError(s.to_owned()))
+    }
+}
+
+/// An error returned when a string isn't the name of a [`Profile`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseProfileError(String);
+
+impl fmt::Display for ParseProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown profile '{}'", self.0)
+    }
+}
+
+impl std::error::Error for ParseProfileError {}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::Profile;
+    use crate::de::Decoder;
+    use crate::en::Encoder;
+
+    #[test]
+    fn profiles_round_trip() {
+        for profile in Profile::ALL {
+            assert_eq!(profile.name().parse(), Ok(profile));
+        }
+        assert!("libtorrent".parse::<Profile>().is_err());
+
+        let map = BTreeMap::from([(1, Some(true)), (2, None)]);
+        let mut en = Encoder::new(vec![]).with_profile(Profile::Transmission);
+        map.serialize(&mut en).unwrap();
+        assert_eq!(en.into_inner(), b"d1:1i1ee");
+        let mut en = Encoder::new(vec![]).with_profile(Profile::Strict);
+        assert!(map.serialize(&mut en).is_err());
+
+        let src = b"li1e4:truei0e5:falsee";
+        let mut de = Decoder::new(src).with_profile(Profile::Transmission);
+        assert_eq!(
+            Vec::<bool>::deserialize(&mut de).unwrap(),
+            [true, true, false, false]
+        );
+        let mut de = Decoder::new(src).with_profile(Profile::Libtorrent);
+        assert!(Vec::<bool>::deserialize(&mut de).is_err());
+    }
+}
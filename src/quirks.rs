@@ -0,0 +1,250 @@
+//! Lenient decoding of torrents with known real-world malformations.
+//!
+//! Torrents made by old or buggy clients are still around in archives, and some of their mistakes are common enough to be recognized and fixed. Each fix is a [`Quirk`] that can be allowed or denied on its own:
+//!
+//! * [`Quirks`] - The set of quirks that are accepted, which normalizes torrents into bytes the strict decoder accepts.
+//!
+//! Fixes are applied with the [`Rewriter`], so every value that isn't fixed, including the `info` dictionary and so the infohash, is kept byte for byte.
+
+use std::borrow::Cow;
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+
+use super::de::Error;
+use super::path::Path;
+use super::rewrite::Rewriter;
+
+/// A known malformation of real-world torrents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Quirk {
+    /// The `creation date` is negative, as written by some historical versions of uTorrent. The date is dropped, as the time it was meant to be can't be recovered.
+    NegativeCreationDate,
+    /// The `announce` URL is an empty string, as written by tools that make trackerless torrents. It's dropped, as if there was no tracker.
+    EmptyAnnounce,
+    /// A stray `e` follows the torrent, as appended by tools that close the root dictionary twice. It's cut off.
+    TrailingEnd,
+}
+
+impl Quirk {
+    /// Every quirk, in the order they're declared in.
+    pub const ALL: [Quirk; 3] =
+        [Quirk::NegativeCreationDate, Quirk::EmptyAnnounce, Quirk::TrailingEnd];
+
+    /// Checks if the encoded value of the key the quirk is about is malformed the way the quirk describes.
+    fn matches(self, raw: &[u8]) -> bool {
+        match self {
+            Quirk::NegativeCreationDate => raw.starts_with(b"i-"),
+            Quirk::EmptyAnnounce => raw == b"0:",
+            Quirk::TrailingEnd => false,
+        }
+    }
+
+    /// Gets the bit of the quirk in a [`Quirks`] set.
+    #[inline]
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+impl fmt::Display for Quirk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Quirk::NegativeCreationDate => write!(f, "negative creation date"),
+            Quirk::EmptyAnnounce => write!(f, "empty announce URL"),
+            Quirk::TrailingEnd => write!(f, "stray trailing 'e'"),
+        }
+    }
+}
+
+/// The set of quirks accepted when normalizing a torrent. Every quirk is accepted by default.
+///
+/// # Examples
+///
+/// ```
+/// use serde::Deserialize;
+/// use bende::quirks::{Quirk, Quirks};
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct Torrent {
+///     announce: Option<String>,
+///     #[serde(rename = "creation date")]
+///     creation_date: Option<u64>,
+/// }
+///
+/// let src = b"d8:announce0:13:creation datei-1eee";
+/// assert!(bende::decode::<Torrent>(src).is_err());
+///
+/// let quirks = Quirks::all();
+/// let (bytes, found) = quirks.normalize(src).unwrap();
+/// assert_eq!(&*bytes, b"de");
+/// assert_eq!(found, [Quirk::TrailingEnd, Quirk::EmptyAnnounce, Quirk::NegativeCreationDate]);
+///
+/// let torrent: Torrent = quirks.decode(src).unwrap();
+/// assert_eq!(torrent, Torrent { announce: None, creation_date: None });
+///
+/// assert!(quirks.deny(Quirk::TrailingEnd).decode::<Torrent>(src).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Quirks(u8);
+
+impl Quirks {
+    /// Constructs a set that accepts every quirk.
+    #[inline]
+    pub fn all() -> Quirks {
+        Quirk::ALL.into_iter().fold(Quirks::none(), Quirks::allow)
+    }
+
+    /// Constructs a set that doesn't accept any quirk, so torrents are only checked for trailing bytes.
+    #[inline]
+    pub fn none() -> Quirks {
+        Quirks(0)
+    }
+
+    /// Accepts the given quirk.
+    #[inline]
+    pub fn allow(self, quirk: Quirk) -> Quirks {
+        Quirks(self.0 | quirk.bit())
+    }
+
+    /// Stops accepting the given quirk.
+    #[inline]
+    pub fn deny(self, quirk: Quirk) -> Quirks {
+        Quirks(self.0 & !quirk.bit())
+    }
+
+    /// Checks if the given quirk is accepted.
+    #[inline]
+    pub fn allows(&self, quirk: Quirk) -> bool {
+        self.0 & quirk.bit() != 0
+    }
+
+    /// Fixes the accepted quirks of a torrent, returning its normalized bytes and the quirks that were fixed, in the order they were found.
+    ///
+    /// The source is borrowed as is if it has no quirks.
+    ///
+    /// # Errors
+    ///
+    /// * The source is malformed or incomplete.
+    /// * Bytes other than an accepted stray `e` follow the torrent, at which point `Error::Malformed` is returned.
+    pub fn normalize<'a>(
+        &self,
+        src: &'a [u8],
+    ) -> Result<(Cow<'a, [u8]>, Vec<Quirk>), Error> {
+        let mut found = Vec::new();
+        let len = super::frame_len(src)?.ok_or(Error::EOF)?;
+        let src = match &src[len..] {
+            [] => src,
+            b"e" if self.allows(Quirk::TrailingEnd) => {
+                found.push(Quirk::TrailingEnd);
+                &src[..len]
+            }
+            _ => return Err(Error::Malformed),
+        };
+
+        let mut rewriter = Rewriter::new();
+        let keys = [
+            (Quirk::EmptyAnnounce, "announce"),
+            (Quirk::NegativeCreationDate, "creation date"),
+        ];
+        let mut edited = false;
+        for (quirk, key) in keys {
+            if !self.allows(quirk) {
+                continue;
+            }
+            let path = Path::root().join(key);
+            if super::raw_slice(src, &path)?
+                .is_some_and(|raw| quirk.matches(raw))
+            {
+                found.push(quirk);
+                rewriter = rewriter.drop(path);
+                edited = true;
+            }
+        }
+
+        match edited {
+            true => Ok((Cow::Owned(rewriter.rewrite_to_vec(src)?), found)),
+            false => Ok((Cow::Borrowed(src), found)),
+        }
+    }
+
+    /// Fixes the accepted quirks of a torrent, and decodes it.
+    ///
+    /// # Errors
+    ///
+    /// * The source can't be normalized. See [`normalize`](Quirks::normalize).
+    /// * The normalized torrent can't be decoded into the type.
+    pub fn decode<T>(&self, src: &[u8]) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        let (bytes, _) = self.normalize(src)?;
+        super::decode(&bytes)
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::all()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Quirk, Quirks};
+    use crate::de::Error;
+
+    /// Samples of each quirk, modelled on torrents found in the wild, alongside their normalized bytes.
+    const CORPUS: [(Quirk, &[u8], &[u8]); 4] = [
+        (
+            Quirk::NegativeCreationDate,
+            b"d10:created by10:uTorrent/113:creation datei-1171307622e4:infod6:lengthi1e4:name1:aee",
+            b"d10:created by10:uTorrent/14:infod6:lengthi1e4:name1:aee",
+        ),
+        (
+            Quirk::EmptyAnnounce,
+            b"d8:announce0:13:announce-listll12:udp://a:6969ee4:infod6:lengthi1e4:name1:aee",
+            b"d13:announce-listll12:udp://a:6969ee4:infod6:lengthi1e4:name1:aee",
+        ),
+        (
+            Quirk::TrailingEnd,
+            b"d4:infod6:lengthi1e4:name1:aeee",
+            b"d4:infod6:lengthi1e4:name1:aee",
+        ),
+        (
+            Quirk::TrailingEnd,
+            b"d8:announce12:udp://a:6969ee",
+            b"d8:announce12:udp://a:6969e",
+        ),
+    ];
+
+    #[test]
+    fn normalize_corpus() {
+        for (quirk, src, expected) in CORPUS {
+            let (bytes, found) = Quirks::all().normalize(src).unwrap();
+            assert_eq!(&*bytes, expected);
+            assert_eq!(found, [quirk]);
+
+            // The info dictionary, and so the infohash, is left untouched.
+            let info = "info".parse().unwrap();
+            assert_eq!(
+                crate::raw_slice(src, &info).unwrap(),
+                crate::raw_slice(&bytes, &info).unwrap()
+            );
+
+            let denied = Quirks::all().deny(quirk).normalize(src);
+            match quirk {
+                Quirk::TrailingEnd => assert_eq!(denied, Err(Error::Malformed)),
+                _ => assert_eq!(denied, Ok((src.into(), vec![]))),
+            }
+        }
+    }
+
+    #[test]
+    fn normalize_rejects_other_trailing_bytes() {
+        assert_eq!(Quirks::all().normalize(b"dei1e"), Err(Error::Malformed));
+        assert_eq!(Quirks::all().normalize(b"d"), Err(Error::EOF));
+        assert!(Quirks::none().normalize(b"d8:announce0:e").is_ok());
+        assert!(!Quirks::none().allows(Quirk::EmptyAnnounce));
+    }
+}
@@ -0,0 +1,487 @@
+//! Access to the raw, still-encoded bytes of values.
+//!
+//! The types included in this module are:
+//!
+//! * [`RawValue`] - The raw bytes of a single encoded value, borrowed from the source.
+//! * [`Cached`] - A decoded value that remembers its original bytes, so re-encoding it is byte-identical.
+//! * [`Nested`] - A value that's encoded on its own and carried inside a byte array, eg: the `v` of a BEP 44 item.
+//!
+//! `RawValue` and `Cached` rely on cooperation from this crate's [`Encoder`](crate::en::Encoder) and [`Decoder`](crate::de::Decoder), and can't be used with other serde formats.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+use serde::de::DeserializeOwned;
+use serde::de::Error as _;
+use serde::de::Visitor;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+
+use super::cursor::ByteCursor;
+use super::de::Error;
+use super::path::Path;
+use super::path::Segment;
+use super::spec::DICT_START;
+use super::spec::LIST_START;
+use super::spec::TEXT_DELIM;
+use super::spec::TYPE_END;
+use super::Value;
+
+/// A newtype struct name the encoder and decoder recognize as a request to handle raw, still-encoded bytes.
+pub(crate) const RAW_TOKEN: &str = "$bende::private::RawValue";
+
+/// The raw bytes of a single encoded value, borrowed from the source.
+///
+/// Decoding a `RawValue` doesn't decode the value at all - it only finds where the value ends. Encoding one writes the bytes as is.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::BTreeMap;
+///
+/// use bende::raw::RawValue;
+///
+/// let torrent: BTreeMap<&str, RawValue> = bende::decode(b"d4:infod6:lengthi1995eee").unwrap();
+///
+/// assert_eq!(torrent["info"].as_bytes(), b"d6:lengthi1995ee");
+/// assert_eq!(bende::encode(&torrent).unwrap(), b"d4:infod6:lengthi1995eee");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RawValue<'a>(&'a [u8]);
+
+impl<'a> RawValue<'a> {
+    /// Gets the raw, encoded bytes of the value.
+    #[inline]
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Decodes the raw bytes into a type.
+    pub fn decode<T>(&self) -> Result<T, crate::de::Error>
+    where
+        T: Deserialize<'a>,
+    {
+        crate::decode(self.0)
+    }
+}
+
+impl<'a> Serialize for RawValue<'a> {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        ser.serialize_newtype_struct(RAW_TOKEN, &RawBytes(self.0))
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for RawValue<'a> {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct RawVisitor<'a>(PhantomData<&'a ()>);
+
+        impl<'de: 'a, 'a> Visitor<'de> for RawVisitor<'a> {
+            type Value = RawValue<'a>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("the raw bytes of a bencoded value")
+            }
+
+            fn visit_borrowed_bytes<E>(
+                self,
+                v: &'de [u8],
+            ) -> Result<Self::Value, E> {
+                Ok(RawValue(v))
+            }
+        }
+
+        de.deserialize_newtype_struct(RAW_TOKEN, RawVisitor(PhantomData))
+    }
+}
+
+/// Serializes already encoded bytes, which the encoder writes as is.
+struct RawBytes<'a>(&'a [u8]);
+
+impl<'a> Serialize for RawBytes<'a> {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        ser.serialize_bytes(self.0)
+    }
+}
+
+/// A decoded value that remembers the bytes it was decoded from.
+///
+/// Until the value is mutated through [`Cached::get_mut`], encoding it simply copies the original bytes, so the output is guaranteed to be byte-identical to the input - even if the input was not canonical, eg: with unsorted keys. This is needed for signature-preserving workflows, such as re-encoding a torrent's `info` dictionary.
+///
+/// # Examples
+///
+/// ```
+/// use bende::raw::Cached;
+/// use bende::Value;
+///
+/// // The keys are not sorted, so a plain `Value` would re-encode differently.
+/// let src = b"d1:bi2e1:ai1ee";
+/// let mut val: Cached<Value> = bende::decode(src).unwrap();
+///
+/// assert_eq!(bende::encode(&val).unwrap(), src);
+///
+/// val.get_mut().as_dict_mut().unwrap().remove("b");
+/// assert_eq!(bende::encode(&val).unwrap(), b"d1:ai1ee");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Cached<T = Value> {
+    value: T,
+    raw: Option<Vec<u8>>,
+}
+
+impl<T> Cached<T> {
+    /// Wraps a value that has no original bytes.
+    #[inline]
+    pub fn new(value: T) -> Cached<T> {
+        Self { value, raw: None }
+    }
+
+    /// Gets the original bytes, if the value has not been mutated since it was decoded.
+    #[inline]
+    pub fn raw(&self) -> Option<&[u8]> {
+        self.raw.as_deref()
+    }
+
+    /// Gets a mutable reference to the value, discarding the original bytes.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.raw = None;
+        &mut self.value
+    }
+
+    /// Consumes the wrapper, returning the value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T: zeroize::Zeroize> zeroize::Zeroize for Cached<T> {
+    fn zeroize(&mut self) {
+        self.value.zeroize();
+        self.raw.zeroize();
+    }
+}
+
+impl<T> Deref for Cached<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: PartialEq> PartialEq for Cached<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: Serialize> Serialize for Cached<T> {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.raw {
+            Some(ref raw) => RawValue(raw).serialize(ser),
+            None => self.value.serialize(ser),
+        }
+    }
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for Cached<T> {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = RawValue::deserialize(de)?;
+        let value = crate::decode(raw.as_bytes()).map_err(D::Error::custom)?;
+        Ok(Self { value, raw: Some(raw.as_bytes().to_vec()) })
+    }
+}
+
+/// A value that's encoded on its own, and carried as a byte array inside another value, eg: the `v` of a mutable item of BEP 44, whose signature covers the bytes of the inner value.
+///
+/// Decoding a `Nested` decodes the byte array as bencode of type `T`, and keeps the byte array, which is written back as is until the value is mutated through [`Nested::get_mut`]. Encoding a new or mutated one encodes the value and writes the result as a byte array.
+///
+/// # Examples
+///
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// use bende::raw::Nested;
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Item {
+///     seq: i64,
+///     v: Nested<Vec<String>>,
+/// }
+///
+/// let src = b"d3:seqi1e1:v12:l3:foo3:baree";
+/// let mut item: Item = bende::decode(src).unwrap();
+///
+/// assert_eq!(*item.v, ["foo", "bar"]);
+/// assert_eq!(item.v.raw(), Some(&b"l3:foo3:bare"[..]));
+/// assert_eq!(bende::encode(&item).unwrap(), src);
+///
+/// item.v.get_mut().push("baz".into());
+/// assert_eq!(bende::encode(&item).unwrap(), b"d3:seqi1e1:v17:l3:foo3:bar3:bazee");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Nested<T> {
+    value: T,
+    raw: Option<Vec<u8>>,
+}
+
+impl<T> Nested<T> {
+    /// Wraps a value that has no original bytes.
+    #[inline]
+    pub fn new(value: T) -> Nested<T> {
+        Self { value, raw: None }
+    }
+
+    /// Gets the bytes of the byte array the value was decoded from, if the value has not been mutated since it was decoded.
+    #[inline]
+    pub fn raw(&self) -> Option<&[u8]> {
+        self.raw.as_deref()
+    }
+
+    /// Gets a mutable reference to the value, discarding the original bytes.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.raw = None;
+        &mut self.value
+    }
+
+    /// Consumes the wrapper, returning the value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T: zeroize::Zeroize> zeroize::Zeroize for Nested<T> {
+    fn zeroize(&mut self) {
+        self.value.zeroize();
+        self.raw.zeroize();
+    }
+}
+
+impl<T> Deref for Nested<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: PartialEq> PartialEq for Nested<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: Serialize> Serialize for Nested<T> {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.raw {
+            Some(ref raw) => ser.serialize_bytes(raw),
+            None => {
+                let bytes = crate::encode(&self.value)
+                    .map_err(serde::ser::Error::custom)?;
+                ser.serialize_bytes(&bytes)
+            }
+        }
+    }
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for Nested<T> {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct BytesVisitor;
+
+        impl<'de> Visitor<'de> for BytesVisitor {
+            type Value = Vec<u8>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a byte array of bencoded bytes")
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+                Ok(v.to_vec())
+            }
+
+            fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+                Ok(v)
+            }
+        }
+
+        let raw = de.deserialize_bytes(BytesVisitor)?;
+        // The original bytes are written back as is, so they have to be exactly one value, without anything after it.
+        if crate::frame_len(&raw) != Ok(Some(raw.len())) {
+            return Err(D::Error::custom(
+                "nested value is not exactly one bencoded value",
+            ));
+        }
+        let value = crate::decode(&raw).map_err(|e| {
+            D::Error::custom(format!("nested value is malformed: {}", e))
+        })?;
+        Ok(Self { value, raw: Some(raw) })
+    }
+}
+
+/// Finds the raw bytes of the value at the given path, skipping over every value that isn't on the path without decoding it.
+pub(crate) fn slice_at<'a>(
+    src: &'a [u8],
+    path: &Path,
+) -> Result<Option<&'a [u8]>, Error> {
+    let mut cur = ByteCursor::new(src);
+    for seg in path.segments() {
+        let found = match *seg {
+            Segment::Key(ref key) => seek_key(&mut cur, key.as_bytes())?,
+            Segment::Index(i) => seek_index(&mut cur, i)?,
+        };
+        if !found {
+            return Ok(None);
+        }
+    }
+    cur.take_value().map(Some)
+}
+
+/// Advances the cursor to the value of the given key, if the next value is a dictionary that has it.
+fn seek_key(cur: &mut ByteCursor<'_>, key: &[u8]) -> Result<bool, Error> {
+    if !enter(cur, DICT_START)? {
+        return Ok(false);
+    }
+    loop {
+        match cur.peek() {
+            Some(TYPE_END) => return Ok(false),
+            Some(b'0'..=b'9') => {
+                let len = std::str::from_utf8(cur.take_until(TEXT_DELIM)?)?
+                    .parse()
+                    .map_err(|_| Error::Malformed)?;
+                if cur.take(len)? == key {
+                    return Ok(true);
+                }
+                cur.take_value()?;
+            }
+            Some(_) => return Err(Error::Malformed),
+            None => return Err(Error::EOF),
+        }
+    }
+}
+
+/// Advances the cursor to the element at the given index, if the next value is a list that has it.
+fn seek_index(cur: &mut ByteCursor<'_>, index: usize) -> Result<bool, Error> {
+    if !enter(cur, LIST_START)? {
+        return Ok(false);
+    }
+    for _ in 0..index {
+        if cur.peek() == Some(TYPE_END) {
+            return Ok(false);
+        }
+        cur.take_value()?;
+    }
+    Ok(cur.peek() != Some(TYPE_END))
+}
+
+/// Advances the cursor past the start of the next value, if it starts with the given token.
+fn enter(cur: &mut ByteCursor<'_>, token: u8) -> Result<bool, Error> {
+    match cur.peek() {
+        Some(next) if next == token => cur.skip(1).map(|_| true),
+        Some(_) => Ok(false),
+        None => Err(Error::EOF),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Cached, Nested, RawValue};
+    use crate::{decode, encode, Value};
+
+    #[test]
+    fn raw_value_in_struct() {
+        #[derive(Debug, Serialize, Deserialize)]
+        struct Torrent<'a> {
+            announce: String,
+            #[serde(borrow)]
+            info: RawValue<'a>,
+        }
+
+        let src = b"d8:announce7:udp://t4:infod1:bi1e1:ai2eee";
+        let torrent: Torrent = decode(src).unwrap();
+        assert_eq!(torrent.info.as_bytes(), b"d1:bi1e1:ai2ee");
+        assert_eq!(encode(&torrent).unwrap(), src);
+    }
+
+    #[test]
+    fn raw_value_incomplete() {
+        assert!(decode::<RawValue>(b"d1:bi1e").is_err());
+    }
+
+    #[test]
+    fn cached_new_has_no_raw() {
+        let val = Cached::new(Value::Int(1995));
+        assert_eq!(val.raw(), None);
+        assert_eq!(encode(&val).unwrap(), b"i1995e");
+    }
+
+    #[test]
+    fn slice_at_path() {
+        use super::slice_at;
+        use crate::de::Error;
+        use crate::path::Path;
+
+        let at = |src: &'static [u8], path: &str| {
+            slice_at(src, &path.parse::<Path>().unwrap())
+        };
+        let src = b"d1:ad1:bli1e3:fooee1:ci2ee";
+
+        assert_eq!(at(src, ""), Ok(Some(&src[..])));
+        assert_eq!(at(src, "a.b[1]"), Ok(Some(&b"3:foo"[..])));
+        assert_eq!(at(src, "c"), Ok(Some(&b"i2e"[..])));
+        assert_eq!(at(src, "a.b[2]"), Ok(None));
+        assert_eq!(at(src, "c.d"), Ok(None));
+        assert_eq!(at(src, "a[0]"), Ok(None));
+        // Only the values up to the end of the one at the path are scanned.
+        assert_eq!(at(b"d1:ai1e1:bx", "a"), Ok(Some(&b"i1e"[..])));
+        assert_eq!(at(b"d1:ai1e1:bx", "c"), Err(Error::Malformed));
+        assert_eq!(at(b"d1:al", "a[0]"), Err(Error::EOF));
+    }
+
+    #[test]
+    fn nested_round_trip() {
+        let val = Nested::new(Value::from(("foo", 1)));
+        assert_eq!(encode(&val).unwrap(), b"10:l3:fooi1ee");
+
+        let decoded: Nested<Value> = decode(b"10:l3:fooi1ee").unwrap();
+        assert_eq!(decoded, val);
+        assert_eq!(decoded.raw(), Some(&b"l3:fooi1ee"[..]));
+
+        // The inner bytes have to be a single, well-formed value.
+        assert!(decode::<Nested<Value>>(b"6:l3:foo").is_err());
+        assert!(decode::<Nested<Value>>(b"l3:fooe").is_err());
+        assert!(decode::<Nested<Value>>(b"7:i1ejunk").is_err());
+        assert!(decode::<Nested<Value>>(b"6:i1ei2e").is_err());
+    }
+}
@@ -0,0 +1,185 @@
+//! A relaxed dialect of bencode, for files that are edited by hand.
+//!
+//! The dialect allows ASCII whitespace between tokens, eg: around the elements of a list, and a trailing newline. Byte arrays and integers are left untouched, so whitespace inside them is kept or rejected as usual.
+//!
+//! Enabling the `comments` feature also allows comments between tokens, which run from a `#` to the end of the line. A `#` inside a byte array is part of its content. Encoding never writes comments, so a decoded file is always re-encoded in its canonical, comment-free form.
+//!
+//! * [`normalize`] - Strips the whitespace and comments, leaving plain bencode.
+//! * [`decode`] - Decodes a type from the relaxed dialect.
+//!
+//! Wire data should still be decoded with the strict [`decode`](crate::decode), which is the default.
+
+use std::borrow::Cow;
+
+use serde::de::DeserializeOwned;
+
+use super::de::Error;
+use super::spec::DICT_START;
+use super::spec::INT_START;
+use super::spec::LIST_START;
+use super::spec::TEXT_DELIM;
+use super::spec::TYPE_END;
+
+/// Strips the whitespace, and comments with the `comments` feature, between the tokens of the source, leaving plain bencode.
+///
+/// The source is borrowed if there's nothing to strip. Only the token structure is checked, so the result may still fail to decode.
+///
+/// # Errors
+///
+/// * A token starts with an unexpected byte, at which point `Error::Wanted` is returned.
+/// * A byte array's length is invalid.
+/// * The source ends in the middle of a token, at which point `Error::EOF` is returned.
+///
+/// # Examples
+///
+/// ```
+/// use bende::relaxed::normalize;
+///
+/// let src = b"d\n  4:name 3:foo\n  4:tags l 1:a 1:b e\ne\n";
+/// assert_eq!(normalize(src).unwrap(), &b"d4:name3:foo4:tagsl1:a1:bee"[..]);
+/// ```
+pub fn normalize(src: &[u8]) -> Result<Cow<'_, [u8]>, Error> {
+    let mut out = Vec::new();
+    // The end of the last stretch of whitespace that was stripped.
+    let mut kept = 0;
+    let mut pos = 0;
+
+    loop {
+        let start = pos;
+        pos = skip_ignored(src, pos);
+        if pos > start {
+            out.extend_from_slice(&src[kept..start]);
+            kept = pos;
+        }
+
+        let len = match src.get(pos) {
+            Some(&(LIST_START | DICT_START | TYPE_END)) => 1,
+            Some(&INT_START) => token_len(&src[pos..], TYPE_END)?,
+            Some(next) if next.is_ascii_digit() => {
+                let delim = token_len(&src[pos..], TEXT_DELIM)?;
+                let len: usize =
+                    std::str::from_utf8(&src[pos..pos + delim - 1])?
+                        .parse()
+                        .map_err(|_| Error::Malformed)?;
+                match delim.checked_add(len) {
+                    Some(end) if end <= src.len() - pos => end,
+                    _ => return Err(Error::EOF),
+                }
+            }
+            Some(&next) => {
+                return Err(Error::Wanted {
+                    at: pos,
+                    expected: "a bencoded value",
+                    found: (next as char).to_string(),
+                })
+            }
+            None => break,
+        };
+        pos += len;
+    }
+
+    if kept == 0 {
+        return Ok(Cow::Borrowed(src));
+    }
+    out.extend_from_slice(&src[kept..]);
+    Ok(Cow::Owned(out))
+}
+
+/// Gets the position of the next token, skipping any whitespace and, with the `comments` feature, any comments.
+fn skip_ignored(src: &[u8], mut pos: usize) -> usize {
+    loop {
+        match src.get(pos) {
+            Some(next) if next.is_ascii_whitespace() => pos += 1,
+            #[cfg(feature = "comments")]
+            Some(b'#') => {
+                pos = match src[pos..].iter().position(|&b| b == b'\n') {
+                    Some(i) => pos + i + 1,
+                    None => src.len(),
+                };
+            }
+            _ => return pos,
+        }
+    }
+}
+
+/// Gets the length of the token at the start of the source, up to and including the given delimiter.
+fn token_len(src: &[u8], delim: u8) -> Result<usize, Error> {
+    src.iter().position(|&b| b == delim).map(|i| i + 1).ok_or(Error::EOF)
+}
+
+/// Decodes a type from the relaxed dialect.
+///
+/// # Errors
+///
+/// * The source can't be normalized. See [`normalize`].
+/// * The normalized source can't be decoded.
+///
+/// # Examples
+///
+/// ```
+/// use serde::Deserialize;
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct Config {
+///     port: u16,
+///     peers: Vec<String>,
+/// }
+///
+/// let src = b"d\n  5:peers l 3:foo 3:bar e\n  4:port i6881e\ne\n";
+/// let config: Config = bende::relaxed::decode(src).unwrap();
+///
+/// assert_eq!(config, Config { port: 6881, peers: vec!["foo".into(), "bar".into()] });
+/// assert!(bende::decode::<Config>(src).is_err());
+/// ```
+pub fn decode<T>(src: &[u8]) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    super::decode(&normalize(src)?)
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+
+    use super::normalize;
+    use crate::de::Error;
+
+    #[test]
+    fn normalize_keeps_values() {
+        let src = b"l4: a bi 1e0:e";
+        assert!(matches!(normalize(src), Ok(Cow::Borrowed(_))));
+
+        let src = b" l\t4: a b\r\ni 1e 0: e \n";
+        assert_eq!(normalize(src).unwrap(), &b"l4: a bi 1e0:e"[..]);
+    }
+
+    #[cfg(feature = "comments")]
+    #[test]
+    fn normalize_comments() {
+        let src = b"# peers\nd 5:peers l # trackers\n 3:a#b e # end\n e # eof";
+        assert_eq!(normalize(src).unwrap(), &b"d5:peersl3:a#bee"[..]);
+        assert_eq!(normalize(b"#\n#").unwrap(), &b""[..]);
+    }
+
+    #[cfg(not(feature = "comments"))]
+    #[test]
+    fn normalize_without_comments() {
+        assert!(normalize(b"# peers\nle").is_err());
+    }
+
+    #[test]
+    fn normalize_errors() {
+        assert_eq!(normalize(b"l 5:abc"), Err(Error::EOF));
+        assert_eq!(normalize(b"l i1"), Err(Error::EOF));
+        assert_eq!(normalize(b"l 1 :a e"), Err(Error::Malformed));
+        assert_eq!(
+            normalize(b"l x e"),
+            Err(Error::Wanted {
+                at: 2,
+                expected: "a bencoded value",
+                found: "x".into()
+            })
+        );
+    }
+}
@@ -0,0 +1,300 @@
+//! Streaming edits of bencoded bytes, without decoding them.
+//!
+//! * [`Rewriter`] - Re-emits bencoded bytes token by token, while dropping keys, renaming keys and replacing values.
+//!
+//! The rewriter only keeps track of how deeply nested the current token is, so its memory use doesn't grow with the size of the source, and every token that isn't edited is copied as is.
+
+use std::io::Write;
+
+use serde::Serialize;
+
+use super::de::Error;
+use super::en;
+use super::path::Path;
+use super::path::Segment;
+use super::token::Token;
+use super::token::Tokens;
+
+/// An edit applied to the value at a path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Action {
+    Drop,
+    Rename(Vec<u8>),
+    Replace(Vec<u8>),
+}
+
+/// A list or dictionary the rewriter is inside of.
+#[derive(Debug, Clone, Copy)]
+enum Frame<'a> {
+    /// A list, and the index of its next element.
+    List(usize),
+    /// A dictionary, and the key of the value that comes next if it has been read.
+    Dict(Option<&'a [u8]>),
+}
+
+/// A set of edits that are applied to bencoded bytes as they're copied to a writer.
+///
+/// Every edit addresses a value by its [`Path`]. List indices refer to the source, so dropping an element doesn't shift the indices of the ones after it. Renamed keys keep their place in the dictionary, so renaming may leave its keys unsorted.
+///
+/// # Examples
+///
+/// ```
+/// use bende::rewrite::Rewriter;
+///
+/// let src = b"d7:comment3:foo4:infod6:lengthi1995eee";
+/// let rewriter = Rewriter::new()
+///     .drop("comment".parse().unwrap())
+///     .rename("info.length".parse().unwrap(), "size")
+///     .replace("info.length".parse().unwrap(), &7)
+///     .unwrap();
+///
+/// assert_eq!(rewriter.rewrite_to_vec(src).unwrap(), b"d4:infod4:sizei7eee");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Rewriter {
+    rules: Vec<(Path, Action)>,
+}
+
+impl Rewriter {
+    /// Constructs a rewriter without any edits, which copies its source as is.
+    #[inline]
+    pub fn new() -> Rewriter {
+        Self::default()
+    }
+
+    /// Drops the value at the given path, alongside its key if it's in a dictionary.
+    pub fn drop(mut self, path: Path) -> Rewriter {
+        self.rules.push((path, Action::Drop));
+        self
+    }
+
+    /// Renames the key of the value at the given path. Paths that don't end with a key are never renamed.
+    pub fn rename<K>(mut self, path: Path, key: K) -> Rewriter
+    where
+        K: Into<Vec<u8>>,
+    {
+        self.rules.push((path, Action::Rename(encode_key(&key.into()))));
+        self
+    }
+
+    /// Replaces the value at the given path with the given value.
+    ///
+    /// # Errors
+    ///
+    /// * The value can't be encoded.
+    pub fn replace<T>(
+        mut self,
+        path: Path,
+        val: &T,
+    ) -> Result<Rewriter, en::Error>
+    where
+        T: Serialize,
+    {
+        self.rules.push((path, Action::Replace(super::encode(val)?)));
+        Ok(self)
+    }
+
+    /// Copies the source to the writer, applying the edits along the way.
+    ///
+    /// # Errors
+    ///
+    /// * The source is malformed, eg: a dictionary key isn't a byte array, at which point `Error::Malformed` is returned.
+    /// * The source ends in the middle of a value, at which point `Error::EOF` is returned.
+    /// * The writer fails, at which point `Error::Io` is returned.
+    pub fn rewrite<W>(&self, src: &[u8], mut writer: W) -> Result<(), Error>
+    where
+        W: Write,
+    {
+        let mut tokens = Tokens::new(src);
+        let mut stack = Vec::new();
+
+        while let Some(token) = tokens.next() {
+            let (at, token) = token?;
+            let raw = &src[at..tokens.offset()];
+
+            if let Some(Frame::Dict(ref mut key @ None)) = stack.last_mut() {
+                match token {
+                    Token::End => {
+                        stack.pop();
+                        writer.write_all(raw)?;
+                        next_value(&mut stack);
+                    }
+                    Token::Bytes(k) => {
+                        *key = Some(k);
+                        let action = self.action(&stack, |action| {
+                            !matches!(action, Action::Replace(_))
+                        });
+                        match action {
+                            Some(Action::Drop) => {
+                                skip_value(&mut tokens)?;
+                                next_value(&mut stack);
+                            }
+                            Some(Action::Rename(key)) => {
+                                writer.write_all(key)?
+                            }
+                            _ => writer.write_all(raw)?,
+                        }
+                    }
+                    _ => return Err(Error::Malformed),
+                }
+                continue;
+            }
+
+            if token == Token::End {
+                match stack.pop() {
+                    Some(Frame::List(_)) => writer.write_all(raw)?,
+                    _ => return Err(Error::Malformed),
+                }
+                next_value(&mut stack);
+                continue;
+            }
+
+            let action = self
+                .action(&stack, |action| !matches!(action, Action::Rename(_)));
+            match action {
+                Some(Action::Drop) => {
+                    skip_rest(&mut tokens, token)?;
+                    next_value(&mut stack);
+                }
+                Some(Action::Replace(val)) => {
+                    writer.write_all(val)?;
+                    skip_rest(&mut tokens, token)?;
+                    next_value(&mut stack);
+                }
+                _ => {
+                    writer.write_all(raw)?;
+                    match token {
+                        Token::ListStart => stack.push(Frame::List(0)),
+                        Token::DictStart => stack.push(Frame::Dict(None)),
+                        _ => next_value(&mut stack),
+                    }
+                }
+            }
+        }
+
+        match stack.is_empty() {
+            true => Ok(()),
+            false => Err(Error::EOF),
+        }
+    }
+
+    /// Copies the source into a new buffer, applying the edits along the way.
+    ///
+    /// # Errors
+    ///
+    /// * The source is malformed or incomplete. See [`rewrite`](Rewriter::rewrite).
+    pub fn rewrite_to_vec(&self, src: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut buf = Vec::with_capacity(src.len());
+        self.rewrite(src, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Gets the first edit that passes the filter, of the value the stack of lists and dictionaries leads to.
+    fn action<F>(&self, stack: &[Frame<'_>], filter: F) -> Option<&Action>
+    where
+        F: Fn(&Action) -> bool,
+    {
+        self.rules
+            .iter()
+            .filter(|(_, action)| filter(action))
+            .find(|(path, _)| {
+                let segments = path.segments();
+                segments.len() == stack.len()
+                    && segments.iter().zip(stack).all(|(seg, frame)| {
+                        match (seg, frame) {
+                            (Segment::Key(k), Frame::Dict(Some(key))) => {
+                                k.as_bytes() == *key
+                            }
+                            (Segment::Index(i), Frame::List(j)) => i == j,
+                            _ => false,
+                        }
+                    })
+            })
+            .map(|(_, action)| action)
+    }
+}
+
+/// Encodes a dictionary key as a byte array.
+fn encode_key(key: &[u8]) -> Vec<u8> {
+    let mut buf = key.len().to_string().into_bytes();
+    buf.push(b':');
+    buf.extend_from_slice(key);
+    buf
+}
+
+/// Moves the innermost list or dictionary on to its next value, after a value of it has been handled.
+fn next_value(stack: &mut [Frame<'_>]) {
+    match stack.last_mut() {
+        Some(Frame::List(i)) => *i += 1,
+        Some(Frame::Dict(key)) => *key = None,
+        None => {}
+    }
+}
+
+/// Skips the tokens of the next value.
+fn skip_value(tokens: &mut Tokens<'_>) -> Result<(), Error> {
+    match tokens.next() {
+        Some(token) => skip_rest(tokens, token?.1),
+        None => Err(Error::EOF),
+    }
+}
+
+/// Skips the tokens of a value after its first token, which are only there if the value is a list or dictionary.
+fn skip_rest(tokens: &mut Tokens<'_>, first: Token<'_>) -> Result<(), Error> {
+    let mut depth = match first {
+        Token::ListStart | Token::DictStart => 1usize,
+        Token::End => return Err(Error::Malformed),
+        Token::Int(_) | Token::Bytes(_) => return Ok(()),
+    };
+    while depth > 0 {
+        match tokens.next().ok_or(Error::EOF)??.1 {
+            Token::ListStart | Token::DictStart => depth += 1,
+            Token::End => depth -= 1,
+            Token::Int(_) | Token::Bytes(_) => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::Rewriter;
+    use crate::de::Error;
+
+    fn path(s: &str) -> crate::path::Path {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn rewrite_lists() {
+        let src = b"d5:filesl1:a1:bd4:pathi1eee3:keyi1ee";
+        let rewriter = Rewriter::new()
+            .drop(path("files[0]"))
+            .replace(path("files[2].path"), &"c")
+            .unwrap()
+            .rename(path("files[1]"), "x");
+
+        assert_eq!(
+            rewriter.rewrite_to_vec(src).unwrap(),
+            b"d5:filesl1:bd4:path1:cee3:keyi1ee"
+        );
+        assert_eq!(Rewriter::new().rewrite_to_vec(src).unwrap(), src);
+    }
+
+    #[test]
+    fn rewrite_root() {
+        let rewriter = Rewriter::new().replace(path(""), &1).unwrap();
+        assert_eq!(rewriter.rewrite_to_vec(b"l1:ae").unwrap(), b"i1e");
+    }
+
+    #[test]
+    fn rewrite_malformed() {
+        let rewriter = Rewriter::new().drop(path("a"));
+
+        assert_eq!(rewriter.rewrite_to_vec(b"di1ei2ee"), Err(Error::Malformed));
+        assert_eq!(rewriter.rewrite_to_vec(b"le"), Ok(b"le".to_vec()));
+        assert_eq!(rewriter.rewrite_to_vec(b"e"), Err(Error::Malformed));
+        assert_eq!(rewriter.rewrite_to_vec(b"d1:al"), Err(Error::EOF));
+        assert_eq!(rewriter.rewrite_to_vec(b"d1:b"), Err(Error::EOF));
+    }
+}
@@ -0,0 +1,345 @@
+//! Decoders for dictionaries whose keys are known ahead of time.
+//!
+//! * [`Schema`] - The expected keys of a dictionary and the kinds of their values, compiled once and reused for every decode.
+//! * [`Record`] - The fields decoded by a schema, borrowed from the source.
+//!
+//! Decoding with a schema skips serde entirely. The keys of the input are joined against the sorted keys of the schema in a single pass, and the values of unknown keys are skipped over without being decoded or allocated.
+
+use std::str;
+
+use super::de::Decoder;
+use super::de::Error;
+use super::spec::DICT_START;
+use super::spec::TYPE_END;
+
+/// The kind of value expected for a key of a [`Schema`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    /// An integer.
+    Int,
+    /// A byte array that may or **may not** be valid UTF-8.
+    Bytes,
+    /// A byte array that must be valid UTF-8.
+    Str,
+    /// Any value, kept as its raw bencoded bytes.
+    Raw,
+}
+
+/// A field decoded by a [`Schema`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field<'a> {
+    /// An integer.
+    Int(i64),
+    /// A byte array.
+    Bytes(&'a [u8]),
+    /// A UTF-8 string.
+    Str(&'a str),
+    /// The raw bencoded bytes of any value.
+    Raw(&'a [u8]),
+}
+
+/// The expected keys of a dictionary and the kinds of their values, compiled into a reusable decoder.
+///
+/// # Examples
+///
+/// ```
+/// use bende::schema::{Field, FieldKind, Schema};
+///
+/// let schema = Schema::compile(&[
+///     ("port", FieldKind::Int),
+///     ("info_hash", FieldKind::Bytes),
+///     ("event", FieldKind::Str),
+/// ]);
+///
+/// let record = schema
+///     .decode(b"d5:event7:started9:info_hash4:abcd4:porti6881e4:userli1eee")
+///     .unwrap();
+///
+/// assert_eq!(record.int("port"), Some(6881));
+/// assert_eq!(record.bytes("info_hash"), Some(&b"abcd"[..]));
+/// assert_eq!(record.get("event"), Some(Field::Str("started")));
+/// assert_eq!(record.get("user"), None);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schema {
+    keys: Vec<(String, FieldKind)>,
+}
+
+impl Schema {
+    /// Compiles a schema from the given keys and the kinds of their values, in any order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a key is given more than once.
+    pub fn compile(fields: &[(&str, FieldKind)]) -> Schema {
+        let mut keys: Vec<(String, FieldKind)> =
+            fields.iter().map(|&(key, kind)| (key.to_owned(), kind)).collect();
+        keys.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+        if let Some(dup) = keys.windows(2).find(|w| w[0].0 == w[1].0) {
+            panic!("the key `{}` was given more than once", dup[0].0);
+        }
+        Self { keys }
+    }
+
+    /// Returns an iterator over the keys and the kinds of their values, in the order they're encoded in.
+    pub fn keys(&self) -> impl Iterator<Item = (&str, FieldKind)> + '_ {
+        self.keys.iter().map(|(key, kind)| (key.as_str(), *kind))
+    }
+
+    /// Constructs an empty record for this schema, which can be reused across decodes with [`Record::decode`].
+    pub fn record<'a>(&self) -> Record<'_, 'a> {
+        Record { schema: self, fields: vec![None; self.keys.len()] }
+    }
+
+    /// Decodes a dictionary into a new record.
+    ///
+    /// # Errors
+    ///
+    /// See [`Record::decode`].
+    pub fn decode<'a>(&self, src: &'a [u8]) -> Result<Record<'_, 'a>, Error> {
+        let mut record = self.record();
+        record.decode(src)?;
+        Ok(record)
+    }
+
+    /// Finds the position of the key among the sorted keys.
+    fn index(&self, key: &[u8]) -> Option<usize> {
+        self.keys.binary_search_by(|(k, _)| k.as_bytes().cmp(key)).ok()
+    }
+}
+
+/// The fields of a dictionary decoded by a [`Schema`].
+///
+/// Fields that weren't in the dictionary are `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record<'s, 'a> {
+    schema: &'s Schema,
+    fields: Vec<Option<Field<'a>>>,
+}
+
+impl<'a> Record<'_, 'a> {
+    /// Decodes a dictionary into this record, replacing the fields of any previous decode.
+    ///
+    /// Keys that aren't part of the schema are skipped, and if a key is repeated, its first value is kept.
+    ///
+    /// # Errors
+    ///
+    /// * The source isn't a dictionary, or there's anything after it.
+    /// * The value of a key isn't of the expected kind, at which point `Error::Wanted` is returned.
+    /// * The source is malformed, or ends early.
+    pub fn decode(&mut self, src: &'a [u8]) -> Result<(), Error> {
+        self.fields.fill(None);
+        let keys = &self.schema.keys;
+        let mut de = Decoder::new(src);
+        de.advance_if(|next| next == DICT_START, "a dictionary")?;
+
+        let mut next = 0;
+        let mut prev: &[u8] = &[];
+        loop {
+            match de.peek() {
+                Some(TYPE_END) => break,
+                Some(b'0'..=b'9') => {}
+                Some(found) => {
+                    return Err(Error::Wanted {
+                        at: de.pos(),
+                        expected: "a byte array",
+                        found: (found as char).to_string(),
+                    })
+                }
+                None => return Err(Error::EOF),
+            }
+
+            let key = de.decode_bytes()?;
+            if key < prev {
+                // The keys are out of order, so start over from wherever this one would be.
+                next = keys.partition_point(|(k, _)| k.as_bytes() < key);
+            }
+            while next < keys.len() && keys[next].0.as_bytes() < key {
+                next += 1;
+            }
+            prev = key;
+
+            match keys.get(next) {
+                Some(&(ref k, kind)) if k.as_bytes() == key => {
+                    let field = decode_field(&mut de, kind)?;
+                    self.fields[next].get_or_insert(field);
+                }
+                _ => {
+                    de.decode_raw()?;
+                }
+            }
+        }
+
+        de.advance(1);
+        if de.pos() < src.len() {
+            return Err(Error::Malformed);
+        }
+        Ok(())
+    }
+
+    /// Gets the field of the given key, if it was decoded.
+    pub fn get(&self, key: &str) -> Option<Field<'a>> {
+        self.schema.index(key.as_bytes()).and_then(|i| self.fields[i])
+    }
+
+    /// Gets the integer of the given key, if it was decoded.
+    pub fn int(&self, key: &str) -> Option<i64> {
+        match self.get(key)? {
+            Field::Int(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Gets the byte array of the given key, if it was decoded, whether it's expected to be UTF-8 or not.
+    pub fn bytes(&self, key: &str) -> Option<&'a [u8]> {
+        match self.get(key)? {
+            Field::Bytes(v) => Some(v),
+            Field::Str(v) => Some(v.as_bytes()),
+            _ => None,
+        }
+    }
+
+    /// Gets the string of the given key, if it was decoded.
+    pub fn str(&self, key: &str) -> Option<&'a str> {
+        match self.get(key)? {
+            Field::Str(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Gets the raw bencoded bytes of the given key, if it was decoded.
+    pub fn raw(&self, key: &str) -> Option<&'a [u8]> {
+        match self.get(key)? {
+            Field::Raw(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns an iterator over the decoded fields and their keys, in the order they're encoded in.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Field<'a>)> + '_ {
+        self.schema
+            .keys
+            .iter()
+            .zip(&self.fields)
+            .filter_map(|((key, _), field)| Some((key.as_str(), (*field)?)))
+    }
+}
+
+/// Decodes the next value as the given kind.
+fn decode_field<'a>(
+    de: &mut Decoder<'a>,
+    kind: FieldKind,
+) -> Result<Field<'a>, Error> {
+    match kind {
+        FieldKind::Int => de.decode_int().map(Field::Int),
+        FieldKind::Raw => de.decode_raw().map(Field::Raw),
+        FieldKind::Bytes | FieldKind::Str => {
+            if let Some(found) = de.peek().filter(|b| !b.is_ascii_digit()) {
+                return Err(Error::Wanted {
+                    at: de.pos(),
+                    expected: "a byte array",
+                    found: (found as char).to_string(),
+                });
+            }
+            let bytes = de.decode_bytes()?;
+            match kind {
+                FieldKind::Str => Ok(Field::Str(str::from_utf8(bytes)?)),
+                _ => Ok(Field::Bytes(bytes)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Field;
+    use super::FieldKind;
+    use super::Schema;
+    use crate::de::Error;
+
+    fn schema() -> Schema {
+        Schema::compile(&[
+            ("port", FieldKind::Int),
+            ("peer_id", FieldKind::Bytes),
+            ("info", FieldKind::Raw),
+        ])
+    }
+
+    #[test]
+    fn decode_skips_unknown_keys() {
+        let schema = schema();
+        let record = schema
+            .decode(b"d1:ald1:bi1eee4:infod1:xi0ee1:ni2e4:porti80e1:zi3ee")
+            .unwrap();
+
+        assert_eq!(record.raw("info"), Some(&b"d1:xi0ee"[..]));
+        assert_eq!(record.int("port"), Some(80));
+        assert_eq!(record.get("peer_id"), None);
+        assert_eq!(
+            record.iter().collect::<Vec<_>>(),
+            [("info", Field::Raw(b"d1:xi0ee")), ("port", Field::Int(80))]
+        );
+    }
+
+    #[test]
+    fn decode_unsorted_and_repeated_keys() {
+        let schema = schema();
+        let record = schema
+            .decode(b"d4:porti1e4:info0:4:porti2e7:peer_id2:abe")
+            .unwrap();
+
+        assert_eq!(record.int("port"), Some(1));
+        assert_eq!(record.raw("info"), Some(&b"0:"[..]));
+        assert_eq!(record.bytes("peer_id"), Some(&b"ab"[..]));
+    }
+
+    #[test]
+    fn decode_reuses_record() {
+        let schema = schema();
+        let mut record = schema.record();
+
+        record.decode(b"d4:porti1ee").unwrap();
+        assert_eq!(record.int("port"), Some(1));
+        record.decode(b"d7:peer_id0:e").unwrap();
+        assert_eq!(record.int("port"), None);
+        assert_eq!(record.bytes("peer_id"), Some(&b""[..]));
+    }
+
+    #[test]
+    fn decode_invalid() {
+        let schema = schema();
+
+        assert_eq!(
+            schema.decode(b"d4:port2:80e"),
+            Err(Error::Wanted {
+                at: 7,
+                expected: "an integer",
+                found: "2".into()
+            })
+        );
+        assert_eq!(
+            schema.decode(b"d7:peer_idi1ee"),
+            Err(Error::Wanted {
+                at: 10,
+                expected: "a byte array",
+                found: "i".into()
+            })
+        );
+        assert_eq!(
+            schema.decode(b"li1ee"),
+            Err(Error::Wanted {
+                at: 0,
+                expected: "a dictionary",
+                found: "l".into()
+            })
+        );
+        assert_eq!(schema.decode(b"d4:porti1e"), Err(Error::EOF));
+        assert_eq!(schema.decode(b"de0:"), Err(Error::Malformed));
+    }
+
+    #[test]
+    #[should_panic]
+    fn compile_rejects_duplicate_keys() {
+        Schema::compile(&[("a", FieldKind::Int), ("a", FieldKind::Raw)]);
+    }
+}
@@ -0,0 +1,224 @@
+//! Support for signed torrents, as described in [BEP 35](https://www.bittorrent.org/beps/bep_0035.html).
+//!
+//! A signed torrent carries a `signatures` dictionary in its root dictionary, which maps the identity of each signer to a [`Signature`].
+//!
+//! The functions included in this module are:
+//!
+//! * [`signatures`] - Decodes the signature entries of a torrent.
+//! * [`signed_bytes`] - Gets the exact bytes of a torrent that are covered by its signatures.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::de::MapAccess;
+use serde::de::Visitor;
+use serde::Deserialize;
+use serde::Deserializer;
+
+use super::de::Error;
+use super::raw::RawValue;
+use super::spec::DICT_START;
+use super::spec::TYPE_END;
+use super::value::Dict;
+use super::value::TryFromValueError;
+use super::Value;
+
+/// The key of the root dictionary entry that holds the signatures.
+const SIGNATURES_KEY: &[u8] = b"signatures";
+
+/// A single entry of a torrent's `signatures` dictionary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    /// The X.509 certificate of the signer, if it's not expected to be known already.
+    pub certificate: Option<Vec<u8>>,
+    /// Extra data covered by the signature, if any.
+    pub info: Option<Dict>,
+    /// The signature itself.
+    pub signature: Vec<u8>,
+}
+
+impl TryFrom<&Value> for Signature {
+    type Error = TryFromValueError;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        const EXPECTED: &str = "a dictionary with a 'signature' byte array";
+
+        let dict = v.as_dict().ok_or(TryFromValueError::new(EXPECTED))?;
+        let signature = dict
+            .get("signature")
+            .and_then(Value::as_bytes)
+            .ok_or(TryFromValueError::new(EXPECTED))?;
+
+        Ok(Self {
+            certificate: dict
+                .get("certificate")
+                .and_then(Value::as_bytes)
+                .map(<[u8]>::to_vec),
+            info: dict.get("info").and_then(Value::as_dict).cloned(),
+            signature: signature.to_vec(),
+        })
+    }
+}
+
+impl From<&Signature> for Value {
+    fn from(v: &Signature) -> Self {
+        let mut dict = Dict::new();
+        if let Some(ref certificate) = v.certificate {
+            dict.insert("certificate".into(), certificate.clone().into());
+        }
+        if let Some(ref info) = v.info {
+            dict.insert("info".into(), info.clone().into());
+        }
+        dict.insert("signature".into(), v.signature.clone().into());
+        Value::Dict(dict)
+    }
+}
+
+/// The entries of a dictionary, in their original order and with their values still encoded.
+struct RawEntries<'a>(Vec<(&'a [u8], RawValue<'a>)>);
+
+impl<'de> Deserialize<'de> for RawEntries<'de> {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct EntriesVisitor;
+
+        impl<'de> Visitor<'de> for EntriesVisitor {
+            type Value = RawEntries<'de>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a dictionary")
+            }
+
+            fn visit_map<A>(
+                self,
+                mut access: A,
+            ) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut entries = Vec::new();
+                while let Some(entry) = access.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(RawEntries(entries))
+            }
+        }
+
+        de.deserialize_map(EntriesVisitor)
+    }
+}
+
+/// Gets the exact bytes of a torrent that are covered by its signatures - the root dictionary without its `signatures` entry.
+///
+/// Every other entry is copied as is, so the result is byte-identical to what the signer signed, even if the torrent is not canonically encoded.
+///
+/// # Examples
+///
+/// ```
+/// let torrent = b"d8:announce7:udp://t4:infod6:lengthi1ee10:signaturesd5:aliced9:signature3:sigeee";
+///
+/// assert_eq!(
+///     bende::signature::signed_bytes(torrent).unwrap(),
+///     b"d8:announce7:udp://t4:infod6:lengthi1eee"
+/// );
+/// ```
+pub fn signed_bytes(torrent: &[u8]) -> Result<Vec<u8>, Error> {
+    let RawEntries(entries) = crate::decode(torrent)?;
+
+    let mut bytes = Vec::with_capacity(torrent.len());
+    bytes.push(DICT_START);
+    for (key, val) in entries {
+        if key == SIGNATURES_KEY {
+            continue;
+        }
+        bytes.extend_from_slice(key.len().to_string().as_bytes());
+        bytes.push(b':');
+        bytes.extend_from_slice(key);
+        bytes.extend_from_slice(val.as_bytes());
+    }
+    bytes.push(TYPE_END);
+    Ok(bytes)
+}
+
+/// Decodes the entries of a torrent's `signatures` dictionary, keyed by the identity of each signer.
+///
+/// Returns an empty map if the torrent is not signed.
+///
+/// # Examples
+///
+/// ```
+/// let torrent = b"d4:infod6:lengthi1ee10:signaturesd5:aliced9:signature3:sigeee";
+///
+/// let signatures = bende::signature::signatures(torrent).unwrap();
+/// assert_eq!(signatures["alice"].signature, b"sig");
+/// assert_eq!(signatures["alice"].certificate, None);
+/// ```
+pub fn signatures(
+    torrent: &[u8],
+) -> Result<BTreeMap<String, Signature>, Error> {
+    let RawEntries(entries) = crate::decode(torrent)?;
+
+    let Some((_, raw)) = entries.iter().find(|(key, _)| *key == SIGNATURES_KEY)
+    else {
+        return Ok(BTreeMap::new());
+    };
+
+    let signatures: BTreeMap<String, Value> = raw.decode()?;
+    signatures
+        .into_iter()
+        .map(|(signer, val)| {
+            Signature::try_from(&val)
+                .map(|sig| (signer, sig))
+                .map_err(|e| Error::Deserialize(e.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{signatures, signed_bytes, Signature};
+    use crate::{encode, Value};
+
+    #[test]
+    fn signed_bytes_preserves_non_canonical_order() {
+        let torrent = b"d4:infod1:bi1e1:ai2ee10:signaturesde8:announce1:te";
+        assert_eq!(
+            signed_bytes(torrent).unwrap(),
+            b"d4:infod1:bi1e1:ai2ee8:announce1:te"
+        );
+    }
+
+    #[test]
+    fn signed_bytes_of_unsigned_torrent() {
+        let torrent = b"d4:infod6:lengthi1eee";
+        assert_eq!(signed_bytes(torrent).unwrap(), torrent);
+    }
+
+    #[test]
+    fn signatures_of_unsigned_torrent() {
+        assert!(signatures(b"d4:infodee").unwrap().is_empty());
+    }
+
+    #[test]
+    fn signature_round_trip() {
+        let sig = Signature {
+            certificate: Some(b"cert".to_vec()),
+            info: None,
+            signature: b"sig".to_vec(),
+        };
+        let bytes = encode(&Value::from(&sig)).unwrap();
+        assert_eq!(bytes, b"d11:certificate4:cert9:signature3:sige");
+        assert_eq!(
+            Signature::try_from(&crate::decode::<Value>(&bytes).unwrap()),
+            Ok(sig)
+        );
+    }
+
+    #[test]
+    fn signature_missing() {
+        let torrent = b"d10:signaturesd5:alicedeee";
+        assert!(signatures(torrent).is_err());
+    }
+}
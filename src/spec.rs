@@ -0,0 +1,163 @@
+//! Tokens, limits and canonical ordering from the bencode specification.
+//!
+//! Custom parsers and validators can use these to agree with the crate on what canonical bencode is, rather than re-deriving it:
+//!
+//! * [`INT_START`], [`LIST_START`], [`DICT_START`], [`TEXT_DELIM`] and [`TYPE_END`] - The bytes that delimit bencoded values.
+//! * [`MAX_INT_DIGITS`] and [`MAX_INT_LEN`] - The limits of the integers the crate decodes and encodes.
+//! * [`cmp_keys`] - The order dictionary keys are sorted in.
+//! * [`is_sorted_keys`] - Checks the keys of an encoded dictionary against that order.
+
+use std::cmp::Ordering;
+
+use super::de::Error;
+use super::token::Token;
+use super::token::Tokens;
+
+/// Denotes the start of an integer - `i`.
+pub const INT_START: u8 = 0x69;
+
+/// Denotes that start of a list - `l`.
+pub const LIST_START: u8 = 0x6C;
+
+/// Denotes the start of a dictionary - `d`.
+pub const DICT_START: u8 = 0x64;
+
+/// A symbol used to separate a byte array's length from its content - `:`.
+pub const TEXT_DELIM: u8 = 0x3A;
+
+/// Denotes the end of a type - `e`.
+pub const TYPE_END: u8 = 0x65;
+
+/// The maximum number of digits in an integer that fits in an `i64`, which is the default limit of the decoder.
+pub const MAX_INT_DIGITS: usize = 19;
+
+/// The maximum length of an encoded `i64`, eg: `i-9223372036854775808e`.
+pub const MAX_INT_LEN: usize = MAX_INT_DIGITS + 3;
+
+/// Compares two dictionary keys in canonical order.
+///
+/// Keys are sorted as raw byte strings, so a key sorts before any longer key it is a prefix of. Canonical dictionaries have strictly ascending keys, so two keys that compare equal are duplicates.
+///
+/// This is a `const fn`, so it can compare keys at compile time.
+///
+/// # Examples
+///
+/// ```
+/// use std::cmp::Ordering;
+/// use bende::spec::cmp_keys;
+///
+/// assert_eq!(cmp_keys(b"a", b"aa"), Ordering::Less);
+/// assert_eq!(cmp_keys(b"Z", b"a"), Ordering::Less);
+/// assert_eq!(cmp_keys(b"b", b"aa"), Ordering::Greater);
+/// ```
+pub const fn cmp_keys(a: &[u8], b: &[u8]) -> Ordering {
+    let mut i = 0;
+    while i < a.len() && i < b.len() {
+        if a[i] != b[i] {
+            return if a[i] < b[i] {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            };
+        }
+        i += 1;
+    }
+    if a.len() < b.len() {
+        Ordering::Less
+    } else if a.len() > b.len() {
+        Ordering::Greater
+    } else {
+        Ordering::Equal
+    }
+}
+
+/// Checks if the keys of an encoded dictionary are in canonical order, as compared by [`cmp_keys`].
+///
+/// Only the keys of the outermost dictionary are checked, the values are skipped over. Repeated keys aren't in canonical order either.
+///
+/// # Errors
+///
+/// * The source isn't exactly one dictionary, or a key isn't a byte array, at which point `Error::Malformed` is returned.
+/// * The source ends in the middle of the dictionary, at which point `Error::EOF` is returned.
+///
+/// # Examples
+///
+/// ```
+/// use bende::spec::is_sorted_keys;
+///
+/// assert_eq!(is_sorted_keys(b"d1:ai1e2:aai2e1:bi3ee"), Ok(true));
+/// assert_eq!(is_sorted_keys(b"d1:bi1e1:ali1eee"), Ok(false));
+/// assert_eq!(is_sorted_keys(b"d1:ai1e1:ai2ee"), Ok(false));
+/// ```
+pub fn is_sorted_keys(dict: &[u8]) -> Result<bool, Error> {
+    let mut tokens = Tokens::new(dict);
+    let mut next = || tokens.next().ok_or(Error::EOF)?.map(|(_, t)| t);
+
+    if next()? != Token::DictStart {
+        return Err(Error::Malformed);
+    }
+    let mut sorted = true;
+    let mut prev: Option<&[u8]> = None;
+    loop {
+        let key = match next()? {
+            Token::End => break,
+            Token::Bytes(key) => key,
+            _ => return Err(Error::Malformed),
+        };
+        if prev.is_some_and(|prev| cmp_keys(prev, key).is_ge()) {
+            sorted = false;
+        }
+        prev = Some(key);
+
+        let mut depth = 0usize;
+        loop {
+            match next()? {
+                Token::ListStart | Token::DictStart => depth += 1,
+                Token::End if depth == 0 => return Err(Error::Malformed),
+                Token::End => depth -= 1,
+                Token::Int(_) | Token::Bytes(_) => {}
+            }
+            if depth == 0 {
+                break;
+            }
+        }
+    }
+
+    match tokens.next() {
+        None => Ok(sorted),
+        Some(_) => Err(Error::Malformed),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cmp_keys_matches_slices() {
+        let keys: [&[u8]; 6] = [b"", b"a", b"aa", b"ab", b"b", b"\xff"];
+        for a in keys {
+            for b in keys {
+                assert_eq!(cmp_keys(a, b), a.cmp(b));
+            }
+        }
+    }
+
+    #[test]
+    fn is_sorted_keys_skips_values() {
+        assert_eq!(is_sorted_keys(b"de"), Ok(true));
+        assert_eq!(is_sorted_keys(b"d1:bd1:zi0e1:ai0ee1:cl1:zee"), Ok(true));
+        assert_eq!(is_sorted_keys(b"d1:b0:1:a0:e"), Ok(false));
+        assert_eq!(is_sorted_keys(b"d1:ai1ee0:"), Err(Error::Malformed));
+        assert_eq!(is_sorted_keys(b"di1ei1ee"), Err(Error::Malformed));
+        assert_eq!(is_sorted_keys(b"d1:ae"), Err(Error::Malformed));
+        assert_eq!(is_sorted_keys(b"le"), Err(Error::Malformed));
+        assert_eq!(is_sorted_keys(b"d1:al"), Err(Error::EOF));
+    }
+
+    #[test]
+    fn max_int_len() {
+        assert_eq!(i64::MIN.to_string().len(), MAX_INT_DIGITS + 1);
+        assert_eq!(crate::encode(&i64::MIN).unwrap().len(), MAX_INT_LEN);
+    }
+}
@@ -0,0 +1,357 @@
+//! Incremental decoding from readers.
+//!
+//! The types included in this module are:
+//!
+//! * [`NonBlockingDecoder`] - A decoder that can be driven by a non-blocking reader, such as a socket.
+
+use std::io::ErrorKind as IoErrorKind;
+use std::io::Read;
+
+use serde::de::DeserializeOwned;
+
+use super::de::Error;
+use super::de::FrameScanner;
+
+/// The number of bytes requested from the reader at a time.
+const CHUNK_LEN: usize = 4096;
+
+/// The default limit on the length of a single value read from a stream, in bytes, which is larger than any torrent file in practice.
+pub const DEFAULT_MAX_LEN: usize = 16 << 20;
+
+/// The buffer of bytes that have been read, but not yet decoded.
+///
+/// With the `zeroize` feature, the buffer grows by hand, so every allocation it outgrows is scrubbed before it's freed, and it's scrubbed when it's dropped.
+#[derive(Debug, Default)]
+struct Scratch(Vec<u8>);
+
+impl Scratch {
+    /// Appends bytes to the end of the buffer.
+    fn extend_from_slice(&mut self, bytes: &[u8]) {
+        #[cfg(feature = "zeroize")]
+        if self.0.capacity() - self.0.len() < bytes.len() {
+            let cap = usize::max(
+                self.0.len() + bytes.len(),
+                self.0.capacity().saturating_mul(2),
+            );
+            let mut grown = Vec::with_capacity(cap);
+            grown.extend_from_slice(&self.0);
+            // Zeroizing a `Vec` scrubs its whole allocation, spare capacity included.
+            zeroize::Zeroize::zeroize(&mut self.0);
+            self.0 = grown;
+        }
+        self.0.extend_from_slice(bytes);
+    }
+
+    /// Removes the first `len` bytes of the buffer.
+    fn consume(&mut self, len: usize) {
+        self.0.drain(..len);
+
+        // Draining moves the remaining bytes to the front, leaving a stale copy of them past the end of the buffer.
+        #[cfg(feature = "zeroize")]
+        zeroize::Zeroize::zeroize(self.0.spare_capacity_mut());
+    }
+}
+
+impl std::ops::Deref for Scratch {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.0);
+    }
+}
+
+/// A decoder that decodes values from a non-blocking reader.
+///
+/// When the reader returns [`WouldBlock`](std::io::ErrorKind::WouldBlock), the decoder keeps the bytes it has read so far and reports that it needs more data by returning `Ok(None)`, instead of an error. Calling [`poll`](NonBlockingDecoder::poll) again once the reader is ready resumes where it left off.
+///
+/// Values are decoded one after another, so the reader may carry a stream of concatenated values. The bytes of a value are only scanned once, however many reads it takes to arrive.
+///
+/// A stream can't be resynchronized after bytes that aren't valid bencode, so the decoder discards its buffer and stops at the first such error.
+///
+/// # Examples
+///
+/// ```
+/// use bende::stream::NonBlockingDecoder;
+///
+/// let mut de = NonBlockingDecoder::new(&b"i1995e3:foo"[..]);
+///
+/// assert_eq!(de.poll::<i32>().unwrap(), Some(1995));
+/// assert_eq!(de.poll::<String>().unwrap(), Some("foo".to_string()));
+/// ```
+#[derive(Debug)]
+pub struct NonBlockingDecoder<R> {
+    reader: R,
+    buf: Scratch,
+    scanner: FrameScanner,
+    max_len: Option<usize>,
+    failed: bool,
+}
+
+impl<R: Read> NonBlockingDecoder<R> {
+    /// Constructs a new decoder with the given reader, which limits the length of a value to [`DEFAULT_MAX_LEN`].
+    #[inline]
+    pub fn new(reader: R) -> NonBlockingDecoder<R> {
+        Self {
+            reader,
+            buf: Scratch::default(),
+            scanner: FrameScanner::default(),
+            max_len: Some(DEFAULT_MAX_LEN),
+            failed: false,
+        }
+    }
+
+    /// Limits the length of a single value, in bytes, so a peer can't make the decoder buffer an unbounded amount of data. Defaults to [`DEFAULT_MAX_LEN`].
+    ///
+    /// A value is rejected as soon as the buffered bytes show that it's longer than the limit, without waiting for the rest of it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::de::ErrorKind;
+    /// use bende::stream::NonBlockingDecoder;
+    ///
+    /// let mut de = NonBlockingDecoder::new(&b"3:foo6:foobar"[..]).with_max_len(5);
+    ///
+    /// assert_eq!(de.poll::<String>().unwrap(), Some("foo".to_string()));
+    /// assert_eq!(de.poll::<String>().unwrap_err().kind(), ErrorKind::Limit);
+    /// ```
+    #[inline]
+    pub fn with_max_len(mut self, max: usize) -> NonBlockingDecoder<R> {
+        self.max_len = Some(max);
+        self
+    }
+
+    /// Lifts the limit on the length of a single value, for readers that are trusted not to send an unbounded amount of data.
+    #[inline]
+    pub fn without_max_len(mut self) -> NonBlockingDecoder<R> {
+        self.max_len = None;
+        self
+    }
+
+    /// Gets a reference to the underlying reader.
+    #[inline]
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Gets the bytes that have been read, but not yet decoded.
+    #[inline]
+    pub fn buffered(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Consumes the decoder, returning the underlying reader.
+    ///
+    /// Note that any buffered bytes are lost.
+    #[inline]
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Attempts to decode the next value from the reader.
+    ///
+    /// Returns `Ok(None)` if the reader would block before a complete value has been read.
+    ///
+    /// # Errors
+    ///
+    /// * The reader fails with an error other than `WouldBlock` or `Interrupted`.
+    /// * The reader reaches its end before a complete value has been read, at which point `Error::EOF` is returned.
+    /// * The value is longer than the limit set with [`with_max_len`](NonBlockingDecoder::with_max_len), at which point `Error::Length` is returned.
+    /// * The bytes are not valid bencode, or can't be decoded into `T`.
+    ///
+    /// Once the bytes are found not to be valid bencode, or a value is longer than the limit, the buffered bytes are discarded and every later call returns `Error::EOF`.
+    pub fn poll<T>(&mut self) -> Result<Option<T>, Error>
+    where
+        T: DeserializeOwned,
+    {
+        if self.failed {
+            return Err(Error::EOF);
+        }
+
+        let mut chunk = Chunk([0; CHUNK_LEN]);
+        loop {
+            let frame = match self.scan() {
+                Ok(frame) => frame,
+                Err(e) => {
+                    self.consume(self.buf.len());
+                    self.failed = true;
+                    return Err(e);
+                }
+            };
+            if let Some(len) = frame {
+                let val = super::decode(&self.buf[..len]);
+                self.consume(len);
+                return val.map(Some);
+            }
+
+            match self.reader.read(&mut chunk.0) {
+                Ok(0) => return Err(Error::EOF),
+                Ok(n) => self.buf.extend_from_slice(&chunk.0[..n]),
+                Err(e) if e.kind() == IoErrorKind::WouldBlock => {
+                    return Ok(None)
+                }
+                Err(e) if e.kind() == IoErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl<R> NonBlockingDecoder<R> {
+    /// Scans the buffer for the end of the next value, checking it against the limit.
+    fn scan(&mut self) -> Result<Option<usize>, Error> {
+        let frame = self.scanner.scan(&self.buf)?;
+        let len = frame.unwrap_or(self.buf.len());
+        check_len(len, self.max_len)
+            .map_err(|expected| Error::Length { len, expected })?;
+        Ok(frame)
+    }
+
+    /// Removes the first `len` bytes of the buffer.
+    fn consume(&mut self, len: usize) {
+        self.buf.consume(len);
+        self.scanner.reset();
+    }
+}
+
+/// Checks that a value of the given length, or at least that long, is within the limit, returning a description of the lengths it allows otherwise.
+pub(crate) fn check_len(
+    len: usize,
+    max_len: Option<usize>,
+) -> Result<(), String> {
+    match max_len {
+        Some(max) if len > max => {
+            Err(format!("a value of at most {} bytes", max))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The decoder's buffers are scrubbed when it's dropped, including the bytes it has read but not yet decoded.
+#[cfg(feature = "zeroize")]
+impl<R> zeroize::ZeroizeOnDrop for NonBlockingDecoder<R> {}
+
+/// A chunk of bytes read from the reader, which is scrubbed when dropped with the `zeroize` feature.
+struct Chunk([u8; CHUNK_LEN]);
+
+#[cfg(feature = "zeroize")]
+impl Drop for Chunk {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::VecDeque;
+    use std::io;
+
+    use super::NonBlockingDecoder;
+    use crate::de::Error;
+
+    /// A reader that yields the given chunks, and blocks in between them.
+    struct Chunked(VecDeque<Option<&'static [u8]>>);
+
+    impl io::Read for Chunked {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.pop_front() {
+                Some(Some(chunk)) => {
+                    buf[..chunk.len()].copy_from_slice(chunk);
+                    Ok(chunk.len())
+                }
+                Some(None) => Err(io::ErrorKind::WouldBlock.into()),
+                None => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn poll_resumes_after_would_block() {
+        let reader =
+            Chunked(vec![Some(&b"li19"[..]), None, Some(b"95ee")].into());
+        let mut de = NonBlockingDecoder::new(reader);
+
+        assert_eq!(de.poll::<Vec<i32>>(), Ok(None));
+        assert_eq!(de.buffered(), b"li19");
+        assert_eq!(de.poll::<Vec<i32>>(), Ok(Some(vec![1995])));
+        assert!(de.buffered().is_empty());
+    }
+
+    #[test]
+    fn poll_rejects_values_over_the_limit() {
+        let reader =
+            Chunked(vec![Some(&b"i1ei"[..]), None, Some(b"1234")].into());
+        let mut de = NonBlockingDecoder::new(reader).with_max_len(4);
+
+        assert_eq!(de.poll::<i32>(), Ok(Some(1)));
+        assert_eq!(de.poll::<i32>(), Ok(None));
+        assert!(matches!(de.poll::<i32>(), Err(Error::Length { len: 5, .. })));
+    }
+
+    #[test]
+    fn poll_limits_values_by_default() {
+        let text = "a".repeat(super::DEFAULT_MAX_LEN);
+        let src = format!("{}:{}", text.len(), text);
+
+        let mut de = NonBlockingDecoder::new(src.as_bytes());
+        assert!(matches!(de.poll::<String>(), Err(Error::Length { .. })));
+
+        let mut de = NonBlockingDecoder::new(src.as_bytes()).without_max_len();
+        assert_eq!(de.poll::<String>(), Ok(Some(text)));
+    }
+
+    #[test]
+    fn poll_value_over_many_reads() {
+        let chunks = (0..100).map(|_| Some(&b"ab"[..]));
+        let reader = Chunked(
+            std::iter::once(Some(&b"200:"[..])).chain(chunks).collect(),
+        );
+        let mut de = NonBlockingDecoder::new(reader);
+
+        // The buffer grows many times over, without losing any bytes.
+        assert_eq!(de.poll::<String>(), Ok(Some("ab".repeat(100))));
+    }
+
+    #[test]
+    fn poll_eof_mid_value() {
+        let mut de = NonBlockingDecoder::new(&b"3:fo"[..]);
+        assert_eq!(de.poll::<String>(), Err(Error::EOF));
+    }
+
+    #[test]
+    fn poll_fails_early_on_malformed_integer() {
+        let reader =
+            Chunked(vec![Some(&b"li1ei-"[..]), None, Some(b"x")].into());
+        let mut de = NonBlockingDecoder::new(reader);
+
+        assert_eq!(de.poll::<Vec<i32>>(), Ok(None));
+        // The integer is rejected before its closing `e` arrives.
+        assert_eq!(de.poll::<Vec<i32>>(), Err(Error::Malformed));
+    }
+
+    #[test]
+    fn poll_stops_after_malformed_value() {
+        let reader = Chunked(vec![Some(&b"i1exi2e"[..]), Some(b"i3e")].into());
+        let mut de = NonBlockingDecoder::new(reader);
+
+        assert_eq!(de.poll::<i32>(), Ok(Some(1)));
+        assert_eq!(de.poll::<i32>(), Err(Error::Malformed));
+        assert!(de.buffered().is_empty());
+        assert_eq!(de.poll::<i32>(), Err(Error::EOF));
+        assert_eq!(de.poll::<i32>(), Err(Error::EOF));
+    }
+}
@@ -0,0 +1,199 @@
+//! A zero-allocation scanner over the tokens of bencoded bytes.
+//!
+//! * [`Token`] - A single token, borrowed from the source.
+//! * [`Tokens`] - An iterator over the tokens of a source, alongside their offsets.
+//!
+//! The scanner is the building block of the crate's own framing, and can be used to build custom scanners and validators without decoding anything.
+
+use std::iter::FusedIterator;
+use std::str;
+
+use super::de::Error;
+use super::spec::DICT_START;
+use super::spec::INT_START;
+use super::spec::LIST_START;
+use super::spec::TEXT_DELIM;
+use super::spec::TYPE_END;
+
+/// A single token of bencoded bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Token<'a> {
+    /// An integer, as its digits with an optional leading `-`, eg: `-42`.
+    ///
+    /// The digits aren't parsed, so integers of any length can be scanned.
+    Int(&'a [u8]),
+    /// The content of a byte array, without its length.
+    Bytes(&'a [u8]),
+    /// The start of a list - `l`.
+    ListStart,
+    /// The start of a dictionary - `d`.
+    DictStart,
+    /// The end of a list or dictionary - `e`.
+    End,
+}
+
+/// An iterator over the tokens of bencoded bytes, alongside the offset each token starts at.
+///
+/// Only the tokens themselves are checked, not how they nest, so eg: an `End` without a matching start is yielded as is. The iterator stops after the first error.
+///
+/// # Errors
+///
+/// * The source ends in the middle of a token, at which point `Error::EOF` is returned.
+/// * A token is malformed, at which point `Error::Malformed` is returned.
+///
+/// # Examples
+///
+/// ```
+/// use bende::token::Token;
+///
+/// let tokens: Vec<_> = bende::tokens(b"d3:fooi-42ee").collect();
+///
+/// assert_eq!(
+///     tokens,
+///     [
+///         Ok((0, Token::DictStart)),
+///         Ok((1, Token::Bytes(b"foo"))),
+///         Ok((6, Token::Int(b"-42"))),
+///         Ok((11, Token::End)),
+///     ]
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Tokens<'a> {
+    src: &'a [u8],
+    pos: usize,
+    failed: bool,
+}
+
+impl<'a> Tokens<'a> {
+    /// Constructs an iterator over the tokens of the given source.
+    #[inline]
+    pub fn new(src: &'a [u8]) -> Tokens<'a> {
+        Self { src, pos: 0, failed: false }
+    }
+
+    /// Gets the offset of the next token, which is the end of the last one.
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.pos
+    }
+
+    /// Scans the token at the current position, returning it alongside its length.
+    fn scan_token(&self) -> Result<(Token<'a>, usize), Error> {
+        let rest = &self.src[self.pos..];
+        match rest[0] {
+            LIST_START => Ok((Token::ListStart, 1)),
+            DICT_START => Ok((Token::DictStart, 1)),
+            TYPE_END => Ok((Token::End, 1)),
+            INT_START => {
+                let end =
+                    find(rest, TYPE_END, |digits| is_int_prefix(&digits[1..]))?;
+                let digits = &rest[1..end];
+                if !is_int_prefix(digits)
+                    || !digits.last().is_some_and(u8::is_ascii_digit)
+                {
+                    return Err(Error::Malformed);
+                }
+                Ok((Token::Int(digits), end + 1))
+            }
+            b'0'..=b'9' => {
+                let delim = find(rest, TEXT_DELIM, |digits| {
+                    digits.iter().all(u8::is_ascii_digit)
+                })?;
+                let len: usize = str::from_utf8(&rest[..delim])?
+                    .parse()
+                    .map_err(|_| Error::Malformed)?;
+                match (delim + 1).checked_add(len) {
+                    Some(end) if end <= rest.len() => {
+                        Ok((Token::Bytes(&rest[delim + 1..end]), end))
+                    }
+                    _ => Err(Error::EOF),
+                }
+            }
+            _ => Err(Error::Malformed),
+        }
+    }
+}
+
+/// Finds the position of the given delimiter.
+///
+/// If the delimiter is missing, the source is checked with `is_prefix`, so a token that can never become valid is rejected as `Error::Malformed` without waiting for the rest of it.
+#[inline]
+fn find(
+    src: &[u8],
+    delim: u8,
+    is_prefix: impl FnOnce(&[u8]) -> bool,
+) -> Result<usize, Error> {
+    match src.iter().position(|&b| b == delim) {
+        Some(pos) => Ok(pos),
+        None if is_prefix(src) => Err(Error::EOF),
+        None => Err(Error::Malformed),
+    }
+}
+
+/// Checks that the bytes are an optional `-` followed by ASCII digits, which an integer must start with.
+#[inline]
+fn is_int_prefix(digits: &[u8]) -> bool {
+    let digits = digits.strip_prefix(b"-").unwrap_or(digits);
+    digits.iter().all(u8::is_ascii_digit)
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Result<(usize, Token<'a>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.pos >= self.src.len() {
+            return None;
+        }
+        match self.scan_token() {
+            Ok((token, len)) => {
+                let at = self.pos;
+                self.pos += len;
+                Some(Ok((at, token)))
+            }
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<'a> FusedIterator for Tokens<'a> {}
+
+#[cfg(test)]
+mod test {
+    use super::{Token, Tokens};
+    use crate::de::Error;
+
+    #[test]
+    fn tokens_stop_at_errors() {
+        let mut tokens = Tokens::new(b"li1ei1x5e");
+        assert_eq!(tokens.next(), Some(Ok((0, Token::ListStart))));
+        assert_eq!(tokens.next(), Some(Ok((1, Token::Int(b"1")))));
+        assert_eq!(tokens.next(), Some(Err(Error::Malformed)));
+        assert_eq!(tokens.next(), None);
+        assert_eq!(tokens.offset(), 4);
+
+        let truncated: [&[u8]; 3] = [b"i12", b"3:fo", b"12"];
+        for src in truncated {
+            assert_eq!(Tokens::new(src).next(), Some(Err(Error::EOF)));
+        }
+        let malformed: [&[u8]; 4] = [b"i-e", b"ie", b"i1x", b"12x"];
+        for src in malformed {
+            assert_eq!(Tokens::new(src).next(), Some(Err(Error::Malformed)));
+        }
+        let src = b"99999999999999999999999:";
+        assert_eq!(Tokens::new(src).next(), Some(Err(Error::Malformed)));
+        assert_eq!(Tokens::new(b"").next(), None);
+    }
+
+    #[test]
+    fn tokens_ignore_nesting() {
+        let tokens: Result<Vec<_>, _> = Tokens::new(b"ee0:").collect();
+        assert_eq!(
+            tokens,
+            Ok(vec![(0, Token::End), (1, Token::End), (2, Token::Bytes(b""))])
+        );
+    }
+}
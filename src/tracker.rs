@@ -0,0 +1,895 @@
+//! Typed requests and responses of BitTorrent trackers, as described in BEP 3, BEP 23 and BEP 48.
+//!
+//! Trackers are a diverse bunch, so the types of this module are lenient: every field has a default, text that isn't valid UTF-8 is read as Latin-1, and peers are read from either the compact or the dictionary form. The items included in this module are:
+//!
+//! * [`AnnounceResponse`] - The response to an announce, with the peers of a torrent.
+//! * [`ScrapeResponse`] - The response to a scrape, with the statistics of each torrent.
+//! * [`Peer`] - A peer of an announce response.
+//! * [`InfoHash`] - The 20-byte SHA-1 hash that identifies a torrent.
+//! * [`AnnounceRequest`] - A builder of the query string of an announce, whose response is decoded into an `AnnounceResponse`.
+//! * [`Event`] - The event of an announce, eg: the first one of a download.
+//! * `decode_http_response` - Decodes the body of an HTTP response, undoing its `Content-Encoding` under a size limit. Requires the `http` feature.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Write as _;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+
+use serde::de::DeserializeSeed;
+use serde::de::Error as _;
+use serde::de::IgnoredAny;
+use serde::de::MapAccess;
+use serde::de::SeqAccess;
+use serde::de::Visitor;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+
+#[cfg(feature = "http")]
+use serde::de::DeserializeOwned;
+
+#[cfg(feature = "http")]
+use super::de;
+#[cfg(feature = "http")]
+use super::decompress::Wrapping;
+
+/// The 20-byte SHA-1 hash of a torrent's `info` dictionary, which identifies the torrent, encoded as a byte array.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InfoHash(pub [u8; 20]);
+
+impl Serialize for InfoHash {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        ser.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for InfoHash {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct InfoHashVisitor;
+
+        impl<'de> Visitor<'de> for InfoHashVisitor {
+            type Value = InfoHash;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a 20-byte info hash")
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<InfoHash, E>
+            where
+                E: serde::de::Error,
+            {
+                v.try_into()
+                    .map(InfoHash)
+                    .map_err(|_| E::invalid_length(v.len(), &self))
+            }
+        }
+
+        de.deserialize_bytes(InfoHashVisitor)
+    }
+}
+
+/// A peer of an [`AnnounceResponse`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Peer {
+    /// The IP address of the peer, or a DNS name, which the dictionary form allows.
+    pub ip: String,
+    /// The port the peer listens on.
+    pub port: u16,
+    /// The peer's ID, which only the dictionary form has.
+    pub peer_id: Option<Vec<u8>>,
+}
+
+/// The response of a tracker to an announce.
+///
+/// A tracker that refuses the announce only sends a `failure reason`, and every other field is left at its default.
+///
+/// # Examples
+///
+/// ```
+/// use bende::tracker::{AnnounceResponse, Peer};
+///
+/// let src = b"d8:completei3e8:intervali1800e5:peers6:\x0a\x00\x00\x01\x1a\xe1e";
+/// let res: AnnounceResponse = bende::decode(src).unwrap();
+///
+/// assert_eq!(res.interval, 1800);
+/// assert_eq!(res.complete, Some(3));
+/// assert_eq!(res.peers, [Peer { ip: "10.0.0.1".into(), port: 6881, peer_id: None }]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AnnounceResponse {
+    /// Why the announce failed, if it did.
+    pub failure_reason: Option<String>,
+    /// A warning for the client, which doesn't fail the announce.
+    pub warning_message: Option<String>,
+    /// The number of seconds the client should wait before announcing again.
+    pub interval: u32,
+    /// The number of seconds the client must wait before announcing again.
+    pub min_interval: Option<u32>,
+    /// An ID the client should send back in its next announces.
+    pub tracker_id: Option<String>,
+    /// The number of seeders.
+    pub complete: Option<u32>,
+    /// The number of leechers.
+    pub incomplete: Option<u32>,
+    /// The IPv4 peers, or peers of either kind in the dictionary form.
+    pub peers: Vec<Peer>,
+    /// The IPv6 peers of BEP 7, which are only sent in the compact form.
+    pub peers6: Vec<Peer>,
+}
+
+/// The response of a tracker to a scrape.
+///
+/// # Examples
+///
+/// ```
+/// use bende::tracker::{InfoHash, ScrapeResponse};
+///
+/// let src = b"d5:filesd20:aaaaaaaaaaaaaaaaaaaad8:completei5e10:downloadedi50e10:incompletei10eeee";
+/// let res: ScrapeResponse = bende::decode(src).unwrap();
+///
+/// let file = &res.files[&InfoHash([b'a'; 20])];
+/// assert_eq!((file.complete, file.downloaded, file.incomplete), (5, 50, 10));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScrapeResponse {
+    /// Why the scrape failed, if it did.
+    pub failure_reason: Option<String>,
+    /// The statistics of each torrent that was scraped.
+    pub files: BTreeMap<InfoHash, ScrapeFile>,
+}
+
+/// The statistics of a torrent in a [`ScrapeResponse`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScrapeFile {
+    /// The number of seeders.
+    pub complete: u32,
+    /// The number of times the torrent was downloaded.
+    pub downloaded: u32,
+    /// The number of leechers.
+    pub incomplete: u32,
+    /// The name of the torrent, which some trackers include.
+    pub name: Option<String>,
+}
+
+/// The event of an [`AnnounceRequest`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Event {
+    /// The first announce of a download.
+    Started,
+    /// The download finished, which is only announced once.
+    Completed,
+    /// The client is shutting down gracefully.
+    Stopped,
+}
+
+impl Event {
+    /// Gets the name of the event, as it's written in the query string.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Started => "started",
+            Event::Completed => "completed",
+            Event::Stopped => "stopped",
+        }
+    }
+}
+
+/// A builder of the query string of an announce, which asks for a compact response by default.
+///
+/// # Examples
+///
+/// ```
+/// use bende::tracker::{AnnounceRequest, Event, InfoHash};
+///
+/// let req = AnnounceRequest::new(InfoHash([0xab; 20]), *b"-BD0100-abcdefghijkl", 6881)
+///     .with_left(1995)
+///     .with_event(Event::Started);
+///
+/// let url = req.url("http://tracker.example/announce");
+/// assert!(url.starts_with("http://tracker.example/announce?info_hash=%AB%AB"));
+/// assert!(url.ends_with("&peer_id=-BD0100-abcdefghijkl&port=6881&uploaded=0&downloaded=0&left=1995&compact=1&event=started"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AnnounceRequest {
+    info_hash: InfoHash,
+    peer_id: [u8; 20],
+    port: u16,
+    uploaded: u64,
+    downloaded: u64,
+    left: u64,
+    compact: bool,
+    event: Option<Event>,
+    key: Option<String>,
+    numwant: Option<u32>,
+    tracker_id: Option<String>,
+}
+
+impl AnnounceRequest {
+    /// Constructs a request for the given torrent, from the peer with the given ID that listens on the given port.
+    pub fn new(
+        info_hash: InfoHash,
+        peer_id: [u8; 20],
+        port: u16,
+    ) -> AnnounceRequest {
+        Self {
+            info_hash,
+            peer_id,
+            port,
+            uploaded: 0,
+            downloaded: 0,
+            left: 0,
+            compact: true,
+            event: None,
+            key: None,
+            numwant: None,
+            tracker_id: None,
+        }
+    }
+
+    /// Sets the number of bytes uploaded since the `started` event. Defaults to `0`.
+    #[inline]
+    pub fn with_uploaded(mut self, uploaded: u64) -> AnnounceRequest {
+        self.uploaded = uploaded;
+        self
+    }
+
+    /// Sets the number of bytes downloaded since the `started` event. Defaults to `0`.
+    #[inline]
+    pub fn with_downloaded(mut self, downloaded: u64) -> AnnounceRequest {
+        self.downloaded = downloaded;
+        self
+    }
+
+    /// Sets the number of bytes left to download. Defaults to `0`, which tells the tracker the client is a seeder.
+    #[inline]
+    pub fn with_left(mut self, left: u64) -> AnnounceRequest {
+        self.left = left;
+        self
+    }
+
+    /// Sets whether the compact form of BEP 23 is asked for. Defaults to `true`.
+    #[inline]
+    pub fn with_compact(mut self, compact: bool) -> AnnounceRequest {
+        self.compact = compact;
+        self
+    }
+
+    /// Sets the event of the announce, which regular announces don't have.
+    #[inline]
+    pub fn with_event(mut self, event: Event) -> AnnounceRequest {
+        self.event = Some(event);
+        self
+    }
+
+    /// Sets the key, which lets the tracker recognize the client if its IP address changes.
+    #[inline]
+    pub fn with_key<K>(mut self, key: K) -> AnnounceRequest
+    where
+        K: Into<String>,
+    {
+        self.key = Some(key.into());
+        self
+    }
+
+    /// Sets the number of peers the client wants.
+    #[inline]
+    pub fn with_numwant(mut self, numwant: u32) -> AnnounceRequest {
+        self.numwant = Some(numwant);
+        self
+    }
+
+    /// Sets the tracker ID a previous [`AnnounceResponse`] had.
+    #[inline]
+    pub fn with_tracker_id<T>(mut self, tracker_id: T) -> AnnounceRequest
+    where
+        T: Into<String>,
+    {
+        self.tracker_id = Some(tracker_id.into());
+        self
+    }
+
+    /// Builds the query string, without a leading `?`, percent-encoding every byte that isn't unreserved, as RFC 3986 describes.
+    pub fn query_string(&self) -> String {
+        let mut query = String::with_capacity(160);
+        let mut push = |name: &str, val: &[u8]| {
+            if !query.is_empty() {
+                query.push('&');
+            }
+            query.push_str(name);
+            query.push('=');
+            for &b in val {
+                if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+                    query.push(char::from(b));
+                } else {
+                    let _ = write!(query, "%{:02X}", b);
+                }
+            }
+        };
+        push("info_hash", &self.info_hash.0);
+        push("peer_id", &self.peer_id);
+        push("port", self.port.to_string().as_bytes());
+        push("uploaded", self.uploaded.to_string().as_bytes());
+        push("downloaded", self.downloaded.to_string().as_bytes());
+        push("left", self.left.to_string().as_bytes());
+        push("compact", if self.compact { b"1" } else { b"0" });
+        if let Some(event) = self.event {
+            push("event", event.name().as_bytes());
+        }
+        if let Some(ref key) = self.key {
+            push("key", key.as_bytes());
+        }
+        if let Some(numwant) = self.numwant {
+            push("numwant", numwant.to_string().as_bytes());
+        }
+        if let Some(ref tracker_id) = self.tracker_id {
+            push("trackerid", tracker_id.as_bytes());
+        }
+        query
+    }
+
+    /// Appends the query string to the announce URL of a tracker, after a `?`, or after a `&` if the URL already has a query, eg: a passkey.
+    ///
+    /// No separator is added if the URL already ends with a `?` or `&`, and the query is inserted before a fragment, if there's one.
+    pub fn url(&self, announce: &str) -> String {
+        let (base, fragment) = match announce.find('#') {
+            Some(i) => announce.split_at(i),
+            None => (announce, ""),
+        };
+        let sep = if base.ends_with(['?', '&']) {
+            ""
+        } else if base.contains('?') {
+            "&"
+        } else {
+            "?"
+        };
+        let query = self.query_string();
+        let mut url = String::with_capacity(announce.len() + 1 + query.len());
+        url.push_str(base);
+        url.push_str(sep);
+        url.push_str(&query);
+        url.push_str(fragment);
+        url
+    }
+}
+
+/// Decodes the body of a tracker's HTTP response, given the response's headers.
+///
+/// The body is decompressed only if its `Content-Encoding` is `gzip` or `deflate`, where `deflate` may be either zlib or raw DEFLATE, as servers send both. Reading stops as soon as there are more than `max_len` decompressed bytes, so a small compressed body can't expand into an unbounded amount of memory. A UTF-8 byte order mark or whitespace before the decompressed body is skipped. The decoder is as lenient as the [`Libtorrent`](crate::profile::Profile::Libtorrent) profile, and also accepts integers written as byte arrays, eg: a `port` of `4:6881`.
+///
+/// The charset of the `Content-Type` isn't honored: bencode is a binary format, whose byte arrays are prefixed with their length in bytes and may hold binary data, eg: compact peers, so transcoding the body would corrupt it. Trackers also label bencode with all sorts of types and charsets. Text that isn't valid UTF-8 is read as Latin-1 by the types of this module instead.
+///
+/// # Errors
+///
+/// * The `Content-Encoding` isn't one of `gzip`, `x-gzip`, `deflate` or `identity`, or the body is compressed more than once, at which point `de::Error::Unsupported` is returned.
+/// * The body is malformed, or doesn't match its `Content-Encoding`, at which point `de::Error::Io` is returned.
+/// * There are more than `max_len` decompressed bytes, at which point `de::Error::Length` is returned.
+/// * The decompressed body can't be decoded into the type.
+///
+/// # Examples
+///
+/// ```
+/// use bende::tracker::{self, AnnounceResponse};
+///
+/// let body = b"\xef\xbb\xbfd14:failure reason12:Acc\xe8s refus\xe9e";
+/// let headers = [("Content-Type", "text/plain; charset=ISO-8859-1")];
+///
+/// let res: AnnounceResponse = tracker::decode_http_response(body, headers, 1024).unwrap();
+/// assert_eq!(res.failure_reason.as_deref(), Some("Accès refusé"));
+/// ```
+#[cfg(feature = "http")]
+pub fn decode_http_response<T, H, K, V>(
+    body: &[u8],
+    headers: H,
+    max_len: usize,
+) -> Result<T, de::Error>
+where
+    T: DeserializeOwned,
+    H: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    let mut wrapping = Wrapping::Identity;
+    for (name, val) in headers {
+        if !name.as_ref().trim().eq_ignore_ascii_case("content-encoding") {
+            continue;
+        }
+        for coding in val.as_ref().split(',') {
+            let next = match coding.trim().to_ascii_lowercase().as_str() {
+                "" | "identity" => continue,
+                "gzip" | "x-gzip" => Wrapping::Gzip,
+                "deflate" => match Wrapping::detect(&body[..body.len().min(2)])
+                {
+                    Wrapping::Zlib => Wrapping::Zlib,
+                    _ => Wrapping::Deflate,
+                },
+                _ => return Err(de::Error::Unsupported("content encoding")),
+            };
+            // Trackers don't compress twice, so stacked codings aren't undone.
+            if wrapping != Wrapping::Identity {
+                return Err(de::Error::Unsupported("content encoding"));
+            }
+            wrapping = next;
+        }
+    }
+
+    let bytes = super::decompress::unwrap(body, wrapping, max_len)?;
+    let bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(&bytes);
+    let start = bytes.iter().position(|b| !b.is_ascii_whitespace());
+
+    let mut de = de::Decoder::new(&bytes[start.unwrap_or(bytes.len())..])
+        .with_profile(super::profile::Profile::Libtorrent)
+        .with_lenient_ints(true);
+    T::deserialize(&mut de)
+}
+
+impl<'de> Deserialize<'de> for AnnounceResponse {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct AnnounceVisitor;
+
+        impl<'de> Visitor<'de> for AnnounceVisitor {
+            type Value = AnnounceResponse;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an announce response")
+            }
+
+            fn visit_map<A>(
+                self,
+                mut access: A,
+            ) -> Result<AnnounceResponse, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut res = AnnounceResponse::default();
+                while let Some(key) = access.next_key::<String>()? {
+                    match key.as_str() {
+                        "failure reason" => {
+                            res.failure_reason =
+                                Some(access.next_value::<Text>()?.0)
+                        }
+                        "warning message" => {
+                            res.warning_message =
+                                Some(access.next_value::<Text>()?.0)
+                        }
+                        "interval" => res.interval = access.next_value()?,
+                        "min interval" => {
+                            res.min_interval = Some(access.next_value()?)
+                        }
+                        "tracker id" => {
+                            res.tracker_id =
+                                Some(access.next_value::<Text>()?.0)
+                        }
+                        "complete" => res.complete = Some(access.next_value()?),
+                        "incomplete" => {
+                            res.incomplete = Some(access.next_value()?)
+                        }
+                        "peers" => {
+                            res.peers =
+                                access.next_value_seed(PeersVisitor {
+                                    compact_len: 6,
+                                })?
+                        }
+                        "peers6" => {
+                            res.peers6 =
+                                access.next_value_seed(PeersVisitor {
+                                    compact_len: 18,
+                                })?
+                        }
+                        _ => {
+                            access.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+                Ok(res)
+            }
+        }
+
+        de.deserialize_map(AnnounceVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for ScrapeResponse {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ScrapeVisitor;
+
+        impl<'de> Visitor<'de> for ScrapeVisitor {
+            type Value = ScrapeResponse;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a scrape response")
+            }
+
+            fn visit_map<A>(
+                self,
+                mut access: A,
+            ) -> Result<ScrapeResponse, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut res = ScrapeResponse::default();
+                while let Some(key) = access.next_key::<String>()? {
+                    match key.as_str() {
+                        "failure reason" => {
+                            res.failure_reason =
+                                Some(access.next_value::<Text>()?.0)
+                        }
+                        "files" => res.files = access.next_value()?,
+                        _ => {
+                            access.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+                Ok(res)
+            }
+        }
+
+        de.deserialize_map(ScrapeVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for ScrapeFile {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ScrapeFileVisitor;
+
+        impl<'de> Visitor<'de> for ScrapeFileVisitor {
+            type Value = ScrapeFile;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("the statistics of a torrent")
+            }
+
+            fn visit_map<A>(self, mut access: A) -> Result<ScrapeFile, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut file = ScrapeFile::default();
+                while let Some(key) = access.next_key::<String>()? {
+                    match key.as_str() {
+                        "complete" => file.complete = access.next_value()?,
+                        "downloaded" => {
+                            file.downloaded = access.next_value()?
+                        }
+                        "incomplete" => {
+                            file.incomplete = access.next_value()?
+                        }
+                        "name" => {
+                            file.name = Some(access.next_value::<Text>()?.0)
+                        }
+                        _ => {
+                            access.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+                Ok(file)
+            }
+        }
+
+        de.deserialize_map(ScrapeFileVisitor)
+    }
+}
+
+/// Text that may not be valid UTF-8, which is read as Latin-1 if it isn't.
+struct Text(String);
+
+impl<'de> Deserialize<'de> for Text {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct TextVisitor;
+
+        impl<'de> Visitor<'de> for TextVisitor {
+            type Value = Text;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a byte array of text")
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<Text, E> {
+                Ok(Text(match std::str::from_utf8(v) {
+                    Ok(s) => s.to_owned(),
+                    // Every byte of Latin-1 is the code point of the same value.
+                    Err(_) => v.iter().map(|&b| char::from(b)).collect(),
+                }))
+            }
+        }
+
+        de.deserialize_bytes(TextVisitor)
+    }
+}
+
+/// Visits the peers of an announce response, in either the compact form of BEP 23, or the dictionary form.
+struct PeersVisitor {
+    /// The length of a peer in the compact form, which is the length of its address and the 2 bytes of its port.
+    compact_len: usize,
+}
+
+impl<'de> DeserializeSeed<'de> for PeersVisitor {
+    type Value = Vec<Peer>;
+
+    fn deserialize<D>(self, de: D) -> Result<Vec<Peer>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        de.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for PeersVisitor {
+    type Value = Vec<Peer>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a byte array of {}-byte peers or a list of dictionaries",
+            self.compact_len
+        )
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Vec<Peer>, E>
+    where
+        E: serde::de::Error,
+    {
+        if !v.len().is_multiple_of(self.compact_len) {
+            return Err(E::invalid_length(v.len(), &self));
+        }
+        Ok(v.chunks(self.compact_len)
+            .map(|chunk| {
+                let (addr, port) = chunk.split_at(self.compact_len - 2);
+                let ip = match <[u8; 16]>::try_from(addr) {
+                    Ok(addr) => Ipv6Addr::from(addr).to_string(),
+                    Err(_) => Ipv4Addr::new(addr[0], addr[1], addr[2], addr[3])
+                        .to_string(),
+                };
+                let port = u16::from_be_bytes([port[0], port[1]]);
+                Peer { ip, port, peer_id: None }
+            })
+            .collect())
+    }
+
+    fn visit_seq<A>(self, mut access: A) -> Result<Vec<Peer>, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut peers = Vec::new();
+        while let Some(peer) = access.next_element::<DictPeer>()? {
+            peers.push(peer.0);
+        }
+        Ok(peers)
+    }
+}
+
+/// A peer in the dictionary form, whose fields are decoded by the same decoder as the rest of the response, eg: a `port` written as a byte array.
+struct DictPeer(Peer);
+
+impl<'de> Deserialize<'de> for DictPeer {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct DictPeerVisitor;
+
+        impl<'de> Visitor<'de> for DictPeerVisitor {
+            type Value = DictPeer;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a peer dictionary")
+            }
+
+            fn visit_map<A>(self, mut access: A) -> Result<DictPeer, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let (mut ip, mut port, mut peer_id) = (None, None, None);
+                while let Some(key) = access.next_key::<String>()? {
+                    match key.as_str() {
+                        "ip" => ip = Some(access.next_value::<Text>()?.0),
+                        "port" => port = Some(access.next_value()?),
+                        "peer id" => {
+                            peer_id = Some(access.next_value::<PeerId>()?.0)
+                        }
+                        _ => {
+                            access.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+                Ok(DictPeer(Peer {
+                    ip: ip.ok_or_else(|| A::Error::missing_field("ip"))?,
+                    port: port
+                        .ok_or_else(|| A::Error::missing_field("port"))?,
+                    peer_id,
+                }))
+            }
+        }
+
+        de.deserialize_map(DictPeerVisitor)
+    }
+}
+
+/// The ID of a peer in the dictionary form, which is an arbitrary byte array.
+struct PeerId(Vec<u8>);
+
+impl<'de> Deserialize<'de> for PeerId {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct PeerIdVisitor;
+
+        impl<'de> Visitor<'de> for PeerIdVisitor {
+            type Value = PeerId;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a byte array of a peer ID")
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<PeerId, E> {
+                Ok(PeerId(v.to_vec()))
+            }
+        }
+
+        de.deserialize_bytes(PeerIdVisitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AnnounceRequest, AnnounceResponse, Event, InfoHash, Peer};
+    use crate::decode;
+
+    #[test]
+    fn announce_peers_in_either_form() {
+        let src = b"d8:intervali900e5:peersld2:ip8:10.0.0.17:peer id3:abc4:porti6881eed2:ip11:example.org4:porti80eee6:peers618:\x20\x01\x0d\xb8\0\0\0\0\0\0\0\0\0\0\0\x01\x1a\xe1e";
+        let res: AnnounceResponse = decode(src).unwrap();
+        assert_eq!(
+            res.peers,
+            [
+                Peer {
+                    ip: "10.0.0.1".into(),
+                    port: 6881,
+                    peer_id: Some(b"abc".to_vec())
+                },
+                Peer { ip: "example.org".into(), port: 80, peer_id: None },
+            ]
+        );
+        assert_eq!(
+            res.peers6,
+            [Peer { ip: "2001:db8::1".into(), port: 6881, peer_id: None }]
+        );
+
+        let res: AnnounceResponse =
+            decode(b"d14:failure reason6:bannede").unwrap();
+        assert_eq!(res.failure_reason.as_deref(), Some("banned"));
+        assert!(res.peers.is_empty());
+
+        let err = decode::<AnnounceResponse>(b"d5:peers5:abcdee").unwrap_err();
+        assert_eq!(err.kind(), crate::de::ErrorKind::Data);
+        assert!(decode::<AnnounceResponse>(b"d5:peersld4:porti1eeee").is_err());
+        let err = decode::<AnnounceResponse>(
+            b"d5:peersld2:ip8:10.0.0.14:porti65536eeee",
+        )
+        .unwrap_err();
+        assert!(!err.to_string().contains("missing field"));
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn http_response_encodings() {
+        use std::io::Write;
+
+        use flate2::write::{DeflateEncoder, GzEncoder, ZlibEncoder};
+        use flate2::Compression;
+
+        use super::decode_http_response;
+        use crate::de::{Error, ErrorKind};
+
+        let plain = b"d8:intervali60ee";
+        let mut gzip = GzEncoder::new(vec![], Compression::default());
+        gzip.write_all(plain).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let mut zlib = ZlibEncoder::new(vec![], Compression::default());
+        zlib.write_all(plain).unwrap();
+        let zlib = zlib.finish().unwrap();
+        let mut raw = DeflateEncoder::new(vec![], Compression::default());
+        raw.write_all(plain).unwrap();
+        let raw = raw.finish().unwrap();
+
+        let decode = |body: &[u8], coding: &str, max_len| {
+            decode_http_response::<AnnounceResponse, _, _, _>(
+                body,
+                [("Content-Type", "text/plain"), ("content-encoding", coding)],
+                max_len,
+            )
+        };
+        assert_eq!(decode(&gzip, "gzip", 16).unwrap().interval, 60);
+        assert_eq!(decode(&gzip, "identity, x-gzip", 16).unwrap().interval, 60);
+        assert_eq!(decode(&zlib, "deflate", 16).unwrap().interval, 60);
+        assert_eq!(decode(&raw, "Deflate", 16).unwrap().interval, 60);
+
+        let res: AnnounceResponse =
+            decode_http_response(b"\nd8:interval4:1800e\n", [("a", "b")], 64)
+                .unwrap();
+        assert_eq!(res.interval, 1800);
+        let res: AnnounceResponse = decode_http_response(
+            b"d8:intervali900e5:peersld2:ip8:10.0.0.14:port4:6881eee",
+            [("a", "b")],
+            64,
+        )
+        .unwrap();
+        assert_eq!(res.peers[0].port, 6881);
+
+        // Only the declared encoding is undone.
+        assert!(decode(&gzip, "identity", 64).is_err());
+        assert_eq!(
+            decode(plain, "gzip", 64).unwrap_err().kind(),
+            ErrorKind::Io
+        );
+
+        let err = decode(&gzip, "gzip", 15).unwrap_err();
+        assert!(matches!(err, Error::Length { len: 16, .. }));
+        assert_eq!(err.kind(), ErrorKind::Limit);
+
+        assert!(matches!(decode(&gzip, "br", 64), Err(Error::Unsupported(_))));
+        assert!(matches!(
+            decode(&gzip, "gzip, gzip", 64),
+            Err(Error::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn announce_request_query_string() {
+        let mut info_hash = [0; 20];
+        info_hash[..4].copy_from_slice(b"a b~");
+        let req = AnnounceRequest::new(InfoHash(info_hash), [0xff; 20], 80)
+            .with_uploaded(1)
+            .with_downloaded(2)
+            .with_left(3)
+            .with_compact(false)
+            .with_event(Event::Completed)
+            .with_key("k/1")
+            .with_numwant(50)
+            .with_tracker_id("t");
+
+        let query = req.query_string();
+        assert_eq!(
+            query,
+            format!(
+                "info_hash=a%20b~{}&peer_id={}&port=80&uploaded=1&downloaded=2&left=3&compact=0&event=completed&key=k%2F1&numwant=50&trackerid=t",
+                "%00".repeat(16),
+                "%FF".repeat(20)
+            )
+        );
+        assert_eq!(
+            req.url("http://t/announce?passkey=x"),
+            format!("http://t/announce?passkey=x&{}", query)
+        );
+        assert_eq!(
+            req.url("http://t/announce?"),
+            format!("http://t/announce?{}", query)
+        );
+        assert_eq!(
+            req.url("http://t/announce?passkey=x&#frag?a"),
+            format!("http://t/announce?passkey=x&{}#frag?a", query)
+        );
+        assert_eq!(
+            req.url("http://t/announce#frag"),
+            format!("http://t/announce?{}#frag", query)
+        );
+    }
+}
@@ -5,24 +5,210 @@
 //! * [`Value`] - An enumeration over the different bencode data types.
 //! * [`List`] - A list of bencode values.
 //! * [`Dict`] - A **sorted** key-value object.
+//! * `SmallList` - A list that stores its first few elements inline, with the `smallvec` feature.
+//! * [`ValueRef`] - A bencode value that borrows from the source it was decoded from.
+//! * [`CowValue`] - A bencode value that borrows from the source until it's mutated.
+//! * `FastValue` - A bencode value with hashed dictionaries, which are only sorted when encoded, with the `hashbrown` feature.
+//! * [`OrderedValue`] - A bencode value whose dictionaries keep their document order, for byte-faithful round trips of legacy files.
+//! * [`BoundedValue`] - A bencode value whose nesting depth is bounded by its type, so decoding it needs a bounded stack.
+//! * [`ArcValue`] - A bencode value with cheap, thread-safe structural sharing.
+//! * [`Interner`] - A pool that converts values into [`ArcValue`]s, sharing identical byte arrays between them.
+//! * `CompactValue` - A bencode value with a compact, immutable layout, with the `compact` feature.
+//! * [`StaticValue`] - A bencode value that can be constructed in `const` and `static` contexts.
+//! * [`ValueDisplay`] - A configurable adapter for displaying values.
+//! * [`NormalizeConfig`] - Describes which lists are unordered, for order-insensitive comparison of values.
+//! * [`CompareConfig`] - Describes which values may differ, for [`Value::compare`], which reports the differences in a [`CompareReport`].
+//! * [`Skeleton`] - The structure of a value tree without its payloads, returned by [`Value::sample`] for telemetry and bug reports.
+//! * [`VisitValue`] - A visitor over a value tree, that can skip subtrees or stop early.
+//! * [`Utf8Policy`] - How byte arrays are decoded into strings.
+//! * [`ParseValueError`] - An error returned when a string can't be parsed into a value.
+//! * [`TryFromValueError`] - An error returned when a value can't be converted into another type.
+//! * [`EditValueError`] - An error returned when a checked edit of a value is rejected.
 
+use std::collections::btree_map::Entry;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::fmt;
+#[cfg(feature = "value-io")]
+use std::fs::File;
+#[cfg(feature = "value-io")]
+use std::io::BufWriter;
+#[cfg(feature = "value-io")]
+use std::io::Read;
+#[cfg(feature = "value-io")]
+use std::io::Write;
+use std::num::TryFromIntError;
+use std::ops::ControlFlow;
 use std::str;
 use std::str::Utf8Error;
 
-use serde::de::Visitor;
-use serde::ser::SerializeMap;
-use serde::ser::SerializeSeq;
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde::Serialize;
 
-/// A list of bencode values.
-pub type List = Vec<Value>;
+use super::de;
+use super::en;
+use super::path;
+use super::path::Path;
+use super::path::Pattern;
+use super::path::Segment;
 
-/// A **sorted** key-value map with keys that are UTF-8 valid strings.
-pub type Dict = BTreeMap<String, Value>;
+mod borrowed;
+mod bounded;
+#[cfg(feature = "compact")]
+mod compact;
+mod compare;
+mod constant;
+mod cow;
+mod deserializer;
+mod dict;
+mod display;
+#[cfg(feature = "hashbrown")]
+mod fast;
+mod intern;
+mod list;
+mod normalize;
+mod ordered;
+mod parse;
+mod sample;
+mod shared;
+mod visit;
+
+pub use borrowed::DictRef;
+pub use borrowed::ValueRef;
+pub use bounded::BoundedValue;
+#[cfg(feature = "compact")]
+pub use compact::CompactDict;
+#[cfg(feature = "compact")]
+pub use compact::CompactEntry;
+#[cfg(feature = "compact")]
+pub use compact::CompactValue;
+pub use compare::CompareConfig;
+pub use compare::CompareReport;
+pub use compare::Difference;
+pub use compare::DifferenceKind;
+pub use constant::StaticValue;
+pub use cow::CowDict;
+pub use cow::CowValue;
+pub use dict::Dict;
+pub use dict::Key;
+pub use display::ValueDisplay;
+#[cfg(feature = "hashbrown")]
+pub use fast::FastDict;
+#[cfg(feature = "hashbrown")]
+pub use fast::FastValue;
+pub use intern::InternStats;
+pub use intern::Interner;
+pub use list::List;
+pub use normalize::NormalizeConfig;
+pub use ordered::OrderedDict;
+pub use ordered::OrderedValue;
+pub(crate) use ordered::ORDERED_TOKEN;
+pub use parse::ParseValueError;
+pub use sample::Skeleton;
+pub use sample::MAX_SAMPLE_DEPTH;
+pub use shared::ArcDict;
+pub use shared::ArcValue;
+pub use visit::Edge;
+pub use visit::VisitValue;
+pub use visit::Walk;
+
+use visit::BuildValue;
+use visit::ValueVisitor;
+
+/// A list that stores up to `N` elements inline, and only allocates once it grows past them.
+///
+/// Tiny lists are common in bencoded data, eg: the tiers of an `announce-list` usually hold one or two trackers, so decoding them into a `SmallList` saves an allocation per list. Values can't be stored inline in a [`Value`] itself, since a value would then have to contain its own elements, so use this for the fields of your own types instead.
+///
+/// # Examples
+///
+/// ```
+/// use serde::Deserialize;
+/// use bende::value::SmallList;
+///
+/// #[derive(Deserialize)]
+/// struct Torrent {
+///     #[serde(rename = "announce-list")]
+///     announce_list: Vec<SmallList<String>>,
+/// }
+///
+/// let torrent: Torrent = bende::decode(b"d13:announce-listll1:ael1:b1:ceee").unwrap();
+///
+/// assert_eq!(torrent.announce_list[1].as_slice(), ["b", "c"]);
+/// assert!(!torrent.announce_list[1].spilled());
+/// ```
+#[cfg(feature = "smallvec")]
+pub type SmallList<T, const N: usize = 2> = smallvec::SmallVec<[T; N]>;
+
+/// How the bytes of a [`Value::Text`] are turned into a `String`, by [`Value::into_string`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Utf8Policy {
+    /// The bytes must be valid UTF-8.
+    Strict,
+    /// Invalid UTF-8 sequences are replaced with `U+FFFD`, the replacement character.
+    Lossy,
+    /// Every byte is decoded as a Latin-1 (ISO 8859-1) character, so no data is lost.
+    Latin1,
+}
+
+/// An error returned when a [`Value`] can't be converted into another type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TryFromValueError {
+    expected: &'static str,
+}
+
+impl TryFromValueError {
+    /// Constructs a new conversion error with a description of the expected value.
+    #[inline]
+    pub(crate) fn new(expected: &'static str) -> TryFromValueError {
+        Self { expected }
+    }
+}
+
+impl fmt::Display for TryFromValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to convert value, expected {}", self.expected)
+    }
+}
+
+impl std::error::Error for TryFromValueError {}
+
+/// An error returned by the checked editing methods of [`Value`], eg: [`Value::insert_checked`].
+///
+/// The value is left unchanged when an edit is rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditValueError {
+    /// The value being edited is not a `Dict`.
+    NotDict,
+    /// The value being edited is not a `List`.
+    NotList,
+    /// The key is already in the dictionary.
+    DuplicateKey(Key),
+    /// The value can't be represented in bencode, eg: an integer that doesn't fit in an `i64`.
+    Unrepresentable,
+    /// The segment of the path at the given index can't be followed.
+    InvalidPath { at: usize },
+}
+
+impl fmt::Display for EditValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            EditValueError::NotDict => f.write_str("value is not a dictionary"),
+            EditValueError::NotList => f.write_str("value is not a list"),
+            EditValueError::DuplicateKey(ref key) => {
+                write!(f, "duplicate key {:?}", key)
+            }
+            EditValueError::Unrepresentable => {
+                f.write_str("value can't be represented in bencode")
+            }
+            EditValueError::InvalidPath { at } => {
+                write!(f, "path segment {} can't be followed", at)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EditValueError {}
 
 /// Represents any valid data type that can be encoded/decoded to and from bencode.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -33,54 +219,13 @@ pub enum Value {
     Text(Vec<u8>),
     /// A list of bencode values.
     List(List),
-    /// A key-value map with keys that are UTF-8 valid strings.
+    /// A key-value map with keys that are any byte strings.
     Dict(Dict),
 }
 
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match *self {
-            Value::Int(int) => write!(f, "{}", int),
-            Value::Text(ref bytes) => {
-                let v = String::from_utf8_lossy(bytes);
-                write!(f, "\"{}\"", &v)
-            }
-            Value::List(ref list) => {
-                f.write_str("[")?;
-
-                let mut first = true;
-                for elem in list {
-                    if !first {
-                        write!(f, ", ")?;
-                    }
-
-                    write!(f, "{}", elem)?;
-                    first = false;
-                }
-
-                f.write_str("]")
-            }
-            Value::Dict(ref dict) => {
-                f.write_str("{")?;
-
-                let mut first = true;
-                for (key, val) in dict {
-                    if first {
-                        first = false;
-                        f.write_str(" ")?;
-                    } else {
-                        f.write_str(", ")?;
-                    }
-                    write!(f, "{}: {}", key, val)?;
-                }
-
-                if !first {
-                    f.write_str(" ")?;
-                }
-
-                f.write_str("}")
-            }
-        }
+        fmt::Display::fmt(&self.display(), f)
     }
 }
 
@@ -168,6 +313,37 @@ impl Value {
         }
     }
 
+    /// Converts the value into a `String` if it's `Text`, decoding its bytes with the given policy.
+    ///
+    /// If the value is not `Text`, or if its bytes are not valid UTF-8 under [`Utf8Policy::Strict`], the value is handed back as an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::Value;
+    /// use bende::value::Utf8Policy;
+    ///
+    /// let val = Value::Text(b"caf\xe9".to_vec());
+    ///
+    /// assert_eq!(val.clone().into_string(Utf8Policy::Lossy).unwrap(), "caf\u{fffd}");
+    /// assert_eq!(val.clone().into_string(Utf8Policy::Latin1).unwrap(), "caf\u{e9}");
+    /// assert_eq!(val.clone().into_string(Utf8Policy::Strict), Err(val));
+    /// ```
+    pub fn into_string(self, policy: Utf8Policy) -> Result<String, Value> {
+        let Value::Text(v) = self else { return Err(self) };
+
+        match policy {
+            Utf8Policy::Strict => {
+                String::from_utf8(v).map_err(|e| Value::Text(e.into_bytes()))
+            }
+            Utf8Policy::Lossy => Ok(match String::from_utf8(v) {
+                Ok(s) => s,
+                Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+            }),
+            Utf8Policy::Latin1 => Ok(v.into_iter().map(char::from).collect()),
+        }
+    }
+
     /// Returns a slice of values if the value is a `List`. Otherwise, `None` is returned.
     ///
     /// # Examples
@@ -175,7 +351,7 @@ impl Value {
     /// ```
     /// use bende::Value;
     ///
-    /// let val = Value::List(vec![Value::Int(50), Value::Text(b"foo".to_vec())]);
+    /// let val = Value::List(vec![Value::Int(50), Value::Text(b"foo".to_vec())].into());
     /// for elem in val.as_list().unwrap() {
     ///     println!("{:?}", elem);
     /// }
@@ -194,7 +370,7 @@ impl Value {
     /// ```
     /// use bende::Value;
     ///
-    /// let mut val = Value::List(vec![Value::Int(50), Value::Int(50)]);
+    /// let mut val = Value::List(vec![Value::Int(50), Value::Int(50)].into());
     /// for elem in val.as_list_mut().unwrap() {
     ///     *elem = Value::Text(b"foo".to_vec());
     /// }
@@ -206,7 +382,134 @@ impl Value {
         }
     }
 
-    /// Returns a `BTreeMap` if the value is a `Dict`. Otherwise, `None` is returned.
+    /// Appends an element to the back of the value if it's a `List`.
+    ///
+    /// If the value is not a `List`, the element is handed back as an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::value::List;
+    /// use bende::Value;
+    ///
+    /// let mut val = Value::List(List::new());
+    /// val.push("foo").unwrap();
+    /// val.push(1995).unwrap();
+    ///
+    /// assert_eq!(bende::encode(&val).unwrap(), b"l3:fooi1995ee");
+    /// assert!(Value::Int(50).push(1995).is_err());
+    /// ```
+    pub fn push<T>(&mut self, elem: T) -> Result<(), Value>
+    where
+        T: Into<Value>,
+    {
+        match *self {
+            Value::List(ref mut v) => {
+                v.push(elem.into());
+                Ok(())
+            }
+            _ => Err(elem.into()),
+        }
+    }
+
+    /// Appends an element to the back of the value if it's a `List`, checking that the element can be represented in bencode first.
+    ///
+    /// # Errors
+    ///
+    /// * The value is not a `List`, at which point `EditValueError::NotList` is returned.
+    /// * The element can't be converted into a `Value`, at which point `EditValueError::Unrepresentable` is returned.
+    pub fn push_checked<T>(&mut self, elem: T) -> Result<(), EditValueError>
+    where
+        T: TryInto<Value>,
+    {
+        let list = self.as_list_mut().ok_or(EditValueError::NotList)?;
+        let elem =
+            elem.try_into().map_err(|_| EditValueError::Unrepresentable)?;
+        list.push(elem);
+        Ok(())
+    }
+
+    /// Inserts an element at position `index` if the value is a `List`, shifting all elements after it to the right.
+    ///
+    /// If the value is not a `List`, or if `index` is greater than its length, the element is handed back as an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::Value;
+    ///
+    /// let mut val = Value::List(vec![1.into(), 3.into()].into());
+    /// val.insert(1, 2).unwrap();
+    ///
+    /// assert_eq!(val, Value::List(vec![1.into(), 2.into(), 3.into()].into()));
+    /// assert_eq!(val.insert(4, 5), Err(Value::Int(5)));
+    /// ```
+    pub fn insert<T>(&mut self, index: usize, elem: T) -> Result<(), Value>
+    where
+        T: Into<Value>,
+    {
+        match *self {
+            Value::List(ref mut v) if index <= v.len() => {
+                v.insert(index, elem.into());
+                Ok(())
+            }
+            _ => Err(elem.into()),
+        }
+    }
+
+    /// Removes and returns the element at position `index` if the value is a `List`.
+    ///
+    /// Returns `None` if the value is not a `List`, or if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> Option<Value> {
+        match *self {
+            Value::List(ref mut v) if index < v.len() => Some(v.remove(index)),
+            _ => None,
+        }
+    }
+
+    /// Removes consecutive repeated elements if the value is a `List`. Otherwise, this is a no-op.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::Value;
+    ///
+    /// let mut val = Value::List(vec![1.into(), 1.into(), 2.into(), 1.into()].into());
+    /// val.dedup();
+    ///
+    /// assert_eq!(val, Value::List(vec![1.into(), 2.into(), 1.into()].into()));
+    /// ```
+    pub fn dedup(&mut self) {
+        if let Value::List(ref mut v) = *self {
+            v.dedup();
+        }
+    }
+
+    /// Sorts the elements with a key extraction function if the value is a `List`. Otherwise, this is a no-op.
+    ///
+    /// The sort is stable.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::Value;
+    ///
+    /// let mut val = Value::List(vec![3.into(), 1.into(), 2.into()].into());
+    /// val.sort_by_key(|elem| elem.as_i64());
+    ///
+    /// assert_eq!(val, Value::List(vec![1.into(), 2.into(), 3.into()].into()));
+    /// ```
+    pub fn sort_by_key<K, F>(&mut self, f: F)
+    where
+        F: FnMut(&Value) -> K,
+        K: Ord,
+    {
+        if let Value::List(ref mut v) = *self {
+            v.sort_by_key(f);
+        }
+    }
+
+    /// Returns a [`Dict`] if the value is a `Dict`. Otherwise, `None` is returned.
     pub fn as_dict(&self) -> Option<&Dict> {
         match *self {
             Value::Dict(ref v) => Some(v),
@@ -214,142 +517,643 @@ impl Value {
         }
     }
 
-    /// Returns a mutable reference to a `BTreeMap` if the value is a `Dict`. Otherwise, `None` is returned.
+    /// Returns a mutable reference to a [`Dict`] if the value is a `Dict`. Otherwise, `None` is returned.
     pub fn as_dict_mut(&mut self) -> Option<&mut Dict> {
         match *self {
             Value::Dict(ref mut v) => Some(v),
             _ => None,
         }
     }
-}
 
-impl Serialize for Value {
-    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    /// Gets the given key's entry for in-place manipulation if the value is a `Dict`. Otherwise, `None` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::Value;
+    /// use bende::value::Dict;
+    ///
+    /// let mut val = Value::Dict(Dict::new());
+    /// val.entry("announce").unwrap().or_insert_with(|| "udp://t".into());
+    ///
+    /// assert_eq!(val.as_dict().unwrap()["announce"], Value::from("udp://t"));
+    /// ```
+    pub fn entry<K>(&mut self, key: K) -> Option<Entry<'_, Key, Value>>
     where
-        S: serde::Serializer,
+        K: Into<Key>,
     {
-        match *self {
-            Value::Int(v) => ser.serialize_i64(v),
-            Value::Text(ref v) => ser.serialize_bytes(v),
-            Value::List(ref v) => {
-                let mut seq = ser.serialize_seq(Some(v.len()))?;
-                for elem in v {
-                    seq.serialize_element(elem)?;
-                }
-                seq.end()
-            }
-            Value::Dict(ref v) => {
-                let mut map = ser.serialize_map(Some(v.len()))?;
-                for (key, val) in v {
-                    map.serialize_entry(key, val)?;
-                }
-                map.end()
-            }
-        }
+        self.as_dict_mut().map(|dict| dict.entry(key.into()))
     }
-}
 
-impl<'de> Deserialize<'de> for Value {
-    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    /// Inserts a key-value pair if the value is a `Dict`, checking that the key is not in the dictionary yet, and that the value can be represented in bencode.
+    ///
+    /// Unlike inserting into the [`Dict`] directly, an existing key is never overwritten.
+    ///
+    /// # Errors
+    ///
+    /// * The value is not a `Dict`, at which point `EditValueError::NotDict` is returned.
+    /// * The key is already in the dictionary, at which point `EditValueError::DuplicateKey` is returned.
+    /// * The value can't be converted into a `Value`, at which point `EditValueError::Unrepresentable` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::value::{Dict, EditValueError};
+    /// use bende::Value;
+    ///
+    /// let mut val = Value::Dict(Dict::new());
+    /// val.insert_checked("length", 1995u64).unwrap();
+    ///
+    /// assert_eq!(val.insert_checked("length", 0), Err(EditValueError::DuplicateKey("length".into())));
+    /// assert_eq!(val.insert_checked(b"\xff".as_slice(), 0), Ok(()));
+    /// assert_eq!(val.insert_checked("size", u64::MAX), Err(EditValueError::Unrepresentable));
+    /// assert_eq!(bende::encode(&val).unwrap(), b"d6:lengthi1995e1:\xffi0ee");
+    /// ```
+    pub fn insert_checked<K, V>(
+        &mut self,
+        key: K,
+        val: V,
+    ) -> Result<(), EditValueError>
     where
-        D: serde::Deserializer<'de>,
+        K: Into<Key>,
+        V: TryInto<Value>,
     {
-        struct ValueVisitor;
-
-        impl<'de> Visitor<'de> for ValueVisitor {
-            type Value = Value;
-
-            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
-                f.write_str("any valid bencode type")
-            }
-
-            fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
-                Ok(Value::Int(v))
-            }
-
-            fn visit_u64<E>(self, v: u64) -> Result<Value, E> {
-                Ok(Value::Int(v as i64))
+        let dict = self.as_dict_mut().ok_or(EditValueError::NotDict)?;
+        let val =
+            val.try_into().map_err(|_| EditValueError::Unrepresentable)?;
+        match dict.entry(key.into()) {
+            Entry::Occupied(e) => {
+                Err(EditValueError::DuplicateKey(e.key().clone()))
             }
-
-            fn visit_str<E>(self, v: &str) -> Result<Value, E> {
-                Ok(Value::Text(v.as_bytes().to_owned()))
+            Entry::Vacant(e) => {
+                e.insert(val);
+                Ok(())
             }
+        }
+    }
 
-            fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Value, E>
-            where
-                E: serde::de::Error,
-            {
-                self.visit_str(v)
-            }
+    /// Gets the value of the given key if the value is a `Dict`, looking the key up by its bytes. Otherwise, `None` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::Value;
+    ///
+    /// let val: Value = bende::decode(b"d6:nodes60:1:\xffi1ee").unwrap();
+    ///
+    /// assert_eq!(val.get_bytes_key(b"nodes6"), Some(&Value::Text(vec![])));
+    /// assert_eq!(val.get_bytes_key(b"\xff"), Some(&Value::Int(1)));
+    /// ```
+    pub fn get_bytes_key(&self, key: &[u8]) -> Option<&Value> {
+        self.as_dict()?.get_bytes(key)
+    }
 
-            fn visit_string<E>(self, v: String) -> Result<Value, E> {
-                Ok(Value::Text(v.into_bytes()))
-            }
+    /// Returns an iterator over the entries whose keys start with the given bytes, in sorted order, if the value is a `Dict`. Otherwise, the iterator is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::Value;
+    ///
+    /// let val: Value = bende::decode(b"d4:file0:5:filesle4:namei0ee").unwrap();
+    /// let keys: Vec<_> = val.keys_with_prefix(b"file").map(|(key, _)| key).collect();
+    ///
+    /// assert_eq!(keys, ["file", "files"]);
+    /// ```
+    pub fn keys_with_prefix<'a>(
+        &'a self,
+        prefix: &'a [u8],
+    ) -> impl Iterator<Item = (&'a Key, &'a Value)> + 'a {
+        self.as_dict()
+            .into_iter()
+            .flat_map(move |dict| dict.keys_with_prefix(prefix))
+    }
 
-            fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
-                Ok(Value::Text(v.to_owned()))
-            }
+    /// Gets the value at the given path, or `None` if there's no value at it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::Value;
+    ///
+    /// let val: Value = bende::decode(b"d5:filesld6:lengthi3eeee").unwrap();
+    /// let path = "files[0].length".parse().unwrap();
+    ///
+    /// assert_eq!(val.get_path(&path), Some(&Value::Int(3)));
+    /// ```
+    pub fn get_path(&self, path: &Path) -> Option<&Value> {
+        path.segments().iter().try_fold(self, |val, seg| match *seg {
+            Segment::Key(ref key) => val.as_dict()?.get(key),
+            Segment::Index(i) => val.as_list()?.get(i),
+        })
+    }
 
-            fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<Value, E>
-            where
-                E: serde::de::Error,
-            {
-                self.visit_bytes(v)
-            }
+    /// Gets the values at each of the given paths, in the same order as the paths.
+    ///
+    /// The tree is walked once for all the paths, and a prefix that several paths share is only followed once, which is cheaper than calling [`Value::get_path`] for each path.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::Value;
+    ///
+    /// let val: Value = bende::decode(b"d4:infod6:lengthi3e4:name3:fooee").unwrap();
+    /// let paths = ["info.name".parse().unwrap(), "info.private".parse().unwrap(), "info.length".parse().unwrap()];
+    ///
+    /// assert_eq!(val.get_many(&paths), [Some(&Value::from("foo")), None, Some(&Value::Int(3))]);
+    /// ```
+    pub fn get_many(&self, paths: &[Path]) -> Vec<Option<&Value>> {
+        path::get_many(paths, self)
+    }
 
-            fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> {
-                Ok(Value::Text(v))
-            }
+    /// Gets all the values that match the given pattern, alongside their paths, in depth-first order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::Value;
+    ///
+    /// let val: Value = bende::decode(b"d5:filesld6:lengthi3eed6:lengthi5eeee").unwrap();
+    /// let lengths: Vec<_> = val
+    ///     .select(&"files[*].length".parse().unwrap())
+    ///     .into_iter()
+    ///     .map(|(path, val)| (path.to_string(), val.as_i64().unwrap()))
+    ///     .collect();
+    ///
+    /// assert_eq!(lengths, [("files[0].length".into(), 3), ("files[1].length".into(), 5)]);
+    /// ```
+    pub fn select(&self, pattern: &Pattern) -> Vec<(Path, &Value)> {
+        path::select(pattern, self)
+    }
 
-            fn visit_some<D>(self, de: D) -> Result<Self::Value, D::Error>
-            where
-                D: serde::Deserializer<'de>,
-            {
-                Deserialize::deserialize(de)
-            }
+    /// Gets a mutable reference to the value at the given path, or `None` if there's no value at it.
+    pub fn get_path_mut(&mut self, path: &Path) -> Option<&mut Value> {
+        path.segments().iter().try_fold(self, |val, seg| match *seg {
+            Segment::Key(ref key) => val.as_dict_mut()?.get_mut(key),
+            Segment::Index(i) => val.as_list_mut()?.get_mut(i),
+        })
+    }
 
-            fn visit_seq<A>(self, mut access: A) -> Result<Value, A::Error>
-            where
-                A: serde::de::SeqAccess<'de>,
-            {
-                let mut list = Vec::new();
-                while let Some(elem) = access.next_element()? {
-                    list.push(elem);
-                }
-                Ok(Value::List(list))
-            }
+    /// Gets the entry at the end of the given path, whose last segment must be a key.
+    ///
+    /// Any missing intermediate dictionaries are created along the way, but list elements are never created. Returns `None` if the path doesn't end with a key, or if an intermediate segment can't be followed, in which case the value is left unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::Value;
+    /// use bende::value::Dict;
+    ///
+    /// let mut val = Value::Dict(Dict::new());
+    /// let path = "info.private".parse().unwrap();
+    /// val.entry_path(&path).unwrap().or_insert(Value::Int(1));
+    ///
+    /// assert_eq!(bende::encode(&val).unwrap(), b"d4:infod7:privatei1eee");
+    /// ```
+    pub fn entry_path(&mut self, path: &Path) -> Option<Entry<'_, Key, Value>> {
+        let (Segment::Key(last), segs) = path.segments().split_last()? else {
+            return None;
+        };
 
-            fn visit_map<A>(self, mut access: A) -> Result<Value, A::Error>
-            where
-                A: serde::de::MapAccess<'de>,
-            {
-                let mut dict = BTreeMap::new();
-                while let Some((key, val)) = access.next_entry()? {
-                    dict.insert(key, val);
+        let mut val = self;
+        for (at, seg) in segs.iter().enumerate() {
+            val = match *seg {
+                Segment::Key(ref key) => {
+                    let dict = val.as_dict_mut()?;
+                    // A new dictionary has no elements, so no index after it can be followed.
+                    if !dict.contains_key(key)
+                        && segs[at + 1..]
+                            .iter()
+                            .any(|s| matches!(s, Segment::Index(_)))
+                    {
+                        return None;
+                    }
+                    dict.entry(key.as_str().into())
+                        .or_insert_with(|| Value::Dict(Dict::new()))
                 }
-                Ok(Value::Dict(dict))
-            }
+                Segment::Index(i) => val.as_list_mut()?.get_mut(i)?,
+            };
         }
-
-        de.deserialize_any(ValueVisitor)
+        Some(val.as_dict_mut()?.entry(last.as_str().into()))
     }
-}
 
-/// Implements `From<T> for Value` for any numerical type.
-macro_rules! impl_value_from_num {
-    ($($t:ty),*) => {
-        $(
-            impl From<$t> for Value {
-                fn from(v: $t) -> Value {
-                    Value::Int(v as i64)
+    /// Sets the value at the given path, returning the value it replaced, if any. An empty path replaces the whole value.
+    ///
+    /// Like [`Value::entry_path`], any missing intermediate dictionaries are created, but list elements are never created, so an index must already be in bounds. The path is checked before anything is created, so a rejected edit leaves the value unchanged.
+    ///
+    /// # Errors
+    ///
+    /// * A segment of the path can't be followed, at which point `EditValueError::InvalidPath` is returned.
+    /// * The value can't be converted into a `Value`, at which point `EditValueError::Unrepresentable` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::value::{Dict, EditValueError};
+    /// use bende::Value;
+    ///
+    /// let mut val = Value::Dict(Dict::new());
+    /// val.set_path_checked(&"info.private".parse().unwrap(), 1).unwrap();
+    ///
+    /// let path = "info.files[0]".parse().unwrap();
+    /// assert_eq!(val.set_path_checked(&path, 1), Err(EditValueError::InvalidPath { at: 2 }));
+    /// assert_eq!(bende::encode(&val).unwrap(), b"d4:infod7:privatei1eee");
+    /// ```
+    pub fn set_path_checked<V>(
+        &mut self,
+        path: &Path,
+        val: V,
+    ) -> Result<Option<Value>, EditValueError>
+    where
+        V: TryInto<Value>,
+    {
+        let val =
+            val.try_into().map_err(|_| EditValueError::Unrepresentable)?;
+        let segments = path.segments();
+        let Some((last, segs)) = segments.split_last() else {
+            return Ok(Some(std::mem::replace(self, val)));
+        };
+
+        let mut cur = self;
+        for (at, seg) in segs.iter().enumerate() {
+            let invalid = EditValueError::InvalidPath { at };
+            cur = match *seg {
+                Segment::Key(ref key) => {
+                    let dict = cur.as_dict_mut().ok_or(invalid)?;
+                    if !dict.contains_key(key) {
+                        // A new dictionary has no elements, so no index after it can be followed.
+                        let rest = &segments[at + 1..];
+                        if let Some(i) = rest
+                            .iter()
+                            .position(|s| matches!(s, Segment::Index(_)))
+                        {
+                            return Err(EditValueError::InvalidPath {
+                                at: at + 1 + i,
+                            });
+                        }
+                    }
+                    dict.entry(key.as_str().into())
+                        .or_insert_with(|| Value::Dict(Dict::new()))
+                }
+                Segment::Index(i) => cur
+                    .as_list_mut()
+                    .and_then(|list| list.get_mut(i))
+                    .ok_or(invalid)?,
+            };
+        }
+
+        let invalid = EditValueError::InvalidPath { at: segs.len() };
+        match *last {
+            Segment::Key(ref key) => {
+                let dict = cur.as_dict_mut().ok_or(invalid)?;
+                Ok(dict.insert(key.as_str().into(), val))
+            }
+            Segment::Index(i) => {
+                let elem = cur
+                    .as_list_mut()
+                    .and_then(|list| list.get_mut(i))
+                    .ok_or(invalid)?;
+                Ok(Some(std::mem::replace(elem, val)))
+            }
+        }
+    }
+
+    /// Returns an adapter for displaying the value, which can be configured further.
+    ///
+    /// The [`Display`](fmt::Display) implementation of `Value` renders the value with the default configuration. See [`ValueDisplay`] for more details.
+    #[inline]
+    pub fn display(&self) -> ValueDisplay<'_> {
+        ValueDisplay::new(self)
+    }
+
+    /// Returns a copy of the value where every list that the config marks as unordered is sorted, so values can be compared regardless of the order of those lists.
+    ///
+    /// Lists are sorted after their own elements are normalized. See [`NormalizeConfig`] for more details.
+    pub fn normalized(&self, config: &NormalizeConfig) -> Value {
+        let mut val = self.clone();
+        val.normalize(config);
+        val
+    }
+
+    /// Normalizes the value in place. See [`Value::normalized`] for more details.
+    pub fn normalize(&mut self, config: &NormalizeConfig) {
+        normalize::normalize(self, config);
+    }
+
+    /// Compares the value tree with another, reporting every path where they differ in ways the config doesn't tolerate.
+    ///
+    /// See [`CompareConfig`] for more details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::Value;
+    /// use bende::value::{CompareConfig, DifferenceKind};
+    ///
+    /// let a: Value = bende::decode(b"d6:lengthi1e4:name3:fooe").unwrap();
+    /// let b: Value = bende::decode(b"d6:lengthi2ee").unwrap();
+    ///
+    /// let report = a.compare(&b, &CompareConfig::new());
+    /// let kinds: Vec<_> = report.differences().iter().map(|d| (d.path.to_string(), d.kind)).collect();
+    ///
+    /// assert_eq!(kinds, [("length".to_string(), DifferenceKind::Changed), ("name".to_string(), DifferenceKind::Removed)]);
+    /// ```
+    pub fn compare(
+        &self,
+        other: &Value,
+        config: &CompareConfig,
+    ) -> CompareReport {
+        compare::compare(self, other, config)
+    }
+
+    /// Samples the structure of the value tree, keeping the types, lengths and keys of its values, but none of their integers or bytes, eg: to attach a malformed torrent to a bug report without leaking its content.
+    ///
+    /// The tree is sampled depth-first, in document order, until the skeleton has `max_nodes` nodes, at which point the remaining children of every list and dictionary are only counted. The same goes for the children of lists and dictionaries nested deeper than [`MAX_SAMPLE_DEPTH`], so sampling needs a bounded stack. The root is always sampled. See [`Skeleton`] for more details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::Value;
+    ///
+    /// let val: Value = bende::decode(b"d4:infod6:lengthi1995e4:name6:secretee").unwrap();
+    ///
+    /// assert_eq!(val.sample(10).to_string(), r#"{"info": {"length": int, "name": bytes(6)}}"#);
+    /// assert_eq!(val.sample(2).to_string(), r#"{"info": {... 2 more}}"#);
+    /// ```
+    pub fn sample(&self, max_nodes: usize) -> Skeleton {
+        sample::sample(self, max_nodes)
+    }
+
+    /// Walks the value tree depth-first with the given visitor, stopping early if the visitor breaks.
+    ///
+    /// See [`VisitValue`] for more details.
+    pub fn accept<V>(&self, visitor: &mut V) -> ControlFlow<V::Break>
+    where
+        V: VisitValue + ?Sized,
+    {
+        visit::accept(Edge::Root, self, visitor)
+    }
+
+    /// Decodes a value from the bencoded bytes of the given reader.
+    ///
+    /// See [`crate::from_reader`] for more details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::Value;
+    ///
+    /// let val = Value::from_reader(&b"l3:fooi1995ee"[..]).unwrap();
+    /// assert_eq!(val, Value::from(("foo", 1995)));
+    /// ```
+    #[cfg(feature = "value-io")]
+    #[inline]
+    pub fn from_reader<R>(reader: R) -> Result<Value, de::Error>
+    where
+        R: Read,
+    {
+        super::from_reader(reader)
+    }
+
+    /// Encodes the value into bencode representation, writing it to the given writer.
+    ///
+    /// See [`crate::to_writer`] for more details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::Value;
+    ///
+    /// let mut buf = vec![];
+    /// Value::from(("foo", 1995)).to_writer(&mut buf).unwrap();
+    ///
+    /// assert_eq!(buf, b"l3:fooi1995ee");
+    /// ```
+    #[cfg(feature = "value-io")]
+    #[inline]
+    pub fn to_writer<W>(&self, writer: W) -> Result<(), en::Error>
+    where
+        W: Write,
+    {
+        super::to_writer(writer, self)
+    }
+
+    /// Decodes a value from the file at the given path.
+    ///
+    /// # Errors
+    ///
+    /// * The file can't be opened or read, at which point `de::Error::Io` is returned.
+    /// * The file isn't a single, well-formed bencode value.
+    #[cfg(feature = "value-io")]
+    pub fn from_file<P>(path: P) -> Result<Value, de::Error>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        Value::from_reader(File::open(path)?)
+    }
+
+    /// Encodes the value into the file at the given path, creating the file if it doesn't exist and truncating it if it does.
+    ///
+    /// # Errors
+    ///
+    /// * The file can't be created or written to, at which point `en::Error::Io` is returned.
+    /// * The value can't be encoded, eg: one of its integers is out of range.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use bende::Value;
+    ///
+    /// let val = Value::from_file("ubuntu.torrent").unwrap();
+    /// val.to_file("ubuntu-copy.torrent").unwrap();
+    /// ```
+    #[cfg(feature = "value-io")]
+    pub fn to_file<P>(&self, path: P) -> Result<(), en::Error>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.to_writer(&mut writer)?;
+        Ok(writer.flush()?)
+    }
+
+    /// Converts the value into a type, the same way the type is decoded from the value's bencoded bytes.
+    ///
+    /// This is a shorthand for deserializing the type from the value, see the [`Deserializer`](serde::Deserializer) implementation of `Value`.
+    ///
+    /// # Errors
+    ///
+    /// * The value doesn't have the shape of the type, eg: a required key is missing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    /// use serde::Deserialize;
+    /// use bende::Value;
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// struct Peer {
+    ///     ip: String,
+    ///     port: u16,
+    /// }
+    ///
+    /// let val = Value::from_typed(&BTreeMap::from([("ip", "10.0.0.1")])).unwrap();
+    /// assert!(val.clone().try_into_typed::<Peer>().is_err());
+    ///
+    /// let mut val = val;
+    /// val.insert_checked("port", 6881).unwrap();
+    /// let peer: Peer = val.try_into_typed().unwrap();
+    /// assert_eq!(peer, Peer { ip: "10.0.0.1".into(), port: 6881 });
+    /// ```
+    #[inline]
+    pub fn try_into_typed<T>(self) -> Result<T, de::Error>
+    where
+        T: DeserializeOwned,
+    {
+        T::deserialize(self)
+    }
+
+    /// Converts a type into a value, by encoding it with the default [`Encoder`](en::Encoder) and decoding the bytes, so the value is exactly the one the type's bencoded bytes decode into.
+    ///
+    /// # Errors
+    ///
+    /// * The type can't be encoded by the default `Encoder`, eg: it has a `None`, at which point the same error is returned.
+    /// * The encoded bytes can't be decoded into a value, eg: a raw value in them is nested too deeply, at which point `en::Error::Serialize` is returned with the decode error's message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    /// use bende::Value;
+    ///
+    /// let val = Value::from_typed(&BTreeMap::from([("port", 6881)])).unwrap();
+    /// assert_eq!(val.get_bytes_key(b"port"), Some(&Value::Int(6881)));
+    /// ```
+    pub fn from_typed<T>(val: &T) -> Result<Value, en::Error>
+    where
+        T: Serialize,
+    {
+        let bytes = crate::encode(val)?;
+        crate::decode(&bytes).map_err(|e| en::Error::Serialize(e.to_string()))
+    }
+}
+
+/// Serializes values in canonical form, with dictionary keys in ascending byte order.
+///
+/// The keys of a [`Dict`] are already in that order, since they compare by their bytes. Keys that are valid UTF-8 are serialized as strings, so serializers like `serde_json` that need string keys can still serialize values, and bende's own encoder writes every key as the exact bytes bencode compares, so `encode(&value)` is always canonical.
+impl Serialize for Value {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match *self {
+            Value::Int(v) => ser.serialize_i64(v),
+            Value::Text(ref v) => ser.serialize_bytes(v),
+            Value::List(ref v) => visit::serialize_list(ser, v),
+            Value::Dict(ref v) => v.serialize(ser),
+        }
+    }
+}
+
+/// A newtype struct name the encoder recognizes as a dictionary key of a value type, which it writes as the key's bytes.
+pub(crate) const KEY_TOKEN: &str = "$bende::private::KeyBytes";
+
+/// Serializes a dictionary key as a string if it's valid UTF-8, or as a byte array otherwise.
+///
+/// The key is wrapped in a newtype struct named [`KEY_TOKEN`], which other serializers see through.
+pub(super) struct KeyBytes<'a>(pub(super) &'a [u8]);
+
+impl Serialize for KeyBytes<'_> {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        ser.serialize_newtype_struct(KEY_TOKEN, &KeyRepr(self.0))
+    }
+}
+
+/// The key wrapped by [`KeyBytes`].
+struct KeyRepr<'a>(&'a [u8]);
+
+impl Serialize for KeyRepr<'_> {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match str::from_utf8(self.0) {
+            Ok(key) => ser.serialize_str(key),
+            Err(_) => ser.serialize_bytes(self.0),
+        }
+    }
+}
+
+impl<'de> BuildValue<'de> for Value {
+    type Key = Key;
+
+    fn from_int(v: i64) -> Self {
+        Value::Int(v)
+    }
+
+    fn from_bytes(v: &[u8]) -> Option<Self> {
+        Some(Value::Text(v.to_owned()))
+    }
+
+    fn from_byte_buf(v: Vec<u8>) -> Option<Self> {
+        Some(Value::Text(v))
+    }
+
+    fn from_list(list: Vec<Self>) -> Self {
+        Value::List(List::from(list))
+    }
+
+    fn from_entries(entries: Vec<(Key, Self)>) -> Self {
+        Value::Dict(entries.into_iter().collect())
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        de.deserialize_any(ValueVisitor::new())
+    }
+}
+
+/// Implements `From<T> for Value` for any numerical type that always fits in an `i64`.
+macro_rules! impl_value_from_num {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for Value {
+                fn from(v: $t) -> Value {
+                    Value::Int(v as i64)
+                }
+            }
+        )*
+    }
+}
+
+// We need to skip i64, and `u64`/`usize` can't always be represented.
+impl_value_from_num!(u8, u16, u32, i8, i16, i32, isize);
+
+/// Implements `TryFrom<T> for Value` for any numerical type that may not fit in an `i64`.
+macro_rules! impl_value_try_from_num {
+    ($($t:ty),*) => {
+        $(
+            impl TryFrom<$t> for Value {
+                type Error = TryFromIntError;
+
+                /// Converts the number into an `Int`, failing instead of wrapping if it's larger than `i64::MAX`.
+                fn try_from(v: $t) -> Result<Value, TryFromIntError> {
+                    i64::try_from(v).map(Value::Int)
                 }
             }
         )*
     }
 }
 
-// We need to skip i64.
-impl_value_from_num!(u8, u16, u32, u64, usize, i8, i16, i32, isize);
+impl_value_try_from_num!(u64, usize);
 
 // We do this manually as to avoid casting `i64 as i64`.
 impl From<i64> for Value {
@@ -384,33 +1188,161 @@ impl From<String> for Value {
 
 impl From<&[Value]> for Value {
     fn from(v: &[Value]) -> Self {
-        Value::List(v.iter().cloned().collect())
+        Value::List(v.into())
     }
 }
 
 impl From<Vec<Value>> for Value {
     fn from(v: Vec<Value>) -> Self {
+        Value::List(v.into())
+    }
+}
+
+impl From<List> for Value {
+    fn from(v: List) -> Self {
         Value::List(v)
     }
 }
 
 impl From<HashMap<String, Value>> for Value {
     fn from(v: HashMap<String, Value>) -> Self {
-        Value::Dict(BTreeMap::from_iter(v.into_iter()))
+        Value::Dict(Dict::from_iter(v))
     }
 }
 
 impl From<BTreeMap<String, Value>> for Value {
     fn from(v: BTreeMap<String, Value>) -> Self {
+        Value::Dict(v.into())
+    }
+}
+
+impl From<Dict> for Value {
+    fn from(v: Dict) -> Self {
         Value::Dict(v)
     }
 }
 
+impl TryFrom<&Value> for i64 {
+    type Error = TryFromValueError;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        v.as_i64().ok_or(TryFromValueError::new("an integer"))
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for &'a [u8] {
+    type Error = TryFromValueError;
+
+    fn try_from(v: &'a Value) -> Result<Self, Self::Error> {
+        v.as_bytes().ok_or(TryFromValueError::new("a byte array"))
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for &'a str {
+    type Error = TryFromValueError;
+
+    fn try_from(v: &'a Value) -> Result<Self, Self::Error> {
+        match v.as_str() {
+            Ok(Some(v)) => Ok(v),
+            _ => Err(TryFromValueError::new("a UTF-8 string")),
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> From<chrono::DateTime<Tz>> for Value {
+    fn from(v: chrono::DateTime<Tz>) -> Self {
+        Value::Int(v.timestamp())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<&Value> for chrono::DateTime<chrono::Utc> {
+    type Error = TryFromValueError;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        const EXPECTED: &str = "an integer of seconds since the unix epoch";
+
+        let secs = v.as_i64().ok_or(TryFromValueError::new(EXPECTED))?;
+        chrono::DateTime::from_timestamp(secs, 0)
+            .ok_or(TryFromValueError::new(EXPECTED))
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<time::OffsetDateTime> for Value {
+    fn from(v: time::OffsetDateTime) -> Self {
+        Value::Int(v.unix_timestamp())
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<&Value> for time::OffsetDateTime {
+    type Error = TryFromValueError;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        const EXPECTED: &str = "an integer of seconds since the unix epoch";
+
+        let secs = v.as_i64().ok_or(TryFromValueError::new(EXPECTED))?;
+        time::OffsetDateTime::from_unix_timestamp(secs)
+            .map_err(|_| TryFromValueError::new(EXPECTED))
+    }
+}
+
+/// Overwrites every byte array, dictionary key and integer with zeroes, and leaves the value as `Int(0)`.
+///
+/// `Value` doesn't implement `ZeroizeOnDrop`, as a `Drop` implementation would prevent moving byte arrays, lists and dictionaries out of it. Wrap it in [`zeroize::Zeroizing`] instead, which is `ZeroizeOnDrop`, to scrub it when it's dropped.
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for Value {
+    fn zeroize(&mut self) {
+        match std::mem::replace(self, Value::Int(0)) {
+            Value::Int(mut v) => v.zeroize(),
+            Value::Text(mut v) => v.zeroize(),
+            Value::List(mut v) => v.zeroize(),
+            Value::Dict(mut v) => v.zeroize(),
+        }
+    }
+}
+
+/// Implements `From<(A, B, ...)> for Value` for tuples whose elements can each be converted into a `Value`.
+macro_rules! impl_value_from_tuple {
+    ($(($($t:ident),+)),*) => {
+        $(
+            impl<$($t),+> From<($($t,)+)> for Value
+            where
+                $($t: Into<Value>),+
+            {
+                #[allow(non_snake_case)]
+                fn from(($($t,)+): ($($t,)+)) -> Value {
+                    Value::List(List::from([$($t.into()),+]))
+                }
+            }
+        )*
+    }
+}
+
+// Tuples are converted to a heterogeneous list, eg: `("udp://t", 1995)` becomes `l7:udp://ti1995ee`.
+impl_value_from_tuple!(
+    (A),
+    (A, B),
+    (A, B, C),
+    (A, B, C, D),
+    (A, B, C, D, E),
+    (A, B, C, D, E, F),
+    (A, B, C, D, E, F, G),
+    (A, B, C, D, E, F, G, H),
+    (A, B, C, D, E, F, G, H, I),
+    (A, B, C, D, E, F, G, H, I, J),
+    (A, B, C, D, E, F, G, H, I, J, K),
+    (A, B, C, D, E, F, G, H, I, J, K, L)
+);
+
 #[cfg(test)]
 mod test {
     use std::collections::{BTreeMap, HashMap};
 
-    use super::Value;
+    use super::{Dict, List, Value};
+    use crate::path::Path;
     use crate::{decode, encode};
 
     #[test]
@@ -427,8 +1359,9 @@ mod test {
 
     #[test]
     fn encode_value_list() {
-        let val =
-            Value::List(vec![Value::Int(1995), Value::Text(b"foo".to_vec())]);
+        let val = Value::List(
+            vec![Value::Int(1995), Value::Text(b"foo".to_vec())].into(),
+        );
         assert_eq!(encode(&val).unwrap(), b"li1995e3:fooe");
     }
 
@@ -441,6 +1374,41 @@ mod test {
         assert_eq!(encode(&map).unwrap(), b"d3:bar3:faz3:fooi1995ee");
     }
 
+    #[test]
+    fn encode_value_dict_canonical() {
+        let mut dict = Dict::new();
+        for key in ["é", "z", "Z", "\u{7f}"] {
+            dict.insert(key.into(), Value::Int(0));
+        }
+        let keys: Vec<_> = dict.keys().collect();
+
+        assert_eq!(keys, ["Z", "z", "\u{7f}", "é"]);
+        assert_eq!(
+            encode(&Value::Dict(dict)).unwrap(),
+            b"d1:Zi0e1:zi0e1:\x7fi0e2:\xc3\xa9i0ee"
+        );
+    }
+
+    #[test]
+    fn serialize_value_dict_string_keys() {
+        let val: Value = decode(b"d1:ai1e2:\xc3\xa9i2ee").unwrap();
+
+        // Serializers that need string keys still serialize dictionaries.
+        assert_eq!(serde_json::to_string(&val).unwrap(), r#"{"a":1,"é":2}"#);
+    }
+
+    #[test]
+    fn keys_with_partial_char_prefix() {
+        let val: Value =
+            decode(b"d1:ei0e2:\xc3\xa9i1e3:\xc3\xa9ai2e1:zi3ee").unwrap();
+        let keys: Vec<_> =
+            val.keys_with_prefix(b"\xc3").map(|(key, _)| key).collect();
+
+        assert_eq!(keys, ["é", "éa"]);
+        assert_eq!(val.keys_with_prefix(b"").count(), 4);
+        assert_eq!(Value::Int(0).keys_with_prefix(b"").count(), 0);
+    }
+
     #[test]
     fn decode_value_int() {
         assert_eq!(decode::<Value>(b"i1995e").unwrap(), Value::Int(1995));
@@ -458,7 +1426,9 @@ mod test {
     fn decode_value_list() {
         assert_eq!(
             decode::<Value>(b"li1995e3:fooe").unwrap(),
-            Value::List(vec![Value::Int(1995), Value::Text(b"foo".to_vec())])
+            Value::List(
+                vec![Value::Int(1995), Value::Text(b"foo".to_vec())].into()
+            )
         )
     }
 
@@ -469,7 +1439,327 @@ mod test {
         map.insert("bar".to_string(), Value::Text(b"faz".to_vec()));
         assert_eq!(
             decode::<Value>(b"d3:bar3:faz3:fooi1995ee").unwrap(),
-            Value::Dict(map)
+            Value::Dict(map.into())
         )
     }
+
+    #[test]
+    fn entry_on_dict() {
+        let mut val = Value::Dict(Dict::new());
+        *val.entry("foo").unwrap().or_insert(Value::Int(0)) = Value::Int(1995);
+        assert_eq!(val.as_dict().unwrap()["foo"], Value::Int(1995));
+    }
+
+    #[test]
+    fn entry_on_non_dict() {
+        assert!(Value::Int(1995).entry("foo").is_none());
+    }
+
+    #[test]
+    fn entry_path_creates_intermediate_dicts() {
+        let mut val = Value::Dict(Dict::new());
+        val.entry_path(&"info.files.length".parse().unwrap())
+            .unwrap()
+            .or_insert(Value::Int(3));
+        assert_eq!(encode(&val).unwrap(), b"d4:infod5:filesd6:lengthi3eeee");
+    }
+
+    #[test]
+    fn entry_path_through_non_dict() {
+        let mut val = Value::Dict(Dict::new());
+        val.entry("info").unwrap().or_insert(Value::Int(1995));
+        assert!(val.entry_path(&"info.private".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn entry_path_through_list() {
+        let mut val: Value = decode(b"d5:filesldeee").unwrap();
+        let path = "files[0].length".parse().unwrap();
+        val.entry_path(&path).unwrap().or_insert(Value::Int(3));
+        assert_eq!(encode(&val).unwrap(), b"d5:filesld6:lengthi3eeee");
+
+        assert!(val.entry_path(&"files[1].length".parse().unwrap()).is_none());
+        assert!(val.entry_path(&"files[0]".parse().unwrap()).is_none());
+
+        // A path that can't be followed creates nothing.
+        let mut val = Value::Dict(Dict::new());
+        let path = "info.files[0].length".parse().unwrap();
+        assert!(val.entry_path(&path).is_none());
+        assert_eq!(encode(&val).unwrap(), b"de");
+    }
+
+    #[test]
+    fn get_path() {
+        let mut val: Value =
+            decode(b"d4:infod5:filesld6:lengthi3eeeee").unwrap();
+        let path = "info.files[0].length".parse().unwrap();
+        assert_eq!(val.get_path(&path), Some(&Value::Int(3)));
+        assert_eq!(val.get_path(&Path::root()), Some(&val));
+
+        *val.get_path_mut(&path).unwrap() = Value::Int(4);
+        assert_eq!(val.get_path(&path), Some(&Value::Int(4)));
+
+        assert!(val.get_path(&"info.files[1]".parse().unwrap()).is_none());
+        assert!(val.get_path(&"info[0]".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn into_string_policies() {
+        use super::Utf8Policy;
+
+        let val = Value::from("foo");
+        for policy in
+            [Utf8Policy::Strict, Utf8Policy::Lossy, Utf8Policy::Latin1]
+        {
+            assert_eq!(val.clone().into_string(policy).unwrap(), "foo");
+        }
+
+        let val = Value::Text(vec![0xff, b'a']);
+        assert_eq!(
+            val.clone().into_string(Utf8Policy::Strict),
+            Err(val.clone())
+        );
+        assert_eq!(
+            val.clone().into_string(Utf8Policy::Lossy).unwrap(),
+            "\u{fffd}a"
+        );
+        assert_eq!(val.into_string(Utf8Policy::Latin1).unwrap(), "\u{ff}a");
+
+        assert_eq!(
+            Value::Int(1).into_string(Utf8Policy::Latin1),
+            Err(Value::Int(1))
+        );
+    }
+
+    #[test]
+    fn push_and_insert_on_list() {
+        let mut val = Value::List(List::new());
+        assert_eq!(val.push(2), Ok(()));
+        assert_eq!(val.insert(0, "foo"), Ok(()));
+        assert_eq!(val, Value::List(vec!["foo".into(), Value::Int(2)].into()));
+    }
+
+    #[test]
+    fn push_and_insert_on_non_list() {
+        let mut val = Value::Int(1995);
+        assert_eq!(val.push("foo"), Err(Value::from("foo")));
+        assert_eq!(val.insert(0, 2), Err(Value::Int(2)));
+        assert_eq!(val, Value::Int(1995));
+    }
+
+    #[test]
+    fn remove_from_list() {
+        let mut val = Value::List(vec![Value::Int(1), Value::Int(2)].into());
+        assert_eq!(val.remove(0), Some(Value::Int(1)));
+        assert_eq!(val.remove(1), None);
+        assert_eq!(Value::Int(1995).remove(0), None);
+    }
+
+    #[test]
+    fn get_many_matches_get_path() {
+        let val: Value =
+            decode(b"d1:ad1:bli1ei2eee1:cld1:di3eee1:e0:e").unwrap();
+        let paths: Vec<Path> = [
+            "", "a.b[1]", "c[0].d", "a.b", "a.b[2]", "c[0].d", "x.y", "e.f",
+            "a",
+        ]
+        .iter()
+        .map(|s| s.parse().unwrap())
+        .collect();
+        let expected: Vec<_> = paths.iter().map(|p| val.get_path(p)).collect();
+
+        assert_eq!(val.get_many(&paths), expected);
+        assert_eq!(val.get_many(&[]), []);
+    }
+
+    #[test]
+    fn checked_edits() {
+        use super::EditValueError;
+
+        let mut val: Value = decode(b"d4:listli1eee").unwrap();
+        let path = |s: &str| s.parse::<Path>().unwrap();
+
+        assert_eq!(
+            val.set_path_checked(&path("list[0]"), 2),
+            Ok(Some(1.into()))
+        );
+        assert_eq!(
+            val.set_path_checked(&path("list[1]"), 2),
+            Err(EditValueError::InvalidPath { at: 1 })
+        );
+        assert_eq!(
+            val.set_path_checked(&path("a.b[0].c"), 2),
+            Err(EditValueError::InvalidPath { at: 2 })
+        );
+        assert_eq!(
+            val.set_path_checked(&path("list.a"), 2),
+            Err(EditValueError::InvalidPath { at: 1 })
+        );
+        assert_eq!(val.set_path_checked(&path("a.b"), "c"), Ok(None));
+        assert_eq!(encode(&val).unwrap(), b"d1:ad1:b1:ce4:listli2eee");
+
+        let list = val.get_path_mut(&path("list")).unwrap();
+        assert_eq!(
+            list.push_checked(usize::MAX),
+            Err(EditValueError::Unrepresentable)
+        );
+        assert_eq!(list.push_checked(3u64), Ok(()));
+        assert_eq!(list.insert_checked("a", 1), Err(EditValueError::NotDict));
+        assert_eq!(val.push_checked(1), Err(EditValueError::NotList));
+        assert_eq!(encode(&val).unwrap(), b"d1:ad1:b1:ce4:listli2ei3eee");
+    }
+
+    #[cfg(feature = "value-io")]
+    #[test]
+    fn reader_writer_and_file_round_trip() {
+        let val: Value =
+            decode(b"d4:infod4:name3:fooe4:listli1ei2eee").unwrap();
+
+        let mut buf = vec![];
+        val.to_writer(&mut buf).unwrap();
+        assert_eq!(Value::from_reader(&buf[..]).unwrap(), val);
+
+        let path = std::env::temp_dir()
+            .join(format!("bende-value-{}.torrent", std::process::id()));
+        val.to_file(&path).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), buf);
+        assert_eq!(Value::from_file(&path).unwrap(), val);
+        std::fs::remove_file(&path).unwrap();
+
+        let err = Value::from_file(&path).unwrap_err();
+        assert_eq!(err.kind(), crate::de::ErrorKind::Io);
+    }
+
+    #[test]
+    fn from_tuple() {
+        let val = Value::from(("udp://t", 1995, b"hash".as_slice()));
+        assert_eq!(encode(&val).unwrap(), b"l7:udp://ti1995e4:hashe");
+    }
+
+    #[test]
+    fn unsigned_out_of_range() {
+        use serde::de::value::{Error, U64Deserializer};
+        use serde::Deserialize;
+
+        assert_eq!(Value::try_from(i64::MAX as u64), Ok(Value::Int(i64::MAX)));
+        assert!(Value::try_from(u64::MAX).is_err());
+        assert!(Value::try_from(usize::MAX).is_err());
+
+        let de = U64Deserializer::<Error>::new(1995);
+        assert_eq!(Value::deserialize(de), Ok(Value::Int(1995)));
+        let de = U64Deserializer::<Error>::new(u64::MAX);
+        assert!(Value::deserialize(de).is_err());
+    }
+
+    #[test]
+    fn list_helpers() {
+        let mut val = Value::List(List::new());
+        val.push(1).unwrap();
+        val.insert(0, 0).unwrap();
+        val.insert(2, 2).unwrap();
+        assert_eq!(val, Value::List(vec![0.into(), 1.into(), 2.into()].into()));
+
+        assert_eq!(val.insert(4, 4), Err(Value::Int(4)));
+        assert_eq!(val.remove(3), None);
+        assert_eq!(val.remove(1), Some(Value::Int(1)));
+        assert_eq!(encode(&val).unwrap(), b"li0ei2ee");
+
+        let mut int = Value::Int(1);
+        assert_eq!(int.push(2), Err(Value::Int(2)));
+        assert_eq!(int.insert(0, 2), Err(Value::Int(2)));
+        assert_eq!(int.remove(0), None);
+    }
+
+    #[test]
+    fn from_typed_like_encode_then_decode() {
+        use serde::Serialize;
+        use serde_bytes::ByteBuf;
+
+        use crate::raw::RawValue;
+
+        #[derive(Serialize)]
+        enum Event {
+            Started,
+            Moved(u16, u16),
+            Renamed { from: String },
+        }
+
+        #[derive(Serialize)]
+        struct Torrent<'a> {
+            name: &'a str,
+            private: bool,
+            pieces: ByteBuf,
+            events: Vec<Event>,
+            meta: BTreeMap<&'a str, (char, i8)>,
+        }
+
+        let torrent = Torrent {
+            name: "foo",
+            private: true,
+            pieces: ByteBuf::from(vec![0xff, 0]),
+            events: vec![
+                Event::Started,
+                Event::Moved(1, 2),
+                Event::Renamed { from: "bar".into() },
+            ],
+            meta: BTreeMap::from([("z", ('x', -1)), ("a", ('y', 1))]),
+        };
+
+        let val = Value::from_typed(&torrent).unwrap();
+        let roundtrip: Value = decode(&encode(&torrent).unwrap()).unwrap();
+        assert_eq!(val, roundtrip);
+
+        let raw: RawValue = decode(b"d1:ai1ee").unwrap();
+        let val = Value::from_typed(&raw).unwrap();
+        assert_eq!(encode(&val).unwrap(), b"d1:ai1ee");
+
+        let bytes = BTreeMap::from([(ByteBuf::from(vec![0xff]), 1)]);
+        let val = Value::from_typed(&bytes).unwrap();
+        assert_eq!(val.get_bytes_key(b"\xff"), Some(&Value::Int(1)));
+        assert_eq!(Value::from_typed(&()).unwrap(), Value::Dict(Dict::new()));
+    }
+
+    #[test]
+    fn from_typed_errors() {
+        use crate::en::Error;
+        use crate::raw::RawValue;
+
+        assert!(matches!(
+            Value::from_typed(&Some(1.5)),
+            Err(Error::Unsupported("f64"))
+        ));
+        assert!(matches!(
+            Value::from_typed(&u64::MAX),
+            Err(Error::IntegerOverflow(u64::MAX))
+        ));
+
+        assert!(matches!(
+            Value::from_typed(&BTreeMap::from([(1, 2)])),
+            Err(Error::InvalidKeyType)
+        ));
+        // The raw integer is copied as is, but doesn't fit in a `Value`.
+        let raw: RawValue = decode(b"i99999999999999999999999e").unwrap();
+        assert!(matches!(Value::from_typed(&raw), Err(Error::Serialize(_))));
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn zeroize_value() {
+        use zeroize::Zeroize;
+
+        let mut val = decode::<Value>(b"d3:keyl6:secretee").unwrap();
+        val.zeroize();
+        assert_eq!(val, Value::Int(0));
+    }
+
+    #[cfg(feature = "smallvec")]
+    #[test]
+    fn small_list_round_trip() {
+        let tiers: Vec<super::SmallList<String>> =
+            decode(b"ll1:ael1:b1:c1:dee").unwrap();
+
+        assert!(!tiers[0].spilled());
+        assert!(tiers[1].spilled());
+        assert_eq!(encode(&tiers).unwrap(), b"ll1:ael1:b1:c1:dee");
+    }
 }
@@ -0,0 +1,196 @@
+//! A borrowed bencode value.
+
+use std::collections::BTreeMap;
+use std::ops::Bound::Included;
+use std::ops::Bound::Unbounded;
+
+use serde::Deserialize;
+
+use super::visit::BuildValue;
+use super::visit::ValueVisitor;
+use super::Dict;
+use super::Value;
+
+/// A **sorted** key-value map with keys that borrow from the source, and may or **may not** be valid UTF-8.
+pub type DictRef<'a> = BTreeMap<&'a [u8], ValueRef<'a>>;
+
+/// A bencode value that borrows its byte arrays and dictionary keys from the source it was decoded from.
+///
+/// Inspecting a `ValueRef` doesn't require any owned byte arrays, so it's useful when you only need to read a few fields. Use [`decode_ref`](crate::decode_ref) to decode one, and [`ValueRef::to_value`] to turn it into a [`Value`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueRef<'a> {
+    /// A 64-bit signed integer.
+    Int(i64),
+    /// An array of bytes that may or **may not** be valid UTF-8.
+    Text(&'a [u8]),
+    /// A list of bencode values.
+    List(Vec<ValueRef<'a>>),
+    /// A key-value map with keys that may or **may not** be valid UTF-8.
+    Dict(DictRef<'a>),
+}
+
+impl<'a> ValueRef<'a> {
+    /// Returns an `i64` if the value is an `Int`. Otherwise, `None` is returned.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            ValueRef::Int(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns a slice of bytes if the value is `Text`. Otherwise `None` is returned.
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match *self {
+            ValueRef::Text(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns a slice of values if the value is a `List`. Otherwise, `None` is returned.
+    pub fn as_list(&self) -> Option<&[ValueRef<'a>]> {
+        match *self {
+            ValueRef::List(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns a `BTreeMap` if the value is a `Dict`. Otherwise, `None` is returned.
+    pub fn as_dict(&self) -> Option<&DictRef<'a>> {
+        match *self {
+            ValueRef::Dict(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Gets the value of the given key if the value is a `Dict`. Otherwise, `None` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let val = bende::decode_ref(b"d8:announce7:udp://te").unwrap();
+    /// assert_eq!(val.get("announce").unwrap().as_bytes(), Some(b"udp://t".as_slice()));
+    /// ```
+    pub fn get<K>(&self, key: K) -> Option<&ValueRef<'a>>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.as_dict()?.get(key.as_ref())
+    }
+
+    /// Returns an iterator over the entries whose keys start with the given bytes, in sorted order, if the value is a `Dict`. Otherwise, the iterator is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let val = bende::decode_ref(b"d5:nodes0:6:nodes60:1:pi0ee").unwrap();
+    /// let keys: Vec<_> = val.keys_with_prefix(b"nodes").map(|(key, _)| key).collect();
+    ///
+    /// assert_eq!(keys, [b"nodes".as_slice(), b"nodes6"]);
+    /// ```
+    pub fn keys_with_prefix<'b>(
+        &'b self,
+        prefix: &'b [u8],
+    ) -> impl Iterator<Item = (&'a [u8], &'b ValueRef<'a>)> + 'b {
+        self.as_dict()
+            .into_iter()
+            .flat_map(move |dict| {
+                dict.range::<[u8], _>((Included(prefix), Unbounded))
+            })
+            .take_while(move |(key, _)| key.starts_with(prefix))
+            .map(|(key, val)| (*key, val))
+    }
+
+    /// Copies the borrowed data into an owned [`Value`], keeping every dictionary key as is.
+    pub fn to_value(&self) -> Value {
+        match *self {
+            ValueRef::Int(v) => Value::Int(v),
+            ValueRef::Text(v) => Value::Text(v.to_vec()),
+            ValueRef::List(ref v) => {
+                Value::List(v.iter().map(ValueRef::to_value).collect())
+            }
+            ValueRef::Dict(ref v) => Value::Dict(
+                v.iter()
+                    .map(|(key, val)| (*key, val.to_value()))
+                    .collect::<Dict>(),
+            ),
+        }
+    }
+}
+
+impl<'a> From<ValueRef<'a>> for Value {
+    fn from(v: ValueRef<'a>) -> Self {
+        v.to_value()
+    }
+}
+
+impl<'de> BuildValue<'de> for ValueRef<'de> {
+    type Key = &'de [u8];
+
+    const EXPECTING: &'static str =
+        "any valid bencode type, borrowed from the source";
+
+    fn from_int(v: i64) -> Self {
+        ValueRef::Int(v)
+    }
+
+    fn from_bytes(_: &[u8]) -> Option<Self> {
+        None
+    }
+
+    fn from_borrowed_bytes(v: &'de [u8]) -> Option<Self> {
+        Some(ValueRef::Text(v))
+    }
+
+    fn from_list(list: Vec<Self>) -> Self {
+        ValueRef::List(list)
+    }
+
+    fn from_entries(entries: Vec<(&'de [u8], Self)>) -> Self {
+        ValueRef::Dict(entries.into_iter().collect())
+    }
+}
+
+impl<'de> Deserialize<'de> for ValueRef<'de> {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        de.deserialize_any(ValueVisitor::new())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ValueRef;
+    use crate::{decode_ref, Value};
+
+    #[test]
+    fn decode_borrows_from_source() {
+        let src = b"d3:bar3:faz3:fool1:aee";
+        let val = decode_ref(src).unwrap();
+
+        let bar = val.get("bar").and_then(ValueRef::as_bytes).unwrap();
+        assert_eq!(bar, b"faz");
+        assert!(std::ptr::eq(bar.as_ptr(), src[8..].as_ptr()));
+    }
+
+    #[test]
+    fn decode_non_utf8_key() {
+        let val = decode_ref(b"d2:\xff\xfei1ee").unwrap();
+        assert_eq!(val.get(b"\xff\xfe").and_then(ValueRef::as_i64), Some(1));
+    }
+
+    #[test]
+    fn to_value() {
+        let val = decode_ref(b"d3:fool3:bari1995eee").unwrap();
+        assert_eq!(
+            val.to_value(),
+            crate::decode::<Value>(b"d3:fool3:bari1995eee").unwrap()
+        );
+
+        let val = decode_ref(b"d2:\xff\xfei1ee").unwrap();
+        let owned = Value::from(val);
+        assert_eq!(owned.get_bytes_key(b"\xff\xfe"), Some(&Value::Int(1)));
+        assert_eq!(crate::encode(&owned).unwrap(), b"d2:\xff\xfei1ee");
+    }
+}
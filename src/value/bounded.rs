@@ -0,0 +1,116 @@
+//! A bencode value whose nesting depth is bounded at compile time.
+
+use std::ops::Deref;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::visit::ValueVisitor;
+use super::Value;
+
+/// A [`Value`] with at most `MAX_DEPTH` levels of nested lists and dictionaries.
+///
+/// Decoding a value recurses once per level of nesting, so the stack a decode needs grows with how deeply the source is nested. Decoding a `BoundedValue` fails as soon as the source goes deeper than `MAX_DEPTH`, so the stack it needs is bounded by the type itself, for targets where the stack is small and fixed. A value of integers and byte arrays has a depth of `0`, and every list or dictionary adds one level.
+///
+/// # Examples
+///
+/// ```
+/// use bende::value::BoundedValue;
+///
+/// let val: BoundedValue<2> = bende::decode(b"d4:pingl1:aee").unwrap();
+/// assert_eq!(val.depth(), 2);
+///
+/// assert!(bende::decode::<BoundedValue<2>>(b"lllee").is_err());
+/// assert!(bende::decode::<BoundedValue<0>>(b"i1995e").is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoundedValue<const MAX_DEPTH: usize>(Value);
+
+impl<const MAX_DEPTH: usize> BoundedValue<MAX_DEPTH> {
+    /// Wraps the value, or returns `None` if it's nested deeper than `MAX_DEPTH`.
+    pub fn new(val: Value) -> Option<BoundedValue<MAX_DEPTH>> {
+        (depth_of(&val) <= MAX_DEPTH).then_some(Self(val))
+    }
+
+    /// Gets the number of levels of nested lists and dictionaries in the value.
+    pub fn depth(&self) -> usize {
+        depth_of(&self.0)
+    }
+
+    /// Consumes and returns the underlying value.
+    #[inline]
+    pub fn into_inner(self) -> Value {
+        self.0
+    }
+}
+
+impl<const MAX_DEPTH: usize> Deref for BoundedValue<MAX_DEPTH> {
+    type Target = Value;
+
+    fn deref(&self) -> &Value {
+        &self.0
+    }
+}
+
+impl<const MAX_DEPTH: usize> From<BoundedValue<MAX_DEPTH>> for Value {
+    fn from(val: BoundedValue<MAX_DEPTH>) -> Value {
+        val.0
+    }
+}
+
+impl<const MAX_DEPTH: usize> Serialize for BoundedValue<MAX_DEPTH> {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.0.serialize(ser)
+    }
+}
+
+impl<'de, const MAX_DEPTH: usize> Deserialize<'de> for BoundedValue<MAX_DEPTH> {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        de.deserialize_any(ValueVisitor::bounded(MAX_DEPTH)).map(Self)
+    }
+}
+
+/// Gets the depth of the value, without recursing, as values built in memory aren't bounded.
+fn depth_of(val: &Value) -> usize {
+    let mut max = 0;
+    let mut stack = vec![(val, 0)];
+    while let Some((val, depth)) = stack.pop() {
+        match val {
+            Value::List(list) => {
+                stack.extend(list.iter().map(|v| (v, depth + 1)))
+            }
+            Value::Dict(dict) => {
+                stack.extend(dict.values().map(|v| (v, depth + 1)))
+            }
+            _ => continue,
+        }
+        max = max.max(depth + 1);
+    }
+    max
+}
+
+#[cfg(test)]
+mod test {
+    use super::BoundedValue;
+    use crate::Value;
+
+    #[test]
+    fn bounded_depth() {
+        let src = b"d1:ali1eee";
+        let val: BoundedValue<2> = crate::decode(src).unwrap();
+        assert_eq!(val.depth(), 2);
+        assert_eq!(val.into_inner(), crate::decode::<Value>(src).unwrap());
+        assert!(crate::decode::<BoundedValue<1>>(src).is_err());
+
+        let deep = crate::decode::<Value>(b"llli1eeee").unwrap();
+        assert!(BoundedValue::<2>::new(deep.clone()).is_none());
+        assert_eq!(BoundedValue::<3>::new(deep).unwrap().depth(), 3);
+        assert_eq!(BoundedValue::<0>::new(Value::Int(1)).unwrap().depth(), 0);
+    }
+}
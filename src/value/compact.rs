@@ -0,0 +1,226 @@
+//! A bencode value with a compact, immutable layout.
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::visit;
+use super::visit::BuildValue;
+use super::visit::ValueVisitor;
+use super::Key;
+use super::Value;
+
+/// A **sorted** slice of key-value pairs with keys that are any byte strings.
+pub type CompactDict = Box<[CompactEntry]>;
+
+/// A key-value pair of a [`CompactDict`].
+pub type CompactEntry = (Box<[u8]>, CompactValue);
+
+/// A bencode value that stores its byte arrays, lists and dictionaries in exactly sized boxed slices.
+///
+/// A `CompactValue` is 24 bytes instead of the 32 bytes of a [`Value`], has no spare capacity in any of its buffers, and stores each dictionary as one sorted slice instead of a tree of nodes, which is where most of the memory of a decoded [`Value`] goes. Looking up a key is a binary search. The tradeoff is that the value can't be edited in place, so it suits caches that hold many decoded values and only read them. Run `cargo bench --features compact` to compare both layouts.
+///
+/// # Examples
+///
+/// ```
+/// use bende::value::CompactValue;
+///
+/// let val: CompactValue = bende::decode(b"d6:lengthi1995e4:name3:fooe").unwrap();
+///
+/// assert_eq!(val.get("length").and_then(CompactValue::as_i64), Some(1995));
+/// assert_eq!(bende::encode(&val).unwrap(), b"d6:lengthi1995e4:name3:fooe");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompactValue {
+    /// A 64-bit signed integer.
+    Int(i64),
+    /// An array of bytes that may or **may not** be valid UTF-8.
+    Text(Box<[u8]>),
+    /// A list of bencode values.
+    List(Box<[CompactValue]>),
+    /// A key-value map with keys that are any byte strings, sorted by their bytes.
+    Dict(CompactDict),
+}
+
+impl CompactValue {
+    /// Returns an `i64` if the value is an `Int`. Otherwise, `None` is returned.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            CompactValue::Int(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns a slice of bytes if the value is `Text`. Otherwise `None` is returned.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match *self {
+            CompactValue::Text(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns a slice of values if the value is a `List`. Otherwise, `None` is returned.
+    pub fn as_list(&self) -> Option<&[CompactValue]> {
+        match *self {
+            CompactValue::List(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the sorted key-value pairs if the value is a `Dict`. Otherwise, `None` is returned.
+    pub fn as_dict(&self) -> Option<&[CompactEntry]> {
+        match *self {
+            CompactValue::Dict(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Gets the value of the given key if the value is a `Dict`. Otherwise, `None` is returned.
+    pub fn get<K>(&self, key: &K) -> Option<&CompactValue>
+    where
+        K: AsRef<[u8]> + ?Sized,
+    {
+        let dict = self.as_dict()?;
+        let i = dict.binary_search_by(|(k, _)| (**k).cmp(key.as_ref())).ok()?;
+        Some(&dict[i].1)
+    }
+
+    /// Copies the value into an owned [`Value`].
+    pub fn to_value(&self) -> Value {
+        match *self {
+            CompactValue::Int(v) => Value::Int(v),
+            CompactValue::Text(ref v) => Value::Text(v.to_vec()),
+            CompactValue::List(ref v) => {
+                Value::List(v.iter().map(CompactValue::to_value).collect())
+            }
+            CompactValue::Dict(ref v) => Value::Dict(
+                v.iter()
+                    .map(|(key, val)| (Key::from(&**key), val.to_value()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Sorts the entries of a dictionary by their keys, keeping the last entry of any repeated key like a `BTreeMap` would.
+fn into_dict(mut entries: Vec<CompactEntry>) -> CompactDict {
+    // Reversing first puts the last entry of a repeated key first, which a stable sort keeps, and `dedup` retains.
+    entries.reverse();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries.dedup_by(|a, b| a.0 == b.0);
+    entries.into_boxed_slice()
+}
+
+impl From<Value> for CompactValue {
+    fn from(v: Value) -> Self {
+        match v {
+            Value::Int(v) => CompactValue::Int(v),
+            Value::Text(v) => CompactValue::Text(v.into_boxed_slice()),
+            Value::List(v) => CompactValue::List(
+                v.into_iter().map(CompactValue::from).collect(),
+            ),
+            // The keys of a `Dict` are already sorted and unique.
+            Value::Dict(v) => CompactValue::Dict(
+                v.into_iter()
+                    .map(|(key, val)| (key.into_bytes().into(), val.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<&CompactValue> for Value {
+    fn from(v: &CompactValue) -> Self {
+        v.to_value()
+    }
+}
+
+impl Serialize for CompactValue {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match *self {
+            CompactValue::Int(v) => ser.serialize_i64(v),
+            CompactValue::Text(ref v) => ser.serialize_bytes(v),
+            CompactValue::List(ref v) => visit::serialize_list(ser, v),
+            CompactValue::Dict(ref v) => visit::serialize_dict(
+                ser,
+                v.iter().map(|(key, val)| (&**key, val)),
+            ),
+        }
+    }
+}
+
+impl<'de> BuildValue<'de> for CompactValue {
+    type Key = Key;
+
+    fn from_int(v: i64) -> Self {
+        CompactValue::Int(v)
+    }
+
+    fn from_bytes(v: &[u8]) -> Option<Self> {
+        Some(CompactValue::Text(v.into()))
+    }
+
+    fn from_byte_buf(v: Vec<u8>) -> Option<Self> {
+        Some(CompactValue::Text(v.into_boxed_slice()))
+    }
+
+    fn from_list(list: Vec<Self>) -> Self {
+        CompactValue::List(list.into_boxed_slice())
+    }
+
+    fn from_entries(entries: Vec<(Key, Self)>) -> Self {
+        CompactValue::Dict(into_dict(
+            entries
+                .into_iter()
+                .map(|(key, val)| (key.into_bytes().into(), val))
+                .collect(),
+        ))
+    }
+}
+
+impl<'de> Deserialize<'de> for CompactValue {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        de.deserialize_any(ValueVisitor::new())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::mem::size_of;
+
+    use super::CompactValue;
+    use crate::{decode, encode, Value};
+
+    #[test]
+    fn compact_layout() {
+        assert_eq!(size_of::<Value>(), 32);
+        assert_eq!(size_of::<CompactValue>(), 24);
+    }
+
+    #[test]
+    fn decode_unsorted_dict() {
+        let val: CompactValue = decode(b"d1:bi1e1:ai2e1:bi3ee").unwrap();
+
+        assert_eq!(val.get("b"), Some(&CompactValue::Int(3)));
+        assert_eq!(val.get("c"), None);
+        assert_eq!(encode(&val).unwrap(), b"d1:ai2e1:bi3ee");
+        assert_eq!(
+            val.to_value(),
+            decode::<Value>(b"d1:bi1e1:ai2e1:bi3ee").unwrap()
+        );
+    }
+
+    #[test]
+    fn convert_from_value() {
+        let val: Value = decode(b"d3:fool3:bari1995eee").unwrap();
+        let compact = CompactValue::from(val.clone());
+
+        assert_eq!(compact, decode(b"d3:fool3:bari1995eee").unwrap());
+        assert_eq!(Value::from(&compact), val);
+    }
+}
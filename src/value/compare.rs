@@ -0,0 +1,284 @@
+//! Comparison of value trees that tolerates differences in chosen places.
+
+use super::normalize;
+use super::Path;
+use super::Pattern;
+use super::Segment;
+use super::Value;
+
+/// Describes which parts of two value trees don't have to match, for [`Value::compare`].
+///
+/// * Ignored values aren't compared at all, and may be missing from either tree.
+/// * Unordered lists are equal if they have the same elements in any order. Their elements are compared as a whole.
+/// * Lenient values are equal if they only differ cosmetically: byte arrays are compared without surrounding whitespace and regardless of ASCII case, and an integer equals a byte array of its digits.
+///
+/// # Examples
+///
+/// ```
+/// use bende::Value;
+/// use bende::value::CompareConfig;
+///
+/// let a: Value = bende::decode(b"d7:comment3:foo13:creation datei1e4:infod4:name3:fooee").unwrap();
+/// let b: Value = bende::decode(b"d13:creation datei2e4:infod4:name4:FOO ee").unwrap();
+///
+/// let config = CompareConfig::torrent().lenient("info.name".parse().unwrap());
+/// assert!(a.compare(&b, &config).is_equal());
+/// assert!(!a.compare(&b, &CompareConfig::new()).is_equal());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompareConfig {
+    ignored: Vec<Pattern>,
+    unordered: Vec<Pattern>,
+    lenient: Vec<Pattern>,
+}
+
+impl CompareConfig {
+    /// Constructs a config where every value has to match exactly.
+    #[inline]
+    pub fn new() -> CompareConfig {
+        CompareConfig::default()
+    }
+
+    /// Constructs a config for torrent files, which ignores the `comment`, `created by`, `creation date` and `encoding` of the torrent, and treats the tiers of the `announce-list` as unordered.
+    ///
+    /// Two torrents that are equal under this config describe the same content, from the same trackers.
+    pub fn torrent() -> CompareConfig {
+        let pattern = |s: &str| s.parse().expect("the pattern should be valid");
+        CompareConfig::new()
+            .ignore(pattern("comment"))
+            .ignore(pattern("created by"))
+            .ignore(pattern("creation date"))
+            .ignore(pattern("encoding"))
+            .unordered(pattern("announce-list[*]"))
+    }
+
+    /// Ignores the values that match the given pattern, alongside their children.
+    #[inline]
+    pub fn ignore(mut self, pattern: Pattern) -> CompareConfig {
+        self.ignored.push(pattern);
+        self
+    }
+
+    /// Marks the lists that match the given pattern as unordered.
+    #[inline]
+    pub fn unordered(mut self, pattern: Pattern) -> CompareConfig {
+        self.unordered.push(pattern);
+        self
+    }
+
+    /// Compares the values that match the given pattern leniently.
+    #[inline]
+    pub fn lenient(mut self, pattern: Pattern) -> CompareConfig {
+        self.lenient.push(pattern);
+        self
+    }
+}
+
+/// How the values at a path of two value trees differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DifferenceKind {
+    /// The value is only in the first tree.
+    Removed,
+    /// The value is only in the second tree.
+    Added,
+    /// The values in both trees don't match.
+    Changed,
+}
+
+/// A difference between two value trees, at a single path.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Difference {
+    /// The path of the value that differs.
+    pub path: Path,
+    /// How the value differs.
+    pub kind: DifferenceKind,
+}
+
+/// The outcome of [`Value::compare`].
+///
+/// A difference is reported at the outermost path it's found at, eg: a list and a dictionary at the same path are reported as one `Changed` difference, not as the values they're made of.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompareReport {
+    differences: Vec<Difference>,
+}
+
+impl CompareReport {
+    /// Checks if the value trees are equal, under the config they were compared with.
+    #[inline]
+    pub fn is_equal(&self) -> bool {
+        self.differences.is_empty()
+    }
+
+    /// Gets the differences, in the order of the paths they were found at.
+    #[inline]
+    pub fn differences(&self) -> &[Difference] {
+        &self.differences
+    }
+
+    /// Records a difference, unless the value at the path is ignored.
+    fn push(
+        &mut self,
+        config: &CompareConfig,
+        path: &Path,
+        kind: DifferenceKind,
+    ) {
+        if !any_matches(&config.ignored, path) {
+            let path = path.clone();
+            self.differences.push(Difference { path, kind });
+        }
+    }
+}
+
+/// Compares two value trees under the given config.
+pub(super) fn compare(
+    a: &Value,
+    b: &Value,
+    config: &CompareConfig,
+) -> CompareReport {
+    let mut report = CompareReport::default();
+    compare_at(a, b, config, &mut Path::root(), &mut report);
+    report
+}
+
+fn compare_at(
+    a: &Value,
+    b: &Value,
+    config: &CompareConfig,
+    path: &mut Path,
+    report: &mut CompareReport,
+) {
+    if any_matches(&config.ignored, path)
+        || (any_matches(&config.lenient, path) && lenient_eq(a, b))
+    {
+        return;
+    }
+
+    match (a, b) {
+        (Value::List(a), Value::List(b))
+            if any_matches(&config.unordered, path) =>
+        {
+            let mut a: Vec<_> = a.iter().collect();
+            let mut b: Vec<_> = b.iter().collect();
+            a.sort_by(|a, b| normalize::cmp(a, b));
+            b.sort_by(|a, b| normalize::cmp(a, b));
+            if a != b {
+                report.push(config, path, DifferenceKind::Changed);
+            }
+        }
+        (Value::List(a), Value::List(b)) => {
+            for i in 0..a.len().max(b.len()) {
+                path.push(i);
+                match (a.get(i), b.get(i)) {
+                    (Some(a), Some(b)) => {
+                        compare_at(a, b, config, path, report)
+                    }
+                    (Some(_), None) => {
+                        report.push(config, path, DifferenceKind::Removed)
+                    }
+                    _ => report.push(config, path, DifferenceKind::Added),
+                }
+                path.pop();
+            }
+        }
+        (Value::Dict(a), Value::Dict(b)) => {
+            // Both dictionaries are sorted, so merging their keys keeps the differences in order.
+            let mut a = a.iter().peekable();
+            let mut b = b.iter().peekable();
+            loop {
+                let (key, kind) = match (a.peek(), b.peek()) {
+                    (Some((ka, va)), Some((kb, vb))) if ka == kb => {
+                        path.push(Segment::from_key_bytes(ka));
+                        compare_at(va, vb, config, path, report);
+                        path.pop();
+                        a.next();
+                        b.next();
+                        continue;
+                    }
+                    (Some((ka, _)), Some((kb, _))) if ka < kb => {
+                        (a.next().unwrap().0, DifferenceKind::Removed)
+                    }
+                    (Some(_), None) => {
+                        (a.next().unwrap().0, DifferenceKind::Removed)
+                    }
+                    (_, Some(_)) => {
+                        (b.next().unwrap().0, DifferenceKind::Added)
+                    }
+                    (None, None) => break,
+                };
+                path.push(Segment::from_key_bytes(key));
+                report.push(config, path, kind);
+                path.pop();
+            }
+        }
+        _ if a == b => {}
+        _ => report.push(config, path, DifferenceKind::Changed),
+    }
+}
+
+/// Checks if any of the patterns matches the path.
+fn any_matches(patterns: &[Pattern], path: &Path) -> bool {
+    patterns.iter().any(|pattern| pattern.matches(path))
+}
+
+/// Checks if two values only differ cosmetically. Lists and dictionaries have to be equal, otherwise their elements are compared instead.
+fn lenient_eq(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Text(a), Value::Text(b)) => {
+            a.trim_ascii().eq_ignore_ascii_case(b.trim_ascii())
+        }
+        (Value::Int(v), Value::Text(text))
+        | (Value::Text(text), Value::Int(v)) => {
+            text.trim_ascii() == v.to_string().as_bytes()
+        }
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CompareConfig, Difference, DifferenceKind};
+    use crate::Value;
+
+    #[test]
+    fn compare_reports_paths() {
+        let a: Value = crate::decode(b"d1:ali1ei2ee1:bi1e1:ci1ee").unwrap();
+        let b: Value =
+            crate::decode(b"d1:ali1ei3ei4ee1:b1:12:bbi0e1:di1ee").unwrap();
+        let diff =
+            |path: &str, kind| Difference { path: path.parse().unwrap(), kind };
+
+        let report = a.compare(&b, &CompareConfig::new());
+        assert_eq!(
+            report.differences(),
+            [
+                diff("a[1]", DifferenceKind::Changed),
+                diff("a[2]", DifferenceKind::Added),
+                diff("b", DifferenceKind::Changed),
+                diff("bb", DifferenceKind::Added),
+                diff("c", DifferenceKind::Removed),
+                diff("d", DifferenceKind::Added),
+            ]
+        );
+
+        let config = CompareConfig::new()
+            .ignore("a".parse().unwrap())
+            .ignore("*".parse().unwrap());
+        assert!(a.compare(&b, &config).is_equal());
+
+        let config = CompareConfig::new()
+            .lenient("b".parse().unwrap())
+            .ignore("c".parse().unwrap());
+        assert_eq!(a.compare(&b, &config).differences().len(), 4);
+    }
+
+    #[test]
+    fn compare_unordered_lists() {
+        let a: Value = crate::decode(b"lli1ei2eeli3eee").unwrap();
+        let b: Value = crate::decode(b"lli2ei1eeli3eee").unwrap();
+        let config = CompareConfig::new().unordered("[*]".parse().unwrap());
+
+        assert!(a.compare(&b, &config).is_equal());
+        assert!(!a.compare(&b, &CompareConfig::new()).is_equal());
+        assert!(!b.compare(&a, &CompareConfig::torrent()).is_equal());
+    }
+}
@@ -0,0 +1,138 @@
+//! A bencode value that can be constructed in `const` and `static` contexts.
+
+use serde::Serialize;
+
+use super::visit;
+use super::Dict;
+use super::Value;
+
+/// A bencode value made entirely of `&'static` data, so it can be constructed in `const` and `static` contexts.
+///
+/// Useful for protocol constants, such as fixed error replies, that would otherwise need to be built at runtime. Dictionary entries don't need to be sorted, as they're sorted when encoded.
+///
+/// # Examples
+///
+/// ```
+/// use bende::value::StaticValue;
+///
+/// static GENERIC_ERROR: StaticValue = StaticValue::dict(&[
+///     ("t", StaticValue::str("aa")),
+///     ("y", StaticValue::str("e")),
+///     ("e", StaticValue::list(&[StaticValue::int(201), StaticValue::str("A Generic Error Ocurred")])),
+/// ]);
+///
+/// assert_eq!(
+///     bende::encode(&GENERIC_ERROR).unwrap(),
+///     b"d1:eli201e23:A Generic Error Ocurrede1:t2:aa1:y1:ee"
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StaticValue {
+    /// A 64-bit signed integer.
+    Int(i64),
+    /// An array of bytes that may or **may not** be valid UTF-8.
+    Text(&'static [u8]),
+    /// A list of bencode values.
+    List(&'static [StaticValue]),
+    /// A key-value map with keys that are UTF-8 valid strings.
+    Dict(&'static [(&'static str, StaticValue)]),
+}
+
+impl StaticValue {
+    /// Constructs an `Int`.
+    #[inline]
+    pub const fn int(v: i64) -> StaticValue {
+        StaticValue::Int(v)
+    }
+
+    /// Constructs `Text` from a string.
+    #[inline]
+    pub const fn str(v: &'static str) -> StaticValue {
+        StaticValue::Text(v.as_bytes())
+    }
+
+    /// Constructs `Text` from a slice of bytes.
+    #[inline]
+    pub const fn bytes(v: &'static [u8]) -> StaticValue {
+        StaticValue::Text(v)
+    }
+
+    /// Constructs a `List`.
+    #[inline]
+    pub const fn list(v: &'static [StaticValue]) -> StaticValue {
+        StaticValue::List(v)
+    }
+
+    /// Constructs a `Dict`.
+    #[inline]
+    pub const fn dict(
+        v: &'static [(&'static str, StaticValue)],
+    ) -> StaticValue {
+        StaticValue::Dict(v)
+    }
+
+    /// Copies the value into an owned [`Value`].
+    ///
+    /// If a dictionary has duplicate keys, the last entry wins.
+    pub fn to_value(&self) -> Value {
+        match *self {
+            StaticValue::Int(v) => Value::Int(v),
+            StaticValue::Text(v) => Value::Text(v.to_vec()),
+            StaticValue::List(v) => {
+                Value::List(v.iter().map(StaticValue::to_value).collect())
+            }
+            StaticValue::Dict(v) => Value::Dict(
+                v.iter()
+                    .map(|(key, val)| (key.to_string(), val.to_value()))
+                    .collect::<Dict>(),
+            ),
+        }
+    }
+}
+
+impl From<StaticValue> for Value {
+    fn from(v: StaticValue) -> Self {
+        v.to_value()
+    }
+}
+
+impl Serialize for StaticValue {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match *self {
+            StaticValue::Int(v) => ser.serialize_i64(v),
+            StaticValue::Text(v) => ser.serialize_bytes(v),
+            StaticValue::List(v) => visit::serialize_list(ser, v),
+            StaticValue::Dict(v) => visit::serialize_sorted_dict(
+                ser,
+                v.iter().map(|(key, val)| (key.as_bytes(), val)),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::StaticValue;
+    use crate::{decode, encode, Value};
+
+    const PING: StaticValue = StaticValue::dict(&[
+        ("y", StaticValue::str("q")),
+        ("q", StaticValue::str("ping")),
+    ]);
+
+    #[test]
+    fn const_value_encodes_sorted() {
+        assert_eq!(encode(&PING).unwrap(), b"d1:q4:ping1:y1:qe");
+    }
+
+    #[test]
+    fn const_value_equals_decoded() {
+        assert_eq!(
+            decode::<Value>(b"d1:q4:ping1:y1:qe").unwrap(),
+            PING.to_value()
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! A bencode value that is borrowed until it's mutated.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::ops::Bound::Included;
+use std::ops::Bound::Unbounded;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::visit;
+use super::visit::BuildValue;
+use super::visit::ValueVisitor;
+use super::Dict;
+use super::Value;
+use super::ValueRef;
+
+/// A **sorted** key-value map with keys that are borrowed until they're replaced.
+pub type CowDict<'a> = BTreeMap<Cow<'a, [u8]>, CowValue<'a>>;
+
+/// A bencode value whose byte arrays and dictionary keys borrow from the source until they're mutated.
+///
+/// Decoding a `CowValue` doesn't copy any byte arrays. When a byte array is mutated, only that byte array is copied, so tweaking one field of a huge torrent doesn't clone the entire tree. Use [`CowValue::into_owned`] to turn it into a [`Value`] once you're done.
+///
+/// # Examples
+///
+/// ```
+/// use bende::value::CowValue;
+///
+/// let src = b"d8:announce7:udp://t4:infod6:pieces4:\x01\x02\x03\x04ee";
+/// let mut val: CowValue = bende::decode(src).unwrap();
+///
+/// val.insert("announce", "udp://u").unwrap();
+///
+/// // The pieces are still borrowed from the source.
+/// assert!(val.get("info").unwrap().get("pieces").unwrap().is_borrowed());
+/// assert_eq!(
+///     bende::encode(&val).unwrap(),
+///     b"d8:announce7:udp://u4:infod6:pieces4:\x01\x02\x03\x04ee"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CowValue<'a> {
+    /// A 64-bit signed integer.
+    Int(i64),
+    /// An array of bytes that may or **may not** be valid UTF-8.
+    Text(Cow<'a, [u8]>),
+    /// A list of bencode values.
+    List(Vec<CowValue<'a>>),
+    /// A key-value map with keys that may or **may not** be valid UTF-8.
+    Dict(CowDict<'a>),
+}
+
+impl<'a> CowValue<'a> {
+    /// Returns an `i64` if the value is an `Int`. Otherwise, `None` is returned.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            CowValue::Int(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns a slice of bytes if the value is `Text`. Otherwise `None` is returned.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match *self {
+            CowValue::Text(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns a mutable reference to the bytes if the value is `Text`, copying them if they're borrowed. Otherwise, `None` is returned.
+    pub fn as_bytes_mut(&mut self) -> Option<&mut Vec<u8>> {
+        match *self {
+            CowValue::Text(ref mut v) => Some(v.to_mut()),
+            _ => None,
+        }
+    }
+
+    /// Returns a mutable reference to the list if the value is a `List`. Otherwise, `None` is returned.
+    pub fn as_list_mut(&mut self) -> Option<&mut Vec<CowValue<'a>>> {
+        match *self {
+            CowValue::List(ref mut v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns a mutable reference to the map if the value is a `Dict`. Otherwise, `None` is returned.
+    pub fn as_dict_mut(&mut self) -> Option<&mut CowDict<'a>> {
+        match *self {
+            CowValue::Dict(ref mut v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the value is `Text` that still borrows from the source.
+    pub fn is_borrowed(&self) -> bool {
+        matches!(*self, CowValue::Text(Cow::Borrowed(_)))
+    }
+
+    /// Gets the value of the given key if the value is a `Dict`. Otherwise, `None` is returned.
+    pub fn get<K>(&self, key: K) -> Option<&CowValue<'a>>
+    where
+        K: AsRef<[u8]>,
+    {
+        match *self {
+            CowValue::Dict(ref v) => v.get(key.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// Gets a mutable reference to the value of the given key if the value is a `Dict`. Otherwise, `None` is returned.
+    pub fn get_mut<K>(&mut self, key: K) -> Option<&mut CowValue<'a>>
+    where
+        K: AsRef<[u8]>,
+    {
+        match *self {
+            CowValue::Dict(ref mut v) => v.get_mut(key.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// Returns an iterator over the entries whose keys start with the given bytes, in sorted order, if the value is a `Dict`. Otherwise, the iterator is empty.
+    pub fn keys_with_prefix<'b>(
+        &'b self,
+        prefix: &'b [u8],
+    ) -> impl Iterator<Item = (&'b [u8], &'b CowValue<'a>)> + 'b {
+        let dict = match *self {
+            CowValue::Dict(ref v) => Some(v),
+            _ => None,
+        };
+        dict.into_iter()
+            .flat_map(move |dict| {
+                dict.range::<[u8], _>((Included(prefix), Unbounded))
+            })
+            .take_while(move |(key, _)| key.starts_with(prefix))
+            .map(|(key, val)| (key.as_ref(), val))
+    }
+
+    /// Inserts a key-value pair if the value is a `Dict`, returning the old value of the key, if any.
+    ///
+    /// If the value is not a `Dict`, the given value is handed back as an error.
+    pub fn insert<K, V>(
+        &mut self,
+        key: K,
+        val: V,
+    ) -> Result<Option<CowValue<'a>>, CowValue<'a>>
+    where
+        K: Into<Vec<u8>>,
+        V: Into<Value>,
+    {
+        match *self {
+            CowValue::Dict(ref mut v) => {
+                Ok(v.insert(Cow::Owned(key.into()), CowValue::from(val.into())))
+            }
+            _ => Err(CowValue::from(val.into())),
+        }
+    }
+
+    /// Converts the value into an owned [`Value`], copying any data that is still borrowed.
+    pub fn into_owned(self) -> Value {
+        match self {
+            CowValue::Int(v) => Value::Int(v),
+            CowValue::Text(v) => Value::Text(v.into_owned()),
+            CowValue::List(v) => {
+                Value::List(v.into_iter().map(CowValue::into_owned).collect())
+            }
+            CowValue::Dict(v) => Value::Dict(
+                v.into_iter()
+                    .map(|(key, val)| (key.into_owned(), val.into_owned()))
+                    .collect::<Dict>(),
+            ),
+        }
+    }
+}
+
+impl<'a> From<ValueRef<'a>> for CowValue<'a> {
+    fn from(v: ValueRef<'a>) -> Self {
+        match v {
+            ValueRef::Int(v) => CowValue::Int(v),
+            ValueRef::Text(v) => CowValue::Text(Cow::Borrowed(v)),
+            ValueRef::List(v) => {
+                CowValue::List(v.into_iter().map(CowValue::from).collect())
+            }
+            ValueRef::Dict(v) => CowValue::Dict(
+                v.into_iter()
+                    .map(|(key, val)| (Cow::Borrowed(key), CowValue::from(val)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl<'a> From<Value> for CowValue<'a> {
+    fn from(v: Value) -> Self {
+        match v {
+            Value::Int(v) => CowValue::Int(v),
+            Value::Text(v) => CowValue::Text(Cow::Owned(v)),
+            Value::List(v) => {
+                CowValue::List(v.into_iter().map(CowValue::from).collect())
+            }
+            Value::Dict(v) => CowValue::Dict(
+                v.into_iter()
+                    .map(|(key, val)| {
+                        (Cow::Owned(key.into_bytes()), CowValue::from(val))
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl<'a> Serialize for CowValue<'a> {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match *self {
+            CowValue::Int(v) => ser.serialize_i64(v),
+            CowValue::Text(ref v) => ser.serialize_bytes(v),
+            CowValue::List(ref v) => visit::serialize_list(ser, v),
+            CowValue::Dict(ref v) => visit::serialize_dict(
+                ser,
+                v.iter().map(|(key, val)| (&**key, val)),
+            ),
+        }
+    }
+}
+
+impl<'de> BuildValue<'de> for CowValue<'de> {
+    type Key = &'de [u8];
+
+    fn from_int(v: i64) -> Self {
+        CowValue::Int(v)
+    }
+
+    fn from_bytes(v: &[u8]) -> Option<Self> {
+        Some(CowValue::Text(Cow::Owned(v.to_vec())))
+    }
+
+    fn from_borrowed_bytes(v: &'de [u8]) -> Option<Self> {
+        Some(CowValue::Text(Cow::Borrowed(v)))
+    }
+
+    fn from_byte_buf(v: Vec<u8>) -> Option<Self> {
+        Some(CowValue::Text(Cow::Owned(v)))
+    }
+
+    fn from_list(list: Vec<Self>) -> Self {
+        CowValue::List(list)
+    }
+
+    fn from_entries(entries: Vec<(&'de [u8], Self)>) -> Self {
+        CowValue::Dict(
+            entries
+                .into_iter()
+                .map(|(key, val)| (Cow::Borrowed(key), val))
+                .collect(),
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for CowValue<'de> {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        de.deserialize_any(ValueVisitor::new())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CowValue;
+    use crate::{decode, encode, Value};
+
+    #[test]
+    fn mutation_copies_only_the_subtree() {
+        let mut val: CowValue = decode(b"l3:foo3:bare").unwrap();
+        val.as_list_mut().unwrap()[0].as_bytes_mut().unwrap()[0] = b'b';
+
+        let list = val.as_list_mut().unwrap();
+        assert!(!list[0].is_borrowed());
+        assert!(list[1].is_borrowed());
+        assert_eq!(encode(&val).unwrap(), b"l3:boo3:bare");
+    }
+
+    #[test]
+    fn into_owned_value() {
+        let val: CowValue = decode(b"d3:fooi1995ee").unwrap();
+        assert_eq!(
+            val.into_owned(),
+            decode::<Value>(b"d3:fooi1995ee").unwrap()
+        );
+    }
+
+    #[test]
+    fn encode_non_utf8_key() {
+        let val: CowValue = decode(b"d1:\xffi1ee").unwrap();
+        assert_eq!(encode(&val).unwrap(), b"d1:\xffi1ee");
+    }
+
+    #[test]
+    fn keys_with_binary_prefix() {
+        let val: CowValue =
+            decode(b"d2:\x00\x01i0e2:\x00\xffi1e1:\xffi2ee").unwrap();
+        let keys: Vec<_> =
+            val.keys_with_prefix(b"\x00").map(|(key, _)| key).collect();
+
+        assert_eq!(keys, [b"\x00\x01".as_slice(), b"\x00\xff"]);
+        assert_eq!(val.get(b"\xff"), Some(&CowValue::Int(2)));
+    }
+}
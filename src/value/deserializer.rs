@@ -0,0 +1,302 @@
+//! Deserialization of other types from an owned value tree.
+
+use serde::de::value::MapAccessDeserializer;
+use serde::de::value::MapDeserializer;
+use serde::de::value::SeqDeserializer;
+use serde::de::DeserializeSeed;
+use serde::de::Error as _;
+use serde::de::IntoDeserializer;
+use serde::de::MapAccess;
+use serde::de::SeqAccess;
+use serde::de::Unexpected;
+use serde::de::Visitor;
+use serde::Deserializer;
+
+use super::Key;
+use super::Value;
+use crate::de::visit_int;
+use crate::de::Error;
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Value;
+
+    fn into_deserializer(self) -> Value {
+        self
+    }
+}
+
+/// Deserializes a key the same way as a byte array that holds its bytes.
+impl<'de> IntoDeserializer<'de, Error> for Key {
+    type Deserializer = Value;
+
+    fn into_deserializer(self) -> Value {
+        Value::Text(self.into_bytes())
+    }
+}
+
+/// Deserializes a type from the value, the same way it's deserialized from the value's bencoded bytes.
+///
+/// # Examples
+///
+/// ```
+/// use serde::Deserialize;
+/// use bende::Value;
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct File {
+///     length: u32,
+///     path: Vec<String>,
+/// }
+///
+/// let val: Value = bende::decode(b"d6:lengthi1995e4:pathl1:aee").unwrap();
+///
+/// let file = File::deserialize(val).unwrap();
+/// assert_eq!(file, File { length: 1995, path: vec!["a".into()] });
+/// ```
+impl<'de> Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Int(v) => visit_int(visitor, v),
+            Value::Text(v) => visitor.visit_byte_buf(v),
+            Value::List(v) => {
+                let mut seq = Given::new(SeqDeserializer::new(v.into_iter()));
+                let val =
+                    visitor.visit_seq(&mut seq).map_err(|e| seq.fail(e))?;
+                seq.access.end()?;
+                Ok(val)
+            }
+            Value::Dict(v) => {
+                let mut map = Given::new(MapDeserializer::new(v.into_iter()));
+                let val =
+                    visitor.visit_map(&mut map).map_err(|e| map.fail(e))?;
+                map.access.end()?;
+                Ok(val)
+            }
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Int(0) => visitor.visit_bool(false),
+            Value::Int(1) => visitor.visit_bool(true),
+            Value::Int(v) => {
+                Err(Error::invalid_value(Unexpected::Signed(v), &visitor))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            // A string is never too short for its type, so a length error means it didn't fit, eg: into a `heapless::String`.
+            Value::Text(v) => visitor
+                .visit_string(String::from_utf8(v)?)
+                .map_err(Error::into_overflow),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            // Unit variants are encoded as their names, and the others as a dictionary with a single entry.
+            Value::Text(v) => {
+                let name = String::from_utf8(v)?;
+                visitor.visit_enum(name.into_deserializer())
+            }
+            Value::Dict(v) if v.len() == 1 => {
+                let map = MapDeserializer::new(v.into_iter());
+                visitor.visit_enum(MapAccessDeserializer::new(map))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map struct identifier
+        ignored_any
+    }
+}
+
+/// The elements or entries of a value given to a visitor, which tracks whether an error the visitor returns is about the last one it was given, the same way the decoder does.
+struct Given<A> {
+    access: A,
+    given: bool,
+}
+
+impl<A> Given<A> {
+    fn new(access: A) -> Given<A> {
+        Self { access, given: false }
+    }
+
+    /// Reports an error returned by the visitor, which is turned into `Error::Length` if the visitor ran out of room for an element or entry it was given.
+    fn fail(&self, e: Error) -> Error {
+        if self.given {
+            e.into_overflow()
+        } else {
+            e
+        }
+    }
+}
+
+impl<'de, A> SeqAccess<'de> for Given<A>
+where
+    A: SeqAccess<'de, Error = Error>,
+{
+    type Error = Error;
+
+    fn next_element_seed<T>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        self.given = false;
+        let elem = self.access.next_element_seed(seed)?;
+        self.given = elem.is_some();
+        Ok(elem)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.access.size_hint()
+    }
+}
+
+impl<'de, A> MapAccess<'de> for Given<A>
+where
+    A: MapAccess<'de, Error = Error>,
+{
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        self.given = false;
+        self.access.next_key_seed(seed)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let val = self.access.next_value_seed(seed)?;
+        self.given = true;
+        Ok(val)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.access.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use serde::Deserialize;
+
+    use crate::Value;
+
+    #[test]
+    fn deserialize_from_value() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        enum Kind {
+            File,
+            Link(String),
+        }
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Entry {
+            kinds: Vec<Kind>,
+            private: bool,
+            size: Option<i64>,
+            extra: BTreeMap<String, u8>,
+        }
+
+        let val: Value = crate::decode(
+            b"d5:extrad1:ai1ee5:kindsl4:Filed4:Link1:xee7:privatei1e4:sizei-1ee",
+        )
+        .unwrap();
+        assert_eq!(
+            Entry::deserialize(val.clone()).unwrap(),
+            crate::decode::<Entry>(&crate::encode(&val).unwrap()).unwrap()
+        );
+        assert!(bool::deserialize(Value::Int(2)).is_err());
+        assert!(String::deserialize(Value::Text(vec![0xff])).is_err());
+    }
+
+    #[test]
+    fn deserialize_length_errors() {
+        use crate::de::Error;
+
+        let pair: Value = crate::decode(b"li1ee").unwrap();
+        assert_eq!(
+            pair.try_into_typed::<(i32, i32)>(),
+            Err(Error::InvalidLength {
+                len: 1,
+                expected: "a tuple of size 2".into()
+            })
+        );
+
+        #[cfg(feature = "heapless")]
+        {
+            let list: Value = crate::decode(b"li1ei2ei3ee").unwrap();
+            let err = list.try_into_typed::<heapless::Vec<i32, 2>>();
+            assert_eq!(
+                err,
+                crate::decode::<heapless::Vec<i32, 2>>(b"li1ei2ei3ee")
+            );
+            assert!(matches!(err, Err(Error::Length { .. })));
+
+            let name = Value::from("bende");
+            let err = name.try_into_typed::<heapless::String<2>>();
+            assert!(matches!(err, Err(Error::Length { .. })));
+        }
+    }
+}
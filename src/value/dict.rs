@@ -0,0 +1,512 @@
+//! A sorted key-value map of bencode values.
+
+use std::borrow::Borrow;
+use std::collections::btree_map;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Bound::Included;
+use std::ops::Bound::Unbounded;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::ops::Index;
+use std::str;
+
+use serde::de::Visitor;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::KeyBytes;
+use super::Value;
+
+/// The key of a [`Dict`], which can be any byte string, like the keys of a bencoded dictionary.
+///
+/// Keys compare by their bytes, so they sort in the order bencode requires. A key derefs to its bytes, and [`as_str`](Key::as_str) returns it as a string if it's valid UTF-8.
+///
+/// # Examples
+///
+/// ```
+/// use bende::value::Key;
+///
+/// let key = Key::from("announce");
+/// assert_eq!(key, "announce");
+/// assert_eq!(key.as_str(), Some("announce"));
+///
+/// let key = Key::from(vec![0xff, 0xfe]);
+/// assert_eq!(key.as_str(), None);
+/// assert_eq!(format!("{key:?}"), r#"b"\xff\xfe""#);
+/// ```
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Key(Vec<u8>);
+
+impl Key {
+    /// Returns the bytes of the key.
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the key as a string, or `None` if it isn't valid UTF-8.
+    #[inline]
+    pub fn as_str(&self) -> Option<&str> {
+        str::from_utf8(&self.0).ok()
+    }
+
+    /// Unwraps the key into its bytes.
+    #[inline]
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+/// Formats the key as a string if it's valid UTF-8, or as an escaped byte string literal otherwise.
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.as_str() {
+            Some(key) => fmt::Debug::fmt(key, f),
+            None => write!(f, "b\"{}\"", self.0.escape_ascii()),
+        }
+    }
+}
+
+impl Deref for Key {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for Key {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Borrow<[u8]> for Key {
+    #[inline]
+    fn borrow(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<&str> for Key {
+    fn from(v: &str) -> Self {
+        Self(v.as_bytes().to_vec())
+    }
+}
+
+impl From<String> for Key {
+    fn from(v: String) -> Self {
+        Self(v.into_bytes())
+    }
+}
+
+impl From<&[u8]> for Key {
+    fn from(v: &[u8]) -> Self {
+        Self(v.to_vec())
+    }
+}
+
+impl<const N: usize> From<&[u8; N]> for Key {
+    fn from(v: &[u8; N]) -> Self {
+        Self(v.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for Key {
+    fn from(v: Vec<u8>) -> Self {
+        Self(v)
+    }
+}
+
+impl From<Key> for Vec<u8> {
+    fn from(v: Key) -> Self {
+        v.0
+    }
+}
+
+impl PartialEq<str> for Key {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl PartialEq<&str> for Key {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl PartialEq<[u8]> for Key {
+    fn eq(&self, other: &[u8]) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&[u8]> for Key {
+    fn eq(&self, other: &&[u8]) -> bool {
+        self.0 == *other
+    }
+}
+
+/// Serializes the key as a string if it's valid UTF-8, or as a byte array otherwise.
+impl Serialize for Key {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        KeyBytes(&self.0).serialize(ser)
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        de.deserialize_byte_buf(KeyVisitor)
+    }
+}
+
+/// Visits a key as either a string or a byte array.
+struct KeyVisitor;
+
+impl Visitor<'_> for KeyVisitor {
+    type Value = Key;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a dictionary key")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(Key::from(v))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Self::Value, E> {
+        Ok(Key::from(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(Key::from(v))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(Key::from(v))
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for Key {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// A **sorted** key-value map with keys that are any byte strings.
+///
+/// Keys are always iterated in ascending byte order, which is the order bencode requires. A `Dict` derefs to the `BTreeMap` it wraps, so all of its methods, including the entry API, are available; its own lookup methods take anything that's bytes, such as a `&str` or a `&[u8]`.
+///
+/// # Examples
+///
+/// ```
+/// use bende::value::Dict;
+/// use bende::Value;
+///
+/// let mut dict = Dict::new();
+/// dict.insert("zeta".into(), Value::Int(2));
+/// dict.entry("alpha".into()).or_insert(Value::Int(1));
+/// dict.insert(b"\xff".into(), Value::Int(3));
+///
+/// assert_eq!(dict.get("alpha"), Some(&Value::Int(1)));
+/// assert_eq!(dict[b"\xff".as_slice()], Value::Int(3));
+/// assert_eq!(bende::encode(&dict).unwrap(), b"d5:alphai1e4:zetai2e1:\xffi3ee");
+/// ```
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Dict(BTreeMap<Key, Value>);
+
+impl Dict {
+    /// Constructs an empty dictionary.
+    #[inline]
+    pub fn new() -> Dict {
+        Self(BTreeMap::new())
+    }
+
+    /// Gets the value of the given key.
+    #[inline]
+    pub fn get<K>(&self, key: &K) -> Option<&Value>
+    where
+        K: AsRef<[u8]> + ?Sized,
+    {
+        self.0.get(key.as_ref())
+    }
+
+    /// Gets a mutable reference to the value of the given key.
+    #[inline]
+    pub fn get_mut<K>(&mut self, key: &K) -> Option<&mut Value>
+    where
+        K: AsRef<[u8]> + ?Sized,
+    {
+        self.0.get_mut(key.as_ref())
+    }
+
+    /// Returns `true` if the dictionary contains the given key.
+    #[inline]
+    pub fn contains_key<K>(&self, key: &K) -> bool
+    where
+        K: AsRef<[u8]> + ?Sized,
+    {
+        self.0.contains_key(key.as_ref())
+    }
+
+    /// Removes the given key, returning its value if it was in the dictionary.
+    #[inline]
+    pub fn remove<K>(&mut self, key: &K) -> Option<Value>
+    where
+        K: AsRef<[u8]> + ?Sized,
+    {
+        self.0.remove(key.as_ref())
+    }
+
+    /// Gets the value of the given key, looking the key up by its bytes.
+    #[inline]
+    pub fn get_bytes(&self, key: &[u8]) -> Option<&Value> {
+        self.0.get(key)
+    }
+
+    /// Gets a mutable reference to the value of the given key, looking the key up by its bytes.
+    #[inline]
+    pub fn get_bytes_mut(&mut self, key: &[u8]) -> Option<&mut Value> {
+        self.0.get_mut(key)
+    }
+
+    /// Returns an iterator over the entries whose keys start with the given bytes, in sorted order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::value::Dict;
+    /// use bende::Value;
+    ///
+    /// let dict: Dict = bende::decode(b"d4:file0:5:filesle4:namei0ee").unwrap();
+    /// let keys: Vec<_> = dict.keys_with_prefix(b"file").map(|(key, _)| key).collect();
+    ///
+    /// assert_eq!(keys, ["file", "files"]);
+    /// ```
+    pub fn keys_with_prefix<'a>(
+        &'a self,
+        prefix: &'a [u8],
+    ) -> impl Iterator<Item = (&'a Key, &'a Value)> + 'a {
+        self.0
+            .range::<[u8], _>((Included(prefix), Unbounded))
+            .take_while(move |(key, _)| key.starts_with(prefix))
+    }
+
+    /// Unwraps the dictionary into the `BTreeMap` it wraps.
+    #[inline]
+    pub fn into_inner(self) -> BTreeMap<Key, Value> {
+        self.0
+    }
+}
+
+impl fmt::Debug for Dict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl Deref for Dict {
+    type Target = BTreeMap<Key, Value>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Dict {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// Gets the value of the given key.
+///
+/// # Panics
+///
+/// Panics if the key isn't in the dictionary.
+impl<K> Index<&K> for Dict
+where
+    K: AsRef<[u8]> + ?Sized,
+{
+    type Output = Value;
+
+    fn index(&self, key: &K) -> &Value {
+        self.get(key).expect("key not found in the dictionary")
+    }
+}
+
+impl From<BTreeMap<Key, Value>> for Dict {
+    fn from(v: BTreeMap<Key, Value>) -> Self {
+        Self(v)
+    }
+}
+
+impl From<BTreeMap<String, Value>> for Dict {
+    fn from(v: BTreeMap<String, Value>) -> Self {
+        v.into_iter().collect()
+    }
+}
+
+impl From<Dict> for BTreeMap<Key, Value> {
+    fn from(v: Dict) -> Self {
+        v.0
+    }
+}
+
+impl<K, const N: usize> From<[(K, Value); N]> for Dict
+where
+    K: Into<Key>,
+{
+    fn from(v: [(K, Value); N]) -> Self {
+        v.into_iter().collect()
+    }
+}
+
+impl<K> FromIterator<(K, Value)> for Dict
+where
+    K: Into<Key>,
+{
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (K, Value)>,
+    {
+        Self(iter.into_iter().map(|(key, val)| (key.into(), val)).collect())
+    }
+}
+
+impl<K> Extend<(K, Value)> for Dict
+where
+    K: Into<Key>,
+{
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = (K, Value)>,
+    {
+        self.0.extend(iter.into_iter().map(|(key, val)| (key.into(), val)));
+    }
+}
+
+impl IntoIterator for Dict {
+    type Item = (Key, Value);
+    type IntoIter = btree_map::IntoIter<Key, Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Dict {
+    type Item = (&'a Key, &'a Value);
+    type IntoIter = btree_map::Iter<'a, Key, Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Dict {
+    type Item = (&'a Key, &'a mut Value);
+    type IntoIter = btree_map::IterMut<'a, Key, Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter_mut()
+    }
+}
+
+/// Serializes the dictionary in canonical form, with its keys as byte arrays in ascending byte order.
+impl Serialize for Dict {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let entries = self.0.iter().map(|(key, val)| (key.as_bytes(), val));
+        super::visit::serialize_dict(ser, entries)
+    }
+}
+
+impl<'de> Deserialize<'de> for Dict {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        BTreeMap::deserialize(de).map(Dict)
+    }
+}
+
+/// Overwrites every key and value with zeroes, as the implementation for [`Value`] does, and leaves the dictionary empty.
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for Dict {
+    fn zeroize(&mut self) {
+        for (mut key, mut val) in std::mem::take(&mut self.0) {
+            key.zeroize();
+            val.zeroize();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::{Dict, Key};
+    use crate::{decode, encode, Value};
+
+    #[test]
+    fn dict_binary_keys() {
+        let mut dict: Dict =
+            decode(b"d1:ai1e2:abi2e1:bi3e2:\xff\xfei4ee").unwrap();
+
+        assert_eq!(dict.get_bytes(b"ab"), Some(&Value::Int(2)));
+        assert_eq!(dict.get_bytes(b"\xff\xfe"), Some(&Value::Int(4)));
+        assert_eq!(dict.get_bytes(b"\xff"), None);
+        *dict.get_bytes_mut(b"b").unwrap() = Value::Int(5);
+
+        let keys: Vec<_> = dict.keys_with_prefix(b"a").map(|e| e.0).collect();
+        assert_eq!(keys, ["a", "ab"]);
+        let keys: Vec<_> =
+            dict.keys_with_prefix(b"\xff").map(|e| e.0).collect();
+        assert_eq!(keys, [b"\xff\xfe".as_slice()]);
+        assert_eq!(
+            encode(&dict).unwrap(),
+            b"d1:ai1e2:abi2e1:bi5e2:\xff\xfei4ee"
+        );
+    }
+
+    #[test]
+    fn value_binary_keys() {
+        let val: Value = decode(b"d2:\xff\xfei1ee").unwrap();
+        let Value::Dict(dict) = &val else {
+            panic!("expected a dictionary, got {val:?}");
+        };
+
+        assert_eq!(dict[b"\xff\xfe".as_slice()], Value::Int(1));
+        assert_eq!(format!("{dict:?}"), r#"{b"\xff\xfe": Int(1)}"#);
+        assert_eq!(encode(&val).unwrap(), b"d2:\xff\xfei1ee");
+    }
+
+    #[test]
+    fn dict_std_conversions() {
+        let map = BTreeMap::from([("a".to_owned(), Value::Int(1))]);
+        let dict = Dict::from(map.clone());
+
+        assert_eq!(format!("{dict:?}"), format!("{map:?}"));
+        assert_eq!(
+            dict.clone().into_inner(),
+            BTreeMap::from([(Key::from("a"), Value::Int(1))])
+        );
+        assert_eq!(dict.clone().into_iter().collect::<Dict>(), dict);
+    }
+}
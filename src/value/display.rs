@@ -0,0 +1,295 @@
+//! Configurable textual rendering of values.
+
+use std::fmt;
+use std::fmt::Write;
+
+use super::Value;
+
+/// A configurable [`Display`](fmt::Display) adapter for a [`Value`], returned by [`Value::display`].
+///
+/// Byte arrays are rendered as quoted strings. By default, quotes, backslashes, control characters and bytes that aren't valid UTF-8 are escaped, so untrusted values can't inject anything into logs or terminals. Dictionary keys are rendered as-is when they're plain, or quoted and escaped otherwise.
+///
+/// # Examples
+///
+/// ```
+/// use bende::Value;
+///
+/// let val = Value::List(vec![Value::from("a\"b\n"), Value::Text(vec![0xff])].into());
+///
+/// assert_eq!(val.display().to_string(), r#"["a\"b\x0a", "\xff"]"#);
+/// assert_eq!(val.display().escape(false).to_string(), "[\"a\"b\n\", \"\u{fffd}\"]");
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct ValueDisplay<'a> {
+    val: &'a Value,
+    escape: bool,
+    max_len: Option<usize>,
+}
+
+impl<'a> ValueDisplay<'a> {
+    #[inline]
+    pub(super) fn new(val: &'a Value) -> ValueDisplay<'a> {
+        Self { val, escape: true, max_len: None }
+    }
+
+    /// Sets whether byte arrays and keys are escaped. When disabled, they're rendered as lossy UTF-8 instead, and keys are never quoted.
+    #[inline]
+    pub fn escape(mut self, escape: bool) -> ValueDisplay<'a> {
+        self.escape = escape;
+        self
+    }
+
+    /// Limits the rendering to roughly the given number of bytes.
+    ///
+    /// Once the limit is reached, byte arrays are cut short and lists and dictionaries skip their remaining elements, each marked with an ellipsis and the number of bytes or elements omitted. The markers and closing brackets are still rendered, so the output can exceed the limit slightly.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::Value;
+    ///
+    /// let val = Value::List(vec![Value::from("foobar"), Value::Int(1), Value::Int(2)].into());
+    ///
+    /// assert_eq!(val.display().max_len(5).to_string(), r#"["foo…" (+3 bytes), … (+2 elements)]"#);
+    /// assert_eq!(val.display().max_len(14).to_string(), r#"["foobar", 1, … (+1 elements)]"#);
+    /// ```
+    #[inline]
+    pub fn max_len(mut self, max_len: usize) -> ValueDisplay<'a> {
+        self.max_len = Some(max_len);
+        self
+    }
+}
+
+impl fmt::Display for ValueDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let left = self.max_len.unwrap_or(usize::MAX);
+        Printer { f, escape: self.escape, left }.value(self.val)
+    }
+}
+
+/// Renders a value, keeping track of how many more bytes can be rendered.
+struct Printer<'a, 'b> {
+    f: &'a mut fmt::Formatter<'b>,
+    escape: bool,
+    left: usize,
+}
+
+impl Printer<'_, '_> {
+    fn put(&mut self, s: &str) -> fmt::Result {
+        self.left = self.left.saturating_sub(s.len());
+        self.f.write_str(s)
+    }
+
+    fn value(&mut self, val: &Value) -> fmt::Result {
+        match *val {
+            Value::Int(int) => self.put(&int.to_string()),
+            Value::Text(ref bytes) => self.text(bytes),
+            Value::List(ref list) => {
+                self.put("[")?;
+                for (i, elem) in list.iter().enumerate() {
+                    if i > 0 {
+                        self.put(", ")?;
+                    }
+                    if self.left == 0 {
+                        let omitted = list.len() - i;
+                        self.put(&format!("… (+{} elements)", omitted))?;
+                        break;
+                    }
+                    self.value(elem)?;
+                }
+                self.put("]")
+            }
+            Value::Dict(ref dict) => {
+                self.put("{")?;
+                for (i, (key, val)) in dict.iter().enumerate() {
+                    self.put(if i > 0 { ", " } else { " " })?;
+                    if self.left == 0 {
+                        let omitted = dict.len() - i;
+                        self.put(&format!("… (+{} entries)", omitted))?;
+                        break;
+                    }
+                    match key.as_str() {
+                        Some(key) if !self.escape || is_plain_key(key) => {
+                            self.put(key)?
+                        }
+                        _ if !self.escape => {
+                            self.put(&String::from_utf8_lossy(key))?
+                        }
+                        _ => self.put(&quote(key))?,
+                    }
+                    self.put(": ")?;
+                    self.value(val)?;
+                }
+                if !dict.is_empty() {
+                    self.put(" ")?;
+                }
+                self.put("}")
+            }
+        }
+    }
+
+    /// Renders a byte array as a quoted string, cutting it short if there isn't enough room left.
+    ///
+    /// Unless escaping, invalid UTF-8 sequences are rendered as `U+FFFD`.
+    fn text(&mut self, bytes: &[u8]) -> fmt::Result {
+        self.put("\"")?;
+        let mut done = 0;
+        let mut buf = String::new();
+        for chunk in bytes.utf8_chunks() {
+            for c in chunk.valid().chars() {
+                buf.clear();
+                push_char(&mut buf, c, self.escape);
+                if !self.piece(&buf, c.len_utf8(), &mut done, bytes.len())? {
+                    return Ok(());
+                }
+            }
+
+            let invalid = chunk.invalid();
+            if invalid.is_empty() {
+                continue;
+            }
+            if !self.escape {
+                let len = invalid.len();
+                if !self.piece("\u{fffd}", len, &mut done, bytes.len())? {
+                    return Ok(());
+                }
+                continue;
+            }
+            for b in invalid {
+                buf.clear();
+                push_byte(&mut buf, *b);
+                if !self.piece(&buf, 1, &mut done, bytes.len())? {
+                    return Ok(());
+                }
+            }
+        }
+        self.put("\"")
+    }
+
+    /// Renders a piece of a byte array that stands for `len` of its bytes, or cuts the byte array short if there isn't enough room for it.
+    ///
+    /// Returns `false` if the byte array was cut short.
+    fn piece(
+        &mut self,
+        piece: &str,
+        len: usize,
+        done: &mut usize,
+        total: usize,
+    ) -> Result<bool, fmt::Error> {
+        if piece.len() > self.left {
+            self.put("…\"")?;
+            self.put(&format!(" (+{} bytes)", total - *done))?;
+            return Ok(false);
+        }
+        self.put(piece)?;
+        *done += len;
+        Ok(true)
+    }
+}
+
+/// Checks if the key can be rendered without quotes.
+pub(super) fn is_plain_key(key: &str) -> bool {
+    !key.is_empty()
+        && !key.starts_with(' ')
+        && !key.ends_with(' ')
+        && key.bytes().all(|b| {
+            (b.is_ascii_graphic() || b == b' ')
+                && !matches!(b, b'"' | b'\\' | b':' | b',' | b'[' | b']')
+                && !matches!(b, b'{' | b'}')
+        })
+}
+
+/// Renders the key as a quoted, escaped string.
+fn quote(key: &[u8]) -> String {
+    let mut quoted = String::from("\"");
+    for chunk in key.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            push_char(&mut quoted, c, true);
+        }
+        for b in chunk.invalid() {
+            push_byte(&mut quoted, *b);
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Pushes the character, escaping quotes, backslashes and control characters if asked to.
+fn push_char(buf: &mut String, c: char, escape: bool) {
+    match c {
+        _ if !escape => buf.push(c),
+        '"' => buf.push_str("\\\""),
+        '\\' => buf.push_str("\\\\"),
+        c if c.is_ascii_control() => push_byte(buf, c as u8),
+        c if is_hidden(c) => {
+            let _ = write!(buf, "\\u{{{:x}}}", c as u32);
+        }
+        c => buf.push(c),
+    }
+}
+
+/// Pushes the byte as a hex escape.
+fn push_byte(buf: &mut String, b: u8) {
+    let _ = write!(buf, "\\x{:02x}", b);
+}
+
+/// Checks if the character is a non-ASCII control character, or a bidirectional formatting character that can reorder the text around it.
+fn is_hidden(c: char) -> bool {
+    c.is_control()
+        || matches!(c, '\u{200e}' | '\u{200f}' | '\u{61c}')
+        || matches!(c, '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}')
+}
+
+#[cfg(test)]
+mod test {
+    use crate::value::Dict;
+    use crate::Value;
+
+    #[test]
+    fn display_escapes_text() {
+        let val = Value::Text(
+            b"\x1b[31m\"\\\xc3\xa9\xc3\x7f\xe2\x80\x8e\xc2\x85".to_vec(),
+        );
+        assert_eq!(val.to_string(), r#""\x1b[31m\"\\é\xc3\x7f\u{200e}\u{85}""#);
+    }
+
+    #[test]
+    fn display_max_len() {
+        let val: Value =
+            crate::decode(b"d1:ali1ei2ei3ee1:b5:\x00\xffabc1:ci1ee").unwrap();
+
+        assert_eq!(val.display().max_len(0).to_string(), "{ … (+3 entries) }");
+        assert_eq!(
+            val.display().max_len(11).to_string(),
+            "{ a: [1, 2, … (+1 elements)], … (+2 entries) }"
+        );
+        assert_eq!(
+            val.display().max_len(29).to_string(),
+            r#"{ a: [1, 2, 3], b: "\x00\xffa…" (+2 bytes), … (+1 entries) }"#
+        );
+        assert_eq!(
+            val.display().escape(false).max_len(24).to_string(),
+            "{ a: [1, 2, 3], b: \"\0\u{fffd}…\" (+3 bytes), … (+1 entries) }"
+        );
+        assert_eq!(val.display().max_len(100).to_string(), val.to_string());
+    }
+
+    #[test]
+    fn display_quotes_keys() {
+        let mut dict = Dict::new();
+        dict.insert("creation date".into(), Value::Int(1));
+        dict.insert("a: b".into(), Value::Int(2));
+        dict.insert("\n".into(), Value::Int(3));
+        let val = Value::Dict(dict);
+
+        assert_eq!(
+            val.to_string(),
+            r#"{ "\x0a": 3, "a: b": 2, creation date: 1 }"#
+        );
+        assert_eq!(
+            val.display().escape(false).to_string(),
+            "{ \n: 3, a: b: 2, creation date: 1 }"
+        );
+        assert_eq!(Value::Dict(Dict::new()).to_string(), "{}");
+    }
+}
@@ -0,0 +1,162 @@
+//! A bencode value with hashed dictionaries, for building large values quickly.
+
+use hashbrown::HashMap;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::visit;
+use super::visit::BuildValue;
+use super::visit::ValueVisitor;
+use super::Key;
+use super::Value;
+
+/// An **unsorted** key-value map with keys that are any byte strings.
+pub type FastDict = HashMap<Key, FastValue>;
+
+/// A bencode value whose dictionaries are the hash maps of [`hashbrown`](https://docs.rs/hashbrown), and are only sorted when they're encoded.
+///
+/// Inserting into and looking up a [`FastDict`] is cheaper than a [`Dict`](super::Dict) once dictionaries grow large, eg: when building a tracker's scrape response for many thousands of torrents. Sorted order is only needed on the wire, so the keys are sorted once per dictionary during serialization instead of on every insertion.
+///
+/// The value is meant to be built and encoded. It converts to and from a [`Value`], which has the methods for reading and editing values.
+///
+/// # Examples
+///
+/// ```
+/// use bende::value::{FastDict, FastValue};
+///
+/// let mut files = FastDict::new();
+/// files.insert("zeta".into(), FastValue::Int(2));
+/// files.insert("alpha".into(), FastValue::Int(1));
+///
+/// let files = FastValue::Dict(files);
+/// assert_eq!(bende::encode(&files).unwrap(), b"d5:alphai1e4:zetai2ee");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FastValue {
+    /// A 64-bit signed integer.
+    Int(i64),
+    /// An array of bytes that may or **may not** be valid UTF-8.
+    Text(Vec<u8>),
+    /// A list of bencode values.
+    List(Vec<FastValue>),
+    /// A key-value map with keys that are any byte strings.
+    Dict(FastDict),
+}
+
+impl From<Value> for FastValue {
+    fn from(v: Value) -> Self {
+        match v {
+            Value::Int(v) => FastValue::Int(v),
+            Value::Text(v) => FastValue::Text(v),
+            Value::List(v) => {
+                FastValue::List(v.into_iter().map(FastValue::from).collect())
+            }
+            Value::Dict(v) => FastValue::Dict(
+                v.into_iter().map(|(key, val)| (key, val.into())).collect(),
+            ),
+        }
+    }
+}
+
+impl From<FastValue> for Value {
+    fn from(v: FastValue) -> Self {
+        match v {
+            FastValue::Int(v) => Value::Int(v),
+            FastValue::Text(v) => Value::Text(v),
+            FastValue::List(v) => {
+                Value::List(v.into_iter().map(Value::from).collect())
+            }
+            FastValue::Dict(v) => Value::Dict(
+                v.into_iter().map(|(key, val)| (key, val.into())).collect(),
+            ),
+        }
+    }
+}
+
+impl From<&str> for FastValue {
+    fn from(v: &str) -> Self {
+        FastValue::Text(v.as_bytes().to_vec())
+    }
+}
+
+impl From<i64> for FastValue {
+    fn from(v: i64) -> Self {
+        FastValue::Int(v)
+    }
+}
+
+impl Serialize for FastValue {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match *self {
+            FastValue::Int(v) => ser.serialize_i64(v),
+            FastValue::Text(ref v) => ser.serialize_bytes(v),
+            FastValue::List(ref v) => visit::serialize_list(ser, v),
+            FastValue::Dict(ref v) => visit::serialize_sorted_dict(
+                ser,
+                v.iter().map(|(key, val)| (key.as_bytes(), val)),
+            ),
+        }
+    }
+}
+
+impl<'de> BuildValue<'de> for FastValue {
+    type Key = Key;
+
+    fn from_int(v: i64) -> Self {
+        FastValue::Int(v)
+    }
+
+    fn from_bytes(v: &[u8]) -> Option<Self> {
+        Some(FastValue::Text(v.to_owned()))
+    }
+
+    fn from_byte_buf(v: Vec<u8>) -> Option<Self> {
+        Some(FastValue::Text(v))
+    }
+
+    fn from_list(list: Vec<Self>) -> Self {
+        FastValue::List(list)
+    }
+
+    fn from_entries(entries: Vec<(Key, Self)>) -> Self {
+        FastValue::Dict(entries.into_iter().collect())
+    }
+}
+
+impl<'de> Deserialize<'de> for FastValue {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        de.deserialize_any(ValueVisitor::new())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FastValue;
+    use crate::{decode, encode, Value};
+
+    #[test]
+    fn encode_sorts_keys() {
+        let src = b"d1:ai1e2:aai2e1:bli3ed1:bi5e1:ci4eeee";
+        let val: FastValue = decode(src).unwrap();
+
+        let FastValue::Dict(ref dict) = val else {
+            panic!("not a dictionary: {:?}", val);
+        };
+        assert_eq!(dict[b"aa".as_slice()], FastValue::Int(2));
+        assert_eq!(encode(&val).unwrap(), src);
+    }
+
+    #[test]
+    fn convert_to_value() {
+        let val: Value = decode(b"d3:fool3:bari1995eee").unwrap();
+        let fast = FastValue::from(val.clone());
+
+        assert_eq!(Value::from(fast), val);
+    }
+}
@@ -0,0 +1,150 @@
+//! Deduplication of byte arrays across many shared values.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use super::ArcValue;
+use super::Value;
+
+/// Statistics on how much an [`Interner`] has deduplicated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct InternStats {
+    /// The number of distinct byte arrays held by the interner.
+    pub unique: usize,
+    /// The total length of the distinct byte arrays held by the interner.
+    pub unique_bytes: usize,
+    /// The number of byte arrays that were replaced by one the interner already held.
+    pub hits: usize,
+    /// The total length of the byte arrays that were replaced, which is the memory saved, not counting the allocations themselves.
+    pub saved_bytes: usize,
+}
+
+/// A pool that converts values into [`ArcValue`]s, where identical byte arrays share one allocation.
+///
+/// Caches of many similar values, eg: torrents that list the same trackers, hold each distinct byte array once instead of once per value. The interner keeps every byte array it has seen alive until it's [`purge`](Interner::purge)d or dropped.
+///
+/// # Examples
+///
+/// ```
+/// use bende::value::Interner;
+/// use bende::Value;
+///
+/// let a: Value = bende::decode(b"d8:announce13:udp://tracker4:name1:ae").unwrap();
+/// let b: Value = bende::decode(b"d8:announce13:udp://tracker4:name1:be").unwrap();
+///
+/// let mut interner = Interner::new();
+/// let a = interner.intern(a);
+/// let b = interner.intern(b);
+///
+/// assert!(a.get("announce").unwrap().ptr_eq(b.get("announce").unwrap()));
+/// assert_eq!(interner.stats().hits, 1);
+/// assert_eq!(interner.stats().saved_bytes, 13);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    texts: HashSet<Arc<Vec<u8>>>,
+    stats: InternStats,
+}
+
+impl Interner {
+    /// Constructs an interner that doesn't hold any byte arrays yet.
+    #[inline]
+    pub fn new() -> Interner {
+        Self::default()
+    }
+
+    /// Converts the value into an `ArcValue`, replacing each of its byte arrays with an identical one the interner already holds.
+    ///
+    /// Dictionary keys aren't byte arrays of the value, so they aren't interned.
+    pub fn intern(&mut self, val: Value) -> ArcValue {
+        match val {
+            Value::Int(v) => ArcValue::Int(v),
+            Value::Text(v) => ArcValue::Text(self.intern_bytes(v)),
+            Value::List(v) => ArcValue::List(Arc::new(
+                v.into_iter().map(|elem| self.intern(elem)).collect(),
+            )),
+            Value::Dict(v) => ArcValue::Dict(Arc::new(
+                v.into_iter()
+                    .map(|(key, val)| (key, self.intern(val)))
+                    .collect(),
+            )),
+        }
+    }
+
+    /// Gets the shared copy of the given bytes, which is added to the interner if it doesn't hold one yet.
+    pub fn intern_bytes(&mut self, bytes: Vec<u8>) -> Arc<Vec<u8>> {
+        if let Some(shared) = self.texts.get(&bytes) {
+            self.stats.hits += 1;
+            self.stats.saved_bytes += bytes.len();
+            return Arc::clone(shared);
+        }
+        self.stats.unique += 1;
+        self.stats.unique_bytes += bytes.len();
+        let shared = Arc::new(bytes);
+        self.texts.insert(Arc::clone(&shared));
+        shared
+    }
+
+    /// Drops the byte arrays that are only held by the interner, because every value that shared them has been dropped.
+    ///
+    /// The hits and saved bytes are kept, as they count what the interner has saved so far.
+    pub fn purge(&mut self) {
+        let stats = &mut self.stats;
+        self.texts.retain(|text| {
+            let used = Arc::strong_count(text) > 1;
+            if !used {
+                stats.unique -= 1;
+                stats.unique_bytes -= text.len();
+            }
+            used
+        });
+    }
+
+    /// Gets the statistics on what the interner holds and has saved.
+    #[inline]
+    pub fn stats(&self) -> InternStats {
+        self.stats
+    }
+
+    /// Returns the number of distinct byte arrays held by the interner.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.texts.len()
+    }
+
+    /// Returns `true` if the interner doesn't hold any byte arrays.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.texts.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{InternStats, Interner};
+    use crate::Value;
+
+    #[test]
+    fn intern_shares_and_purges() {
+        let val: Value = crate::decode(b"l3:foo3:fooi1el3:bar3:fooee").unwrap();
+        let mut interner = Interner::new();
+        let shared = interner.intern(val.clone());
+
+        let list = shared.as_list().unwrap();
+        assert!(list[0].ptr_eq(&list[1]));
+        assert!(list[0].ptr_eq(&list[3].as_list().unwrap()[1]));
+        assert_eq!(shared.to_value(), val);
+        assert_eq!(
+            interner.stats(),
+            InternStats { unique: 2, unique_bytes: 6, hits: 2, saved_bytes: 6 }
+        );
+
+        interner.purge();
+        assert_eq!(interner.len(), 2);
+        drop(shared);
+        interner.purge();
+        assert!(interner.is_empty());
+        assert_eq!(interner.stats().unique_bytes, 0);
+        assert_eq!(interner.stats().hits, 2);
+    }
+}
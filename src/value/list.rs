@@ -0,0 +1,229 @@
+//! A list of bencode values.
+
+use std::fmt;
+use std::mem;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::vec;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::Value;
+
+/// A list of bencode values.
+///
+/// A `List` derefs to the `Vec` it wraps, so all of its methods are available, and adds helpers for lists whose elements are all of one type, which most lists in bencoded data are.
+///
+/// # Examples
+///
+/// ```
+/// use bende::value::List;
+///
+/// let list: List = bende::decode(b"li1ei2ei3ee").unwrap();
+///
+/// assert!(list.is_homogeneous());
+/// assert_eq!(list.as_slice_of::<i64>(), Ok(vec![1, 2, 3]));
+/// assert!(list.as_slice_of::<&str>().is_err());
+/// ```
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct List(Vec<Value>);
+
+impl List {
+    /// Constructs an empty list.
+    #[inline]
+    pub fn new() -> List {
+        Self(Vec::new())
+    }
+
+    /// Constructs an empty list with space for at least `capacity` elements.
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> List {
+        Self(Vec::with_capacity(capacity))
+    }
+
+    /// Converts every element into `T`, eg: `i64`, `&[u8]` or `&str`.
+    ///
+    /// # Errors
+    ///
+    /// * An element can't be converted, at which point the first conversion error is returned.
+    pub fn as_slice_of<'a, T>(&'a self) -> Result<Vec<T>, T::Error>
+    where
+        T: TryFrom<&'a Value>,
+    {
+        self.0.iter().map(T::try_from).collect()
+    }
+
+    /// Checks if all of the elements are the same kind of value, eg: all integers. An empty list is homogeneous.
+    ///
+    /// Only the outermost kind is compared, so a list of lists is homogeneous even if the inner lists aren't.
+    pub fn is_homogeneous(&self) -> bool {
+        match self.0.split_first() {
+            Some((first, rest)) => {
+                let kind = mem::discriminant(first);
+                rest.iter().all(|elem| mem::discriminant(elem) == kind)
+            }
+            None => true,
+        }
+    }
+
+    /// Unwraps the list into the `Vec` it wraps.
+    #[inline]
+    pub fn into_inner(self) -> Vec<Value> {
+        self.0
+    }
+}
+
+impl fmt::Debug for List {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl Deref for List {
+    type Target = Vec<Value>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for List {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl From<Vec<Value>> for List {
+    fn from(v: Vec<Value>) -> Self {
+        Self(v)
+    }
+}
+
+impl From<List> for Vec<Value> {
+    fn from(v: List) -> Self {
+        v.0
+    }
+}
+
+impl From<&[Value]> for List {
+    fn from(v: &[Value]) -> Self {
+        Self(v.to_vec())
+    }
+}
+
+impl<const N: usize> From<[Value; N]> for List {
+    fn from(v: [Value; N]) -> Self {
+        Self(Vec::from(v))
+    }
+}
+
+impl FromIterator<Value> for List {
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = Value>,
+    {
+        Self(Vec::from_iter(iter))
+    }
+}
+
+impl Extend<Value> for List {
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = Value>,
+    {
+        self.0.extend(iter);
+    }
+}
+
+impl IntoIterator for List {
+    type Item = Value;
+    type IntoIter = vec::IntoIter<Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a List {
+    type Item = &'a Value;
+    type IntoIter = std::slice::Iter<'a, Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut List {
+    type Item = &'a mut Value;
+    type IntoIter = std::slice::IterMut<'a, Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter_mut()
+    }
+}
+
+impl Serialize for List {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.0.serialize(ser)
+    }
+}
+
+impl<'de> Deserialize<'de> for List {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Vec::deserialize(de).map(List)
+    }
+}
+
+/// Overwrites every element with zeroes, as the implementation for [`Value`] does, and leaves the list empty.
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for List {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::List;
+    use crate::{decode, encode, Value};
+
+    #[test]
+    fn list_typed_extraction() {
+        let list: List = decode(b"l3:foo3:bar2:\xff\xffe").unwrap();
+
+        assert!(list.is_homogeneous());
+        assert_eq!(
+            list.as_slice_of::<&[u8]>(),
+            Ok(vec![&b"foo"[..], b"bar", b"\xff\xff"])
+        );
+        assert!(list.as_slice_of::<&str>().is_err());
+        assert!(list.as_slice_of::<i64>().is_err());
+    }
+
+    #[test]
+    fn list_homogeneity() {
+        assert!(List::new().is_homogeneous());
+        assert!(List::from([Value::List(List::new())]).is_homogeneous());
+        assert!(!List::from([Value::Int(1), Value::from("a")]).is_homogeneous());
+    }
+
+    #[test]
+    fn list_std_conversions() {
+        let vec = vec![Value::Int(1), Value::from("a")];
+        let list = List::from(vec.clone());
+
+        assert_eq!(format!("{list:?}"), format!("{vec:?}"));
+        assert_eq!(encode(&list).unwrap(), encode(&vec).unwrap());
+        assert_eq!(list.clone().into_inner(), vec);
+        assert_eq!(list.into_iter().collect::<List>(), List::from(vec));
+    }
+}
@@ -0,0 +1,206 @@
+//! Sorting of lists whose order is irrelevant, and optionally Unicode normalization of strings, for order-insensitive comparison.
+
+use std::cmp::Ordering;
+
+use super::Path;
+use super::Pattern;
+use super::Segment;
+use super::Value;
+
+/// Describes which lists of a value tree are unordered, for [`Value::normalized`].
+///
+/// With the `unicode` feature, it can also describe which byte arrays are strings to normalize to Unicode NFC, so strings that look the same compare equal.
+///
+/// # Examples
+///
+/// ```
+/// use bende::Value;
+/// use bende::value::NormalizeConfig;
+///
+/// let a: Value = bende::decode(b"d13:announce-listll1:a1:bel1:ceee").unwrap();
+/// let b: Value = bende::decode(b"d13:announce-listll1:b1:ael1:ceee").unwrap();
+/// assert_ne!(a, b);
+///
+/// let config = NormalizeConfig::torrent();
+/// assert_eq!(a.normalized(&config), b.normalized(&config));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NormalizeConfig {
+    unordered: Vec<Pattern>,
+    #[cfg(feature = "unicode")]
+    nfc: Vec<Pattern>,
+}
+
+impl NormalizeConfig {
+    /// Constructs a config with no unordered lists.
+    #[inline]
+    pub fn new() -> NormalizeConfig {
+        NormalizeConfig::default()
+    }
+
+    /// Constructs a config for torrent files, where the trackers in each tier of the `announce-list` are unordered, as described in [BEP 12](https://www.bittorrent.org/beps/bep_0012.html).
+    pub fn torrent() -> NormalizeConfig {
+        NormalizeConfig::new().unordered(
+            "announce-list[*]".parse().expect("the pattern should be valid"),
+        )
+    }
+
+    /// Marks the lists that match the given pattern as unordered.
+    #[inline]
+    pub fn unordered(mut self, pattern: Pattern) -> NormalizeConfig {
+        self.unordered.push(pattern);
+        self
+    }
+
+    /// Normalizes the byte arrays that match the given pattern to Unicode NFC, if they're valid UTF-8. Other byte arrays are kept as is.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bende::Value;
+    /// use bende::value::NormalizeConfig;
+    ///
+    /// let a: Value = bende::decode(b"d4:name6:cafe\xcc\x81e").unwrap();
+    /// let b: Value = bende::decode(b"d4:name5:caf\xc3\xa9e").unwrap();
+    ///
+    /// let config = NormalizeConfig::new().nfc("name".parse().unwrap());
+    /// assert_eq!(a.normalized(&config), b);
+    /// ```
+    #[cfg(feature = "unicode")]
+    #[inline]
+    pub fn nfc(mut self, pattern: Pattern) -> NormalizeConfig {
+        self.nfc.push(pattern);
+        self
+    }
+
+    /// Checks if the list at the given path is unordered.
+    fn is_unordered(&self, path: &Path) -> bool {
+        self.unordered.iter().any(|pattern| pattern.matches(path))
+    }
+}
+
+/// Sorts the unordered lists of the value tree in place, children first.
+pub(super) fn normalize(val: &mut Value, config: &NormalizeConfig) {
+    normalize_at(val, config, &mut Path::root());
+}
+
+fn normalize_at(val: &mut Value, config: &NormalizeConfig, path: &mut Path) {
+    match *val {
+        Value::List(ref mut list) => {
+            for (i, elem) in list.iter_mut().enumerate() {
+                path.push(i);
+                normalize_at(elem, config, path);
+                path.pop();
+            }
+            if config.is_unordered(path) {
+                list.sort_by(cmp);
+            }
+        }
+        Value::Dict(ref mut dict) => {
+            for (key, elem) in dict.iter_mut() {
+                path.push(Segment::from_key_bytes(key));
+                normalize_at(elem, config, path);
+                path.pop();
+            }
+        }
+        #[cfg(feature = "unicode")]
+        Value::Text(ref mut text)
+            if config.nfc.iter().any(|pattern| pattern.matches(path)) =>
+        {
+            let normalized = std::str::from_utf8(text)
+                .ok()
+                .map(crate::with::nfc::normalize)
+                .and_then(|normalized| match normalized {
+                    std::borrow::Cow::Owned(normalized) => Some(normalized),
+                    std::borrow::Cow::Borrowed(_) => None,
+                });
+            if let Some(normalized) = normalized {
+                *text = normalized.into_bytes();
+            }
+        }
+        Value::Int(_) | Value::Text(_) => {}
+    }
+}
+
+/// A total order over values, where integers come before byte arrays, which come before lists, which come before dictionaries.
+pub(super) fn cmp(a: &Value, b: &Value) -> Ordering {
+    fn rank(v: &Value) -> u8 {
+        match *v {
+            Value::Int(_) => 0,
+            Value::Text(_) => 1,
+            Value::List(_) => 2,
+            Value::Dict(_) => 3,
+        }
+    }
+
+    match (a, b) {
+        (Value::Int(a), Value::Int(b)) => a.cmp(b),
+        (Value::Text(a), Value::Text(b)) => a.cmp(b),
+        (Value::List(a), Value::List(b)) => {
+            for (a, b) in a.iter().zip(b) {
+                match cmp(a, b) {
+                    Ordering::Equal => {}
+                    ord => return ord,
+                }
+            }
+            a.len().cmp(&b.len())
+        }
+        (Value::Dict(a), Value::Dict(b)) => {
+            for ((ka, va), (kb, vb)) in a.iter().zip(b) {
+                match ka.cmp(kb).then_with(|| cmp(va, vb)) {
+                    Ordering::Equal => {}
+                    ord => return ord,
+                }
+            }
+            a.len().cmp(&b.len())
+        }
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::NormalizeConfig;
+    use crate::Value;
+
+    #[test]
+    fn normalize_nested_lists() {
+        let val: Value = crate::decode(b"lli2ei1eeli0eee").unwrap();
+        let config = NormalizeConfig::new().unordered("**".parse().unwrap());
+
+        assert_eq!(
+            crate::encode(&val.normalized(&config)).unwrap(),
+            b"lli0eeli1ei2eee"
+        );
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn normalize_nfc() {
+        let val: Value =
+            crate::decode(b"l4:e\xcc\x81\xffd1:a3:e\xcc\x81ee").unwrap();
+        let config = NormalizeConfig::new().nfc("[*]".parse().unwrap());
+
+        assert_eq!(
+            crate::encode(&val.normalized(&config)).unwrap(),
+            b"l4:e\xcc\x81\xffd1:a3:e\xcc\x81ee"
+        );
+        let config = NormalizeConfig::new().nfc("**".parse().unwrap());
+        assert_eq!(
+            crate::encode(&val.normalized(&config)).unwrap(),
+            b"l4:e\xcc\x81\xffd1:a2:\xc3\xa9ee"
+        );
+    }
+
+    #[test]
+    fn normalize_only_matched_lists() {
+        let val: Value =
+            crate::decode(b"d1:ali2ei1ee1:bli2ei1e3:fooee").unwrap();
+        let config = NormalizeConfig::new().unordered("b".parse().unwrap());
+
+        assert_eq!(
+            crate::encode(&val.normalized(&config)).unwrap(),
+            b"d1:ali2ei1ee1:bli1ei2e3:fooee"
+        );
+    }
+}
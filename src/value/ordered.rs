@@ -0,0 +1,244 @@
+//! A bencode value that keeps the order its dictionary entries were decoded in.
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::visit;
+use super::visit::BuildValue;
+use super::visit::ValueVisitor;
+use super::Key;
+use super::Value;
+
+/// The name of the newtype struct an [`OrderedDict`] serializes through, which tells the encoder the entries may be written in the order they're in.
+pub(crate) const ORDERED_TOKEN: &str = "$bende::private::OrderedDict";
+
+/// An **unsorted** list of key-value entries, in the order they were decoded or inserted in, with keys that are any byte strings.
+pub type OrderedDict = Vec<(Key, OrderedValue)>;
+
+/// A bencode value whose dictionaries keep their entries in document order, including repeated keys.
+///
+/// Legacy files are sometimes written with unsorted keys, and re-encoding them canonically changes their bytes. An `OrderedValue` remembers the original order, so the file can be edited and written back byte for byte, but **only** by an encoder that was explicitly told to with [`Encoder::with_document_order`](crate::en::Encoder::with_document_order). Every other encoder, including [`encode`](crate::encode), still sorts the entries, so an `OrderedValue` never produces non-canonical bytes by accident.
+///
+/// # Examples
+///
+/// ```
+/// use serde::Serialize;
+/// use bende::en::Encoder;
+/// use bende::value::OrderedValue;
+///
+/// let src = b"d4:name3:foo6:lengthi1ee";
+/// let val: OrderedValue = bende::decode(src).unwrap();
+///
+/// assert_eq!(bende::encode(&val).unwrap(), b"d6:lengthi1e4:name3:fooe");
+///
+/// let mut en = Encoder::new(vec![]).with_document_order(true);
+/// val.serialize(&mut en).unwrap();
+/// assert_eq!(en.into_inner(), src);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrderedValue {
+    /// A 64-bit signed integer.
+    Int(i64),
+    /// An array of bytes that may or **may not** be valid UTF-8.
+    Text(Vec<u8>),
+    /// A list of bencode values.
+    List(Vec<OrderedValue>),
+    /// A list of key-value entries in document order, with keys that are any byte strings.
+    Dict(OrderedDict),
+}
+
+impl OrderedValue {
+    /// Returns an `i64` if the value is an `Int`. Otherwise, `None` is returned.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            OrderedValue::Int(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns a slice of bytes if the value is `Text`. Otherwise `None` is returned.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match *self {
+            OrderedValue::Text(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns a slice of values if the value is a `List`. Otherwise, `None` is returned.
+    pub fn as_list(&self) -> Option<&[OrderedValue]> {
+        match *self {
+            OrderedValue::List(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the entries if the value is a `Dict`. Otherwise, `None` is returned.
+    pub fn as_dict(&self) -> Option<&OrderedDict> {
+        match *self {
+            OrderedValue::Dict(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns a mutable reference to the list if the value is a `List`. Otherwise, `None` is returned.
+    pub fn as_list_mut(&mut self) -> Option<&mut Vec<OrderedValue>> {
+        match *self {
+            OrderedValue::List(ref mut v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns a mutable reference to the entries if the value is a `Dict`. Otherwise, `None` is returned.
+    pub fn as_dict_mut(&mut self) -> Option<&mut OrderedDict> {
+        match *self {
+            OrderedValue::Dict(ref mut v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Gets the value of the first entry with the given key if the value is a `Dict`. Otherwise, `None` is returned.
+    pub fn get<K>(&self, key: &K) -> Option<&OrderedValue>
+    where
+        K: AsRef<[u8]> + ?Sized,
+    {
+        let dict = self.as_dict()?;
+        let key = key.as_ref();
+        dict.iter().find(|(k, _)| **k == *key).map(|(_, val)| val)
+    }
+
+    /// Gets a mutable reference to the value of the first entry with the given key if the value is a `Dict`. Otherwise, `None` is returned.
+    pub fn get_mut<K>(&mut self, key: &K) -> Option<&mut OrderedValue>
+    where
+        K: AsRef<[u8]> + ?Sized,
+    {
+        let dict = self.as_dict_mut()?;
+        let key = key.as_ref();
+        dict.iter_mut().find(|(k, _)| **k == *key).map(|(_, val)| val)
+    }
+}
+
+impl From<Value> for OrderedValue {
+    fn from(v: Value) -> Self {
+        match v {
+            Value::Int(v) => OrderedValue::Int(v),
+            Value::Text(v) => OrderedValue::Text(v),
+            Value::List(v) => OrderedValue::List(
+                v.into_iter().map(OrderedValue::from).collect(),
+            ),
+            Value::Dict(v) => OrderedValue::Dict(
+                v.into_iter().map(|(key, val)| (key, val.into())).collect(),
+            ),
+        }
+    }
+}
+
+/// Sorts the entries, keeping the last of any repeated key, the same way decoding into a [`Value`] does.
+impl From<OrderedValue> for Value {
+    fn from(v: OrderedValue) -> Self {
+        match v {
+            OrderedValue::Int(v) => Value::Int(v),
+            OrderedValue::Text(v) => Value::Text(v),
+            OrderedValue::List(v) => {
+                Value::List(v.into_iter().map(Value::from).collect())
+            }
+            OrderedValue::Dict(v) => Value::Dict(
+                v.into_iter().map(|(key, val)| (key, val.into())).collect(),
+            ),
+        }
+    }
+}
+
+impl Serialize for OrderedValue {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match *self {
+            OrderedValue::Int(v) => ser.serialize_i64(v),
+            OrderedValue::Text(ref v) => ser.serialize_bytes(v),
+            OrderedValue::List(ref v) => visit::serialize_list(ser, v),
+            OrderedValue::Dict(ref v) => {
+                ser.serialize_newtype_struct(ORDERED_TOKEN, &Entries(v))
+            }
+        }
+    }
+}
+
+/// Serializes the entries of a dictionary as a map, in the order they're in.
+struct Entries<'a>(&'a OrderedDict);
+
+impl Serialize for Entries<'_> {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        visit::serialize_dict(
+            ser,
+            self.0.iter().map(|(key, val)| (key.as_bytes(), val)),
+        )
+    }
+}
+
+impl<'de> BuildValue<'de> for OrderedValue {
+    type Key = Key;
+
+    fn from_int(v: i64) -> Self {
+        OrderedValue::Int(v)
+    }
+
+    fn from_bytes(v: &[u8]) -> Option<Self> {
+        Some(OrderedValue::Text(v.to_owned()))
+    }
+
+    fn from_byte_buf(v: Vec<u8>) -> Option<Self> {
+        Some(OrderedValue::Text(v))
+    }
+
+    fn from_list(list: Vec<Self>) -> Self {
+        OrderedValue::List(list)
+    }
+
+    fn from_entries(entries: Vec<(Key, Self)>) -> Self {
+        OrderedValue::Dict(entries)
+    }
+}
+
+impl<'de> Deserialize<'de> for OrderedValue {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        de.deserialize_any(ValueVisitor::new())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde::Serialize;
+
+    use super::OrderedValue;
+    use crate::en::Encoder;
+    use crate::{decode, encode, Value};
+
+    #[test]
+    fn document_order_round_trip() {
+        let src = b"d1:bi1e1:ad1:zi1e1:yli2eee1:bi3ee";
+        let val: OrderedValue = decode(src).unwrap();
+        assert_eq!(val.get("b"), Some(&OrderedValue::Int(1)));
+
+        let mut en = Encoder::new(vec![]).with_document_order(true);
+        val.serialize(&mut en).unwrap();
+        assert_eq!(en.into_inner(), src);
+
+        // Encoders that weren't opted in sort the entries, as they do for a `Value`.
+        let canonical = encode(&Value::from(val.clone())).unwrap();
+        assert_eq!(canonical, b"d1:ad1:yli2ee1:zi1ee1:bi3ee");
+        assert!(crate::check_canonical(&encode(&val).unwrap()).is_ok());
+
+        // Other maps are sorted even by an encoder in document order.
+        let mut en = Encoder::new(vec![]).with_document_order(true);
+        let map = std::collections::HashMap::from([("b", 1), ("a", 2)]);
+        map.serialize(&mut en).unwrap();
+        assert_eq!(en.into_inner(), b"d1:ai2e1:bi1ee");
+    }
+}
@@ -0,0 +1,257 @@
+//! Parsing of values from their textual rendering.
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::display::is_plain_key;
+use super::Dict;
+use super::Key;
+use super::List;
+use super::Value;
+
+/// An error returned when a string isn't a valid textual rendering of a [`Value`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseValueError(usize);
+
+impl ParseValueError {
+    /// Gets the byte offset into the string at which the error was found.
+    #[inline]
+    pub fn at(&self) -> usize {
+        self.0
+    }
+}
+
+impl fmt::Display for ParseValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid value at column {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseValueError {}
+
+/// Parses a value from the textual rendering of its [`Display`](fmt::Display) implementation, so rendered values can be parsed back.
+///
+/// Integers are written in decimal, byte arrays as quoted strings, lists in brackets and dictionaries in braces. Inside quotes, `\"` and `\\` stand for a quote and a backslash, `\xNN` for any byte, and `\u{NNNN}` for any character. Dictionary keys may be quoted, or written bare if they're plain. Whitespace between tokens is ignored.
+///
+/// Values rendered by a [`ValueDisplay`](super::ValueDisplay) with escaping disabled, or with a maximum length, can't always be parsed back.
+///
+/// # Examples
+///
+/// ```
+/// use bende::Value;
+///
+/// let val: Value = r#"{ announce: "udp://t", "info": { length: 1995, name: "a\x00b" } }"#.parse().unwrap();
+///
+/// assert_eq!(
+///     bende::encode(&val).unwrap(),
+///     b"d8:announce7:udp://t4:infod6:lengthi1995e4:name3:a\x00bee"
+/// );
+/// assert_eq!(val.to_string().parse::<Value>(), Ok(val));
+/// ```
+impl FromStr for Value {
+    type Err = ParseValueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { s, pos: 0 };
+        let val = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos < s.len() {
+            return Err(ParseValueError(parser.pos));
+        }
+        Ok(val)
+    }
+}
+
+/// A recursive-descent parser over the textual rendering of a value.
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn rest(&self) -> &str {
+        &self.s[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consumes the character if it's next, after any whitespace.
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        let found = self.rest().starts_with(c);
+        if found {
+            self.pos += c.len_utf8();
+        }
+        found
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ParseValueError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(ParseValueError(self.pos))
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, ParseValueError> {
+        self.skip_whitespace();
+        match self.rest().chars().next() {
+            Some('"') => self.text().map(Value::Text),
+            Some('[') => self.list(),
+            Some('{') => self.dict(),
+            _ => self.int(),
+        }
+    }
+
+    fn int(&mut self) -> Result<Value, ParseValueError> {
+        let rest = self.rest();
+        let sign = usize::from(rest.starts_with('-'));
+        let len =
+            sign + rest[sign..].bytes().take_while(u8::is_ascii_digit).count();
+        let v = rest[..len].parse().map_err(|_| ParseValueError(self.pos))?;
+        self.pos += len;
+        Ok(Value::Int(v))
+    }
+
+    fn list(&mut self) -> Result<Value, ParseValueError> {
+        self.expect('[')?;
+        let mut list = List::new();
+        if !self.eat(']') {
+            loop {
+                list.push(self.value()?);
+                if self.eat(']') {
+                    break;
+                }
+                self.expect(',')?;
+            }
+        }
+        Ok(Value::List(list))
+    }
+
+    fn dict(&mut self) -> Result<Value, ParseValueError> {
+        self.expect('{')?;
+        let mut dict = Dict::new();
+        if !self.eat('}') {
+            loop {
+                self.skip_whitespace();
+                let at = self.pos;
+                let key = self.key()?;
+                self.expect(':')?;
+                if dict.insert(key, self.value()?).is_some() {
+                    return Err(ParseValueError(at));
+                }
+                if self.eat('}') {
+                    break;
+                }
+                self.expect(',')?;
+            }
+        }
+        Ok(Value::Dict(dict))
+    }
+
+    fn key(&mut self) -> Result<Key, ParseValueError> {
+        let at = self.pos;
+        if self.rest().starts_with('"') {
+            return self.text().map(Key::from);
+        }
+
+        let len = self.rest().find(':').unwrap_or(self.rest().len());
+        let key = self.rest()[..len].trim_end().to_owned();
+        if !is_plain_key(&key) {
+            return Err(ParseValueError(at));
+        }
+        self.pos += key.len();
+        Ok(key.into())
+    }
+
+    fn text(&mut self) -> Result<Vec<u8>, ParseValueError> {
+        self.expect('"')?;
+        let mut text = vec![];
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            let at = self.pos + i;
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Ok(text);
+                }
+                '\\' => match chars.next() {
+                    Some((_, c @ ('"' | '\\'))) => text.push(c as u8),
+                    Some((_, 'x')) => {
+                        let hex = chars.as_str().get(..2);
+                        let b = hex
+                            .filter(|hex| {
+                                hex.bytes().all(|b| b.is_ascii_hexdigit())
+                            })
+                            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                            .ok_or(ParseValueError(at))?;
+                        text.push(b);
+                        chars.nth(1);
+                    }
+                    Some((_, 'u')) => {
+                        let rest = chars.as_str();
+                        let end = rest.find('}').ok_or(ParseValueError(at))?;
+                        let c = rest
+                            .strip_prefix('{')
+                            .map(|_| &rest[1..end])
+                            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                            .and_then(char::from_u32)
+                            .ok_or(ParseValueError(at))?;
+                        text.extend_from_slice(
+                            c.encode_utf8(&mut [0; 4]).as_bytes(),
+                        );
+                        chars.nth(end);
+                    }
+                    _ => return Err(ParseValueError(at)),
+                },
+                c => text
+                    .extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            }
+        }
+        Err(ParseValueError(self.s.len()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ParseValueError;
+    use crate::Value;
+
+    #[test]
+    fn parse_values() {
+        let val: Value =
+            " [ -5 ,\"\\u{202e}\\\\\\\"\", [], {}, { a b: 0, \"\": 1 } ] "
+                .parse()
+                .unwrap();
+        assert_eq!(
+            crate::encode(&val).unwrap(),
+            b"li-5e5:\xe2\x80\xae\\\"leded0:i1e3:a bi0eee"
+        );
+    }
+
+    #[test]
+    fn parse_invalid_values() {
+        let parse = |s: &str| s.parse::<Value>();
+
+        assert_eq!(parse(""), Err(ParseValueError(0)));
+        assert_eq!(parse("1 2"), Err(ParseValueError(2)));
+        assert_eq!(parse("[1,]"), Err(ParseValueError(3)));
+        assert_eq!(parse("\"\\xg0\""), Err(ParseValueError(1)));
+        assert_eq!(parse("\"\\u{d800}\""), Err(ParseValueError(1)));
+        assert_eq!(parse("\"abc"), Err(ParseValueError(4)));
+        assert_eq!(parse("{ a: 1, a: 2 }"), Err(ParseValueError(8)));
+        assert_eq!(parse("{ a,b: 1 }"), Err(ParseValueError(2)));
+        assert_eq!(parse("[\"a…\" (+3 bytes)]"), Err(ParseValueError(8)));
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let val: Value =
+            crate::decode(b"d1:\n3:\x00\xffa4:infold1:ai-1eee1:\xfei0ee")
+                .unwrap();
+        assert_eq!(val.to_string().parse::<Value>(), Ok(val));
+    }
+}
@@ -0,0 +1,195 @@
+//! Structural skeletons of value trees, which leave out every payload.
+
+use std::fmt;
+
+use super::Key;
+use super::Value;
+
+/// The deepest level of nested lists and dictionaries that's sampled, where the root has a depth of `0`.
+///
+/// Sampling recurses once per level, so this bounds the stack it needs, however deeply the value is nested.
+pub const MAX_SAMPLE_DEPTH: usize = 64;
+
+/// The structure of a value tree without its payloads, returned by [`Value::sample`].
+///
+/// A skeleton keeps the type of every value, the length of every byte array, list and dictionary, and the keys of every dictionary, but none of the integers or bytes, so it can be attached to telemetry and bug reports without leaking their content. Lists and dictionaries whose `len` is larger than the number of their sampled children were truncated, either to stay within the number of nodes, or because they're nested deeper than [`MAX_SAMPLE_DEPTH`].
+///
+/// The [`Display`](fmt::Display) implementation writes a compact summary, eg: `{"length": int, "name": bytes(3), "pieces": bytes(40)}`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Skeleton {
+    /// An integer.
+    Int,
+    /// A byte array.
+    Text {
+        /// The number of bytes.
+        len: usize,
+    },
+    /// A list.
+    List {
+        /// The number of elements in the list.
+        len: usize,
+        /// The skeletons of the sampled elements, which are the first elements of the list.
+        elems: Vec<Skeleton>,
+    },
+    /// A dictionary.
+    Dict {
+        /// The number of entries in the dictionary.
+        len: usize,
+        /// The keys and skeletons of the sampled entries, which are the first entries of the dictionary.
+        entries: Vec<(Key, Skeleton)>,
+    },
+}
+
+impl Skeleton {
+    /// Checks if any list or dictionary of the skeleton was truncated.
+    pub fn is_truncated(&self) -> bool {
+        match *self {
+            Skeleton::Int | Skeleton::Text { .. } => false,
+            Skeleton::List { len, ref elems } => {
+                len > elems.len() || elems.iter().any(Skeleton::is_truncated)
+            }
+            Skeleton::Dict { len, ref entries } => {
+                len > entries.len()
+                    || entries.iter().any(|(_, val)| val.is_truncated())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Skeleton {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Skeleton::Int => f.write_str("int"),
+            Skeleton::Text { len } => write!(f, "bytes({})", len),
+            Skeleton::List { len, ref elems } => {
+                f.write_str("[")?;
+                for (i, elem) in elems.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", elem)?;
+                }
+                write_rest(f, elems.len(), len)?;
+                f.write_str("]")
+            }
+            Skeleton::Dict { len, ref entries } => {
+                f.write_str("{")?;
+                for (i, (key, val)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{:?}: {}", key, val)?;
+                }
+                write_rest(f, entries.len(), len)?;
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+/// Writes how many children of a list or dictionary were left out, if any were.
+fn write_rest(
+    f: &mut fmt::Formatter<'_>,
+    sampled: usize,
+    len: usize,
+) -> fmt::Result {
+    match (sampled, len - sampled) {
+        (_, 0) => Ok(()),
+        (0, rest) => write!(f, "... {} more", rest),
+        (_, rest) => write!(f, ", ... {} more", rest),
+    }
+}
+
+/// Samples the skeleton of a value tree depth-first, in document order, until it has `max_nodes` nodes. The root is always sampled.
+pub(super) fn sample(val: &Value, max_nodes: usize) -> Skeleton {
+    let mut budget = max_nodes;
+    sample_node(val, &mut budget, 0)
+}
+
+fn sample_node(val: &Value, budget: &mut usize, depth: usize) -> Skeleton {
+    *budget = budget.saturating_sub(1);
+    // The children of the deepest lists and dictionaries are only counted.
+    let mut exhausted = 0;
+    let budget = if depth < MAX_SAMPLE_DEPTH { budget } else { &mut exhausted };
+    match *val {
+        Value::Int(_) => Skeleton::Int,
+        Value::Text(ref v) => Skeleton::Text { len: v.len() },
+        Value::List(ref v) => {
+            let mut elems = Vec::new();
+            for elem in v.iter() {
+                if *budget == 0 {
+                    break;
+                }
+                elems.push(sample_node(elem, budget, depth + 1));
+            }
+            Skeleton::List { len: v.len(), elems }
+        }
+        Value::Dict(ref v) => {
+            let mut entries = Vec::new();
+            for (key, val) in v.iter() {
+                if *budget == 0 {
+                    break;
+                }
+                entries
+                    .push((key.clone(), sample_node(val, budget, depth + 1)));
+            }
+            Skeleton::Dict { len: v.len(), entries }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Skeleton, MAX_SAMPLE_DEPTH};
+    use crate::decode;
+    use crate::Value;
+
+    #[test]
+    fn sample_truncates_and_hides_payloads() {
+        let val: Value = decode(
+            b"d8:announce13:udp://secret/4:infod6:lengthi1995e4:name3:foo6:pieces40:0123456789012345678901234567890123456789e4:listli1ei2ei3eee",
+        )
+        .unwrap();
+
+        let full = val.sample(usize::MAX);
+        assert!(!full.is_truncated());
+        assert_eq!(
+            full.to_string(),
+            r#"{"announce": bytes(13), "info": {"length": int, "name": bytes(3), "pieces": bytes(40)}, "list": [int, int, int]}"#
+        );
+        assert!(!full.to_string().contains("secret"));
+
+        let small = val.sample(4);
+        assert!(small.is_truncated());
+        assert_eq!(
+            small.to_string(),
+            r#"{"announce": bytes(13), "info": {"length": int, ... 2 more}, ... 1 more}"#
+        );
+
+        assert_eq!(
+            Value::from(vec![Value::Int(1)]).sample(0),
+            Skeleton::List { len: 1, elems: vec![] }
+        );
+    }
+
+    #[test]
+    fn sample_bounds_depth() {
+        let mut val = Value::Int(1);
+        for _ in 0..1000 {
+            val = Value::from(vec![val]);
+        }
+
+        let mut skeleton = &val.sample(usize::MAX);
+        let mut depth = 0;
+        while let Skeleton::List { elems, .. } = skeleton {
+            match elems.first() {
+                Some(elem) => skeleton = elem,
+                None => break,
+            }
+            depth += 1;
+        }
+        assert_eq!(depth, MAX_SAMPLE_DEPTH);
+        assert_eq!(*skeleton, Skeleton::List { len: 1, elems: vec![] });
+        assert!(val.sample(usize::MAX).is_truncated());
+    }
+}
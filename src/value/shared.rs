@@ -0,0 +1,230 @@
+//! A bencode value with cheap, thread-safe structural sharing.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::visit;
+use super::Key;
+use super::Value;
+
+/// A **sorted** key-value map of shared values.
+pub type ArcDict = BTreeMap<Key, ArcValue>;
+
+/// An immutable bencode value whose byte arrays, lists and dictionaries are reference counted.
+///
+/// Cloning an `ArcValue` is cheap, as it only bumps a reference count, so decoded metadata can be shared across threads in a cache. Editing is copy-on-write: the `*_mut` accessors only copy the node being edited (and none of its children) if it's shared.
+///
+/// # Examples
+///
+/// ```
+/// use bende::value::ArcValue;
+///
+/// let cached: ArcValue = bende::decode(b"d8:announce7:udp://t4:infod6:lengthi1995eee").unwrap();
+///
+/// let mut edited = cached.clone();
+/// edited.as_dict_mut().unwrap().insert("announce".into(), "udp://u".into());
+///
+/// assert_eq!(cached.get("announce").unwrap().as_bytes(), Some(b"udp://t".as_slice()));
+/// assert_eq!(edited.get("announce").unwrap().as_bytes(), Some(b"udp://u".as_slice()));
+///
+/// // The untouched `info` dictionary is still shared between both values.
+/// assert!(cached.get("info").unwrap().ptr_eq(edited.get("info").unwrap()));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArcValue {
+    /// A 64-bit signed integer.
+    Int(i64),
+    /// An array of bytes that may or **may not** be valid UTF-8.
+    Text(Arc<Vec<u8>>),
+    /// A list of bencode values.
+    List(Arc<Vec<ArcValue>>),
+    /// A key-value map with keys that are any byte strings.
+    Dict(Arc<ArcDict>),
+}
+
+impl ArcValue {
+    /// Returns an `i64` if the value is an `Int`. Otherwise, `None` is returned.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            ArcValue::Int(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns a slice of bytes if the value is `Text`. Otherwise `None` is returned.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match *self {
+            ArcValue::Text(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns a slice of values if the value is a `List`. Otherwise, `None` is returned.
+    pub fn as_list(&self) -> Option<&[ArcValue]> {
+        match *self {
+            ArcValue::List(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns a `BTreeMap` if the value is a `Dict`. Otherwise, `None` is returned.
+    pub fn as_dict(&self) -> Option<&ArcDict> {
+        match *self {
+            ArcValue::Dict(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Gets the value of the given key if the value is a `Dict`. Otherwise, `None` is returned.
+    pub fn get<K>(&self, key: &K) -> Option<&ArcValue>
+    where
+        K: AsRef<[u8]> + ?Sized,
+    {
+        self.as_dict()?.get(key.as_ref())
+    }
+
+    /// Returns a mutable reference to the bytes if the value is `Text`, copying them first if they're shared. Otherwise, `None` is returned.
+    pub fn as_bytes_mut(&mut self) -> Option<&mut Vec<u8>> {
+        match *self {
+            ArcValue::Text(ref mut v) => Some(Arc::make_mut(v)),
+            _ => None,
+        }
+    }
+
+    /// Returns a mutable reference to the list if the value is a `List`, copying it first if it's shared. Otherwise, `None` is returned.
+    ///
+    /// Only the list itself is copied - its elements are still shared.
+    pub fn as_list_mut(&mut self) -> Option<&mut Vec<ArcValue>> {
+        match *self {
+            ArcValue::List(ref mut v) => Some(Arc::make_mut(v)),
+            _ => None,
+        }
+    }
+
+    /// Returns a mutable reference to the map if the value is a `Dict`, copying it first if it's shared. Otherwise, `None` is returned.
+    ///
+    /// Only the map itself is copied - its values are still shared.
+    pub fn as_dict_mut(&mut self) -> Option<&mut ArcDict> {
+        match *self {
+            ArcValue::Dict(ref mut v) => Some(Arc::make_mut(v)),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if both values share the same allocation, or are equal integers.
+    pub fn ptr_eq(&self, other: &ArcValue) -> bool {
+        match (self, other) {
+            (ArcValue::Int(a), ArcValue::Int(b)) => a == b,
+            (ArcValue::Text(a), ArcValue::Text(b)) => Arc::ptr_eq(a, b),
+            (ArcValue::List(a), ArcValue::List(b)) => Arc::ptr_eq(a, b),
+            (ArcValue::Dict(a), ArcValue::Dict(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    /// Copies the value into an owned [`Value`].
+    pub fn to_value(&self) -> Value {
+        match *self {
+            ArcValue::Int(v) => Value::Int(v),
+            ArcValue::Text(ref v) => Value::Text(v.to_vec()),
+            ArcValue::List(ref v) => {
+                Value::List(v.iter().map(ArcValue::to_value).collect())
+            }
+            ArcValue::Dict(ref v) => Value::Dict(
+                v.iter()
+                    .map(|(key, val)| (key.clone(), val.to_value()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<Value> for ArcValue {
+    fn from(v: Value) -> Self {
+        match v {
+            Value::Int(v) => ArcValue::Int(v),
+            Value::Text(v) => ArcValue::Text(Arc::new(v)),
+            Value::List(v) => ArcValue::List(Arc::new(
+                v.into_iter().map(ArcValue::from).collect(),
+            )),
+            Value::Dict(v) => ArcValue::Dict(Arc::new(
+                v.into_iter().map(|(key, val)| (key, val.into())).collect(),
+            )),
+        }
+    }
+}
+
+impl From<&ArcValue> for Value {
+    fn from(v: &ArcValue) -> Self {
+        v.to_value()
+    }
+}
+
+impl From<&str> for ArcValue {
+    fn from(v: &str) -> Self {
+        ArcValue::Text(Arc::new(v.as_bytes().to_vec()))
+    }
+}
+
+impl From<i64> for ArcValue {
+    fn from(v: i64) -> Self {
+        ArcValue::Int(v)
+    }
+}
+
+impl Serialize for ArcValue {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match *self {
+            ArcValue::Int(v) => ser.serialize_i64(v),
+            ArcValue::Text(ref v) => ser.serialize_bytes(v),
+            ArcValue::List(ref v) => visit::serialize_list(ser, v),
+            ArcValue::Dict(ref v) => visit::serialize_dict(
+                ser,
+                v.iter().map(|(key, val)| (key.as_bytes(), val)),
+            ),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ArcValue {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Value::deserialize(de).map(ArcValue::from)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ArcValue;
+    use crate::{decode, encode};
+
+    #[test]
+    fn clone_shares_and_edit_copies() {
+        let a: ArcValue = decode(b"l3:fool3:barei1995ee").unwrap();
+        let mut b = a.clone();
+        assert!(a.ptr_eq(&b));
+
+        b.as_list_mut().unwrap()[2] = ArcValue::Int(2024);
+        assert!(!a.ptr_eq(&b));
+        assert!(a.as_list().unwrap()[1].ptr_eq(&b.as_list().unwrap()[1]));
+        assert_eq!(encode(&a).unwrap(), b"l3:fool3:barei1995ee");
+        assert_eq!(encode(&b).unwrap(), b"l3:fool3:barei2024ee");
+    }
+
+    #[test]
+    fn shared_across_threads() {
+        let val: ArcValue = decode(b"d3:fooi1995ee").unwrap();
+        let other = val.clone();
+        let handle = std::thread::spawn(move || other.get("foo").cloned());
+        assert_eq!(handle.join().unwrap(), Some(ArcValue::Int(1995)));
+        assert_eq!(val.to_value(), decode(b"d3:fooi1995ee").unwrap());
+    }
+}
@@ -0,0 +1,472 @@
+//! Early-exit traversal of a value tree, and the serde visitor and serialization shared by every value type.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::ControlFlow;
+
+use serde::de::DeserializeSeed;
+use serde::de::MapAccess;
+use serde::de::SeqAccess;
+use serde::de::Unexpected;
+use serde::de::Visitor;
+use serde::ser::SerializeMap;
+use serde::ser::SerializeSeq;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::KeyBytes;
+use super::Value;
+
+/// Where a value sits in its parent.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Edge<'a> {
+    /// The value is the root of the traversal.
+    Root,
+    /// The value is held by a dictionary, under the given key.
+    Key(&'a [u8]),
+    /// The value is held by a list, at the given index.
+    Index(usize),
+}
+
+/// Formats keys as byte string literals.
+impl fmt::Debug for Edge<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Edge::Root => f.write_str("Root"),
+            Edge::Key(key) => write!(f, "Key(b\"{}\")", key.escape_ascii()),
+            Edge::Index(i) => f.debug_tuple("Index").field(&i).finish(),
+        }
+    }
+}
+
+/// What a traversal should do after entering a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Walk {
+    /// Visit the value's children, if any.
+    Descend,
+    /// Don't visit the value's children.
+    Skip,
+}
+
+/// A visitor over a value tree, driven by [`Value::accept`].
+///
+/// Each value is first passed to [`enter`](VisitValue::enter), then its children are visited in order, and then it's passed to [`exit`](VisitValue::exit). Returning [`ControlFlow::Break`] from either callback stops the traversal right away.
+///
+/// # Examples
+///
+/// ```
+/// use std::ops::ControlFlow;
+///
+/// use bende::Value;
+/// use bende::value::{Edge, VisitValue, Walk};
+///
+/// struct FindAnnounce;
+///
+/// impl VisitValue for FindAnnounce {
+///     type Break = Vec<u8>;
+///
+///     fn enter(&mut self, edge: Edge<'_>, val: &Value) -> ControlFlow<Vec<u8>, Walk> {
+///         match (edge, val.as_bytes()) {
+///             (Edge::Key(b"announce"), Some(url)) => ControlFlow::Break(url.to_vec()),
+///             // The info dictionary is huge, and never holds the announce URL.
+///             (Edge::Key(b"info"), _) => ControlFlow::Continue(Walk::Skip),
+///             _ => ControlFlow::Continue(Walk::Descend),
+///         }
+///     }
+/// }
+///
+/// let val: Value = bende::decode(b"d8:announce7:udp://t4:infod6:lengthi1eee").unwrap();
+/// assert_eq!(val.accept(&mut FindAnnounce), ControlFlow::Break(b"udp://t".to_vec()));
+/// ```
+pub trait VisitValue {
+    /// The type the traversal stops with.
+    type Break;
+
+    /// Called when a value is entered, before any of its children.
+    fn enter(
+        &mut self,
+        edge: Edge<'_>,
+        val: &Value,
+    ) -> ControlFlow<Self::Break, Walk> {
+        let _ = (edge, val);
+        ControlFlow::Continue(Walk::Descend)
+    }
+
+    /// Called when a value is exited, after all of its children. This is also called for values whose children were skipped.
+    fn exit(
+        &mut self,
+        edge: Edge<'_>,
+        val: &Value,
+    ) -> ControlFlow<Self::Break> {
+        let _ = (edge, val);
+        ControlFlow::Continue(())
+    }
+}
+
+pub(super) fn accept<V>(
+    edge: Edge<'_>,
+    val: &Value,
+    visitor: &mut V,
+) -> ControlFlow<V::Break>
+where
+    V: VisitValue + ?Sized,
+{
+    if let Walk::Descend = visitor.enter(edge, val)? {
+        match *val {
+            Value::List(ref list) => {
+                for (i, elem) in list.iter().enumerate() {
+                    accept(Edge::Index(i), elem, visitor)?;
+                }
+            }
+            Value::Dict(ref dict) => {
+                for (key, elem) in dict {
+                    accept(Edge::Key(key), elem, visitor)?;
+                }
+            }
+            Value::Int(_) | Value::Text(_) => {}
+        }
+    }
+    visitor.exit(edge, val)
+}
+
+/// How a value type is built from what serde visits, so every value type can decode through the one [`ValueVisitor`].
+pub(super) trait BuildValue<'de>: Sized {
+    /// The type dictionary keys are decoded into.
+    type Key: Deserialize<'de>;
+
+    /// What the visitor expects, for error messages.
+    const EXPECTING: &'static str = "any valid bencode type";
+
+    /// Builds an integer.
+    fn from_int(v: i64) -> Self;
+
+    /// Builds a byte array from bytes that only live for the call, or returns `None` if the type can only borrow from the source.
+    fn from_bytes(v: &[u8]) -> Option<Self>;
+
+    /// Builds a byte array from bytes borrowed from the source.
+    fn from_borrowed_bytes(v: &'de [u8]) -> Option<Self> {
+        Self::from_bytes(v)
+    }
+
+    /// Builds a byte array from an owned buffer.
+    fn from_byte_buf(v: Vec<u8>) -> Option<Self> {
+        Self::from_bytes(&v)
+    }
+
+    /// Builds a list from its elements.
+    fn from_list(list: Vec<Self>) -> Self;
+
+    /// Builds a dictionary from its entries, in the order they were decoded in.
+    fn from_entries(entries: Vec<(Self::Key, Self)>) -> Self;
+}
+
+/// The serde visitor of every value type, which can also bound how deeply the value is nested.
+pub(super) struct ValueVisitor<T> {
+    /// The number of levels of nesting left, and the maximum it started at, if the value is bounded.
+    levels: Option<(usize, usize)>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> ValueVisitor<T> {
+    /// Constructs a visitor of values nested any number of levels deep.
+    #[inline]
+    pub(super) fn new() -> ValueVisitor<T> {
+        Self { levels: None, marker: PhantomData }
+    }
+
+    /// Constructs a visitor of values with at most `max` levels of nested lists and dictionaries.
+    #[inline]
+    pub(super) fn bounded(max: usize) -> ValueVisitor<T> {
+        Self { levels: Some((max, max)), marker: PhantomData }
+    }
+
+    /// Gets the visitor of the values one level deeper, failing if there are no levels left.
+    fn nested<E>(self) -> Result<ValueVisitor<T>, E>
+    where
+        E: serde::de::Error,
+    {
+        match self.levels {
+            None => Ok(self),
+            Some((0, max)) => Err(E::custom(format_args!(
+                "value is nested deeper than {} levels",
+                max
+            ))),
+            Some((levels, max)) => {
+                Ok(Self { levels: Some((levels - 1, max)), ..self })
+            }
+        }
+    }
+}
+
+impl<T> Clone for ValueVisitor<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ValueVisitor<T> {}
+
+impl<'de, T> DeserializeSeed<'de> for ValueVisitor<T>
+where
+    T: BuildValue<'de>,
+{
+    type Value = T;
+
+    fn deserialize<D>(self, de: D) -> Result<T, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        de.deserialize_any(self)
+    }
+}
+
+impl<'de, T> Visitor<'de> for ValueVisitor<T>
+where
+    T: BuildValue<'de>,
+{
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.levels {
+            Some((_, max)) => {
+                write!(f, "a bencode value nested at most {} levels deep", max)
+            }
+            None => f.write_str(T::EXPECTING),
+        }
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<T, E> {
+        Ok(T::from_int(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<T, E>
+    where
+        E: serde::de::Error,
+    {
+        i64::try_from(v)
+            .map(T::from_int)
+            .map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &self))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<T, E>
+    where
+        E: serde::de::Error,
+    {
+        T::from_bytes(v.as_bytes())
+            .ok_or_else(|| E::invalid_type(Unexpected::Str(v), &self))
+    }
+
+    fn visit_borrowed_str<E>(self, v: &'de str) -> Result<T, E>
+    where
+        E: serde::de::Error,
+    {
+        self.visit_borrowed_bytes(v.as_bytes())
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<T, E>
+    where
+        E: serde::de::Error,
+    {
+        self.visit_byte_buf(v.into_bytes())
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<T, E>
+    where
+        E: serde::de::Error,
+    {
+        T::from_bytes(v)
+            .ok_or_else(|| E::invalid_type(Unexpected::Bytes(v), &self))
+    }
+
+    fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<T, E>
+    where
+        E: serde::de::Error,
+    {
+        T::from_borrowed_bytes(v)
+            .ok_or_else(|| E::invalid_type(Unexpected::Bytes(v), &self))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<T, E>
+    where
+        E: serde::de::Error,
+    {
+        T::from_byte_buf(v).ok_or_else(|| {
+            E::invalid_type(Unexpected::Other("owned bytes"), &self)
+        })
+    }
+
+    fn visit_some<D>(self, de: D) -> Result<T, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        self.deserialize(de)
+    }
+
+    fn visit_seq<A>(self, mut access: A) -> Result<T, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let seed = self.nested()?;
+        let mut list = Vec::new();
+        while let Some(elem) = access.next_element_seed(seed)? {
+            list.push(elem);
+        }
+        Ok(T::from_list(list))
+    }
+
+    fn visit_map<A>(self, mut access: A) -> Result<T, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let seed = self.nested()?;
+        let mut entries = Vec::new();
+        while let Some(key) = access.next_key()? {
+            entries.push((key, access.next_value_seed(seed)?));
+        }
+        Ok(T::from_entries(entries))
+    }
+}
+
+/// Serializes the elements of a list as a sequence.
+pub(super) fn serialize_list<S, V>(
+    ser: S,
+    list: &[V],
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    V: Serialize,
+{
+    let mut seq = ser.serialize_seq(Some(list.len()))?;
+    for elem in list {
+        seq.serialize_element(elem)?;
+    }
+    seq.end()
+}
+
+/// Serializes the entries of a dictionary as a map, in the order they're in, with each key as a string if it's valid UTF-8 (see [`KeyBytes`]).
+pub(super) fn serialize_dict<'a, S, V, I>(
+    ser: S,
+    entries: I,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    V: Serialize + 'a,
+    I: IntoIterator<Item = (&'a [u8], &'a V)>,
+    I::IntoIter: ExactSizeIterator,
+{
+    let entries = entries.into_iter();
+    let mut map = ser.serialize_map(Some(entries.len()))?;
+    for (key, val) in entries {
+        map.serialize_entry(&KeyBytes(key), val)?;
+    }
+    map.end()
+}
+
+/// Serializes the entries of an unsorted dictionary as a map, sorting them by the bytes of their keys first, so serializers that don't sort keys themselves still produce valid bencode.
+pub(super) fn serialize_sorted_dict<'a, S, V, I>(
+    ser: S,
+    entries: I,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    V: Serialize + 'a,
+    I: IntoIterator<Item = (&'a [u8], &'a V)>,
+{
+    let mut entries: Vec<_> = entries.into_iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    serialize_dict(ser, entries)
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::ControlFlow;
+
+    use serde::de::value::{BytesDeserializer, Error, U64Deserializer};
+    use serde::Deserialize;
+
+    use super::{Edge, VisitValue, Walk};
+    use crate::value::{CowValue, OrderedValue, ValueRef};
+    use crate::Value;
+
+    #[derive(Default)]
+    struct Trace(Vec<String>);
+
+    impl VisitValue for Trace {
+        type Break = ();
+
+        fn enter(
+            &mut self,
+            edge: Edge<'_>,
+            val: &Value,
+        ) -> ControlFlow<(), Walk> {
+            self.0.push(format!("enter {edge:?}"));
+            match *val {
+                Value::Int(3) => ControlFlow::Break(()),
+                Value::Text(_) => ControlFlow::Continue(Walk::Skip),
+                _ => ControlFlow::Continue(Walk::Descend),
+            }
+        }
+
+        fn exit(&mut self, edge: Edge<'_>, _: &Value) -> ControlFlow<()> {
+            self.0.push(format!("exit {edge:?}"));
+            ControlFlow::Continue(())
+        }
+    }
+
+    #[test]
+    fn visit_order() {
+        let val: Value = crate::decode(b"d1:ali1ee1:b3:fooe").unwrap();
+        let mut trace = Trace::default();
+
+        assert_eq!(val.accept(&mut trace), ControlFlow::Continue(()));
+        assert_eq!(
+            trace.0,
+            [
+                "enter Root",
+                "enter Key(b\"a\")",
+                "enter Index(0)",
+                "exit Index(0)",
+                "exit Key(b\"a\")",
+                "enter Key(b\"b\")",
+                "exit Key(b\"b\")",
+                "exit Root",
+            ]
+        );
+    }
+
+    #[test]
+    fn visit_stops_early() {
+        let val: Value = crate::decode(b"li1ei3ei5ee").unwrap();
+        let mut trace = Trace::default();
+
+        assert_eq!(val.accept(&mut trace), ControlFlow::Break(()));
+        assert_eq!(
+            trace.0,
+            ["enter Root", "enter Index(0)", "exit Index(0)", "enter Index(1)"]
+        );
+    }
+
+    #[test]
+    fn value_types_share_visitor() {
+        let src = b"d1:ai1e1:bl3:fooee";
+        assert_eq!(
+            crate::encode(&crate::decode::<Value>(src).unwrap()).unwrap(),
+            src
+        );
+        assert_eq!(
+            crate::encode(&crate::decode::<CowValue>(src).unwrap()).unwrap(),
+            src
+        );
+        assert_eq!(
+            crate::encode(&crate::decode::<OrderedValue>(src).unwrap())
+                .unwrap(),
+            src
+        );
+
+        let big = U64Deserializer::<Error>::new(u64::MAX);
+        assert!(Value::deserialize(big).is_err());
+        // A `ValueRef` can only borrow from the source.
+        let owned = BytesDeserializer::<Error>::new(b"foo");
+        assert!(ValueRef::deserialize(owned).is_err());
+    }
+}
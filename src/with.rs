@@ -0,0 +1,948 @@
+//! Serde adapters for use with `#[serde(with = "...")]`.
+//!
+//! The adapters included in this module are:
+//!
+//! * [`unix_seconds`] - Encodes a date and time as an integer of seconds since the unix epoch, eg: a torrent's `creation date`.
+//! * [`float_string`] - Encodes an `f64` as a byte string, since bencode has no floats. NaN and infinities are rejected, unless [`float_string::sentinel`] is used.
+//! * [`duration_secs`] - Encodes a `std::time::Duration` as an integer of seconds, eg: a tracker's `interval`.
+//! * [`system_time_secs`] - Encodes a `std::time::SystemTime` as an integer of seconds since the unix epoch.
+//! * [`path_list`] - Encodes a `PathBuf` as a list of its components, eg: the `path` of a file in a torrent. Paths from untrusted sources should use [`path_list::safe`], which rejects components that escape the download directory.
+//! * [`entries`] - Encodes a list of key-value pairs as a dictionary, eg: settings built up at runtime, with repeated keys resolved by the encoder's [`DuplicateKeys`](crate::en::DuplicateKeys) policy.
+//! * `nfc` - Normalizes a string to Unicode NFC when decoding. Requires the `unicode` feature.
+//! * `url` - Encodes a `url::Url` as a byte string, validating it when decoding. Requires the `url` feature.
+//! * `uuid` - Encodes a `uuid::Uuid` as 16 raw bytes. Requires the `uuid` feature.
+
+pub mod unix_seconds {
+    //! Encodes a date and time as an integer of seconds since the unix epoch.
+    //!
+    //! Any sub-second precision is truncated when encoding.
+    //!
+    //! # Examples
+    //!
+    //! ```
+    //! # #[cfg(feature = "chrono")]
+    //! # {
+    //! use chrono::{DateTime, Utc};
+    //! use serde::{Deserialize, Serialize};
+    //!
+    //! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    //! struct Torrent {
+    //!     #[serde(rename = "creation date", with = "bende::with::unix_seconds")]
+    //!     creation_date: DateTime<Utc>,
+    //! }
+    //!
+    //! let torrent = Torrent {
+    //!     creation_date: DateTime::from_timestamp(1995, 0).unwrap(),
+    //! };
+    //!
+    //! let bytes = bende::encode(&torrent).unwrap();
+    //! assert_eq!(bytes, b"d13:creation datei1995ee");
+    //! assert_eq!(bende::decode::<Torrent>(&bytes).unwrap(), torrent);
+    //! # }
+    //! ```
+
+    use serde::de::Error as _;
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::Serializer;
+
+    /// A date and time that can be represented as seconds since the unix epoch.
+    pub trait UnixSeconds: Sized {
+        /// Gets the number of seconds since the unix epoch.
+        fn to_unix_seconds(&self) -> i64;
+
+        /// Constructs the date and time from seconds since the unix epoch, returning `None` if it is out of range.
+        fn from_unix_seconds(secs: i64) -> Option<Self>;
+    }
+
+    #[cfg(feature = "chrono")]
+    impl UnixSeconds for chrono::DateTime<chrono::Utc> {
+        fn to_unix_seconds(&self) -> i64 {
+            self.timestamp()
+        }
+
+        fn from_unix_seconds(secs: i64) -> Option<Self> {
+            chrono::DateTime::from_timestamp(secs, 0)
+        }
+    }
+
+    #[cfg(feature = "time")]
+    impl UnixSeconds for time::OffsetDateTime {
+        fn to_unix_seconds(&self) -> i64 {
+            self.unix_timestamp()
+        }
+
+        fn from_unix_seconds(secs: i64) -> Option<Self> {
+            time::OffsetDateTime::from_unix_timestamp(secs).ok()
+        }
+    }
+
+    /// Serializes the date and time as an integer.
+    pub fn serialize<T, S>(v: &T, ser: S) -> Result<S::Ok, S::Error>
+    where
+        T: UnixSeconds,
+        S: Serializer,
+    {
+        ser.serialize_i64(v.to_unix_seconds())
+    }
+
+    /// Deserializes the date and time from an integer.
+    pub fn deserialize<'de, T, D>(de: D) -> Result<T, D::Error>
+    where
+        T: UnixSeconds,
+        D: Deserializer<'de>,
+    {
+        let secs = i64::deserialize(de)?;
+        T::from_unix_seconds(secs).ok_or_else(|| {
+            D::Error::custom(format!(
+                "{} seconds since the unix epoch is out of range",
+                secs
+            ))
+        })
+    }
+}
+
+pub mod float_string {
+    //! Encodes an `f64` as a byte string of its shortest decimal form, eg: `0.5`, which decodes back into the same `f64`.
+    //!
+    //! NaN and infinities have no decimal form, so they're rejected when encoding and decoding. Use [`sentinel`] to encode them as `nan`, `inf` and `-inf` instead.
+    //!
+    //! # Examples
+    //!
+    //! ```
+    //! use serde::{Deserialize, Serialize};
+    //!
+    //! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    //! struct Peer {
+    //!     #[serde(with = "bende::with::float_string")]
+    //!     ratio: f64,
+    //! }
+    //!
+    //! let bytes = bende::encode(&Peer { ratio: 1.25 }).unwrap();
+    //! assert_eq!(bytes, b"d5:ratio4:1.25e");
+    //! assert_eq!(bende::decode::<Peer>(&bytes).unwrap(), Peer { ratio: 1.25 });
+    //!
+    //! assert!(bende::encode(&Peer { ratio: f64::NAN }).is_err());
+    //! assert!(bende::decode::<Peer>(b"d5:ratio3:inge").is_err());
+    //! ```
+
+    use serde::de::Error as _;
+    use serde::ser::Error as _;
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::Serializer;
+
+    /// Serializes the float as a byte string.
+    ///
+    /// # Errors
+    ///
+    /// * The float is NaN or infinite.
+    pub fn serialize<S>(v: &f64, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if !v.is_finite() {
+            return Err(S::Error::custom(format!("{} can't be encoded", v)));
+        }
+        ser.serialize_str(&v.to_string())
+    }
+
+    /// Deserializes the float from a byte string.
+    ///
+    /// # Errors
+    ///
+    /// * The string isn't a decimal number, or is NaN or infinite.
+    pub fn deserialize<'de, D>(de: D) -> Result<f64, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let text = String::deserialize(de)?;
+        parse_finite(&text).ok_or_else(|| {
+            D::Error::custom(format!("{:?} is not a finite float", text))
+        })
+    }
+
+    /// Parses a finite float, rejecting the `NaN` and `inf` strings `f64::from_str` accepts.
+    fn parse_finite(text: &str) -> Option<f64> {
+        text.parse::<f64>().ok().filter(|v| v.is_finite())
+    }
+
+    pub mod sentinel {
+        //! Encodes an `f64` like [`float_string`](super), but encodes NaN and infinities as the sentinel strings `nan`, `inf` and `-inf`.
+        //!
+        //! # Examples
+        //!
+        //! ```
+        //! use serde::{Deserialize, Serialize};
+        //!
+        //! #[derive(Debug, Serialize, Deserialize)]
+        //! struct Peer {
+        //!     #[serde(with = "bende::with::float_string::sentinel")]
+        //!     ratio: f64,
+        //! }
+        //!
+        //! let bytes = bende::encode(&Peer { ratio: f64::INFINITY }).unwrap();
+        //! assert_eq!(bytes, b"d5:ratio3:infe");
+        //! assert!(bende::decode::<Peer>(b"d5:ratio3:nane").unwrap().ratio.is_nan());
+        //! ```
+
+        use serde::de::Error as _;
+        use serde::Deserialize;
+        use serde::Deserializer;
+        use serde::Serializer;
+
+        /// Serializes the float as a byte string, or as a sentinel string if it's NaN or infinite.
+        pub fn serialize<S>(v: &f64, ser: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match *v {
+                v if v.is_nan() => ser.serialize_str("nan"),
+                f64::INFINITY => ser.serialize_str("inf"),
+                f64::NEG_INFINITY => ser.serialize_str("-inf"),
+                v => ser.serialize_str(&v.to_string()),
+            }
+        }
+
+        /// Deserializes the float from a byte string, or from one of the sentinel strings.
+        ///
+        /// # Errors
+        ///
+        /// * The string is neither a decimal number nor a sentinel string.
+        pub fn deserialize<'de, D>(de: D) -> Result<f64, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let text = String::deserialize(de)?;
+            match text.as_str() {
+                "nan" => Ok(f64::NAN),
+                "inf" => Ok(f64::INFINITY),
+                "-inf" => Ok(f64::NEG_INFINITY),
+                _ => super::parse_finite(&text).ok_or_else(|| {
+                    D::Error::custom(format!("{:?} is not a float", text))
+                }),
+            }
+        }
+    }
+}
+
+pub mod duration_secs {
+    //! Encodes a `std::time::Duration` as an integer of whole seconds, instead of serde's default dictionary of `secs` and `nanos`.
+    //!
+    //! Any sub-second precision is truncated when encoding.
+    //!
+    //! # Examples
+    //!
+    //! ```
+    //! use std::time::Duration;
+    //! use serde::{Deserialize, Serialize};
+    //!
+    //! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    //! struct Announce {
+    //!     #[serde(with = "bende::with::duration_secs")]
+    //!     interval: Duration,
+    //! }
+    //!
+    //! let announce = Announce { interval: Duration::from_millis(1800_500) };
+    //!
+    //! let bytes = bende::encode(&announce).unwrap();
+    //! assert_eq!(bytes, b"d8:intervali1800ee");
+    //! assert_eq!(bende::decode::<Announce>(&bytes).unwrap().interval, Duration::from_secs(1800));
+    //! ```
+
+    use std::time::Duration;
+
+    use serde::ser::Error as _;
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::Serializer;
+
+    /// Serializes the duration as an integer.
+    ///
+    /// # Errors
+    ///
+    /// * The duration has more seconds than an `i64` can hold.
+    pub fn serialize<S>(v: &Duration, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let secs = i64::try_from(v.as_secs()).map_err(|_| {
+            S::Error::custom(format!("{:?} is too long to encode", v))
+        })?;
+        ser.serialize_i64(secs)
+    }
+
+    /// Deserializes the duration from an integer.
+    ///
+    /// # Errors
+    ///
+    /// * The integer is negative.
+    pub fn deserialize<'de, D>(de: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        u64::deserialize(de).map(Duration::from_secs)
+    }
+}
+
+pub mod system_time_secs {
+    //! Encodes a `std::time::SystemTime` as an integer of seconds since the unix epoch, which is negative for times before it.
+    //!
+    //! Any sub-second precision is truncated when encoding, rounding towards the past.
+    //!
+    //! # Examples
+    //!
+    //! ```
+    //! use std::time::{Duration, SystemTime};
+    //! use serde::{Deserialize, Serialize};
+    //!
+    //! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    //! struct Torrent {
+    //!     #[serde(rename = "creation date", with = "bende::with::system_time_secs")]
+    //!     creation_date: SystemTime,
+    //! }
+    //!
+    //! let torrent = Torrent {
+    //!     creation_date: SystemTime::UNIX_EPOCH + Duration::from_secs(1995),
+    //! };
+    //!
+    //! let bytes = bende::encode(&torrent).unwrap();
+    //! assert_eq!(bytes, b"d13:creation datei1995ee");
+    //! assert_eq!(bende::decode::<Torrent>(&bytes).unwrap(), torrent);
+    //! ```
+
+    use std::time::Duration;
+    use std::time::SystemTime;
+
+    use serde::de::Error as _;
+    use serde::ser::Error as _;
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::Serializer;
+
+    /// Serializes the time as an integer.
+    ///
+    /// # Errors
+    ///
+    /// * The time is too far from the unix epoch for its seconds to fit in an `i64`.
+    pub fn serialize<S>(v: &SystemTime, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let secs = match v.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(after) => i64::try_from(after.as_secs()).ok(),
+            Err(e) => {
+                let before = e.duration();
+                let secs =
+                    before.as_secs() + u64::from(before.subsec_nanos() > 0);
+                i64::try_from(secs).ok().map(|secs| -secs)
+            }
+        };
+        let secs = secs.ok_or_else(|| {
+            S::Error::custom(format!("{:?} is out of range", v))
+        })?;
+        ser.serialize_i64(secs)
+    }
+
+    /// Deserializes the time from an integer.
+    ///
+    /// # Errors
+    ///
+    /// * The time can't be represented by the platform's `SystemTime`.
+    pub fn deserialize<'de, D>(de: D) -> Result<SystemTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let secs = i64::deserialize(de)?;
+        let offset = Duration::from_secs(secs.unsigned_abs());
+        let time = match secs < 0 {
+            true => SystemTime::UNIX_EPOCH.checked_sub(offset),
+            false => SystemTime::UNIX_EPOCH.checked_add(offset),
+        };
+        time.ok_or_else(|| {
+            D::Error::custom(format!(
+                "{} seconds since the unix epoch is out of range",
+                secs
+            ))
+        })
+    }
+}
+
+pub mod path_list {
+    //! Encodes a `PathBuf` as a list of its components, like the `path` of a file in a multi-file torrent, eg: `a/b.txt` as `l1:a5:b.txte`.
+    //!
+    //! Components have to be valid UTF-8. Absolute paths are rejected when encoding, as torrents only have relative paths, but any other components are kept as is.
+    //!
+    //! Decoded components are pushed onto the path as is, so a component like `..` or `/etc` can point a path outside of where it's joined onto. Use [`safe`] for paths from untrusted sources.
+    //!
+    //! # Examples
+    //!
+    //! ```
+    //! use std::path::PathBuf;
+    //! use serde::{Deserialize, Serialize};
+    //!
+    //! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    //! struct File {
+    //!     length: i64,
+    //!     #[serde(with = "bende::with::path_list")]
+    //!     path: PathBuf,
+    //! }
+    //!
+    //! let file = File { length: 1995, path: ["docs", "a.txt"].iter().collect() };
+    //!
+    //! let bytes = bende::encode(&file).unwrap();
+    //! assert_eq!(bytes, b"d6:lengthi1995e4:pathl4:docs5:a.txtee");
+    //! assert_eq!(bende::decode::<File>(&bytes).unwrap(), file);
+    //! ```
+
+    use std::path::Component;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    use serde::ser::Error as _;
+    use serde::ser::SerializeSeq;
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::Serializer;
+
+    /// Serializes the path as a list of its components.
+    ///
+    /// # Errors
+    ///
+    /// * The path is absolute, or a component isn't valid UTF-8.
+    pub fn serialize<P, S>(v: &P, ser: S) -> Result<S::Ok, S::Error>
+    where
+        P: AsRef<Path>,
+        S: Serializer,
+    {
+        serialize_components(v.as_ref(), ser, false)
+    }
+
+    /// Deserializes the path from a list of components.
+    pub fn deserialize<'de, D>(de: D) -> Result<PathBuf, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Vec::<String>::deserialize(de)?.into_iter().collect())
+    }
+
+    /// Serializes the components of a path, rejecting parent components if `safe` is set.
+    fn serialize_components<S>(
+        path: &Path,
+        ser: S,
+        safe: bool,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = ser.serialize_seq(None)?;
+        for component in path.components() {
+            let component = match component {
+                Component::Normal(c) => c.to_str().ok_or_else(|| {
+                    S::Error::custom(format!("{:?} is not valid UTF-8", c))
+                })?,
+                Component::ParentDir if !safe => "..",
+                Component::CurDir => continue,
+                _ => {
+                    return Err(S::Error::custom(format!(
+                        "{:?} can't be encoded as a relative path",
+                        path
+                    )))
+                }
+            };
+            seq.serialize_element(component)?;
+        }
+        seq.end()
+    }
+
+    pub mod safe {
+        //! Encodes a `PathBuf` like [`path_list`](super), but rejects paths that could escape the directory they're joined onto.
+        //!
+        //! A decoded path is rejected if it's empty, or if a component is empty, is `.` or `..`, or contains a path separator or NUL byte, which also covers absolute paths. On Windows, components that are reserved names or contain reserved characters are rejected too, eg: `NUL` or one with a `:`, as they could name a device, a drive or a stream. Components are checked the same way as by [`check_files`](crate::files::check_files).
+        //!
+        //! # Examples
+        //!
+        //! ```
+        //! use std::path::PathBuf;
+        //! use serde::{Deserialize, Serialize};
+        //!
+        //! #[derive(Debug, Serialize, Deserialize)]
+        //! struct File {
+        //!     #[serde(with = "bende::with::path_list::safe")]
+        //!     path: PathBuf,
+        //! }
+        //!
+        //! assert!(bende::decode::<File>(b"d4:pathl4:docs5:a.txtee").is_ok());
+        //! assert!(bende::decode::<File>(b"d4:pathl2:..6:passwdee").is_err());
+        //! assert!(bende::decode::<File>(b"d4:pathl4:/etcee").is_err());
+        //! assert!(bende::encode(&File { path: PathBuf::from("../a.txt") }).is_err());
+        //! ```
+
+        use std::path::Path;
+        use std::path::PathBuf;
+
+        use serde::de::Error as _;
+        use serde::Deserialize;
+        use serde::Deserializer;
+        use serde::Serializer;
+
+        use crate::files::check_component;
+        use crate::files::FindingKind;
+
+        /// Serializes the path as a list of its components.
+        ///
+        /// # Errors
+        ///
+        /// * The path is absolute, has a `..` component, or a component isn't valid UTF-8.
+        pub fn serialize<P, S>(v: &P, ser: S) -> Result<S::Ok, S::Error>
+        where
+            P: AsRef<Path>,
+            S: Serializer,
+        {
+            super::serialize_components(v.as_ref(), ser, true)
+        }
+
+        /// Deserializes the path from a list of components, checking that each of them is safe.
+        ///
+        /// # Errors
+        ///
+        /// * The list is empty, or one of its components is unsafe.
+        pub fn deserialize<'de, D>(de: D) -> Result<PathBuf, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let components = Vec::<String>::deserialize(de)?;
+            if components.is_empty() {
+                return Err(D::Error::custom("path has no components"));
+            }
+            for component in &components {
+                let reason = match check_component(component.as_bytes()) {
+                    Some(FindingKind::Traversal) => {
+                        "escapes the directory it's joined onto"
+                    }
+                    Some(FindingKind::ReservedName) if cfg!(windows) => {
+                        "is reserved on Windows"
+                    }
+                    _ => continue,
+                };
+                return Err(D::Error::custom(format!(
+                    "path component {:?} {}",
+                    component, reason
+                )));
+            }
+            Ok(components.into_iter().collect())
+        }
+    }
+}
+
+pub mod entries {
+    //! Encodes a list of key-value pairs as a dictionary, and decodes a dictionary into a list of its entries, in the order they're in.
+    //!
+    //! The pairs don't have to be sorted, as the encoder sorts them like any other map. Repeated keys are resolved by the encoder's [`DuplicateKeys`](crate::en::DuplicateKeys) policy, which keeps the last entry by default.
+    //!
+    //! # Examples
+    //!
+    //! ```
+    //! use serde::{Deserialize, Serialize};
+    //!
+    //! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    //! struct Settings {
+    //!     #[serde(with = "bende::with::entries")]
+    //!     limits: Vec<(String, i64)>,
+    //! }
+    //!
+    //! let settings = Settings { limits: vec![("up".into(), 50), ("down".into(), 200)] };
+    //!
+    //! let bytes = bende::encode(&settings).unwrap();
+    //! assert_eq!(bytes, b"d6:limitsd4:downi200e2:upi50eee");
+    //!
+    //! let limits = bende::decode::<Settings>(&bytes).unwrap().limits;
+    //! assert_eq!(limits, [("down".into(), 200), ("up".into(), 50)]);
+    //! ```
+
+    use std::fmt;
+    use std::marker::PhantomData;
+
+    use serde::de::MapAccess;
+    use serde::de::Visitor;
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::Serialize;
+    use serde::Serializer;
+
+    /// Serializes the pairs as a map.
+    ///
+    /// # Errors
+    ///
+    /// * A key can't be encoded as a dictionary key, or a repeated key is rejected by the encoder's policy.
+    pub fn serialize<K, V, S>(v: &[(K, V)], ser: S) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize,
+        V: Serialize,
+        S: Serializer,
+    {
+        ser.collect_map(v.iter().map(|(key, val)| (key, val)))
+    }
+
+    /// Deserializes the entries of a map, in the order they're in, repeated keys included.
+    pub fn deserialize<'de, K, V, D>(de: D) -> Result<Vec<(K, V)>, D::Error>
+    where
+        K: Deserialize<'de>,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        struct EntriesVisitor<K, V>(PhantomData<(K, V)>);
+
+        impl<'de, K, V> Visitor<'de> for EntriesVisitor<K, V>
+        where
+            K: Deserialize<'de>,
+            V: Deserialize<'de>,
+        {
+            type Value = Vec<(K, V)>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a dictionary")
+            }
+
+            fn visit_map<A>(
+                self,
+                mut access: A,
+            ) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut entries =
+                    Vec::with_capacity(access.size_hint().unwrap_or(0));
+                while let Some(entry) = access.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(entries)
+            }
+        }
+
+        de.deserialize_map(EntriesVisitor(PhantomData))
+    }
+}
+
+#[cfg(feature = "unicode")]
+pub mod nfc {
+    //! Normalizes a string to Unicode Normalization Form C when decoding, eg: a file name or a display name.
+    //!
+    //! The same text can be encoded as different code points, eg: `é` as one precomposed code point or as `e` and a combining accent, depending on the platform and the producer. Normalizing it makes strings that look the same compare equal. Strings are encoded as is.
+    //!
+    //! # Examples
+    //!
+    //! ```
+    //! use serde::{Deserialize, Serialize};
+    //!
+    //! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    //! struct Info {
+    //!     #[serde(with = "bende::with::nfc")]
+    //!     name: String,
+    //! }
+    //!
+    //! let decomposed = bende::decode::<Info>(b"d4:name6:cafe\xcc\x81e").unwrap();
+    //! let composed = bende::decode::<Info>(b"d4:name5:caf\xc3\xa9e").unwrap();
+    //!
+    //! assert_eq!(decomposed, composed);
+    //! assert_eq!(composed.name, "caf\u{e9}");
+    //! ```
+
+    use std::borrow::Cow;
+
+    use icu_normalizer::ComposingNormalizerBorrowed;
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::Serializer;
+
+    /// Serializes the string as a byte string, as is.
+    pub fn serialize<S>(v: &str, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        ser.serialize_str(v)
+    }
+
+    /// Deserializes a string, normalizing it to NFC.
+    pub fn deserialize<'de, D>(de: D) -> Result<String, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let text = String::deserialize(de)?;
+        match normalize(&text) {
+            Cow::Borrowed(_) => Ok(text),
+            Cow::Owned(text) => Ok(text),
+        }
+    }
+
+    /// Normalizes a string to NFC, borrowing it if it's normalized already.
+    pub(crate) fn normalize(text: &str) -> Cow<'_, str> {
+        ComposingNormalizerBorrowed::new_nfc().normalize(text)
+    }
+}
+
+#[cfg(feature = "url")]
+pub mod url {
+    //! Encodes a `url::Url` as a byte string, eg: a torrent's `announce` URL.
+    //!
+    //! The string is parsed and validated when decoding.
+    //!
+    //! # Examples
+    //!
+    //! ```
+    //! use serde::{Deserialize, Serialize};
+    //! use url::Url;
+    //!
+    //! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    //! struct Torrent {
+    //!     #[serde(with = "bende::with::url")]
+    //!     announce: Url,
+    //! }
+    //!
+    //! let bytes = b"d8:announce22:udp://tracker.io:6969/e";
+    //! let torrent = bende::decode::<Torrent>(bytes).unwrap();
+    //!
+    //! assert_eq!(torrent.announce.scheme(), "udp");
+    //! assert_eq!(bende::encode(&torrent).unwrap(), bytes);
+    //! ```
+
+    use serde::de::Error as _;
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::Serializer;
+
+    /// Serializes the URL as a byte string.
+    pub fn serialize<S>(v: &::url::Url, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        ser.serialize_str(v.as_str())
+    }
+
+    /// Deserializes and validates a URL from a byte string.
+    pub fn deserialize<'de, D>(de: D) -> Result<::url::Url, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let text = String::deserialize(de)?;
+        ::url::Url::parse(&text).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(feature = "uuid")]
+pub mod uuid {
+    //! Encodes a `uuid::Uuid` as 16 raw bytes, instead of its human-readable form.
+    //!
+    //! # Examples
+    //!
+    //! ```
+    //! use serde::{Deserialize, Serialize};
+    //! use uuid::Uuid;
+    //!
+    //! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    //! struct Client {
+    //!     #[serde(with = "bende::with::uuid")]
+    //!     id: Uuid,
+    //! }
+    //!
+    //! let client = Client { id: Uuid::from_bytes([7; 16]) };
+    //!
+    //! let bytes = bende::encode(&client).unwrap();
+    //! assert_eq!(bytes, b"d2:id16:\x07\x07\x07\x07\x07\x07\x07\x07\x07\x07\x07\x07\x07\x07\x07\x07e");
+    //! assert_eq!(bende::decode::<Client>(&bytes).unwrap(), client);
+    //! ```
+
+    use std::fmt;
+
+    use serde::de::Visitor;
+    use serde::Deserializer;
+    use serde::Serializer;
+
+    /// Serializes the UUID as 16 raw bytes.
+    pub fn serialize<S>(v: &::uuid::Uuid, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        ser.serialize_bytes(v.as_bytes())
+    }
+
+    /// Deserializes a UUID from 16 raw bytes.
+    pub fn deserialize<'de, D>(de: D) -> Result<::uuid::Uuid, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct UuidVisitor;
+
+        impl<'de> Visitor<'de> for UuidVisitor {
+            type Value = ::uuid::Uuid;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("16 raw bytes")
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                ::uuid::Uuid::from_slice(v)
+                    .map_err(|_| E::invalid_length(v.len(), &self))
+            }
+        }
+
+        de.deserialize_bytes(UuidVisitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(feature = "time")]
+    #[test]
+    fn unix_seconds_time() {
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Foo {
+            #[serde(with = "super::unix_seconds")]
+            at: time::OffsetDateTime,
+        }
+
+        let foo = Foo {
+            at: time::OffsetDateTime::from_unix_timestamp(1995).unwrap(),
+        };
+        let bytes = crate::encode(&foo).unwrap();
+        assert_eq!(bytes, b"d2:ati1995ee");
+        assert_eq!(crate::decode::<Foo>(&bytes).unwrap(), foo);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn unix_seconds_out_of_range() {
+        use serde::Deserialize;
+
+        #[derive(Debug, Deserialize)]
+        struct Foo {
+            #[serde(with = "super::unix_seconds")]
+            #[allow(dead_code)]
+            at: chrono::DateTime<chrono::Utc>,
+        }
+
+        assert!(crate::decode::<Foo>(b"d2:ati9223372036854775807ee").is_err());
+    }
+
+    #[test]
+    fn float_string_non_finite() {
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Foo {
+            #[serde(with = "super::float_string")]
+            a: f64,
+            #[serde(with = "super::float_string::sentinel")]
+            b: f64,
+        }
+
+        let foo = Foo { a: -0.1, b: f64::NEG_INFINITY };
+        let bytes = crate::encode(&foo).unwrap();
+        assert_eq!(bytes, b"d1:a4:-0.11:b4:-infe");
+        assert_eq!(crate::decode::<Foo>(&bytes).unwrap(), foo);
+
+        assert!(crate::encode(&Foo { a: f64::INFINITY, b: 0.0 }).is_err());
+        assert!(crate::decode::<Foo>(b"d1:a3:NaN1:b1:0e").is_err());
+        assert!(crate::decode::<Foo>(b"d1:a1:01:b3:NaNe").is_err());
+        assert!(crate::decode::<Foo>(b"d1:a1:01:b8:infinitye").is_err());
+    }
+
+    #[test]
+    fn std_time_secs() {
+        use std::time::{Duration, SystemTime};
+
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Foo {
+            #[serde(with = "super::duration_secs")]
+            a: Duration,
+            #[serde(with = "super::system_time_secs")]
+            b: SystemTime,
+        }
+
+        let before = SystemTime::UNIX_EPOCH - Duration::from_millis(1500);
+        let foo = Foo { a: Duration::from_secs(60), b: before };
+        let bytes = crate::encode(&foo).unwrap();
+        assert_eq!(bytes, b"d1:ai60e1:bi-2ee");
+
+        let foo = crate::decode::<Foo>(&bytes).unwrap();
+        assert_eq!(foo.b, SystemTime::UNIX_EPOCH - Duration::from_secs(2));
+        assert!(crate::decode::<Foo>(b"d1:ai-1e1:bi0ee").is_err());
+        assert!(crate::encode(&Foo {
+            a: Duration::MAX,
+            b: SystemTime::UNIX_EPOCH
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn path_list_safe() {
+        use std::path::PathBuf;
+
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Foo {
+            #[serde(with = "super::path_list")]
+            a: PathBuf,
+            #[serde(with = "super::path_list::safe")]
+            b: PathBuf,
+        }
+
+        let foo = Foo { a: PathBuf::from("../x/./y"), b: PathBuf::from("x/y") };
+        let bytes = crate::encode(&foo).unwrap();
+        assert_eq!(bytes, b"d1:al2:..1:x1:ye1:bl1:x1:yee");
+        assert_eq!(
+            crate::decode::<Foo>(&bytes).unwrap().a,
+            PathBuf::from("../x/y")
+        );
+
+        let unsafe_paths: [&[u8]; 5] =
+            [b"le", b"l0:e", b"l1:.e", b"l3:a/be", b"l3:a\\be"];
+        for path in unsafe_paths {
+            let bytes = [&b"d1:al1:xe1:b"[..], path, b"e"].concat();
+            assert!(crate::decode::<Foo>(&bytes).is_err());
+        }
+        // Reserved names are only rejected where they're reserved.
+        let reserved = crate::decode::<Foo>(b"d1:al1:xe1:bl3:NULee");
+        assert_eq!(reserved.is_err(), cfg!(windows));
+        let absolute = Foo { a: PathBuf::from("/x"), b: PathBuf::from("x") };
+        assert!(crate::encode(&absolute).is_err());
+    }
+
+    #[cfg(feature = "url")]
+    #[test]
+    fn url_invalid() {
+        use serde::Deserialize;
+
+        #[derive(Debug, Deserialize)]
+        struct Foo {
+            #[serde(with = "super::url")]
+            #[allow(dead_code)]
+            announce: ::url::Url,
+        }
+
+        assert!(crate::decode::<Foo>(b"d8:announce3:fooe").is_err());
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuid_wrong_length() {
+        use serde::Deserialize;
+
+        #[derive(Debug, Deserialize)]
+        struct Foo {
+            #[serde(with = "super::uuid")]
+            #[allow(dead_code)]
+            id: ::uuid::Uuid,
+        }
+
+        assert!(crate::decode::<Foo>(b"d2:id3:fooe").is_err());
+    }
+}